@@ -0,0 +1,147 @@
+//! Declarative bot configuration loaded from TOML.
+//!
+//! ```toml
+//! bot = "mcts_puct"
+//!
+//! [params]
+//! num_iterations = 100
+//!
+//! [runner]
+//! max_iterations = 500
+//! random_seed = 0
+//! ```
+//!
+//! `params` are interpreted by each bot constructor of [crate::registry::BotFactory], which rejects
+//! unknown keys. The keys accepted by the default factory are:
+//!
+//! - `simple`, `tree`: none.
+//! - `simple_tree`: `chance_depth`, `st_stacking`, `prune_dominated`, `commitment` and
+//!   `repair_depth`, or `max_nodes`, `max_depth` and `step_bound` instead of `chance_depth` for
+//!   the best-first search.
+//! - `four_wide`: `well_x`, `min_depth` and `exit_height`.
+//! - `mcts_puct`: `num_iterations`.
+use std::collections::HashMap;
+use std::error::Error;
+use std::path::Path;
+use serde::Deserialize;
+use crate::{Bot, BotRunner, GameClock};
+use crate::registry::{BotFactory, BotParams};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct RunnerConfig {
+    pub max_iterations: usize,
+    pub quick_action: bool,
+    pub random_seed: Option<u64>,
+    pub debug_print: bool,
+    pub max_repetitions: Option<usize>,
+    /// See [GameClock].
+    pub ms_per_piece: u32,
+    pub ms_per_input: u32,
+    /// The misdrop rate in percent. See [BotRunner::set_misdrop_rate].
+    pub misdrop_percent: u32,
+}
+
+impl Default for RunnerConfig {
+    fn default() -> Self {
+        Self {
+            max_iterations: 100,
+            quick_action: true,
+            random_seed: None,
+            debug_print: false,
+            max_repetitions: None,
+            ms_per_piece: 0,
+            ms_per_input: 0,
+            misdrop_percent: 0,
+        }
+    }
+}
+
+impl RunnerConfig {
+    pub fn to_runner(&self) -> Result<BotRunner, &'static str> {
+        let mut runner = BotRunner::new(self.max_iterations, self.quick_action, self.random_seed, self.debug_print);
+        runner.set_max_repetitions(self.max_repetitions)?;
+        runner.set_clock(GameClock::new(self.ms_per_piece as f32, self.ms_per_input as f32));
+        runner.set_misdrop_rate(self.misdrop_percent as f32 / 100.0);
+        Ok(runner)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct BotConfig {
+    /// Bot name registered in [BotFactory].
+    pub bot: String,
+    #[serde(default)]
+    params: HashMap<String, toml::Value>,
+    #[serde(default)]
+    pub runner: RunnerConfig,
+}
+
+impl BotConfig {
+    pub fn from_toml(s: &str) -> Result<Self, Box<dyn Error>> {
+        Ok(toml::from_str(s)?)
+    }
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, Box<dyn Error>> {
+        Self::from_toml(&std::fs::read_to_string(path)?)
+    }
+    /// Returns the parameters for bot constructors. Non-string values are stored as their TOML text.
+    pub fn params(&self) -> BotParams {
+        self.params.iter()
+            .map(|(k, v)| {
+                let v = match v {
+                    toml::Value::String(s) => s.clone(),
+                    v => v.to_string(),
+                };
+                (k.clone(), v)
+            })
+            .collect()
+    }
+    pub fn create_bot(&self, factory: &BotFactory) -> Result<Box<dyn Bot>, Box<dyn Error>> {
+        factory.create(&self.bot, &self.params())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bot_config() {
+        let conf = BotConfig::from_toml(r#"
+            bot = "simple"
+
+            [params]
+            weight = 0.5
+            name = "x"
+
+            [runner]
+            max_iterations = 3
+            random_seed = 0
+        "#).unwrap();
+        assert_eq!("simple", conf.bot);
+        let params = conf.params();
+        assert_eq!(Some(&"0.5".to_string()), params.get("weight"));
+        assert_eq!(Some(&"x".to_string()), params.get("name"));
+        assert_eq!(3, conf.runner.max_iterations);
+        assert!(conf.runner.quick_action);
+        assert_eq!(Some(0), conf.runner.random_seed);
+
+        assert!(conf.create_bot(&BotFactory::default()).is_err());
+
+        let conf = BotConfig::from_toml(r#"
+            bot = "four_wide"
+
+            [params]
+            well_x = 0
+
+            [runner]
+            max_iterations = 3
+            random_seed = 0
+        "#).unwrap();
+        let mut bot = conf.create_bot(&BotFactory::default()).unwrap();
+        let game = conf.runner.to_runner().unwrap().run(bot.as_mut(), &mut crate::DummyBotRunnerHooks).unwrap();
+        assert!(game.stats.lock > 0);
+
+        assert!(BotConfig::from_toml("[params]").is_err());
+    }
+}
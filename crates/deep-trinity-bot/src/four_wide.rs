@@ -0,0 +1,190 @@
+//! 4-wide combo play: builds the walls around a 4-wide well with 3 residual blocks, continues the
+//! combo in the well, and switches to [SimpleTreeBot] when the stack gets too high.
+use std::error::Error;
+use deep_trinity_core::{LockOutcome, MoveTransition};
+use deep_trinity_core::attack::calc_combo_attack;
+use deep_trinity_core::helper::{ComboWell, FOUR_WIDE};
+use deep_trinity_grid::{Grid, X, Y};
+use crate::simple_tree::SimpleTreeBot;
+use crate::{Action, Bot, Game};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum FourWideMode {
+    /// Stacking the walls around the well.
+    Build,
+    /// Clearing lines in the well.
+    Combo,
+    /// The fallback bot is used.
+    Exit,
+}
+
+#[derive(Clone, Debug)]
+pub struct FourWideBot {
+    /// The leftmost column of the well.
+    pub well_x: X,
+    /// The depth of the well to start the combo.
+    pub min_depth: Y,
+    /// Switches to the fallback bot when the stack gets higher than this.
+    pub exit_height: Y,
+    mode: FourWideMode,
+    fallback: SimpleTreeBot,
+}
+
+impl Default for FourWideBot {
+    fn default() -> Self {
+        Self {
+            well_x: 3,
+            min_depth: 6,
+            exit_height: 16,
+            mode: FourWideMode::Build,
+            fallback: SimpleTreeBot::default(),
+        }
+    }
+}
+
+fn simulate(game: &Game, mt: &MoveTransition) -> Result<(Game, LockOutcome), Box<dyn Error>> {
+    let mut next = game.clone();
+    let fp = next.state.falling_piece.as_mut().unwrap();
+    if let Some(hint) = mt.hint {
+        fp.placement = hint.placement;
+        fp.apply_move(hint.by, &next.state.playfield, next.rules.rotation_mode);
+    } else {
+        fp.placement = mt.placement;
+    }
+    let outcome = next.lock_ex()?;
+    Ok((next, outcome))
+}
+
+impl FourWideBot {
+    pub fn mode(&self) -> FourWideMode { self.mode }
+    fn well(&self, game: &Game) -> ComboWell {
+        ComboWell::at(&game.state.playfield, self.well_x).expect("well out of the playfield")
+    }
+    fn update_mode(&mut self, game: &Game) {
+        if self.mode == FourWideMode::Exit {
+            return;
+        }
+        if game.state.playfield.stack_height() > self.exit_height {
+            self.mode = FourWideMode::Exit;
+            return;
+        }
+        let well = self.well(game);
+        self.mode = match self.mode {
+            FourWideMode::Build if well.depth() >= self.min_depth => FourWideMode::Combo,
+            FourWideMode::Combo if well.depth() == 0 => FourWideMode::Build,
+            mode => mode,
+        };
+    }
+    /// Returns the best score of the placements of the falling piece without holding.
+    fn best_score(&self, game: &Game) -> Result<f32, Box<dyn Error>> {
+        let mut r = f32::MIN;
+        for mt in game.get_move_candidates()? {
+            let (next, outcome) = simulate(game, &mt)?;
+            r = r.max(self.evaluate(&next, &outcome));
+        }
+        Ok(r)
+    }
+    /// Higher is better.
+    fn evaluate(&self, game: &Game, outcome: &LockOutcome) -> f32 {
+        if outcome.game_over {
+            return f32::MIN;
+        }
+        let pf = &game.state.playfield;
+        let well = self.well(game);
+        let holes = pf.grid.num_covered_empty_cells() as f32;
+        let residual_error = (well.residual as f32 - 3.0).abs();
+        match self.mode {
+            FourWideMode::Build => {
+                let heights = pf.heights();
+                let bumpiness = heights.windows(2).enumerate()
+                    .filter(|(i, _)| {
+                        let x = *i as X;
+                        x + 1 < self.well_x || x >= self.well_x + FOUR_WIDE
+                    })
+                    .map(|(_, w)| (w[0] - w[1]).abs() as f32)
+                    .sum::<f32>();
+                -holes * 2.0 - residual_error - bumpiness * 0.3 - outcome.line_clear.num_lines as f32
+                    + well.depth().min(self.min_depth) as f32 * 0.5
+            }
+            FourWideMode::Combo => {
+                let combo = if outcome.num_combos.is_some() { 2.0 } else { -5.0 };
+                let potential = calc_combo_attack(outcome.num_combos, well.depth().max(0) as u32, &game.rules.attack_table);
+                combo + outcome.attack_sent as f32 + potential as f32 * 0.5 - holes * 2.0 - residual_error
+            }
+            FourWideMode::Exit => 0.0,
+        }
+    }
+}
+
+impl Bot for FourWideBot {
+    fn think(&mut self, game: &Game) -> Result<Action, Box<dyn Error>> {
+        if ComboWell::at(&game.state.playfield, self.well_x).is_none() {
+            return Err("well out of the playfield".into());
+        }
+        self.update_mode(game);
+        if self.mode == FourWideMode::Exit {
+            return self.fallback.think(game);
+        }
+        let mut best: Option<(f32, Action)> = None;
+        let mut consider = |g: &Game, action: Option<Action>| -> Result<(), Box<dyn Error>> {
+            let mut candidates = g.get_move_candidates()?.into_iter().collect::<Vec<_>>();
+            // Sort to make tie-breaks deterministic.
+            candidates.sort();
+            for mt in candidates {
+                let (next, outcome) = simulate(g, &mt)?;
+                let mut score = self.evaluate(&next, &outcome);
+                if self.mode == FourWideMode::Combo && next.state.falling_piece.is_some() && score > f32::MIN {
+                    score += self.best_score(&next)? * 0.5;
+                }
+                if best.as_ref().map_or(true, |(s, _)| score > *s) {
+                    best = Some((score, action.unwrap_or(Action::Move(mt))));
+                }
+            }
+            Ok(())
+        };
+        consider(game, None)?;
+        if game.can_hold() {
+            let mut held = game.clone();
+            if held.hold()? {
+                consider(&held, Some(Action::Hold))?;
+            }
+        }
+        best.map(|(_, action)| action).ok_or_else(|| "no movable placements".into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use deep_trinity_core::Piece;
+
+    #[test]
+    fn test_four_wide_bot() {
+        let mut game = Game::default();
+        game.state.playfield.set_rows_with_strs((0, 0).into(), &[
+            "@@@    @@@",
+            "@@@    @@@",
+            "@@@    @@@",
+            "@@@@@@ @@@",
+        ]);
+        game.supply_next_pieces(&[Piece::S, Piece::Z]);
+        game.setup_falling_piece(None).unwrap();
+        let mut bot = FourWideBot::default();
+        bot.min_depth = 4;
+        match bot.think(&game).unwrap() {
+            Action::Move(mt) => {
+                let (next, outcome) = simulate(&game, &mt).unwrap();
+                assert_eq!(1, outcome.line_clear.num_lines);
+                game = next;
+            }
+            Action::Hold => unreachable!(),
+        }
+        assert_eq!(FourWideMode::Combo, bot.mode());
+        assert_eq!(Some(0), game.state.num_combos);
+        assert!(ComboWell::at(&game.state.playfield, 3).unwrap().is_three_residual());
+
+        game.state.playfield.set_rows_with_strs((0, 20).into(), &["@         "]);
+        bot.think(&game).unwrap();
+        assert_eq!(FourWideMode::Exit, bot.mode());
+    }
+}
@@ -99,7 +99,7 @@ impl<'a, NodeData: StackTreeNodeData<'a>> StackTree<'a, NodeData> {
             let common_data = target_data.common_data();
 
             if common_data.game.state.falling_piece.is_some() {
-                for placement in common_data.move_decision_resource.dst_candidates.iter() {
+                for placement in common_data.move_decision_resource.sorted_candidates().iter() {
                     if !expander.filter_destination(&target_data, placement) {
                         continue;
                     }
@@ -119,7 +119,7 @@ impl<'a, NodeData: StackTreeNodeData<'a>> StackTree<'a, NodeData> {
             }
 
             // Using only one loop while for the readability.
-            while common_data.game.state.can_hold {
+            while common_data.game.can_hold() {
                 if !expander.filter_hold(&target_data) {
                     break;
                 }
@@ -158,6 +158,8 @@ pub trait StackTreeSimulator<'a> {
     fn on_expanded(&mut self, tree: &mut StackTree<'a, Self::NodeData>, target: NodeHandle, expander: &Self::NodeExpander) -> Result<(), Box<dyn Error>>;
 }
 
+/// Returns `Ok(false)` if no node is selected or the budget of the arena is exceeded
+/// (see [deep_trinity_tree::arena::ArenaBudget]).
 pub fn simulate_once<'a, NodeData, NodeExpander>(
     tree: &mut StackTree<'a, NodeData>,
     simulator: &mut impl StackTreeSimulator<'a, NodeData=NodeData, NodeExpander=NodeExpander>,
@@ -165,6 +167,9 @@ pub fn simulate_once<'a, NodeData, NodeExpander>(
     NodeData: StackTreeNodeData<'a>,
     NodeExpander: StackTreeNodeExpander<'a, NodeData=NodeData>
 {
+    if tree.arena().is_budget_exceeded() {
+        return Ok(false);
+    }
     if let Some(target) = simulator.select(tree)? {
         let mut expander = simulator.expander(tree, target)?;
         tree.expand(target, &mut expander)?;
@@ -190,6 +195,8 @@ mod tests {
     use chrono::prelude::*;
     use prost::Message;
     use deep_trinity_grid::Grid;
+    use deep_trinity_core::goal::Goal;
+    use deep_trinity_tree::arena::ArenaBudget;
 
     #[derive(Default)]
     struct ExpansionStats {
@@ -321,6 +328,8 @@ mod tests {
         let profile_result_file_path = format!("tmp/{}-profile.pb", now);
         // let max_expansion_count = 10;
         let max_expansion_count = -1;
+        // let budget = ArenaBudget::bytes(1 << 30);
+        let budget = ArenaBudget::default();
         let enable_logging = true;
         // let log_file_path = LogSink::File(format!("tmp/{}.log", now));
         let log_sink = LogSink::Stderr;
@@ -355,6 +364,7 @@ mod tests {
 
         let root_node_data = DefaultStackTreeNodeData::new(StackTreeCommonNodeData::new(None, game).unwrap());
         let mut tree = StackTree::<DefaultStackTreeNodeData<'static>>::new(root_node_data).unwrap();
+        tree.arena_mut().set_budget(budget);
         let mut simulator = SimpleSimulator::new(tree.root());
 
         let mut last_i = 0;
@@ -372,11 +382,15 @@ mod tests {
             }
         }
         writeln!(&mut log_file, "[{}] {} (finished)", Local::now().to_rfc3339_opts(SecondsFormat::Millis, false), last_i).unwrap();
+        if tree.arena().is_budget_exceeded() {
+            writeln!(&mut log_file, "budget exceeded: {} nodes", tree.arena().used_len()).unwrap();
+        }
 
         let mut found = Vec::new();
-        let root_pc_count = tree.arena()[tree.root()].data.common_data().game.stats.perfect_clear;
+        let goal = Goal::PerfectClear;
+        let root_stats = tree.arena()[tree.root()].data.common_data().game.stats.clone();
         tree.visit(|tree, node, _| {
-            if tree[node].data.common_data.game.stats.perfect_clear > root_pc_count {
+            if goal.is_achieved(&root_stats, &tree[node].data.common_data.game) {
                 found.push(node);
             }
         });
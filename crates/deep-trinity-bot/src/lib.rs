@@ -1,14 +1,26 @@
+use std::collections::HashMap;
 use std::error::Error;
-use rand::SeedableRng;
+use rand::{Rng, SeedableRng};
+use rand::seq::SliceRandom;
 use rand::rngs::StdRng;
-use deep_trinity_core::{MoveTransition, RandomPieceGenerator, MovePlayer, FallingPiece};
+use deep_trinity_core::{Count, MoveTransition, RandomPieceGenerator, MovePlayer, FallingPiece, Placement};
+use deep_trinity_core::helper::MoveDecisionResource;
+use deep_trinity_core::bot::PlanDivergence;
+use deep_trinity_core::bot::noise::{Noise, perturb_placement};
 
 pub mod simple;
 pub mod simple_tree;
+pub mod four_wide;
+#[cfg(feature = "mcts")]
 pub mod mcts_puct;
 pub mod tree;
 pub mod template;
 pub mod helper;
+pub mod registry;
+#[cfg(feature = "config")]
+pub mod config;
+#[cfg(feature = "rating")]
+pub mod rating;
 
 pub type Game = deep_trinity_core::Game<'static>;
 
@@ -19,9 +31,25 @@ pub enum Action {
     Hold,
 }
 
+#[allow(deprecated)]
+impl From<Action> for deep_trinity_core::bot::Action {
+    fn from(action: Action) -> Self {
+        match action {
+            Action::Move(mt) => Self::Move(mt),
+            Action::Hold => Self::Hold,
+        }
+    }
+}
+
+pub type ThinkInfo = deep_trinity_core::bot::ThinkInfo<Action>;
+
 #[deprecated(note = "Use deep_trinity_core::bot::Bot")]
 pub trait Bot {
     fn think(&mut self, game: &Game) -> Result<Action, Box<dyn Error>>;
+    /// Returns the diagnostics of the last `think()` if supported.
+    fn last_think_info(&self) -> Option<ThinkInfo> { None }
+    /// Returns why the plan was discarded in the last `think()` if the bot commits to plans.
+    fn last_plan_divergence(&self) -> Option<PlanDivergence> { None }
 }
 
 //---
@@ -30,7 +58,13 @@ pub trait BotRunnerHooks {
     fn on_start(&mut self, _game: &mut Game) -> Result<(), Box<dyn Error>> { Ok(()) }
     fn on_iter(&mut self, _game: &mut Game) -> Result<bool, Box<dyn Error>> { Ok(true) }
     fn on_action(&mut self, _game: &Game, _action: &Action) -> Result<(), Box<dyn Error>> { Ok(()) }
+    /// Called before `on_action()` if the bot provides [Bot::last_think_info].
+    fn on_think_info(&mut self, _game: &Game, _info: &ThinkInfo) -> Result<(), Box<dyn Error>> { Ok(()) }
+    /// Called before `on_action()` if the bot discarded its plan (see [Bot::last_plan_divergence]).
+    fn on_replan(&mut self, _game: &Game, _divergence: &PlanDivergence) -> Result<(), Box<dyn Error>> { Ok(()) }
     fn on_action_step(&mut self, _game: &Game) -> Result<(), Box<dyn Error>> { Ok(()) }
+    /// Called when the same position appeared too many times. The run will be terminated after this.
+    fn on_loop_detected(&mut self, _game: &Game, _position_key: u64) -> Result<(), Box<dyn Error>> { Ok(()) }
     fn on_end(&mut self, _game: &mut Game) -> Result<(), Box<dyn Error>> { Ok(()) }
 }
 
@@ -38,82 +72,231 @@ pub struct DummyBotRunnerHooks;
 
 impl BotRunnerHooks for DummyBotRunnerHooks {}
 
+/// Calls all the hooks in order, e.g. to attach a stats recorder and a visualizer to one run.
+/// The run continues only while all the `on_iter()` return `true`.
+#[derive(Default)]
+pub struct CompositeHooks(pub Vec<Box<dyn BotRunnerHooks>>);
+
+impl CompositeHooks {
+    pub fn new() -> Self { Self::default() }
+    pub fn push(&mut self, hooks: Box<dyn BotRunnerHooks>) { self.0.push(hooks); }
+}
+
+impl BotRunnerHooks for CompositeHooks {
+    fn on_start(&mut self, game: &mut Game) -> Result<(), Box<dyn Error>> {
+        self.0.iter_mut().try_for_each(|h| h.on_start(game))
+    }
+    fn on_iter(&mut self, game: &mut Game) -> Result<bool, Box<dyn Error>> {
+        let mut r = true;
+        for h in self.0.iter_mut() {
+            r &= h.on_iter(game)?;
+        }
+        Ok(r)
+    }
+    fn on_action(&mut self, game: &Game, action: &Action) -> Result<(), Box<dyn Error>> {
+        self.0.iter_mut().try_for_each(|h| h.on_action(game, action))
+    }
+    fn on_think_info(&mut self, game: &Game, info: &ThinkInfo) -> Result<(), Box<dyn Error>> {
+        self.0.iter_mut().try_for_each(|h| h.on_think_info(game, info))
+    }
+    fn on_replan(&mut self, game: &Game, divergence: &PlanDivergence) -> Result<(), Box<dyn Error>> {
+        self.0.iter_mut().try_for_each(|h| h.on_replan(game, divergence))
+    }
+    fn on_action_step(&mut self, game: &Game) -> Result<(), Box<dyn Error>> {
+        self.0.iter_mut().try_for_each(|h| h.on_action_step(game))
+    }
+    fn on_loop_detected(&mut self, game: &Game, position_key: u64) -> Result<(), Box<dyn Error>> {
+        self.0.iter_mut().try_for_each(|h| h.on_loop_detected(game, position_key))
+    }
+    fn on_end(&mut self, game: &mut Game) -> Result<(), Box<dyn Error>> {
+        self.0.iter_mut().try_for_each(|h| h.on_end(game))
+    }
+}
+
+/// Simulated time to play pieces, e.g. to compare bots under the same PPS cap.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct GameClock {
+    /// The time taken for every piece regardless of the inputs (e.g. thinking).
+    pub ms_per_piece: f32,
+    pub ms_per_input: f32,
+}
+
+impl GameClock {
+    pub fn new(ms_per_piece: f32, ms_per_input: f32) -> Self { Self { ms_per_piece, ms_per_input } }
+}
+
+/// The result of [BotRunner::run_with_summary].
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct RunSummary {
+    pub num_pieces: usize,
+    pub attack: Count,
+    /// Counted only if the moves are played, i.e. `quick_action` is off or
+    /// [GameClock::ms_per_input] is set.
+    pub num_inputs: usize,
+    pub num_misdrops: usize,
+    /// The time on the [GameClock].
+    pub elapsed_ms: f32,
+}
+
+impl RunSummary {
+    /// Pieces per second. `None` if no time elapsed.
+    pub fn pps(&self) -> Option<f32> {
+        if self.elapsed_ms > 0.0 { Some(self.num_pieces as f32 * 1000.0 / self.elapsed_ms) } else { None }
+    }
+    /// Attack per minute. `None` if no time elapsed.
+    pub fn apm(&self) -> Option<f32> {
+        if self.elapsed_ms > 0.0 { Some(self.attack as f32 * 60000.0 / self.elapsed_ms) } else { None }
+    }
+}
+
+/// Returns another placement next to `placement` if possible, otherwise any other one.
+fn misdrop_placement(game: &Game, placement: Placement, rng: &mut StdRng) -> Option<Placement> {
+    if let Some(pl) = perturb_placement(game, placement, Noise::Shift, rng) {
+        return Some(pl);
+    }
+    let resource = MoveDecisionResource::with_game(game).ok()?;
+    let others = resource.sorted_candidates().into_iter()
+        .filter(|pl| *pl != placement)
+        .collect::<Vec<_>>();
+    others.choose(rng).copied()
+}
+
 #[deprecated(note = "Use deep_trinity_core::bot::SimpleBotRunner")]
 pub struct BotRunner {
     max_iterations: usize,
     quick_action: bool,
     random_seed: Option<u64>,
     debug_print: bool,
+    max_repetitions: Option<usize>,
+    clock: GameClock,
+    misdrop_rate: f32,
 }
 
 impl BotRunner {
     pub fn new(max_iterations: usize, quick_action: bool, random_seed: Option<u64>, debug_print: bool) -> Self {
-        Self { max_iterations, quick_action, random_seed, debug_print }
+        Self { max_iterations, quick_action, random_seed, debug_print, max_repetitions: None, clock: Default::default(), misdrop_rate: 0.0 }
     }
-    pub fn run_with_no_hooks(&self, bot: &mut impl Bot) -> Result<Game, Box<dyn Error>> {
+    /// If `Some(n)` is specified, the run is terminated when the same position (see
+    /// [deep_trinity_core::Game::position_key]) appears `n` times. `n` should be 2 or more.
+    /// Disabled by default.
+    pub fn set_max_repetitions(&mut self, n: Option<usize>) -> Result<(), &'static str> {
+        if n.is_some_and(|n| n < 2) {
+            return Err("max repetitions should be 2 or more.");
+        }
+        self.max_repetitions = n;
+        Ok(())
+    }
+    /// The clock to report PPS and APM in [RunSummary]. No time elapses by default.
+    pub fn set_clock(&mut self, clock: GameClock) {
+        self.clock = clock;
+    }
+    /// The probability of a piece to be placed at another placement than the bot decided,
+    /// to test robustness. 0 by default.
+    pub fn set_misdrop_rate(&mut self, rate: f32) {
+        self.misdrop_rate = rate;
+    }
+    pub fn run_with_no_hooks(&self, bot: &mut (impl Bot + ?Sized)) -> Result<Game, Box<dyn Error>> {
         let mut dummy = DummyBotRunnerHooks;
         self.run(bot, &mut dummy)
     }
-    pub fn run(&self, bot: &mut impl Bot, hook: &mut impl BotRunnerHooks) -> Result<Game, Box<dyn Error>> {
+    pub fn run(&self, bot: &mut (impl Bot + ?Sized), hook: &mut (impl BotRunnerHooks + ?Sized)) -> Result<Game, Box<dyn Error>> {
+        self.run_with_summary(bot, hook).map(|(game, _)| game)
+    }
+    pub fn run_with_summary(&self, bot: &mut (impl Bot + ?Sized), hook: &mut (impl BotRunnerHooks + ?Sized)) -> Result<(Game, RunSummary), Box<dyn Error>> {
         let mut game: Game = Default::default();
+        let mut summary = RunSummary::default();
+        let mut misdrop_rng = StdRng::seed_from_u64(self.random_seed.unwrap_or(0));
 
-        let mut rpg = self.random_seed.map(|seed| RandomPieceGenerator::new(StdRng::seed_from_u64(seed)));
-        if let Some(rpg) = rpg.as_mut() {
-            game.supply_next_pieces(&rpg.generate());
+        if let Some(seed) = self.random_seed {
+            game.set_piece_generator(Some(Box::new(RandomPieceGenerator::new(StdRng::seed_from_u64(seed)))));
             game.setup_falling_piece(None).unwrap();
         }
         hook.on_start(&mut game)?;
 
+        let mut position_counts: HashMap<u64, usize> = HashMap::new();
         for n in 0..self.max_iterations {
             if !hook.on_iter(&mut game)? {
                 break;
             }
             if self.debug_print { println!("===== {} =====\n{}", n, game); }
-            if game.should_supply_next_pieces() {
-                if let Some(rpg) = rpg.as_mut() {
-                    game.supply_next_pieces(&rpg.generate());
+            if let Some(max_repetitions) = self.max_repetitions {
+                let key = game.position_key();
+                let count = position_counts.entry(key).or_insert(0);
+                *count += 1;
+                if *count >= max_repetitions {
+                    if self.debug_print { println!("Loop detected: {:016x}", key); }
+                    hook.on_loop_detected(&game, key)?;
+                    break;
                 }
             }
 
             let action = bot.think(&game)?;
             if self.debug_print { println!("Action: {:?}", action); }
+            if let Some(info) = bot.last_think_info() {
+                hook.on_think_info(&game, &info)?;
+            }
+            if let Some(divergence) = bot.last_plan_divergence() {
+                if self.debug_print { println!("Replan: {:?}", divergence); }
+                hook.on_replan(&game, &divergence)?;
+            }
             hook.on_action(&game, &action)?;
 
-            match action {
-                Action::Move(mt) => {
-                    if self.quick_action {
-                        let fp = FallingPiece::new_with_last_move_transition(
-                            game.state.falling_piece.unwrap().piece_spec,
-                            &mt,
-                        );
-                        game.state.falling_piece = Some(fp);
-                        if self.debug_print { println!("{}", game); }
-                        hook.on_action_step(&game)?;
-                    } else {
-                        let path = game.get_almost_good_move_path(&mt)?;
-
-                        let mut mp = MovePlayer::new(path);
-                        while mp.step(&mut game)? {
-                            if self.debug_print { println!("{}", game); }
-                            hook.on_action_step(&game)?;
-                        }
-                    }
-
-                    game.lock().unwrap();
-                    if game.state.is_game_over() {
-                        break;
+            let mut mt = match action {
+                Action::Move(mt) => Some(mt),
+                Action::Hold => None,
+            };
+            if let Some(placement) = mt.map(|mt| mt.placement) {
+                if self.misdrop_rate > 0.0 && misdrop_rng.gen::<f32>() < self.misdrop_rate {
+                    if let Some(misdropped) = misdrop_placement(&game, placement, &mut misdrop_rng) {
+                        if self.debug_print { println!("Misdrop: {:?}", misdropped); }
+                        mt = Some(MoveTransition::new(misdropped, None));
+                        summary.num_misdrops += 1;
                     }
                 }
-                Action::Hold => {
-                    game.hold()?;
+            }
+            let num_inputs = match mt {
+                Some(mt) if self.quick_action => {
+                    let num_inputs = if self.clock.ms_per_input > 0.0 {
+                        game.get_action_path(false, Some(&mt))?.len()
+                    } else {
+                        0
+                    };
+                    let mt = game.establish_move_hint(&mt)?;
+                    let fp = FallingPiece::new_with_last_move_transition(
+                        game.state.falling_piece.unwrap().piece_spec,
+                        &mt,
+                    );
+                    game.state.falling_piece = Some(fp);
                     if self.debug_print { println!("{}", game); }
                     hook.on_action_step(&game)?;
+                    num_inputs
+                }
+                _ => {
+                    let path = game.get_action_path(mt.is_none(), mt.as_ref())?;
+                    let num_inputs = path.len();
+                    let mut mp = MovePlayer::new(path);
+                    while mp.step(&mut game)? {
+                        if self.debug_print { println!("{}", game); }
+                        hook.on_action_step(&game)?;
+                    }
+                    num_inputs
+                }
+            };
+            summary.num_inputs += num_inputs;
+            summary.elapsed_ms += self.clock.ms_per_input * num_inputs as f32;
+            if mt.is_some() {
+                let outcome = game.lock_ex().unwrap();
+                summary.num_pieces += 1;
+                summary.attack += outcome.attack_sent;
+                summary.elapsed_ms += self.clock.ms_per_piece;
+                if game.state.is_game_over() {
+                    break;
                 }
             }
         }
 
         if self.debug_print { println!("===== END =====\n{}", game); }
         hook.on_end(&mut game)?;
-        Ok(game)
+        Ok((game, summary))
     }
 }
@@ -1,6 +1,6 @@
 /// MCTS-base bot implementation by the PUCT algorithm.
 /// https://doi.org/10.1007/978-3-642-40988-2_13
-use crate::{Bot, Action};
+use crate::{Bot, Action, ThinkInfo};
 use deep_trinity_core::{Game, StatisticsEntryType, LineClear, TSpin};
 use deep_trinity_grid::Grid;
 use std::error::Error;
@@ -14,8 +14,8 @@ fn eval_game(game: &Game) -> f32 {
     let mut reward = 0.0;
     let pf = &game.state.playfield;
     {
-        let height = pf.height() as f32;
-        let top_padding = pf.grid.top_padding() as f32;
+        let height = pf.visible_height as f32;
+        let top_padding = pf.visible_top_padding() as f32;
         reward += top_padding / height;
     }
     {
@@ -60,15 +60,20 @@ struct GameData {
     by: Action,
     game: Rc<Game<'static>>,
     actions: Vec<Action>,
+    /// [eval_game] cached since final nodes are evaluated on every visit.
+    value: f32,
 }
 
 impl GameData {
     fn new<R: Rng + ?Sized>(by: Action, game: Game<'static>, rng: &mut R) -> Result<Self, Box<dyn Error>> {
         let actions = if game.state.falling_piece.is_some() {
-            let mut actions = game.get_move_candidates()?.iter()
-                .map(|mt| { Action::Move(mt.clone()) })
+            let mut candidates = game.get_move_candidates()?.into_iter().collect::<Vec<_>>();
+            // Sort before shuffling so that the result only depends on the seed of `rng`.
+            candidates.sort();
+            let mut actions = candidates.into_iter()
+                .map(Action::Move)
                 .collect::<Vec<_>>();
-            if game.state.can_hold {
+            if game.can_hold() {
                 actions.push(Action::Hold);
             }
             actions.shuffle(rng);
@@ -76,7 +81,8 @@ impl GameData {
         } else {
             Vec::new()
         };
-        Ok(Self { by, game: Rc::new(game), actions })
+        let value = eval_game(&game);
+        Ok(Self { by, game: Rc::new(game), actions, value })
     }
 }
 
@@ -114,6 +120,20 @@ impl Node {
             .unwrap();
         Some(best.borrow().game_data.by)
     }
+    fn mean_value(&self) -> f32 {
+        if self.num_visits == 0 { 0.0 } else { self.sum_value / self.num_visits as f32 }
+    }
+    /// Returns the number of the descendant nodes and the max depth from this node.
+    fn count_nodes(&self) -> (usize, usize) {
+        let mut num = 0;
+        let mut depth = 0;
+        for child in self.children.iter() {
+            let (n, d) = child.borrow().count_nodes();
+            num += n + 1;
+            depth = depth.max(d + 1);
+        }
+        (num, depth)
+    }
     #[allow(dead_code)]
     fn visit(&self, visitor: fn(node: &Node)) {
         visitor(self);
@@ -220,7 +240,7 @@ fn iterate<R: Rng + ?Sized>(mut node: Rc<RefCell<Node>>, rng: &mut R) -> Result<
             }
         }
     }
-    let value = eval_game(&node.borrow().game_data.game);
+    let value = node.borrow().game_data.value;
     node.borrow_mut().sum_value += value;
     while let Some(parent) = &node.clone().borrow().parent {
         match parent.upgrade() {
@@ -234,19 +254,27 @@ fn iterate<R: Rng + ?Sized>(mut node: Rc<RefCell<Node>>, rng: &mut R) -> Result<
     Ok(())
 }
 
-#[derive(Copy, Clone, Debug, Default)]
-pub struct MctsPuctBot {}
+#[derive(Clone, Debug)]
+pub struct MctsPuctBot {
+    pub num_iterations: usize,
+    last_think_info: Option<ThinkInfo>,
+}
+
+impl Default for MctsPuctBot {
+    fn default() -> Self { Self { num_iterations: NUM_ITERATIONS, last_think_info: None } }
+}
 
 impl Bot for MctsPuctBot {
     fn think(&mut self, game: &Game<'static>) -> Result<Action, Box<dyn Error>> {
         let mut rng = StdRng::seed_from_u64(0);
         let mut game = game.clone();
         game.state.next_pieces.remove_invisible();
+        game.set_color_tracking(false);
         let game_data = GameData::new(Action::Hold /* dummy */, game, &mut rng)?;
         let root = Rc::new(RefCell::new(
             Node::new(None, 0, game_data)
         ));
-        for _ in 0..NUM_ITERATIONS {
+        for _ in 0..self.num_iterations {
             iterate(root.clone(), &mut rng)?;
         }
         // root.borrow().visit(|node| {
@@ -256,9 +284,24 @@ impl Bot for MctsPuctBot {
         //         node.children.len(),
         //     );
         // });
-        let action = root.borrow().best_action();
-        Ok(action.unwrap())
+        let root = root.borrow();
+        let action = root.best_action().unwrap();
+        let (num_expanded_nodes, max_depth) = root.count_nodes();
+        let candidate_scores = root.children.iter()
+            .map(|n| {
+                let n = n.borrow();
+                (n.game_data.by, n.mean_value())
+            })
+            .collect::<Vec<_>>();
+        self.last_think_info = Some(ThinkInfo {
+            num_expanded_nodes,
+            max_depth,
+            chosen_score: candidate_scores.iter().find(|(a, _)| *a == action).map(|(_, s)| *s),
+            candidate_scores,
+        });
+        Ok(action)
     }
+    fn last_think_info(&self) -> Option<ThinkInfo> { self.last_think_info.clone() }
 }
 
 #[cfg(test)]
@@ -273,5 +316,8 @@ mod tests {
         let mut bot = MctsPuctBot::default();
         let game = runner.run_with_no_hooks(&mut bot).unwrap();
         assert!(game.stats.lock > 1);
+        let info = bot.last_think_info().unwrap();
+        assert!(info.num_expanded_nodes >= info.candidate_scores.len());
+        assert!(info.chosen_score.is_some());
     }
 }
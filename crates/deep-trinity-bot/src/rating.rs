@@ -0,0 +1,142 @@
+//! Incremental Elo ratings of bots across evaluation rounds, persisted to JSON.
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::path::Path;
+use serde::{Deserialize, Serialize};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum MatchOutcome {
+    Win,
+    Loss,
+    Draw,
+}
+
+impl MatchOutcome {
+    /// The score from the perspective of the first player.
+    pub fn score(&self) -> f64 {
+        match self {
+            MatchOutcome::Win => 1.0,
+            MatchOutcome::Loss => 0.0,
+            MatchOutcome::Draw => 0.5,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Rating {
+    pub rating: f64,
+    pub wins: u32,
+    pub losses: u32,
+    pub draws: u32,
+}
+
+impl Rating {
+    pub fn new(rating: f64) -> Self { Self { rating, wins: 0, losses: 0, draws: 0 } }
+    pub fn num_games(&self) -> u32 { self.wins + self.losses + self.draws }
+    fn count(&mut self, score: f64) {
+        if score > 0.5 {
+            self.wins += 1;
+        } else if score < 0.5 {
+            self.losses += 1;
+        } else {
+            self.draws += 1;
+        }
+    }
+}
+
+/// Returns the expected score of `a` against `b`.
+pub fn expected_score(a: f64, b: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf((b - a) / 400.0))
+}
+
+/// Elo ratings keyed by bot names (e.g. with versions).
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RatingTracker {
+    pub k_factor: f64,
+    pub initial_rating: f64,
+    pub ratings: BTreeMap<String, Rating>,
+}
+
+impl Default for RatingTracker {
+    fn default() -> Self { Self::new(32.0, 1500.0) }
+}
+
+impl RatingTracker {
+    pub fn new(k_factor: f64, initial_rating: f64) -> Self {
+        Self { k_factor, initial_rating, ratings: BTreeMap::new() }
+    }
+    pub fn from_json(s: &str) -> Result<Self, Box<dyn Error>> { Ok(serde_json::from_str(s)?) }
+    pub fn to_json(&self) -> Result<String, Box<dyn Error>> { Ok(serde_json::to_string_pretty(self)?) }
+    /// Returns the default tracker if the file doesn't exist.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, Box<dyn Error>> {
+        match std::fs::read_to_string(path) {
+            Ok(s) => Self::from_json(&s),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), Box<dyn Error>> {
+        std::fs::write(path, self.to_json()?)?;
+        Ok(())
+    }
+    /// Unknown names have the initial rating.
+    pub fn rating(&self, name: &str) -> f64 {
+        self.ratings.get(name).map_or(self.initial_rating, |r| r.rating)
+    }
+    /// Updates the ratings by a single match.
+    pub fn record(&mut self, a: &str, b: &str, outcome: MatchOutcome) {
+        self.record_round(&[(a, b, outcome)]);
+    }
+    /// Updates the ratings by the matches of a round.
+    /// All the expected scores are calculated with the ratings before the round,
+    /// so the result doesn't depend on the order of the matches.
+    pub fn record_round(&mut self, matches: &[(&str, &str, MatchOutcome)]) {
+        let mut deltas = BTreeMap::<&str, f64>::new();
+        for (a, b, outcome) in matches {
+            let (ra, rb) = (self.rating(a), self.rating(b));
+            let score = outcome.score();
+            *deltas.entry(a).or_default() += self.k_factor * (score - expected_score(ra, rb));
+            *deltas.entry(b).or_default() += self.k_factor * ((1.0 - score) - expected_score(rb, ra));
+        }
+        for (a, b, outcome) in matches {
+            let score = outcome.score();
+            self.entry(a).count(score);
+            self.entry(b).count(1.0 - score);
+        }
+        for (name, delta) in deltas {
+            self.entry(name).rating += delta;
+        }
+    }
+    fn entry(&mut self, name: &str) -> &mut Rating {
+        let initial_rating = self.initial_rating;
+        self.ratings.entry(name.to_string()).or_insert_with(|| Rating::new(initial_rating))
+    }
+    /// Returns the names and ratings in the descending order of ratings.
+    pub fn standings(&self) -> Vec<(&str, &Rating)> {
+        let mut r = self.ratings.iter().map(|(name, r)| (name.as_str(), r)).collect::<Vec<_>>();
+        r.sort_by(|a, b| b.1.rating.total_cmp(&a.1.rating));
+        r
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rating_tracker() {
+        let mut tracker = RatingTracker::default();
+        tracker.record("v2", "v1", MatchOutcome::Win);
+        assert_eq!(1516.0, tracker.rating("v2"));
+        assert_eq!(1484.0, tracker.rating("v1"));
+        tracker.record_round(&[("v1", "v3", MatchOutcome::Draw), ("v2", "v3", MatchOutcome::Win)]);
+        assert_eq!(vec!["v2", "v1", "v3"], tracker.standings().iter().map(|(name, _)| *name).collect::<Vec<_>>());
+        assert_eq!(2, tracker.ratings["v2"].wins);
+        assert_eq!(1, tracker.ratings["v3"].draws);
+        let total = tracker.ratings.values().map(|r| r.rating).sum::<f64>();
+        assert!((total - 1500.0 * 3.0).abs() < 1e-9);
+
+        let restored = RatingTracker::from_json(&tracker.to_json().unwrap()).unwrap();
+        assert_eq!(tracker, restored);
+    }
+}
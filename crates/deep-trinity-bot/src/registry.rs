@@ -0,0 +1,150 @@
+use std::collections::{BTreeMap, HashMap};
+use std::error::Error;
+use std::str::FromStr;
+use crate::Bot;
+
+/// Parameters for bot constructors, e.g. parsed from config files.
+pub type BotParams = HashMap<String, String>;
+pub type BotConstructor = fn(&BotParams) -> Result<Box<dyn Bot>, Box<dyn Error>>;
+
+/// Returns the parsed value of `key`, or `default` if not specified.
+pub fn get_param<T: FromStr>(params: &BotParams, key: &str, default: T) -> Result<T, Box<dyn Error>> {
+    match params.get(key) {
+        Some(v) => v.parse::<T>().map_err(|_| format!("invalid value for {}: {}", key, v).into()),
+        None => Ok(default),
+    }
+}
+
+/// Returns `Err` if `params` has any key other than `keys`, e.g. a typo in a config file.
+pub fn check_param_keys(params: &BotParams, keys: &[&str]) -> Result<(), Box<dyn Error>> {
+    let mut unknown = params.keys().filter(|k| !keys.contains(&k.as_str())).cloned().collect::<Vec<_>>();
+    if unknown.is_empty() {
+        return Ok(());
+    }
+    unknown.sort();
+    Err(format!("unknown params: {}", unknown.join(", ")).into())
+}
+
+/// Registry to instantiate bots by name.
+/// `BotFactory::default()` has the built-in bots.
+#[derive(Clone, Debug)]
+pub struct BotFactory {
+    constructors: BTreeMap<String, BotConstructor>,
+}
+
+impl BotFactory {
+    /// Creates an empty registry.
+    pub fn new() -> Self { Self { constructors: BTreeMap::new() } }
+    /// Registers the constructor for `name`. The existing one is replaced.
+    pub fn register(&mut self, name: &str, constructor: BotConstructor) {
+        self.constructors.insert(name.to_string(), constructor);
+    }
+    pub fn contains(&self, name: &str) -> bool { self.constructors.contains_key(name) }
+    /// Returns the registered names in the sorted order.
+    pub fn names(&self) -> impl Iterator<Item=&str> {
+        self.constructors.keys().map(|s| s.as_str())
+    }
+    pub fn create(&self, name: &str, params: &BotParams) -> Result<Box<dyn Bot>, Box<dyn Error>> {
+        match self.constructors.get(name) {
+            Some(constructor) => constructor(params),
+            None => Err(format!("unknown bot: {}", name).into()),
+        }
+    }
+}
+
+impl Default for BotFactory {
+    fn default() -> Self {
+        let mut factory = Self::new();
+        factory.register("simple", |params| {
+            check_param_keys(params, &[])?;
+            Ok(Box::new(crate::simple::SimpleBot::default()))
+        });
+        factory.register("simple_tree", |params| {
+            if !params.contains_key("max_nodes") {
+                check_param_keys(params, &["chance_depth", "st_stacking", "prune_dominated", "commitment", "repair_depth"])?;
+                let mut bot = crate::simple_tree::SimpleTreeBot::default();
+                bot.set_chance_depth(get_param(params, "chance_depth", 0)?);
+                bot.set_st_stacking(get_param(params, "st_stacking", false)?);
+                bot.set_prune_dominated(get_param(params, "prune_dominated", false)?);
+                bot.set_commitment(get_param(params, "commitment", 0)?);
+                bot.set_repair_depth(get_param(params, "repair_depth", 0)?);
+                return Ok(Box::new(bot));
+            }
+            check_param_keys(params, &["max_nodes", "max_depth", "step_bound", "st_stacking", "prune_dominated", "commitment", "repair_depth"])?;
+            let max_nodes = get_param(params, "max_nodes", 0)?;
+            let max_depth = get_param(params, "max_depth", 3)?;
+            if max_nodes == 0 || max_depth == 0 {
+                return Err("max_nodes and max_depth should be positive".into());
+            }
+            let mut bot = crate::simple_tree::SimpleTreeBot::best_first(max_nodes, max_depth);
+            if params.contains_key("step_bound") {
+                bot.set_step_bound(get_param(params, "step_bound", 0.0)?);
+            }
+            bot.set_st_stacking(get_param(params, "st_stacking", false)?);
+            bot.set_prune_dominated(get_param(params, "prune_dominated", false)?);
+            bot.set_commitment(get_param(params, "commitment", 0)?);
+            bot.set_repair_depth(get_param(params, "repair_depth", 0)?);
+            Ok(Box::new(bot))
+        });
+        factory.register("four_wide", |params| {
+            check_param_keys(params, &["well_x", "min_depth", "exit_height"])?;
+            let mut bot = crate::four_wide::FourWideBot::default();
+            bot.well_x = get_param(params, "well_x", bot.well_x)?;
+            bot.min_depth = get_param(params, "min_depth", bot.min_depth)?;
+            bot.exit_height = get_param(params, "exit_height", bot.exit_height)?;
+            Ok(Box::new(bot))
+        });
+        factory.register("tree", |params| {
+            check_param_keys(params, &[])?;
+            Ok(Box::new(crate::tree::TreeBot::default()))
+        });
+        #[cfg(feature = "mcts")]
+        factory.register("mcts_puct", |params| {
+            check_param_keys(params, &["num_iterations"])?;
+            let mut bot = crate::mcts_puct::MctsPuctBot::default();
+            bot.num_iterations = get_param(params, "num_iterations", bot.num_iterations)?;
+            Ok(Box::new(bot))
+        });
+        factory
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bot_factory() {
+        let factory = BotFactory::default();
+        assert!(factory.contains("simple"));
+        assert!(factory.names().any(|name| name == "simple_tree"));
+        let mut bot = factory.create("simple", &BotParams::new()).unwrap();
+        let mut game = crate::Game::default();
+        game.supply_next_pieces(&[deep_trinity_core::Piece::O]);
+        game.setup_falling_piece(None).unwrap();
+        assert!(bot.think(&game).is_ok());
+        assert!(factory.create("unknown", &BotParams::new()).is_err());
+
+        let mut params = BotParams::new();
+        params.insert("num_iterations".into(), "x".into());
+        assert!(get_param(&params, "num_iterations", 1usize).is_err());
+        assert_eq!(2, get_param(&params, "missing", 2usize).unwrap());
+
+        let mut params = BotParams::new();
+        params.insert("max_nodes".into(), "0".into());
+        assert!(factory.create("simple_tree", &params).is_err());
+        params.insert("max_nodes".into(), "10".into());
+        params.insert("max_depth".into(), "0".into());
+        assert!(factory.create("simple_tree", &params).is_err());
+
+        // Unknown keys.
+        params.insert("max_depth".into(), "1".into());
+        assert!(factory.create("simple_tree", &params).is_ok());
+        params.insert("chance_depth".into(), "1".into());
+        params.insert("max_dpeth".into(), "1".into());
+        let err = factory.create("simple_tree", &params).err().unwrap();
+        assert_eq!("unknown params: chance_depth, max_dpeth", err.to_string());
+        assert!(factory.create("simple", &params).is_err());
+        assert!(check_param_keys(&BotParams::new(), &[]).is_ok());
+    }
+}
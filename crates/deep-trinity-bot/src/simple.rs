@@ -7,7 +7,9 @@ pub struct SimpleBot {}
 
 impl Bot for SimpleBot {
     fn think(&mut self, game: &Game) -> Result<Action, Box<dyn Error>> {
-        let candidates = game.get_move_candidates()?;
+        let mut candidates = game.get_move_candidates()?.into_iter().collect::<Vec<_>>();
+        // Sort to make tie-breaks deterministic.
+        candidates.sort();
         if candidates.is_empty() {
             return Err("no movable placements".into());
         }
@@ -21,7 +23,7 @@ impl Bot for SimpleBot {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::BotRunner;
+    use crate::{BotRunner, DummyBotRunnerHooks, GameClock};
 
     #[test]
     fn test_simple_bot() {
@@ -32,4 +34,25 @@ mod tests {
         // let game = test_bot(&mut bot, seed, 100, false).unwrap();
         assert!(game.stats.lock > 40);
     }
+
+    #[test]
+    fn test_run_summary() {
+        let mut runner = BotRunner::new(20, true, Some(0), false);
+        let (game, summary) = runner.run_with_summary(&mut SimpleBot::default(), &mut DummyBotRunnerHooks).unwrap();
+        assert_eq!(game.stats.lock as usize, summary.num_pieces);
+        assert_eq!(None, summary.pps());
+        assert_eq!(0, summary.num_misdrops);
+
+        runner.set_clock(GameClock::new(100.0, 10.0));
+        let (_, summary) = runner.run_with_summary(&mut SimpleBot::default(), &mut DummyBotRunnerHooks).unwrap();
+        assert!(summary.num_inputs > 0);
+        let elapsed = 100.0 * summary.num_pieces as f32 + 10.0 * summary.num_inputs as f32;
+        assert!((summary.elapsed_ms - elapsed).abs() < 1e-3);
+        assert!(summary.pps().unwrap() < 10.0);
+        assert!(summary.apm().is_some());
+
+        runner.set_misdrop_rate(1.0);
+        let (_, summary) = runner.run_with_summary(&mut SimpleBot::default(), &mut DummyBotRunnerHooks).unwrap();
+        assert_eq!(summary.num_pieces, summary.num_misdrops);
+    }
 }
@@ -1,10 +1,13 @@
 use super::Bot;
-use deep_trinity_core::{Game, Placement, StatisticsEntryType, TSpin, LineClear, Statistics, MoveTransition};
+use deep_trinity_core::{Game, Placement, TSpin, LineClear, LockOutcome, MoveTransition, Piece, PIECES};
+use deep_trinity_core::helper::{PieceHistory, StackingFeatures, infer_bag_state, prune_dominated_transitions, pieces_to_repair};
+use deep_trinity_core::bot::{Plan, PlanStep, PlanExecutor, PlanDivergence};
 use deep_trinity_grid::Grid;
 use std::rc::{Weak, Rc};
 use std::cell::RefCell;
-use std::collections::HashMap;
-use crate::Action;
+use std::collections::{HashMap, BinaryHeap};
+use std::cmp::Ordering;
+use crate::{Action, ThinkInfo};
 use std::error::Error;
 
 const BUDGET: f32 = 5.0;
@@ -15,7 +18,7 @@ const CONSUMPTION_GROWTH_IN_MOVE: f32 = 0.0;
 fn eval_state(game: &Game) -> f32 {
     let pf = &game.state.playfield;
     let n = pf.grid.num_covered_empty_cells() as f32;
-    let threshold = pf.width() as f32 * pf.height() as f32 / 20.0;
+    let threshold = pf.width() as f32 * pf.visible_height as f32 / 20.0;
     let r = if n > threshold {
         0.0
     } else {
@@ -24,21 +27,25 @@ fn eval_state(game: &Game) -> f32 {
     r
 }
 
-fn calc_reward(diff: &Statistics) -> f32 {
+fn calc_reward(outcome: &LockOutcome) -> f32 {
     let mut reward = 0.0;
-    for (ent_type, val) in &[
-        (StatisticsEntryType::LineClear(LineClear::new(1, None)), 0.1),
-        (StatisticsEntryType::LineClear(LineClear::new(2, None)), 0.2),
-        (StatisticsEntryType::LineClear(LineClear::new(3, None)), 0.3),
-        (StatisticsEntryType::LineClear(LineClear::new(4, None)), 5.0),
-        (StatisticsEntryType::LineClear(LineClear::new(1, Some(TSpin::Standard))), 1.0),
-        (StatisticsEntryType::LineClear(LineClear::new(2, Some(TSpin::Standard))), 5.0),
-        (StatisticsEntryType::LineClear(LineClear::new(3, Some(TSpin::Standard))), 5.0),
-        (StatisticsEntryType::LineClear(LineClear::new(1, Some(TSpin::Mini))), 0.2),
-        (StatisticsEntryType::LineClear(LineClear::new(2, Some(TSpin::Mini))), 0.2),
-        (StatisticsEntryType::PerfectClear, 5.0),
+    for (lc, val) in &[
+        (LineClear::new(1, None), 0.1),
+        (LineClear::new(2, None), 0.2),
+        (LineClear::new(3, None), 0.3),
+        (LineClear::new(4, None), 5.0),
+        (LineClear::new(1, Some(TSpin::Standard)), 1.0),
+        (LineClear::new(2, Some(TSpin::Standard)), 5.0),
+        (LineClear::new(3, Some(TSpin::Standard)), 5.0),
+        (LineClear::new(1, Some(TSpin::Mini)), 0.2),
+        (LineClear::new(2, Some(TSpin::Mini)), 0.2),
     ] {
-        reward += diff.get(*ent_type) as f32 * val;
+        if outcome.line_clear == *lc {
+            reward += val;
+        }
+    }
+    if outcome.perfect_clear {
+        reward += 5.0;
     }
     reward
 }
@@ -47,10 +54,60 @@ fn eval_placement(p: &Placement) -> f32 {
     1.0 - (p.pos.1 + 5) as f32 / 50.0
 }
 
+/// Upper bound of the reward of a step by `simulate()`:
+/// placement (pos.1 >= -5) + tetris or TSD/TST + perfect clear + state.
+const MAX_STEP_REWARD: f32 = 1.0 * 0.2 + (5.0 + 5.0) * 1.0 + 1.0 * 0.5;
+
+const ST_OVERHANG_REWARD: f32 = 0.3;
+const SZ_CHAIN_PENALTY: f32 = 0.05;
+const REPAIR_PENALTY_PER_PIECE: f32 = 0.1;
+
+/// Optional terms of the evaluation and the expansion.
+#[derive(Copy, Clone, Debug, Default)]
+struct EvalConfig {
+    st_stacking: bool,
+    prune_dominated: bool,
+    /// 0 disables the repair penalty.
+    repair_depth: usize,
+}
+
+impl EvalConfig {
+    fn max_step_reward(&self) -> f32 {
+        MAX_STEP_REWARD + if self.st_stacking { ST_OVERHANG_REWARD } else { 0.0 }
+    }
+}
+
+/// Returns the move candidates in a deterministic order.
+fn move_candidates(game: &Game, conf: &EvalConfig) -> Result<Vec<MoveTransition>, Box<dyn Error>> {
+    let mut candidates = game.get_move_candidates()?.into_iter().collect::<Vec<_>>();
+    candidates.sort();
+    if conf.prune_dominated {
+        let spec = game.state.falling_piece.as_ref().unwrap().piece_spec;
+        candidates = prune_dominated_transitions(&game.state.playfield, spec, &candidates);
+    }
+    Ok(candidates)
+}
+
+/// Rewards keeping an ST notch, and punishes S/Z dependencies which restrict the next pieces.
+fn eval_st_stacking(game: &Game) -> f32 {
+    let features = StackingFeatures::new(&game.state.playfield);
+    let r = if features.has_st_overhang() { ST_OVERHANG_REWARD } else { 0.0 };
+    r - features.sz_chain_len as f32 * SZ_CHAIN_PENALTY
+}
+
+/// Punishes holes by the pieces to uncover them. Unrepairable holes within `depth` pieces
+/// count as `depth + 1`.
+fn eval_repair(game: &Game, depth: usize) -> f32 {
+    let n = pieces_to_repair(game, depth).ok().flatten().unwrap_or(depth + 1);
+    -(n as f32) * REPAIR_PENALTY_PER_PIECE
+}
+
 #[derive(Debug)]
 struct Node {
     _parent: Option<Weak<RefCell<Node>>>,
     children: HashMap<Action, Rc<RefCell<Node>>>,
+    /// Children for each possible unseen piece with the probabilities when the queue runs out.
+    chance_children: Vec<(f32, Rc<RefCell<Node>>)>,
     game: Game<'static>,
     reward: f32,
     max_future_reward: f32,
@@ -61,6 +118,7 @@ impl Node {
         Self {
             _parent: parent,
             children: HashMap::new(),
+            chance_children: Vec::new(),
             game,
             reward,
             max_future_reward: 0.0,
@@ -69,7 +127,15 @@ impl Node {
     fn max_reward(&self) -> f32 { self.reward + self.max_future_reward }
 }
 
-fn expand(rc_node: Rc<RefCell<Node>>, budget: f32) -> Result<(), Box<dyn Error>> {
+/// State to expand chance nodes over the unseen pieces.
+struct ChanceContext {
+    /// The remaining number of unseen pieces to be expanded.
+    depth: usize,
+    /// The observed pieces followed by the pieces supplied in the expansion.
+    history: Vec<Piece>,
+}
+
+fn expand(rc_node: Rc<RefCell<Node>>, budget: f32, mut chance: Option<&mut ChanceContext>, conf: &EvalConfig) -> Result<(), Box<dyn Error>> {
     if budget <= 0.0 {
         return Ok(());
     }
@@ -77,22 +143,22 @@ fn expand(rc_node: Rc<RefCell<Node>>, budget: f32) -> Result<(), Box<dyn Error>>
     let mut max_future_reward = 0.0;
     {
         let mut node = rc_node.borrow_mut();
-        if node.game.state.can_hold {
+        if node.game.can_hold() {
             let mut next = node.game.clone();
             let ok = next.hold()?;
             let rc_child = Rc::new(RefCell::new(Node::new(next, 0.0, Some(Rc::downgrade(&rc_node)))));
             node.children.insert(Action::Hold, rc_child.clone());
             remain -= CONSUMPTION_BY_HOLD;
             if ok {
-                expand(rc_child.clone(), remain)?;
+                expand(rc_child.clone(), remain, chance.as_deref_mut(), conf)?;
             }
             max_future_reward = rc_child.borrow().max_reward();
         }
     }
-    let candidates = rc_node.borrow().game.get_move_candidates()?;
+    let candidates = move_candidates(&rc_node.borrow().game, conf)?;
     let mut children = candidates.iter()
         .map(|mt| {
-            let (simulated, reward) = simulate(&rc_node.borrow().game, mt);
+            let (simulated, reward) = simulate(&rc_node.borrow().game, mt, conf);
             let rc_child = Rc::new(RefCell::new(Node::new(simulated, reward, Some(Rc::downgrade(&rc_node)))));
             (mt, rc_child, reward)
         })
@@ -106,7 +172,9 @@ fn expand(rc_node: Rc<RefCell<Node>>, budget: f32) -> Result<(), Box<dyn Error>>
         }
         rc_node.borrow_mut().children.insert(Action::Move((*mt).clone()), rc_child.clone());
         if rc_child.borrow().game.state.falling_piece.is_some() {
-            expand(rc_child.clone(), remain)?;
+            expand(rc_child.clone(), remain, chance.as_deref_mut(), conf)?;
+        } else if let Some(chance) = chance.as_deref_mut().filter(|c| c.depth > 0) {
+            expand_chance(rc_child, remain, chance, conf)?;
         }
         let r = rc_child.borrow().max_reward();
         if max_future_reward < r {
@@ -119,7 +187,34 @@ fn expand(rc_node: Rc<RefCell<Node>>, budget: f32) -> Result<(), Box<dyn Error>>
     Ok(())
 }
 
-fn simulate(game: &Game<'static>, mt: &MoveTransition) -> (Game<'static>, f32) {
+/// Expands the node whose queue ran out for each possible next piece inferred from the bag.
+/// The expected reward is set as the future reward of the node.
+fn expand_chance(rc_node: &Rc<RefCell<Node>>, budget: f32, chance: &mut ChanceContext, conf: &EvalConfig) -> Result<(), Box<dyn Error>> {
+    let bag = infer_bag_state(&chance.history);
+    chance.depth -= 1;
+    let mut expected = 0.0;
+    for piece in PIECES {
+        let prob = bag.probability(piece);
+        if prob == 0.0 {
+            continue;
+        }
+        let mut game = rc_node.borrow().game.clone();
+        game.supply_next_pieces(&[piece]);
+        game.setup_falling_piece(None)?;
+        let rc_child = Rc::new(RefCell::new(Node::new(game, 0.0, Some(Rc::downgrade(rc_node)))));
+        chance.history.push(piece);
+        let r = expand(rc_child.clone(), budget, Some(chance), conf);
+        chance.history.pop();
+        r?;
+        expected += prob * rc_child.borrow().max_reward();
+        rc_node.borrow_mut().chance_children.push((prob, rc_child));
+    }
+    chance.depth += 1;
+    rc_node.borrow_mut().max_future_reward = expected;
+    Ok(())
+}
+
+fn simulate(game: &Game<'static>, mt: &MoveTransition, conf: &EvalConfig) -> (Game<'static>, f32) {
     let mut next_game = game.clone();
     let fp = next_game.state.falling_piece.as_mut().unwrap();
     if let Some(hint) = mt.hint {
@@ -130,40 +225,293 @@ fn simulate(game: &Game<'static>, mt: &MoveTransition) -> (Game<'static>, f32) {
         fp.placement = mt.placement;
     }
     debug_assert_eq!(mt.placement, fp.placement);
-    next_game.lock().unwrap();
-    let stats_diff = next_game.stats.clone() - game.stats.clone();
-    let reward =
+    let outcome = next_game.lock_ex().unwrap();
+    let mut reward =
         eval_placement(&mt.placement) * 0.2
-            + calc_reward(&stats_diff) * 1.0
+            + calc_reward(&outcome) * 1.0
             + eval_state(&next_game) * 0.5;
+    if conf.st_stacking {
+        reward += eval_st_stacking(&next_game);
+    }
+    if conf.repair_depth > 0 {
+        reward += eval_repair(&next_game, conf.repair_depth);
+    }
     (next_game, reward)
 }
 
-#[derive(Copy, Clone, Debug, Default)]
-pub struct SimpleTreeBot {}
+/// Creates the children of the node ordered by their rewards (higher first).
+fn new_children(rc_node: &Rc<RefCell<Node>>, conf: &EvalConfig) -> Result<Vec<(Action, Rc<RefCell<Node>>)>, Box<dyn Error>> {
+    let node = rc_node.borrow();
+    let mut children = Vec::new();
+    if node.game.can_hold() {
+        let mut next = node.game.clone();
+        next.hold()?;
+        children.push((Action::Hold, Rc::new(RefCell::new(Node::new(next, 0.0, Some(Rc::downgrade(rc_node)))))));
+    }
+    for mt in move_candidates(&node.game, conf)? {
+        let (simulated, reward) = simulate(&node.game, &mt, conf);
+        children.push((Action::Move(mt), Rc::new(RefCell::new(Node::new(simulated, reward, Some(Rc::downgrade(rc_node)))))));
+    }
+    // Stable sort to keep tie-breaks deterministic.
+    children.sort_by(|(_, n1), (_, n2)| n2.borrow().reward.total_cmp(&n1.borrow().reward));
+    Ok(children)
+}
+
+struct OpenEntry {
+    /// Upper bound of the total reward via this node.
+    f: f32,
+    seq: usize,
+    depth: usize,
+    g: f32,
+    node: Rc<RefCell<Node>>,
+}
+
+impl PartialEq for OpenEntry {
+    fn eq(&self, other: &Self) -> bool { self.cmp(other) == Ordering::Equal }
+}
+
+impl Eq for OpenEntry {}
+
+impl PartialOrd for OpenEntry {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> { Some(self.cmp(other)) }
+}
+
+impl Ord for OpenEntry {
+    /// Higher `f` first, and then the earlier pushed first.
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.f.total_cmp(&other.f).then_with(|| other.seq.cmp(&self.seq))
+    }
+}
+
+/// Expands the most promising leaf first until `max_nodes` nodes are created.
+/// Leaves whose bound can't exceed the best reward reached at `max_depth` are pruned.
+fn expand_best_first(root: &Rc<RefCell<Node>>, max_nodes: usize, max_depth: usize, step_bound: f32, conf: &EvalConfig) -> Result<(), Box<dyn Error>> {
+    let h = |depth: usize| (max_depth - depth) as f32 * step_bound;
+    let mut open = BinaryHeap::new();
+    let mut seq = 0;
+    open.push(OpenEntry { f: h(0), seq, depth: 0, g: 0.0, node: root.clone() });
+    let mut num_nodes = 0;
+    let mut best_complete = f32::MIN;
+    while let Some(ent) = open.pop() {
+        if ent.f <= best_complete {
+            break;
+        }
+        let is_final = ent.node.borrow().game.state.falling_piece.is_none();
+        if ent.depth == max_depth || is_final {
+            best_complete = best_complete.max(ent.g);
+            continue;
+        }
+        if num_nodes >= max_nodes {
+            break;
+        }
+        for (action, child) in new_children(&ent.node, conf)? {
+            let g = ent.g + child.borrow().reward;
+            seq += 1;
+            num_nodes += 1;
+            open.push(OpenEntry { f: g + h(ent.depth + 1), seq, depth: ent.depth + 1, g, node: child.clone() });
+            ent.node.borrow_mut().children.insert(action, child);
+        }
+    }
+    update_max_future_reward(&mut root.borrow_mut());
+    Ok(())
+}
+
+fn update_max_future_reward(node: &mut Node) {
+    let mut r = 0.0f32;
+    for child in node.children.values() {
+        let mut child = child.borrow_mut();
+        update_max_future_reward(&mut child);
+        r = r.max(child.max_reward());
+    }
+    node.max_future_reward = r;
+}
+
+fn to_action(action: deep_trinity_core::bot::Action) -> Action {
+    match action {
+        deep_trinity_core::bot::Action::Move(mt) => Action::Move(mt),
+        deep_trinity_core::bot::Action::Hold => Action::Hold,
+    }
+}
+
+/// Follows the best children from the root.
+fn best_plan(root: &Rc<RefCell<Node>>) -> Plan {
+    let mut plan = Plan::default();
+    let mut hold = false;
+    let mut node = root.clone();
+    loop {
+        let best = node.borrow().children.iter()
+            .max_by(|(_, n1), (_, n2)| n1.borrow().max_reward().total_cmp(&n2.borrow().max_reward()))
+            .map(|(a, n)| (*a, n.clone()));
+        let (action, child) = match best {
+            Some(v) => v,
+            None => break,
+        };
+        match action {
+            Action::Hold => hold = true,
+            Action::Move(mt) => {
+                let piece = node.borrow().game.state.falling_piece.as_ref().unwrap().piece();
+                plan.push(PlanStep::new(hold, piece, mt));
+                hold = false;
+            }
+        }
+        node = child;
+    }
+    plan
+}
+
+/// Returns the number of the descendant nodes and the max depth from `node`.
+fn count_nodes(node: &Node) -> (usize, usize) {
+    let mut num = 0;
+    let mut depth = 0;
+    for child in node.children.values().chain(node.chance_children.iter().map(|(_, c)| c)) {
+        let (n, d) = count_nodes(&child.borrow());
+        num += n + 1;
+        depth = depth.max(d + 1);
+    }
+    (num, depth)
+}
+
+#[derive(Clone, Debug)]
+pub struct SimpleTreeBot {
+    best_first: Option<BestFirstParams>,
+    chance_depth: usize,
+    piece_history: PieceHistory,
+    eval: EvalConfig,
+    /// The number of pieces to commit to. See [SimpleTreeBot::set_commitment].
+    commitment: usize,
+    executor: PlanExecutor,
+    last_plan: Option<Plan>,
+    last_plan_divergence: Option<PlanDivergence>,
+    last_think_info: Option<ThinkInfo>,
+}
+
+impl Default for SimpleTreeBot {
+    fn default() -> Self {
+        Self {
+            best_first: None,
+            chance_depth: 0,
+            piece_history: PieceHistory::new(PIECE_HISTORY_LEN),
+            eval: EvalConfig::default(),
+            commitment: 0,
+            executor: PlanExecutor::default(),
+            last_plan: None,
+            last_plan_divergence: None,
+            last_think_info: None,
+        }
+    }
+}
+
+const PIECE_HISTORY_LEN: usize = 28;
+
+#[derive(Copy, Clone, Debug)]
+struct BestFirstParams {
+    max_nodes: usize,
+    max_depth: usize,
+    /// `None` for the bound of the evaluation.
+    step_bound: Option<f32>,
+}
+
+impl SimpleTreeBot {
+    /// Uses the best-first expansion within `max_nodes` nodes instead of the default budget-based one.
+    pub fn best_first(max_nodes: usize, max_depth: usize) -> Self {
+        Self {
+            best_first: Some(BestFirstParams { max_nodes, max_depth, step_bound: None }),
+            ..Default::default()
+        }
+    }
+    /// Expands up to `depth` unseen pieces beyond the visible queue as chance nodes, averaging the
+    /// rewards by the probabilities inferred from the observed 7-bag sequence.
+    /// Only for the default budget-based expansion.
+    pub fn set_chance_depth(&mut self, depth: usize) { self.chance_depth = depth; }
+    /// Overrides the assumed max reward per step of the best-first expansion.
+    /// Smaller values than the default make the search greedier (and inadmissible) so it goes deeper.
+    pub fn set_step_bound(&mut self, step_bound: f32) {
+        if let Some(params) = self.best_first.as_mut() {
+            params.step_bound = Some(step_bound);
+        }
+    }
+    /// Enables the "stacking style" evaluation rewarding to maintain an ST overhang
+    /// (see [deep_trinity_core::helper::st_notches]).
+    pub fn set_st_stacking(&mut self, enabled: bool) { self.eval.st_stacking = enabled; }
+    /// Skips the placements obviously worse than another one
+    /// (see [deep_trinity_core::helper::prune_dominated]).
+    pub fn set_prune_dominated(&mut self, enabled: bool) { self.eval.prune_dominated = enabled; }
+    /// Punishes holes by the pieces to uncover them searched up to `depth` pieces
+    /// (see [deep_trinity_core::helper::find_repair_plan]). 0 (default) disables it.
+    pub fn set_repair_depth(&mut self, depth: usize) { self.eval.repair_depth = depth; }
+    /// Commits to the best line of the search for up to `num_pieces` pieces and replays it without
+    /// searching while the game matches it. 0 (default) searches on every action.
+    pub fn set_commitment(&mut self, num_pieces: usize) {
+        self.commitment = num_pieces;
+        self.executor = PlanExecutor::default();
+    }
+    /// The plan committed at the last search.
+    pub fn last_plan(&self) -> Option<&Plan> { self.last_plan.as_ref() }
+}
 
 impl Bot for SimpleTreeBot {
     fn think(&mut self, game: &Game<'static>) -> Result<Action, Box<dyn Error>> {
+        match self.executor.poll(game) {
+            Ok(Some(action)) => {
+                self.last_plan_divergence = None;
+                // Nothing is searched for the action.
+                self.last_think_info = None;
+                return Ok(to_action(action));
+            }
+            Ok(None) => self.last_plan_divergence = None,
+            Err(divergence) => self.last_plan_divergence = Some(divergence),
+        }
+        let original = game;
         let mut game = game.clone();
         game.state.next_pieces.remove_invisible();
+        game.set_color_tracking(false);
+        let visible = game.state.next_pieces.iter().copied().collect::<Vec<_>>();
+        self.piece_history.observe(&visible);
         let node = Rc::new(RefCell::new(Node::new(game, 0.0, None)));
-        expand(node.clone(), BUDGET)?;
-        let action = node.borrow().children.iter()
-            .max_by(|(_, n1), (_, n2)| {
-                let n1 = n1.borrow();
-                let n2 = n2.borrow();
-                n1.max_reward().partial_cmp(&n2.max_reward()).unwrap()
-            })
-            .map(|(a, _)| a.clone())
-            .unwrap();
+        match self.best_first {
+            Some(p) => {
+                let step_bound = p.step_bound.unwrap_or_else(|| self.eval.max_step_reward());
+                expand_best_first(&node, p.max_nodes, p.max_depth, step_bound, &self.eval)?
+            }
+            None if self.chance_depth > 0 => {
+                let mut chance = ChanceContext { depth: self.chance_depth, history: self.piece_history.pieces().to_vec() };
+                expand(node.clone(), BUDGET, Some(&mut chance), &self.eval)?
+            }
+            None => expand(node.clone(), BUDGET, None, &self.eval)?,
+        }
+        let (action, score) = node.borrow().children.iter()
+            .max_by(|(_, n1), (_, n2)| n1.borrow().max_reward().total_cmp(&n2.borrow().max_reward()))
+            .map(|(a, n)| (*a, n.borrow().max_reward()))
+            .ok_or("no candidates")?;
+        let root = node.borrow();
+        let (num_expanded_nodes, max_depth) = count_nodes(&root);
+        self.last_think_info = Some(ThinkInfo {
+            num_expanded_nodes,
+            max_depth,
+            candidate_scores: root.children.iter().map(|(a, n)| (*a, n.borrow().max_reward())).collect(),
+            chosen_score: Some(score),
+        });
+        if self.commitment > 0 {
+            drop(root);
+            let mut plan = best_plan(&node);
+            plan.truncate(self.commitment);
+            self.executor = PlanExecutor::new(plan.clone());
+            self.last_plan = Some(plan);
+            if let Some(action) = self.executor.next_action(original) {
+                return Ok(to_action(action));
+            }
+        }
         Ok(action)
     }
+    fn last_think_info(&self) -> Option<ThinkInfo> { self.last_think_info.clone() }
+    fn last_plan_divergence(&self) -> Option<PlanDivergence> { self.last_plan_divergence }
 }
 
 #[cfg(test)]
 mod tests {
     use super::SimpleTreeBot;
-    use crate::BotRunner;
+    use crate::{Bot, BotRunner};
+    use deep_trinity_core::Piece;
 
     #[test]
     #[ignore]
@@ -174,4 +522,160 @@ mod tests {
         let game = runner.run_with_no_hooks(&mut bot).unwrap();
         assert!(game.stats.lock > 5);
     }
+
+    #[test]
+    fn test_chance_nodes() {
+        let mut game = deep_trinity_core::Game::default();
+        game.set_num_visible_next_pieces(1);
+        game.supply_next_pieces(&[deep_trinity_core::Piece::T, deep_trinity_core::Piece::O]);
+        game.setup_falling_piece(None).unwrap();
+        let mut bot = SimpleTreeBot::default();
+        bot.think(&game).unwrap();
+        let without_chance = bot.last_think_info().unwrap();
+        bot.set_chance_depth(1);
+        bot.think(&game).unwrap();
+        let with_chance = bot.last_think_info().unwrap();
+        assert!(with_chance.max_depth > without_chance.max_depth);
+        assert!(with_chance.num_expanded_nodes > without_chance.num_expanded_nodes);
+    }
+
+    #[test]
+    fn test_st_stacking() {
+        use deep_trinity_core::{Piece, Playfield};
+
+        let mut game = deep_trinity_core::Game::default();
+        game.state.playfield.set_rows_with_strs((0, 0).into(), &[
+            "@         ",
+            "@     @@@@",
+            "@@@ @@@@@@",
+        ]);
+        game.set_num_visible_next_pieces(0);
+        game.supply_next_pieces(&[Piece::S]);
+        game.setup_falling_piece(None).unwrap();
+        let mut bot = SimpleTreeBot::default();
+        bot.set_st_stacking(true);
+        let locked = |bot: &mut SimpleTreeBot| -> Playfield<'static> {
+            let mut game = game.clone();
+            match bot.think(&game).unwrap() {
+                crate::Action::Move(mt) => {
+                    game.state.falling_piece.as_mut().unwrap().placement = mt.placement;
+                    game.lock().unwrap();
+                }
+                crate::Action::Hold => unreachable!(),
+            }
+            game.state.playfield
+        };
+        let pf = locked(&mut bot);
+        assert!(!deep_trinity_core::helper::st_notches(&pf).is_empty());
+        bot.set_st_stacking(false);
+        let pf = locked(&mut bot);
+        assert!(deep_trinity_core::helper::st_notches(&pf).is_empty());
+    }
+
+    #[test]
+    fn test_repair_depth() {
+        let mut game = deep_trinity_core::Game::default();
+        game.state.playfield.set_rows_with_strs((0, 0).into(), &[
+            "@@@@@@@@@ ",
+            "@@@ @@@@@@",
+        ]);
+        game.supply_next_pieces(&[Piece::I, Piece::O, Piece::T]);
+        game.setup_falling_piece(None).unwrap();
+        let mut bot = SimpleTreeBot::default();
+        bot.set_repair_depth(1);
+        match bot.think(&game).unwrap() {
+            crate::Action::Move(mt) => {
+                game.state.falling_piece.as_mut().unwrap().placement = mt.placement;
+                game.lock().unwrap();
+            }
+            crate::Action::Hold => unreachable!(),
+        }
+        assert_eq!(0, deep_trinity_grid::Grid::num_covered_empty_cells(&game.state.playfield.grid));
+    }
+
+    #[test]
+    fn test_best_first() {
+        let runner = BotRunner::new(3, true, Some(0), false);
+        let mut bot = SimpleTreeBot::best_first(300, 4);
+        bot.set_step_bound(0.5);
+        let game = runner.run_with_no_hooks(&mut bot).unwrap();
+        assert!(game.stats.lock > 1);
+        let info = bot.last_think_info().unwrap();
+        assert!(info.max_depth >= 3);
+        // The budget can be exceeded by the children of the last expansion.
+        assert!(info.num_expanded_nodes < 400);
+
+        let mut game = deep_trinity_core::Game::default();
+        game.supply_next_pieces(&[Piece::T, Piece::O]);
+        game.setup_falling_piece(None).unwrap();
+        assert!(SimpleTreeBot::best_first(0, 3).think(&game).is_err());
+        assert!(SimpleTreeBot::best_first(10, 0).think(&game).is_err());
+    }
+
+    #[test]
+    fn test_prune_dominated() {
+        let runner = BotRunner::new(5, true, Some(0), false);
+        let mut bot = SimpleTreeBot::default();
+        bot.set_prune_dominated(true);
+        let game = runner.run_with_no_hooks(&mut bot).unwrap();
+        assert!(game.stats.lock > 2);
+    }
+
+    #[test]
+    fn test_commitment() {
+        let mut game = deep_trinity_core::Game::default();
+        game.supply_next_pieces(&[Piece::T, Piece::O, Piece::I, Piece::J, Piece::L, Piece::S]);
+        game.setup_falling_piece(None).unwrap();
+        let mut bot = SimpleTreeBot::default();
+        bot.set_commitment(2);
+        let action = bot.think(&game).unwrap();
+        assert!(bot.last_think_info().is_some());
+        let plan = bot.last_plan().unwrap().clone();
+        assert_eq!(2, plan.len());
+        plan.validate(&game).unwrap();
+
+        // The next action is replayed from the plan without searching.
+        let mut next = game.clone();
+        match action {
+            crate::Action::Move(mt) => {
+                next.state.falling_piece.as_mut().unwrap().placement = mt.placement;
+                next.lock().unwrap();
+            }
+            crate::Action::Hold => { next.hold().unwrap(); }
+        }
+        bot.think(&next).unwrap();
+        assert!(bot.last_think_info().is_none());
+
+        let runner = BotRunner::new(6, true, Some(0), false);
+        let game = runner.run_with_no_hooks(&mut bot).unwrap();
+        assert!(game.stats.lock > 3);
+    }
+
+    #[test]
+    fn test_replan_on_garbage() {
+        use deep_trinity_core::bot::PlanDivergence;
+        use crate::{BotRunnerHooks, Game};
+        use std::error::Error;
+
+        struct Hooks { n: usize, replans: Vec<PlanDivergence> }
+        impl BotRunnerHooks for Hooks {
+            fn on_iter(&mut self, game: &mut Game) -> Result<bool, Box<dyn Error>> {
+                self.n += 1;
+                if self.n.is_multiple_of(2) {
+                    game.receive_garbage(&[0]);
+                }
+                Ok(true)
+            }
+            fn on_replan(&mut self, _game: &Game, divergence: &PlanDivergence) -> Result<(), Box<dyn Error>> {
+                self.replans.push(*divergence);
+                Ok(())
+            }
+        }
+        let runner = BotRunner::new(6, true, Some(0), false);
+        let mut bot = SimpleTreeBot::default();
+        bot.set_commitment(3);
+        let mut hooks = Hooks { n: 0, replans: Vec::new() };
+        runner.run(&mut bot, &mut hooks).unwrap();
+        assert!(hooks.replans.contains(&PlanDivergence::Playfield));
+    }
 }
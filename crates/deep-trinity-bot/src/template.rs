@@ -1,15 +1,24 @@
 use std::collections::HashSet;
 use std::error::Error;
-use deep_trinity_grid::Vec2;
-use deep_trinity_core::{Orientation, Orientation::*, Piece, Move, MoveTransition, Placement, MovePathItem};
+use std::fmt;
+use deep_trinity_grid::{Grid, Cell as _, Vec2, X, Y};
+use deep_trinity_core::{Orientation, Orientation::*, Piece, Move, MoveTransition, Placement, MovePathItem, Playfield};
+use deep_trinity_core::bot::Plan;
+use deep_trinity_core::helper::{MoveDecisionResource, SkylineFingerprint};
 use crate::{Game, Bot, Action};
 
 pub type MoveName = &'static str;
 
+/// The names of the moves converted from plans, chained by the dependencies to keep the order.
+const PLAN_STEP_NAMES: [MoveName; 16] = [
+    "#0", "#1", "#2", "#3", "#4", "#5", "#6", "#7", "#8", "#9", "#10", "#11", "#12", "#13", "#14", "#15",
+];
+
 #[derive(Clone, Debug)]
 pub struct Opener {
     moves: Vec<(Piece, MoveTransition, MoveName, Vec<MoveName>)>,
     last_move: (Piece, MoveTransition),
+    continuation: Option<Box<Template>>,
 }
 
 impl Opener {
@@ -24,10 +33,222 @@ impl Opener {
                 MovePathItem::new(m, Placement::new(o, p))
             })),
         );
-        Self { moves, last_move }
+        Self { moves, last_move, continuation: None }
+    }
+    /// Converts the plan, e.g. a route found by a solver, where the pieces are placed in the order
+    /// of the plan, holding the other ones. Plans up to 16 steps are supported.
+    pub fn from_plan(plan: &Plan) -> Result<Self, &'static str> {
+        let (last, steps) = plan.steps.split_last().ok_or("empty plan")?;
+        if plan.len() > PLAN_STEP_NAMES.len() {
+            return Err("too long plan");
+        }
+        let moves = steps.iter().enumerate().map(|(i, step)| {
+            let deps = if i == 0 { vec![] } else { vec![PLAN_STEP_NAMES[i - 1]] };
+            (step.piece, step.transition, PLAN_STEP_NAMES[i], deps)
+        }).collect::<Vec<_>>();
+        // The last move is done after all the other ones.
+        Ok(Self { moves, last_move: (last.piece, last.transition), continuation: None })
+    }
+    /// Sets the template used after the last move of this opener.
+    pub fn then(mut self, continuation: Template) -> Self {
+        self.continuation = Some(Box::new(continuation));
+        self
+    }
+    /// Returns the opener for the mirrored playfield of `width`.
+    pub fn mirrored(&self, width: X) -> Self {
+        let mirror_mt = |piece: Piece, mt: &MoveTransition| -> MoveTransition {
+            MoveTransition::new(
+                mirror_placement(piece, &mt.placement, width),
+                mt.hint.map(|h| MovePathItem::new(mirror_move(h.by), mirror_placement(piece, &h.placement, width))),
+            )
+        };
+        Self {
+            moves: self.moves.iter()
+                .map(|(piece, mt, name, deps)| (mirror_piece(*piece), mirror_mt(*piece, mt), *name, deps.clone()))
+                .collect(),
+            last_move: (mirror_piece(self.last_move.0), mirror_mt(self.last_move.0, &self.last_move.1)),
+            continuation: self.continuation.as_ref().map(|t| Box::new(t.mirrored(width))),
+        }
+    }
+    /// Returns the opener with all the placements moved up by `dy`, e.g. for a higher stack.
+    pub fn translated(&self, dy: Y) -> Self {
+        let translate = |mt: &MoveTransition| -> MoveTransition {
+            let up = |p: &Placement| Placement::new(p.orientation, p.pos + Vec2(0, dy));
+            MoveTransition::new(up(&mt.placement), mt.hint.map(|h| MovePathItem::new(h.by, up(&h.placement))))
+        };
+        Self {
+            moves: self.moves.iter()
+                .map(|(piece, mt, name, deps)| (*piece, translate(mt), *name, deps.clone()))
+                .collect(),
+            last_move: (self.last_move.0, translate(&self.last_move.1)),
+            continuation: self.continuation.as_ref().map(|t| Box::new(t.translated(dy))),
+        }
+    }
+}
+
+fn mirror_piece(piece: Piece) -> Piece {
+    match piece {
+        Piece::S => Piece::Z,
+        Piece::Z => Piece::S,
+        Piece::L => Piece::J,
+        Piece::J => Piece::L,
+        _ => piece,
+    }
+}
+
+fn mirror_move(m: Move) -> Move {
+    match m {
+        Move::Shift(n) => Move::Shift(-n),
+        Move::Rotate(n) => Move::Rotate(-n),
+        Move::Drop(n) => Move::Drop(n),
+    }
+}
+
+/// Returns the leftmost x, the rightmost x and the bottom y of the blocks in the grid of the orientation.
+fn block_bounds(piece: Piece, orientation: Orientation) -> (X, X, Y) {
+    let g = &piece.default_spec().grids[orientation.to_usize()];
+    let (mut left, mut right, mut bottom) = (g.width(), 0, g.height());
+    for y in 0..g.height() {
+        for x in 0..g.width() {
+            if !g.cell((x, y).into()).is_empty() {
+                left = left.min(x);
+                right = right.max(x);
+                bottom = bottom.min(y);
+            }
+        }
+    }
+    (left, right, bottom)
+}
+
+fn mirror_placement(piece: Piece, placement: &Placement, width: X) -> Placement {
+    let orientation = match placement.orientation {
+        Orientation1 => Orientation3,
+        Orientation3 => Orientation1,
+        o => o,
+    };
+    let (_, right, bottom) = block_bounds(piece, placement.orientation);
+    let (mirrored_left, _, mirrored_bottom) = block_bounds(mirror_piece(piece), orientation);
+    let pos = Vec2(
+        width - 1 - (placement.pos.0 + right) - mirrored_left,
+        placement.pos.1 + bottom - mirrored_bottom,
+    );
+    Placement::new(orientation, pos)
+}
+
+/// A condition of the queue, i.e. the falling piece followed by the visible next pieces.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum QueueCondition {
+    Always,
+    Contains(Piece),
+    /// The first piece comes before the second one. Also true if only the first one is in the queue.
+    Before(Piece, Piece),
+    Not(Box<QueueCondition>),
+    All(Vec<QueueCondition>),
+    Any(Vec<QueueCondition>),
+}
+
+impl QueueCondition {
+    pub fn eval(&self, queue: &[Piece]) -> bool {
+        let position = |p: &Piece| queue.iter().position(|q| q == p);
+        match self {
+            QueueCondition::Always => true,
+            QueueCondition::Contains(p) => position(p).is_some(),
+            QueueCondition::Before(p1, p2) => match (position(p1), position(p2)) {
+                (Some(i1), Some(i2)) => i1 < i2,
+                (Some(_), None) => true,
+                _ => false,
+            },
+            QueueCondition::Not(c) => !c.eval(queue),
+            QueueCondition::All(cs) => cs.iter().all(|c| c.eval(queue)),
+            QueueCondition::Any(cs) => cs.iter().any(|c| c.eval(queue)),
+        }
+    }
+    /// Swaps S with Z and L with J.
+    pub fn mirrored(&self) -> Self {
+        match self {
+            QueueCondition::Always => QueueCondition::Always,
+            QueueCondition::Contains(p) => QueueCondition::Contains(mirror_piece(*p)),
+            QueueCondition::Before(p1, p2) => QueueCondition::Before(mirror_piece(*p1), mirror_piece(*p2)),
+            QueueCondition::Not(c) => QueueCondition::Not(Box::new(c.mirrored())),
+            QueueCondition::All(cs) => QueueCondition::All(cs.iter().map(|c| c.mirrored()).collect()),
+            QueueCondition::Any(cs) => QueueCondition::Any(cs.iter().map(|c| c.mirrored()).collect()),
+        }
+    }
+    pub fn eval_with_game(&self, game: &deep_trinity_core::Game) -> bool {
+        self.eval(&queue_of(game))
+    }
+}
+
+fn queue_of(game: &deep_trinity_core::Game) -> Vec<Piece> {
+    let np = &game.state.next_pieces;
+    game.state.falling_piece.as_ref().map(|fp| fp.piece()).into_iter()
+        .chain(np.iter().take(np.visible_num).copied())
+        .collect()
+}
+
+/// Openers with the conditions to be selected. The first opener whose condition is satisfied is used.
+#[derive(Clone, Debug, Default)]
+pub struct Template {
+    branches: Vec<(QueueCondition, Opener)>,
+}
+
+impl Template {
+    pub fn new() -> Self { Default::default() }
+    pub fn branch(mut self, cond: QueueCondition, opener: Opener) -> Self {
+        self.branches.push((cond, opener));
+        self
+    }
+    pub fn otherwise(self, opener: Opener) -> Self {
+        self.branch(QueueCondition::Always, opener)
+    }
+    pub fn select(&self, game: &deep_trinity_core::Game) -> Option<&Opener> {
+        let queue = queue_of(game);
+        self.branches.iter().find(|(cond, _)| cond.eval(&queue)).map(|(_, opener)| opener)
+    }
+    /// Returns the template for the mirrored playfield of `width`.
+    pub fn mirrored(&self, width: X) -> Self {
+        Self { branches: self.branches.iter().map(|(cond, opener)| (cond.mirrored(), opener.mirrored(width))).collect() }
+    }
+    /// See [Opener::translated].
+    pub fn translated(&self, dy: Y) -> Self {
+        Self { branches: self.branches.iter().map(|(cond, opener)| (cond.clone(), opener.translated(dy))).collect() }
     }
 }
 
+/// Templates keyed by the skyline fingerprints of the surfaces they start from,
+/// to continue from mid-game setups (e.g. LST stacking) regardless of the side and the height.
+#[derive(Clone, Debug, Default)]
+pub struct OpenerBook {
+    /// The fingerprints, the lowest heights of the surfaces and the templates.
+    entries: Vec<(SkylineFingerprint, Y, Template)>,
+}
+
+impl OpenerBook {
+    pub fn new() -> Self { Default::default() }
+    /// `heights` are the column heights of the surface expected by the template.
+    pub fn register(&mut self, heights: &[Y], template: Template) {
+        let bottom = heights.iter().copied().min().unwrap_or(0);
+        self.entries.push((SkylineFingerprint::from_heights(heights), bottom, template));
+    }
+    pub fn len(&self) -> usize { self.entries.len() }
+    pub fn is_empty(&self) -> bool { self.entries.is_empty() }
+    /// Returns the template registered for the surface of the playfield, mirrored if needed and
+    /// moved to the height of the surface.
+    pub fn lookup(&self, pf: &Playfield) -> Option<Template> {
+        let heights = pf.heights();
+        let fp = SkylineFingerprint::from_heights(&heights);
+        self.entries.iter().find(|(key, _, _)| key.deltas == fp.deltas).map(|(key, bottom, template)| {
+            let template = if key.is_mirror_of(&fp) { template.mirrored(pf.width()) } else { template.clone() };
+            let dy = heights.iter().copied().min().unwrap_or(0) - bottom;
+            if dy == 0 { template } else { template.translated(dy) }
+        })
+    }
+}
+
+impl From<Opener> for Template {
+    fn from(opener: Opener) -> Self { Self::new().otherwise(opener) }
+}
+
 pub fn tsd_opener_l_01() -> Opener {
     Opener::new(
         &[
@@ -107,8 +328,8 @@ impl OpenerMoveDirector {
                 None
             };
         }
-        let r = opener.moves.iter().enumerate().find(|(_, (p, _, _, deps))| {
-            if piece != *p {
+        let r = opener.moves.iter().enumerate().find(|(i, (p, _, _, deps))| {
+            if piece != *p || self.moved.contains(i) {
                 return false;
             }
             deps.iter().filter(|&name| self.moved_names.contains(name)).count() == deps.len()
@@ -128,45 +349,172 @@ impl OpenerMoveDirector {
 
 #[derive(Clone, Debug)]
 pub struct TemplateBot {
-    template: Opener,
+    template: Template,
+    opener: Option<Opener>,
     director: OpenerMoveDirector,
+    book: Option<OpenerBook>,
 }
 
 impl TemplateBot {
-    pub fn new(template: Opener) -> Self {
-        Self { template, director: OpenerMoveDirector::new() }
+    pub fn new(opener: Opener) -> Self {
+        Self::with_template(opener.into())
+    }
+    pub fn with_template(template: Template) -> Self {
+        Self { template, opener: None, director: OpenerMoveDirector::new(), book: None }
+    }
+    /// The templates are looked up from `book` by the surface at the start and at the end of each
+    /// opener without the continuation.
+    pub fn with_book(book: OpenerBook) -> Self {
+        Self { book: Some(book), ..Self::with_template(Template::new()) }
     }
 }
 
-impl Bot for TemplateBot {
-    fn think(&mut self, game: &Game) -> Result<Action, Box<dyn Error>> {
-        let candidates: HashSet<MoveTransition> = game.get_move_candidates()?;
-        if candidates.is_empty() {
-            return Err("no move candidates".into());
+enum TemplateStep {
+    Move(MoveTransition),
+    Hold,
+    End,
+}
+
+impl TemplateBot {
+    fn next_step(&mut self, game: &deep_trinity_core::Game) -> Result<TemplateStep, DivergenceKind> {
+        if self.director.is_end {
+            let next = match self.opener.as_ref().and_then(|o| o.continuation.as_ref()) {
+                Some(continuation) => Some(continuation.as_ref().clone()),
+                None => self.book.as_ref().and_then(|book| book.lookup(&game.state.playfield)),
+            };
+            if let Some(template) = next {
+                self.template = template;
+                self.opener = None;
+                self.director = OpenerMoveDirector::new();
+            }
+        }
+        if self.opener.is_none() {
+            if let (Some(book), true) = (self.book.as_ref(), self.template.branches.is_empty()) {
+                self.template = book.lookup(&game.state.playfield).ok_or(DivergenceKind::NoMatchingBranch)?;
+            }
+            self.opener = Some(self.template.select(game).ok_or(DivergenceKind::NoMatchingBranch)?.clone());
         }
 
-        let tmpl = &self.template;
+        let tmpl = self.opener.as_ref().unwrap();
         let current_piece = game.state.falling_piece.as_ref().unwrap().piece();
         match self.director.step(tmpl, current_piece) {
-            Some(mt) => {
-                Ok(Action::Move(mt))
-            }
+            Some(mt) => Ok(TemplateStep::Move(mt)),
             None => {
-                if !self.director.is_end && game.state.can_hold {
-                    Ok(Action::Hold)
+                if self.director.is_end {
+                    Ok(TemplateStep::End)
+                } else if game.can_hold() {
+                    Ok(TemplateStep::Hold)
                 } else {
-                    Err("end".into())
+                    Err(DivergenceKind::UnexpectedPiece(current_piece))
                 }
             }
         }
     }
 }
 
+impl Bot for TemplateBot {
+    fn think(&mut self, game: &Game) -> Result<Action, Box<dyn Error>> {
+        let candidates: HashSet<MoveTransition> = game.get_move_candidates()?;
+        if candidates.is_empty() {
+            return Err("no move candidates".into());
+        }
+
+        match self.next_step(game) {
+            Ok(TemplateStep::Move(mt)) => Ok(Action::Move(mt)),
+            Ok(TemplateStep::Hold) => Ok(Action::Hold),
+            Ok(TemplateStep::End) | Err(DivergenceKind::UnexpectedPiece(_)) => Err("end".into()),
+            Err(kind) => Err(kind.to_string().into()),
+        }
+    }
+}
+
+//---
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum DivergenceKind {
+    /// No branch of the template matches the queue.
+    NoMatchingBranch,
+    /// The falling piece is not expected by the template and cannot be held.
+    UnexpectedPiece(Piece),
+    /// The placement of the template cannot be reached on the current board.
+    UnreachablePlacement(Piece, Placement),
+}
+
+impl fmt::Display for DivergenceKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DivergenceKind::NoMatchingBranch => write!(f, "no branch matches the queue"),
+            DivergenceKind::UnexpectedPiece(p) => write!(f, "unexpected piece {}", p),
+            DivergenceKind::UnreachablePlacement(p, pl) => write!(f, "{:?} of piece {} is unreachable", pl, p),
+        }
+    }
+}
+
+/// The error returned by [TemplateController] when the game doesn't match the template.
+#[derive(Clone, Debug)]
+pub struct TemplateDivergence {
+    /// The number of actions done by the template before the divergence.
+    pub step: usize,
+    pub kind: DivergenceKind,
+    /// The game at the divergence point.
+    pub game: String,
+}
+
+impl fmt::Display for TemplateDivergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "diverged from the template at step {}: {}", self.step, self.kind)?;
+        write!(f, "{}", self.game)
+    }
+}
+
+impl Error for TemplateDivergence {}
+
+/// Drives a game by a template, validating each placement with [MoveDecisionResource].
+/// When the template is finished, `think` returns an error other than [TemplateDivergence].
+#[derive(Clone, Debug)]
+pub struct TemplateController {
+    bot: TemplateBot,
+    step: usize,
+}
+
+impl TemplateController {
+    pub fn new(template: Template) -> Self {
+        Self { bot: TemplateBot::with_template(template), step: 0 }
+    }
+    /// See [TemplateBot::with_book].
+    pub fn with_book(book: OpenerBook) -> Self {
+        Self { bot: TemplateBot::with_book(book), step: 0 }
+    }
+    pub fn step(&self) -> usize { self.step }
+}
+
+impl deep_trinity_core::bot::Bot for TemplateController {
+    fn think(&mut self, game: &deep_trinity_core::Game) -> Result<deep_trinity_core::bot::Action, Box<dyn Error>> {
+        let diverged = |step: usize, kind: DivergenceKind| -> Box<dyn Error> {
+            Box::new(TemplateDivergence { step, kind, game: game.to_string() })
+        };
+        let action = match self.bot.next_step(game).map_err(|kind| diverged(self.step, kind))? {
+            TemplateStep::Move(mt) => {
+                let mdr = MoveDecisionResource::with_game(game)?;
+                if !mdr.dst_candidates.contains(&mt.placement) {
+                    let piece = game.state.falling_piece.as_ref().unwrap().piece();
+                    return Err(diverged(self.step, DivergenceKind::UnreachablePlacement(piece, mt.placement)));
+                }
+                deep_trinity_core::bot::Action::Move(mt)
+            }
+            TemplateStep::Hold => deep_trinity_core::bot::Action::Hold,
+            TemplateStep::End => return Err("end of the template".into()),
+        };
+        self.step += 1;
+        Ok(action)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::{BotRunner, BotRunnerHooks};
-    use deep_trinity_core::Cell;
+    use deep_trinity_core::{Cell, StaticPieceGenerator};
 
     #[test]
     fn test_template_bot() {
@@ -223,4 +571,197 @@ mod tests {
             runner.run(&mut bot, &mut hooks).unwrap();
         }
     }
+
+    #[test]
+    fn test_queue_condition() {
+        let queue = [Piece::I, Piece::L, Piece::O, Piece::T];
+        assert!(QueueCondition::Before(Piece::T, Piece::J).eval(&queue));
+        assert!(!QueueCondition::Before(Piece::T, Piece::O).eval(&queue));
+        assert!(!QueueCondition::Before(Piece::J, Piece::T).eval(&queue));
+        assert!(QueueCondition::All(vec![
+            QueueCondition::Contains(Piece::L),
+            QueueCondition::Not(Box::new(QueueCondition::Contains(Piece::S))),
+        ]).eval(&queue));
+        assert!(!QueueCondition::Any(vec![]).eval(&queue));
+    }
+
+    #[test]
+    fn test_template_bot_with_branches() {
+        struct Hooks {
+            next_pieces: Vec<Piece>,
+            locked: Vec<Placement>,
+        }
+
+        impl BotRunnerHooks for Hooks {
+            fn on_start(&mut self, game: &mut Game) -> Result<(), Box<dyn Error>> {
+                game.set_piece_generator(Some(Box::new(StaticPieceGenerator::new(&self.next_pieces, false))));
+                game.setup_falling_piece(None)?;
+                Ok(())
+            }
+            fn on_iter(&mut self, game: &mut Game) -> Result<bool, Box<dyn Error>> {
+                Ok(game.state.falling_piece.is_some())
+            }
+            fn on_action(&mut self, _game: &Game, action: &Action) -> Result<(), Box<dyn Error>> {
+                if let Action::Move(mt) = action {
+                    self.locked.push(mt.placement);
+                }
+                Ok(())
+            }
+        }
+
+        let template = Template::new()
+            .branch(QueueCondition::Before(Piece::L, Piece::J), tsd_opener_l_01())
+            .otherwise(tsd_opener_r_01());
+        for (next_pieces, expected) in [("ILOSZJT", tsd_opener_l_01()), ("IJOSZLT", tsd_opener_r_01())] {
+            let runner = BotRunner::new(100, true, None, false);
+            let mut hooks = Hooks {
+                next_pieces: next_pieces.chars().map(|c| Piece::try_from_char(c).unwrap()).collect(),
+                locked: Vec::new(),
+            };
+            let mut bot = TemplateBot::with_template(template.clone());
+            runner.run(&mut bot, &mut hooks).unwrap();
+            assert_eq!(Some(&expected.last_move.1.placement), hooks.locked.last());
+        }
+    }
+
+    #[test]
+    fn test_opener_book() {
+        let mirrored = tsd_opener_l_01().mirrored(10);
+        let expected = tsd_opener_r_01();
+        // Only the I placements differ.
+        let placements = |o: &Opener| o.moves[1..].iter().map(|(p, mt, _, _)| (*p, *mt)).collect::<Vec<_>>();
+        assert_eq!(placements(&expected), placements(&mirrored));
+        assert_eq!(expected.last_move, mirrored.last_move);
+        assert_eq!(QueueCondition::Before(Piece::J, Piece::S), QueueCondition::Before(Piece::L, Piece::Z).mirrored());
+
+        let mut book = OpenerBook::new();
+        book.register(&[2, 2, 1, 1, 0, 0, 0, 0, 0, 0], tsd_opener_l_01().into());
+        let mut pf = Playfield::default();
+        pf.set_rows_with_strs((0, 0).into(), &[
+            "       @@@",
+            "@@@@@@@@@@",
+        ]);
+        assert!(book.lookup(&pf).is_none());
+        pf.set_rows_with_strs((0, 0).into(), &[
+            "        @@",
+            "      @@@@",
+        ]);
+        let template = book.lookup(&pf).unwrap();
+        assert_eq!(mirrored.last_move, template.branches[0].1.last_move);
+        pf.set_rows_with_strs((0, 0).into(), &[
+            "@@        ",
+            "@@@@      ",
+        ]);
+        assert_eq!(tsd_opener_l_01().last_move, book.lookup(&pf).unwrap().branches[0].1.last_move);
+
+        // The placements are moved to the height of the surface, e.g. the opener on a garbage row
+        // with the well at the right is used on two rows.
+        let mut book = OpenerBook::new();
+        book.register(&[1, 1, 1, 1, 1, 1, 1, 1, 1, 0], tsd_opener_l_01().translated(1).into());
+        let mut game: Game = Default::default();
+        game.state.playfield.append_garbage(&[0, 9]);
+        let template = book.lookup(&game.state.playfield).unwrap();
+        let (piece, mt) = template.branches[0].1.last_move;
+        assert_eq!((Piece::T, Vec2(1, 2)), (piece, mt.placement.pos));
+        assert_eq!(Vec2(0, 3), mt.hint.unwrap().placement.pos);
+
+        // Driven by the book.
+        game.supply_next_pieces(&"ILOSZJT".chars().map(|c| Piece::try_from_char(c).unwrap()).collect::<Vec<_>>());
+        game.setup_falling_piece(None).unwrap();
+        let mut bot = TemplateBot::with_book(book);
+        while game.state.falling_piece.is_some() {
+            match bot.think(&game).unwrap() {
+                Action::Move(mt) => {
+                    let spec = game.state.falling_piece.as_ref().unwrap().piece_spec;
+                    game.state.falling_piece = Some(deep_trinity_core::FallingPiece::new_with_last_move_transition(spec, &mt));
+                    game.lock().unwrap();
+                }
+                Action::Hold => { game.hold().unwrap(); }
+            }
+        }
+        assert_eq!(1, game.stats.line_clear.get(&deep_trinity_core::LineClear::tsd()));
+        assert_eq!(2, game.state.playfield.num_garbage_rows());
+        assert!(TemplateBot::with_book(OpenerBook::new()).think(&game).is_err());
+    }
+
+    #[test]
+    fn test_template_controller() {
+        use deep_trinity_core::bot::Bot as _;
+        use deep_trinity_core::FallingPiece;
+
+        fn run(next_pieces: &str, garbage: Option<(i8, i8)>) -> Result<Game, Box<dyn Error>> {
+            let mut game: Game = Default::default();
+            if let Some(pos) = garbage {
+                game.state.playfield.set_cell(pos.into(), Cell::Garbage);
+            }
+            let pieces = next_pieces.chars().map(|c| Piece::try_from_char(c).unwrap()).collect::<Vec<_>>();
+            game.set_piece_generator(Some(Box::new(StaticPieceGenerator::new(&pieces, false))));
+            game.setup_falling_piece(None)?;
+            let mut controller = TemplateController::new(tsd_opener_l_01().into());
+            while game.state.falling_piece.is_some() {
+                match controller.think(&game)? {
+                    deep_trinity_core::bot::Action::Move(mt) => {
+                        let spec = game.state.falling_piece.as_ref().unwrap().piece_spec;
+                        game.state.falling_piece = Some(FallingPiece::new_with_last_move_transition(spec, &mt));
+                        game.lock()?;
+                    }
+                    deep_trinity_core::bot::Action::Hold => {
+                        game.hold()?;
+                    }
+                }
+            }
+            Ok(game)
+        }
+
+        let game = run("ILOSZJT", None).unwrap();
+        assert_eq!(1, game.stats.line_clear.get(&deep_trinity_core::LineClear::tsd()));
+
+        let e = run("ILOSZJT", Some((8, 0))).unwrap_err();
+        let d = e.downcast_ref::<TemplateDivergence>().unwrap();
+        assert_eq!(2, d.step);
+        assert_eq!(DivergenceKind::UnreachablePlacement(Piece::O, Placement::new(Orientation0, (7, -1).into())), d.kind);
+
+        let e = run("TTLOSZJ", None).unwrap_err();
+        let d = e.downcast_ref::<TemplateDivergence>().unwrap();
+        assert_eq!(1, d.step);
+        assert_eq!(DivergenceKind::UnexpectedPiece(Piece::T), d.kind);
+    }
+
+    #[test]
+    fn test_opener_from_plan() {
+        use deep_trinity_core::bot::{Action, Bot as _};
+        use deep_trinity_core::FallingPiece;
+
+        fn run(template: Template, game: &mut Game) -> Vec<Action> {
+            let mut controller = TemplateController::new(template);
+            let mut actions = Vec::new();
+            while let Ok(action) = controller.think(game) {
+                match action {
+                    Action::Move(mt) => {
+                        let spec = game.state.falling_piece.as_ref().unwrap().piece_spec;
+                        game.state.falling_piece = Some(FallingPiece::new_with_last_move_transition(spec, &mt));
+                        game.lock().unwrap();
+                    }
+                    Action::Hold => { game.hold().unwrap(); }
+                }
+                actions.push(action);
+            }
+            actions
+        }
+
+        let mut initial: Game = Default::default();
+        initial.supply_next_pieces(&"ILOSZJTI".chars().map(|c| Piece::try_from_char(c).unwrap()).collect::<Vec<_>>());
+        initial.setup_falling_piece(None).unwrap();
+        let mut game = initial.clone();
+        let actions = run(tsd_opener_l_01().into(), &mut game);
+        let plan = Plan::from_actions(&initial, &actions).unwrap();
+        assert_eq!(7, plan.len());
+
+        let mut replayed = initial.clone();
+        assert_eq!(actions, run(Opener::from_plan(&plan).unwrap().into(), &mut replayed));
+        assert_eq!(game.state.playfield, replayed.state.playfield);
+        assert_eq!(1, replayed.stats.line_clear.get(&deep_trinity_core::LineClear::tsd()));
+
+        assert_eq!(Err("empty plan"), Opener::from_plan(&Plan::default()).map(|_| ()));
+    }
 }
@@ -1,6 +1,7 @@
-use crate::{Bot, Action};
+use crate::{Bot, Action, ThinkInfo};
 use deep_trinity_core::{Game, FallingPiece, Piece, LineClear};
 use deep_trinity_grid::Grid;
+use std::collections::HashSet;
 use std::error::Error;
 use std::cell::RefCell;
 use std::rc::Rc;
@@ -28,7 +29,7 @@ impl NodeData {
 type Node = deep_trinity_tree::Node<NodeData>;
 
 fn expand_node(node: &Rc<RefCell<Node>>) -> Result<(), Box<dyn Error>> {
-    if node.borrow().data.game.state.can_hold {
+    if node.borrow().data.game.can_hold() {
         let mut game = node.borrow().data.game.clone();
         game.stats = Default::default();
         let ok = game.hold()?;
@@ -36,8 +37,21 @@ fn expand_node(node: &Rc<RefCell<Node>>) -> Result<(), Box<dyn Error>> {
         let data = NodeData::new(Some(Action::Hold), game, false);
         deep_trinity_tree::append_child(node, data);
     }
-    let move_candidates = node.borrow().data.game.get_move_candidates()?;
+    let mut move_candidates = node.borrow().data.game.get_move_candidates()?.into_iter().collect::<Vec<_>>();
+    move_candidates.sort();
+    // Placements resulting in the same playfield by the same line clear lead to the same subtree.
+    let mut locked = HashSet::new();
     for mt in move_candidates.iter() {
+        {
+            let game = &node.borrow().data.game;
+            let fp = FallingPiece::new_with_last_move_transition(game.state.falling_piece.as_ref().unwrap().piece_spec, mt);
+            if let Some(preview) = game.state.lock_preview(&fp) {
+                let tspin = game.state.playfield.check_tspin(&fp, game.rules.tspin_judgement_mode);
+                if !locked.insert((preview.grid_hash, preview.num_cleared_lines, tspin)) {
+                    continue;
+                }
+            }
+        }
         let mut game = node.borrow().data.game.clone();
         game.stats = Default::default();
         let piece_spec = game.state.falling_piece.unwrap().piece_spec;
@@ -176,9 +190,10 @@ fn min_covered_empty_cells<'a>(root: &Rc<RefCell<Node>>, paths: &[&'a deep_trini
 }
 
 fn hold_i<'a>(root: &Rc<RefCell<Node>>, paths: &[&'a deep_trinity_tree::Path]) -> Vec<&'a deep_trinity_tree::Path> {
-    let state = &root.borrow().data.game.state;
+    let game = &root.borrow().data.game;
+    let state = &game.state;
     let piece = state.falling_piece.as_ref().unwrap().piece_spec.piece;
-    if !state.can_hold || piece != Piece::I || matches!(state.hold_piece, Some(Piece::I)) {
+    if !game.can_hold() || piece != Piece::I || matches!(state.hold_piece, Some(Piece::I)) {
         return vec![];
     }
     paths.iter()
@@ -220,8 +235,7 @@ impl Filter for SuppressLineClear {
     fn filter<'a>(&mut self, root: &Rc<RefCell<Node>>, paths: &[&'a deep_trinity_tree::Path]) -> Vec<&'a deep_trinity_tree::Path> {
         let h = {
             let game = &root.borrow().data.game;
-            let grid = &game.state.playfield.grid;
-            grid.height() - grid.top_padding()
+            game.state.playfield.stack_height()
         };
         if h > self.height {
             paths.to_vec()
@@ -260,7 +274,7 @@ fn _exclude_trenches<'a>(root: &Rc<RefCell<Node>>, paths: &[&'a deep_trinity_tre
     paths.iter()
         .filter(|path| {
             let node = deep_trinity_tree::get(root, path.iter()).unwrap();
-            let hs = node.borrow().data.game.state.playfield.grid.contour();
+            let hs = node.borrow().data.game.state.playfield.heights();
             let mut n = 0;
             for i in 0..hs.len() {
                 let left = if i == 0 { true } else { (hs[i] as i8 - hs[i - 1] as i8).abs() >= TRENCH_HEIGHT };
@@ -283,7 +297,7 @@ fn _min_trenches<'a>(root: &Rc<RefCell<Node>>, paths: &[&'a deep_trinity_tree::P
     paths.iter()
         .fold((-1, vec![]), |(min, mut paths), &path| {
             let node = deep_trinity_tree::get(root, path.iter()).unwrap();
-            let hs = node.borrow().data.game.state.playfield.grid.contour();
+            let hs = node.borrow().data.game.state.playfield.heights();
             let mut n = 0;
             for i in 0..hs.len() {
                 let left = if i == 0 { true } else { (hs[i] as i8 - hs[i - 1] as i8).abs() >= TRENCH_HEIGHT };
@@ -316,7 +330,7 @@ fn _filter_by_contour<'a>(root: &Rc<RefCell<Node>>, paths: &[&'a deep_trinity_tr
     paths.iter()
         .filter(|path| {
             let node = deep_trinity_tree::get(root, path.iter()).unwrap();
-            let hs = node.borrow().data.game.state.playfield.grid.contour();
+            let hs = node.borrow().data.game.state.playfield.heights();
             let stddev = calc_stddev(&hs);
             stddev < 5.0
         })
@@ -357,8 +371,8 @@ fn _min_height<'a>(root: &Rc<RefCell<Node>>, paths: &[&'a deep_trinity_tree::Pat
     let path = paths.iter()
         .map(|&path| {
             let node = deep_trinity_tree::get(root, path.iter()).unwrap();
-            let grid = &node.borrow().data.game.state.playfield.grid;
-            (grid.height() - grid.top_padding(), path)
+            let stack_height = node.borrow().data.game.state.playfield.stack_height();
+            (stack_height, path)
         })
         .min()
         .unwrap().1;
@@ -371,11 +385,15 @@ fn _min_height<'a>(root: &Rc<RefCell<Node>>, paths: &[&'a deep_trinity_tree::Pat
 pub struct TreeBot {
     pub expansion_duration: std::time::Duration,
     pub num_expanded: usize,
+    max_depth: usize,
 }
 
 impl Bot for TreeBot {
     fn think(&mut self, game: &Game<'static>) -> Result<Action, Box<dyn Error>> {
-        let root = deep_trinity_tree::new(NodeData::new(None, game.clone(), false));
+        let mut game = game.clone();
+        // The piece types of the cells aren't needed in the search.
+        game.set_color_tracking(false);
+        let root = deep_trinity_tree::new(NodeData::new(None, game, false));
         let started_at = std::time::SystemTime::now();
         const NUM_EXPANSIONS: usize = 2;
         for _ in 0..NUM_EXPANSIONS {
@@ -413,6 +431,7 @@ impl Bot for TreeBot {
         }
 
         let paths = deep_trinity_tree::get_all_paths_to_leaves(&root);
+        self.max_depth = paths.iter().map(|path| path.indices.len()).max().unwrap_or(0);
         let paths = paths.iter().map(|path| path).collect::<Vec<_>>();
 
         let mut filter_chain = FilterChain::new(vec![
@@ -434,6 +453,14 @@ impl Bot for TreeBot {
         let action = deep_trinity_tree::get(&root, [path.indices[0]].iter()).unwrap().borrow().data.by.unwrap();
         Ok(action)
     }
+    /// Only the stats of the expansion are available since candidates are filtered without scores.
+    fn last_think_info(&self) -> Option<ThinkInfo> {
+        Some(ThinkInfo {
+            num_expanded_nodes: self.num_expanded,
+            max_depth: self.max_depth,
+            ..Default::default()
+        })
+    }
 }
 
 #[cfg(test)]
@@ -441,6 +468,18 @@ mod tests {
     use super::*;
     use crate::BotRunner;
 
+    #[test]
+    fn test_expand_node() {
+        let mut game: Game<'static> = Default::default();
+        game.supply_next_pieces(&[Piece::I]);
+        game.setup_falling_piece(None).unwrap();
+        game.state.can_hold = false;
+        let root = deep_trinity_tree::new(NodeData::new(None, game, false));
+        expand_node(&root).unwrap();
+        // 7 horizontal and 10 vertical ones without the duplicates by the rotations.
+        assert_eq!(17, root.borrow().children.len());
+    }
+
     #[test]
     fn test_tree_bot() {
         let seed = 0;
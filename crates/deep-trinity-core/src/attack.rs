@@ -0,0 +1,214 @@
+//! Attack (garbage lines sent) calculation independent of the game state.
+
+use std::borrow::Cow;
+use crate::{Count, LineClear, TSpin};
+
+/// How combos add garbage lines.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ComboTable {
+    /// Added lines indexed by the number of combos. The last value is used for more combos.
+    /// Presets borrow static tables and custom ones (e.g. read from JSON) are owned.
+    Additive(Cow<'static, [Count]>),
+    /// TETR.IO style. The attack is multiplied by `1 + 0.25 * combos`,
+    /// or `ln(1 + 1.25 * combos)` lines are sent if the attack is zero.
+    Multiplier,
+}
+
+impl ComboTable {
+    pub const GUIDELINE: Self = Self::Additive(Cow::Borrowed(&[0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 4, 5]));
+    /// Applies the combo bonus to `attack`.
+    pub fn apply(&self, attack: Count, num_combos: Count) -> Count {
+        match self {
+            Self::Additive(table) => {
+                attack + table.get(num_combos as usize).or_else(|| table.last()).copied().unwrap_or(0)
+            }
+            Self::Multiplier => {
+                let n = num_combos as f32;
+                if attack > 0 {
+                    (attack as f32 * (1.0 + 0.25 * n)).floor() as Count
+                } else {
+                    (1.0 + 1.25 * n).ln().floor() as Count
+                }
+            }
+        }
+    }
+}
+
+/// How back-to-back chains add garbage lines.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum BtbRule {
+    /// Constant bonus while in a chain.
+    Flat(Count),
+    /// TETR.IO style B2B charging. In addition to the bonus while in a chain, breaking a chain of
+    /// `threshold` or more sends `num_btbs - threshold + 1` lines at once.
+    Charge { bonus: Count, threshold: Count },
+}
+
+impl BtbRule {
+    pub fn bonus(&self) -> Count {
+        match *self {
+            Self::Flat(bonus) | Self::Charge { bonus, .. } => bonus,
+        }
+    }
+    /// Returns the lines sent when a chain of `num_btbs` is broken.
+    pub fn release(&self, num_btbs: Count) -> Count {
+        match *self {
+            Self::Flat(_) => 0,
+            Self::Charge { threshold, .. } => if num_btbs >= threshold { num_btbs - threshold + 1 } else { 0 },
+        }
+    }
+}
+
+/// Garbage lines for each kind of line clear and bonuses.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AttackTable {
+    /// Indexed by the number of lines - 1.
+    pub normal: [Count; 4],
+    /// Indexed by the number of lines - 1.
+    pub tspin: [Count; 3],
+    /// Indexed by the number of lines - 1.
+    pub tspin_mini: [Count; 2],
+    pub combo: ComboTable,
+    pub btb: BtbRule,
+    pub perfect_clear: Count,
+}
+
+impl AttackTable {
+    pub const GUIDELINE: Self = Self {
+        normal: [0, 1, 2, 4],
+        tspin: [2, 4, 6],
+        tspin_mini: [0, 1],
+        combo: ComboTable::GUIDELINE,
+        btb: BtbRule::Flat(1),
+        perfect_clear: 10,
+    };
+    pub const TETRIO: Self = Self {
+        combo: ComboTable::Multiplier,
+        btb: BtbRule::Charge { bonus: 1, threshold: 4 },
+        perfect_clear: 5,
+        ..Self::GUIDELINE
+    };
+}
+
+impl Default for AttackTable {
+    fn default() -> Self { Self::GUIDELINE }
+}
+
+impl LineClear {
+    /// Returns the garbage lines of the line clear itself without any bonuses.
+    pub fn base_attack(&self, table: &AttackTable) -> Count {
+        if self.num_lines == 0 {
+            return 0;
+        }
+        let i = self.num_lines as usize - 1;
+        match self.tspin {
+            None => table.normal[i.min(table.normal.len() - 1)],
+            Some(TSpin::Standard) => table.tspin[i.min(table.tspin.len() - 1)],
+            Some(TSpin::Mini) => table.tspin_mini[i.min(table.tspin_mini.len() - 1)],
+        }
+    }
+    /// Returns the guideline score of the line clear at `level` without any bonuses.
+    pub fn score(&self, level: Count) -> Count {
+        const NORMAL: [Count; 5] = [0, 100, 300, 500, 800];
+        const TSPIN: [Count; 4] = [400, 800, 1200, 1600];
+        const TSPIN_MINI: [Count; 3] = [100, 200, 400];
+        let i = self.num_lines as usize;
+        let base = match self.tspin {
+            None => NORMAL[i.min(NORMAL.len() - 1)],
+            Some(TSpin::Standard) => TSPIN[i.min(TSPIN.len() - 1)],
+            Some(TSpin::Mini) => TSPIN_MINI[i.min(TSPIN_MINI.len() - 1)],
+        };
+        base * level
+    }
+}
+
+/// Returns the lines released by [BtbRule::Charge] when the chain of `prev_num_btbs` is broken.
+/// `num_btbs` should be the value updated by the lock.
+pub fn calc_btb_release(prev_num_btbs: Option<Count>, num_btbs: Option<Count>, table: &AttackTable) -> Count {
+    match (prev_num_btbs, num_btbs) {
+        (Some(n), None) => table.btb.release(n),
+        _ => 0,
+    }
+}
+
+/// Calculates the number of garbage lines sent by the line clear.
+/// `num_combos` and `num_btbs` should be the values updated by the line clear.
+pub fn calc_attack(lc: &LineClear, num_combos: Option<Count>, num_btbs: Option<Count>, is_perfect_clear: bool, table: &AttackTable) -> Count {
+    if lc.num_lines == 0 {
+        return 0;
+    }
+    let mut attack = lc.base_attack(table);
+    if matches!(num_btbs, Some(n) if n > 0) {
+        attack += table.btb.bonus();
+    }
+    if let Some(n) = num_combos {
+        attack = table.combo.apply(attack, n);
+    }
+    if is_perfect_clear {
+        attack += table.perfect_clear;
+    }
+    attack
+}
+
+/// Returns the total attack of `num_clears` more single line clears continuing the combo of
+/// `num_combos` (`None` if no combo continues), e.g. to estimate the value of a combo well.
+pub fn calc_combo_attack(num_combos: Option<Count>, num_clears: Count, table: &AttackTable) -> Count {
+    let start = num_combos.map_or(0, |n| n + 1);
+    (start..start + num_clears)
+        .map(|n| calc_attack(&LineClear::new(1, None), Some(n), None, false, table))
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_calc_attack() {
+        let t = &AttackTable::GUIDELINE;
+        assert_eq!(0, calc_attack(&LineClear::new(0, None), None, None, false, t));
+        assert_eq!(0, calc_attack(&LineClear::new(1, None), Some(0), None, false, t));
+        assert_eq!(4, calc_attack(&LineClear::tetris(), Some(0), Some(0), false, t));
+        assert_eq!(5, calc_attack(&LineClear::tetris(), Some(0), Some(1), false, t));
+        assert_eq!(6, calc_attack(&LineClear::tsd(), Some(4), Some(0), false, t));
+        assert_eq!(1, calc_attack(&LineClear::tsmd(), Some(0), Some(0), false, t));
+        assert_eq!(11, calc_attack(&LineClear::new(2, None), Some(0), None, true, t));
+        assert_eq!(9, calc_attack(&LineClear::tetris(), Some(100), None, false, t));
+        // 0 + 0 + 1 + 1
+        assert_eq!(2, calc_combo_attack(None, 4, t));
+        // 2 + 2
+        assert_eq!(4, calc_combo_attack(Some(3), 2, t));
+        assert_eq!(0, calc_combo_attack(Some(3), 0, t));
+    }
+
+    #[test]
+    fn test_line_clear_helpers() {
+        let t = &AttackTable::GUIDELINE;
+        assert_eq!(0, LineClear::new(0, None).base_attack(t));
+        assert_eq!(4, LineClear::tetris().base_attack(t));
+        assert_eq!(6, LineClear::tst().base_attack(t));
+        assert_eq!(1, LineClear::tsmd().base_attack(t));
+        assert_eq!(0, LineClear::tsmz().base_attack(t));
+        assert_eq!(0, LineClear::new(0, None).score(1));
+        assert_eq!(800, LineClear::tetris().score(1));
+        assert_eq!(2400, LineClear::tsd().score(2));
+        assert_eq!(400, LineClear::new(0, Some(TSpin::Standard)).score(1));
+        assert_eq!(100, LineClear::tsmz().score(1));
+    }
+
+    #[test]
+    fn test_tetrio() {
+        let t = &AttackTable::TETRIO;
+        // 1 * (1 + 0.25 * 4)
+        assert_eq!(2, calc_attack(&LineClear::new(2, None), Some(4), None, false, t));
+        // ln(1 + 1.25 * 2) = 1.25...
+        assert_eq!(1, calc_attack(&LineClear::new(1, None), Some(2), None, false, t));
+        assert_eq!(0, calc_attack(&LineClear::new(1, None), Some(1), None, false, t));
+        // (4 + 1) * (1 + 0.25 * 1)
+        assert_eq!(6, calc_attack(&LineClear::tetris(), Some(1), Some(1), false, t));
+        assert_eq!(0, calc_btb_release(Some(3), None, t));
+        assert_eq!(3, calc_btb_release(Some(6), None, t));
+        assert_eq!(0, calc_btb_release(Some(6), Some(7), t));
+        assert_eq!(0, calc_btb_release(Some(6), None, &AttackTable::GUIDELINE));
+    }
+}
@@ -1,8 +1,16 @@
+pub mod noise;
+
+use std::collections::HashMap;
 use std::error::Error;
+use std::str::FromStr;
 use rand::SeedableRng;
+use rand::seq::SliceRandom;
 use rand::rngs::StdRng;
-use crate::{Game, MoveTransition, RandomPieceGenerator, MovePlayer, FallingPiece};
-use crate::helper::MoveDecisionResource;
+use crate::{Cell, Game, LockOutcome, MoveTransition, RandomPieceGenerator, PieceGenerator, MovePlayer, FallingPiece, Piece, Placement};
+use crate::mode::RisingGarbage;
+use crate::move_search::SearchScratch;
+use crate::helper::{MoveDecisionResource, derive_spin_hint, derive_tspin_hint, CheeseMetrics, pieces_per_garbage_line, SurvivalSolver, find_survival_line, is_flush};
+use deep_trinity_grid::{Cell as _, Grid, Vec2, X};
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 pub enum Action {
@@ -10,26 +18,466 @@ pub enum Action {
     Hold,
 }
 
+/// Diagnostics of the last `think()` to inspect why the action was chosen.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ThinkInfo<A = Action> {
+    pub num_expanded_nodes: usize,
+    pub max_depth: usize,
+    /// Scores of the candidate actions. Higher is better.
+    pub candidate_scores: Vec<(A, f32)>,
+    pub chosen_score: Option<f32>,
+}
+
+impl<A> Default for ThinkInfo<A> {
+    fn default() -> Self {
+        Self { num_expanded_nodes: 0, max_depth: 0, candidate_scores: Vec::new(), chosen_score: None }
+    }
+}
+
+impl<A> ThinkInfo<A> {
+    /// Converts the candidate actions, e.g. from the actions of another crate.
+    pub fn map_actions<B>(self, f: impl Fn(A) -> B) -> ThinkInfo<B> {
+        ThinkInfo {
+            num_expanded_nodes: self.num_expanded_nodes,
+            max_depth: self.max_depth,
+            candidate_scores: self.candidate_scores.into_iter().map(|(a, score)| (f(a), score)).collect(),
+            chosen_score: self.chosen_score,
+        }
+    }
+}
+
+impl Action {
+    /// Returns `true` if both are holds or lock the piece at the same placement regardless of
+    /// the last moves.
+    pub fn is_same_decision(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Action::Hold, Action::Hold) => true,
+            (Action::Move(a), Action::Move(b)) => a.placement == b.placement,
+            _ => false,
+        }
+    }
+}
+
+impl<'a> Game<'a> {
+    /// Returns `mt` with the hint of the final rotation if `mt` has no hint and is a T move placed
+    /// by a rotation as a T-spin (see [derive_tspin_hint]) or only by rotations (see
+    /// [derive_spin_hint]). Otherwise `mt` is returned.
+    pub fn establish_move_hint(&self, mt: &MoveTransition) -> Result<MoveTransition, &'static str> {
+        let fp = self.state.falling_piece.as_ref().ok_or("no falling piece")?;
+        if mt.hint.is_some() || fp.piece() != Piece::T {
+            return Ok(*mt);
+        }
+        let pf = &self.state.playfield;
+        let sr = &MoveDecisionResource::with_game(self)?.brute_force_search_result;
+        let hint = derive_tspin_hint(pf, fp, mt.placement, &self.rules, sr)
+            .or_else(|| derive_spin_hint(pf, fp, mt.placement, &self.rules, sr));
+        Ok(MoveTransition::new(mt.placement, hint))
+    }
+    /// Plays the action at once without the move path, e.g. in the quick mode of runners, where
+    /// the move hint is established by [Self::establish_move_hint] so that spins are credited.
+    /// Returns the outcome of the lock for a move.
+    pub fn apply_action(&mut self, action: &Action) -> Result<Option<LockOutcome>, &'static str> {
+        let mt = match action {
+            Action::Move(mt) => self.establish_move_hint(mt)?,
+            Action::Hold => {
+                self.hold()?;
+                return Ok(None);
+            }
+        };
+        let spec = self.state.falling_piece.as_ref().ok_or("no falling piece")?.piece_spec;
+        self.state.falling_piece = Some(FallingPiece::new_with_last_move_transition(spec, &mt));
+        self.lock_ex().map(Some)
+    }
+}
+
+/// What the bot would have played instead of the played action, e.g. to review human play.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Suggestion<A = Action> {
+    pub suggested: A,
+    pub suggested_score: Option<f32>,
+    /// `None` if the played action is not scored in [ThinkInfo::candidate_scores].
+    pub played_score: Option<f32>,
+    pub is_played: bool,
+}
+
+impl<A> Suggestion<A> {
+    /// `is_played` should return `true` for the action equivalent to the played one.
+    pub fn new(suggested: A, info: Option<&ThinkInfo<A>>, is_played: impl Fn(&A) -> bool) -> Self {
+        let is_suggestion_played = is_played(&suggested);
+        let suggested_score = info.and_then(|info| info.chosen_score);
+        let played_score = info
+            .and_then(|info| info.candidate_scores.iter().find(|(a, _)| is_played(a)).map(|(_, s)| *s))
+            .or(if is_suggestion_played { suggested_score } else { None });
+        Self { suggested, suggested_score, played_score, is_played: is_suggestion_played }
+    }
+    /// Returns how much the suggested action is better than the played one.
+    pub fn score_delta(&self) -> Option<f32> {
+        Some(self.suggested_score? - self.played_score?)
+    }
+}
+
+/// Asks `bot` for the action at `game` where `played` was actually played.
+pub fn suggest(bot: &mut impl Bot, game: &Game, played: &Action) -> Result<Suggestion, Box<dyn Error>> {
+    let suggested = bot.think(game)?;
+    let info = bot.last_think_info();
+    Ok(Suggestion::new(suggested, info.as_ref(), |a| a.is_same_decision(played)))
+}
+
 pub trait Bot {
     fn think(&mut self, game: &Game) -> Result<Action, Box<dyn Error>>;
+    /// Returns the diagnostics of the last `think()` if supported.
+    fn last_think_info(&self) -> Option<ThinkInfo> { None }
+    /// Returns why the plan was discarded in the last `think()` if the bot commits to plans.
+    fn last_plan_divergence(&self) -> Option<PlanDivergence> { None }
+}
+
+//---
+
+/// A decision for a piece of [Plan].
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct PlanStep {
+    /// Whether to hold before placing the piece.
+    pub hold: bool,
+    /// The piece to be placed, i.e. the one after the hold if any.
+    pub piece: Piece,
+    pub transition: MoveTransition,
+}
+
+impl PlanStep {
+    pub fn new(hold: bool, piece: Piece, transition: MoveTransition) -> Self { Self { hold, piece, transition } }
+    /// Returns the piece which would be placed by this step in `game`.
+    fn piece_in(&self, game: &Game) -> Result<Piece, &'static str> {
+        if self.hold {
+            return game.peek_hold_result().map(|r| r.piece);
+        }
+        game.state.falling_piece.as_ref().map(|fp| fp.piece()).ok_or("no falling piece")
+    }
+}
+
+/// Decisions for the next pieces, so that bots can commit to them instead of searching on every
+/// lock. A plan also serves as a replay of found routes (see [Self::from_actions] and [PlanExecutor]).
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Plan {
+    pub steps: Vec<PlanStep>,
+}
+
+impl Plan {
+    pub fn new(steps: Vec<PlanStep>) -> Self { Self { steps } }
+    pub fn len(&self) -> usize { self.steps.len() }
+    pub fn is_empty(&self) -> bool { self.steps.is_empty() }
+    pub fn push(&mut self, step: PlanStep) { self.steps.push(step); }
+    pub fn truncate(&mut self, len: usize) { self.steps.truncate(len); }
+    /// Converts the actions played from `game`, e.g. a route found by a solver, where each hold is
+    /// merged into the following step. A trailing hold is dropped.
+    pub fn from_actions(game: &Game, actions: &[Action]) -> Result<Self, &'static str> {
+        let mut game = game.clone();
+        let mut plan = Self::default();
+        let mut hold = false;
+        for action in actions.iter() {
+            match action {
+                Action::Hold => {
+                    game.hold()?;
+                    hold = true;
+                }
+                Action::Move(mt) => {
+                    let spec = game.state.falling_piece.as_ref().ok_or("no falling piece")?.piece_spec;
+                    plan.push(PlanStep::new(hold, spec.piece, *mt));
+                    game.state.falling_piece = Some(FallingPiece::new_with_last_move_transition(spec, mt));
+                    game.lock()?;
+                    hold = false;
+                }
+            }
+        }
+        Ok(plan)
+    }
+    /// Plays all the steps on `game` locking each piece.
+    /// `Err` is returned when the piece doesn't match or the placement isn't reachable, where the
+    /// game is left in the middle of the plan.
+    pub fn execute(&self, game: &mut Game) -> Result<(), &'static str> {
+        for step in self.steps.iter() {
+            if step.piece_in(game)? != step.piece {
+                return Err("piece mismatch");
+            }
+            let path = game.get_action_path(step.hold, Some(&step.transition))?;
+            MovePlayer::new(path).fast_forward(game)?;
+            game.lock()?;
+        }
+        Ok(())
+    }
+    /// Returns `Ok` if the plan can be executed from `game`. See [Self::execute].
+    pub fn validate(&self, game: &Game) -> Result<(), &'static str> {
+        self.execute(&mut game.clone())
+    }
+    /// Encodes the plan executed from `game` in fumen (see [crate::parse::encode_fumen]), where the
+    /// first page is the playfield of `game` and the following ones show each piece locked.
+    /// The pieces are colored even if the playfield doesn't hold the cells, e.g. in performance mode.
+    pub fn to_fumen(&self, game: &Game) -> Result<String, &'static str> {
+        let mut game = game.clone();
+        let pf = &game.state.playfield;
+        // From the bottom.
+        let mut rows = (0..pf.height())
+            .map(|y| (0..pf.width()).map(|x| pf.grid.cell((x, y).into())).collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        let mut pages = vec![fumen_page(&rows)];
+        for step in self.steps.iter() {
+            if step.piece_in(&game)? != step.piece {
+                return Err("piece mismatch");
+            }
+            let path = game.get_action_path(step.hold, Some(&step.transition))?;
+            MovePlayer::new(path).fast_forward(&mut game)?;
+            let fp = game.state.falling_piece.as_ref().ok_or("no falling piece")?;
+            let grid = fp.grid();
+            for y in 0..grid.height() {
+                for x in 0..grid.width() {
+                    if !grid.cell((x, y).into()).is_empty() {
+                        let pos = fp.placement.pos + Vec2(x, y);
+                        rows[pos.1 as usize][pos.0 as usize] = step.piece.into();
+                    }
+                }
+            }
+            pages.push(fumen_page(&rows));
+            let width = rows[0].len();
+            rows.retain(|row| row.iter().any(|c| c.is_empty()));
+            rows.resize(game.state.playfield.height() as usize, vec![Cell::Empty; width]);
+            game.lock()?;
+        }
+        crate::parse::encode_fumen(&pages)
+    }
+}
+
+/// Returns the rows from the top of the stack for the rows from the bottom.
+fn fumen_page(rows: &[Vec<Cell>]) -> Vec<Vec<Cell>> {
+    let top = rows.iter().rposition(|row| row.iter().any(|c| !c.is_empty())).map_or(0, |y| y + 1);
+    rows[..top].iter().rev().cloned().collect()
+}
+
+/// How the game deviated from the assumptions of a plan.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum PlanDivergence {
+    /// The playfield differs from the result of the executed steps, e.g. garbage arrived.
+    Playfield,
+    /// The piece to be placed differs from the planned one, e.g. by a misqueue.
+    Piece { expected: Piece, actual: Option<Piece> },
+    /// The planned hold can't be done.
+    Hold,
+    /// The planned placement isn't lockable any more.
+    Placement,
+}
+
+/// Replays a [Plan] action by action, verifying that the game still matches it.
+#[derive(Clone, Debug, Default)]
+pub struct PlanExecutor {
+    plan: Plan,
+    i: usize,
+    held: bool,
+    /// The key of the playfield expected after the executed steps.
+    expected_playfield: Option<u64>,
+}
+
+impl PlanExecutor {
+    pub fn new(plan: Plan) -> Self { Self { plan, i: 0, held: false, expected_playfield: None } }
+    pub fn is_end(&self) -> bool { self.i >= self.plan.len() }
+    /// The steps not started yet.
+    pub fn remaining(&self) -> &[PlanStep] {
+        &self.plan.steps[self.i.min(self.plan.len())..]
+    }
+    /// Returns the next action of the plan, or `None` if the plan is over or `game` deviates from
+    /// it, when the executor is terminated and the caller should plan again.
+    pub fn next_action(&mut self, game: &Game) -> Option<Action> {
+        self.poll(game).ok().flatten()
+    }
+    /// Same as [Self::next_action] but returns how `game` deviates from the plan as `Err`.
+    pub fn poll(&mut self, game: &Game) -> Result<Option<Action>, PlanDivergence> {
+        let r = self.next_action_checked(game);
+        if r.is_err() {
+            self.i = self.plan.len();
+            self.held = false;
+        }
+        r
+    }
+    fn next_action_checked(&mut self, game: &Game) -> Result<Option<Action>, PlanDivergence> {
+        let step = match self.plan.steps.get(self.i) {
+            Some(step) => *step,
+            None => return Ok(None),
+        };
+        if self.expected_playfield.is_some_and(|key| key != game.state.playfield.grid_hash()) {
+            return Err(PlanDivergence::Playfield);
+        }
+        if step.hold && !self.held {
+            if !game.can_hold() {
+                return Err(PlanDivergence::Hold);
+            }
+            let actual = step.piece_in(game).ok();
+            if actual != Some(step.piece) {
+                return Err(PlanDivergence::Piece { expected: step.piece, actual });
+            }
+            self.held = true;
+            return Ok(Some(Action::Hold));
+        }
+        let fp = match game.state.falling_piece.as_ref() {
+            Some(fp) if fp.piece() == step.piece => fp,
+            fp => return Err(PlanDivergence::Piece { expected: step.piece, actual: fp.map(|fp| fp.piece()) }),
+        };
+        let dst = FallingPiece::new_with_last_move_transition(fp.piece_spec, &step.transition);
+        if !game.state.playfield.can_lock(&dst) {
+            return Err(PlanDivergence::Placement);
+        }
+        let mut pf = game.state.playfield.clone();
+        pf.lock(&dst, game.rules.tspin_judgement_mode);
+        self.expected_playfield = Some(pf.grid_hash());
+        self.i += 1;
+        self.held = false;
+        Ok(Some(Action::Move(step.transition)))
+    }
 }
 
 //---
 
-#[derive(Copy, Clone, Debug, Default)]
-pub struct SimpleBot {}
+/// How to choose among the equally scored placements.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum TieBreak {
+    /// The first one in the order of [MoveDecisionResource::sorted_candidates].
+    #[default]
+    Order,
+    /// The lowest bottom of the blocks.
+    LowestLanding,
+    /// The closest to the center of the playfield.
+    Center,
+    /// The fewest moves from the spawn.
+    FewestInputs,
+    /// Random with the seed.
+    Random(u64),
+}
+
+impl FromStr for TieBreak {
+    type Err = &'static str;
+    /// e.g. `order`, `landing`, `center`, `inputs` and `random:0`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':') {
+            Some(("random", seed)) => seed.parse().map(Self::Random).map_err(|_| "invalid seed"),
+            None => match s {
+                "order" => Ok(Self::Order),
+                "landing" => Ok(Self::LowestLanding),
+                "center" => Ok(Self::Center),
+                "inputs" => Ok(Self::FewestInputs),
+                _ => Err("unknown tie-break"),
+            },
+            _ => Err("unknown tie-break"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct SimpleBot {
+    tie_break: TieBreak,
+    rng: Option<StdRng>,
+    last_think_info: Option<ThinkInfo>,
+    scratch: SearchScratch,
+}
+
+impl SimpleBot {
+    pub fn with_tie_break(tie_break: TieBreak) -> Self {
+        let rng = match tie_break {
+            TieBreak::Random(seed) => Some(StdRng::seed_from_u64(seed)),
+            _ => None,
+        };
+        Self { tie_break, rng, ..Default::default() }
+    }
+    pub fn tie_break(&self) -> TieBreak { self.tie_break }
+    fn break_tie(&mut self, ties: &[Placement], game: &Game, mdr: &MoveDecisionResource) -> Placement {
+        let spec = game.state.falling_piece.as_ref().unwrap().piece_spec;
+        let width = game.state.playfield.width();
+        let selected = match self.tie_break {
+            TieBreak::Order => ties.first(),
+            TieBreak::LowestLanding => ties.iter()
+                .min_by_key(|pl| pl.pos.1 + spec.grid(pl.orientation).bottom_padding()),
+            TieBreak::Center => ties.iter().min_by_key(|pl| {
+                let g = spec.grid(pl.orientation);
+                let (left, right) = (pl.pos.0 + g.left_padding(), pl.pos.0 + g.width() - g.right_padding());
+                // Doubled to keep the half cells.
+                (left + right - width).abs()
+            }),
+            TieBreak::FewestInputs => ties.iter()
+                .min_by_key(|pl| mdr.brute_force_search_result.get(pl).map_or(usize::MAX, |path| path.len())),
+            TieBreak::Random(_) => ties.choose(self.rng.as_mut().unwrap()),
+        };
+        *selected.unwrap()
+    }
+}
 
 impl Bot for SimpleBot {
     fn think(&mut self, game: &Game) -> Result<Action, Box<dyn Error>> {
-        let mdr = MoveDecisionResource::with_game(game)?;
+        let mdr = MoveDecisionResource::with_game_and_scratch(game, &mut self.scratch)?;
         if mdr.dst_candidates.is_empty() {
+            self.scratch.recycle(mdr.brute_force_search_result);
             return Err("no movable placements".into());
         }
-        let selected = mdr.dst_candidates.iter()
-            .min_by(|pl1, pl2| pl1.pos.1.cmp(&pl2.pos.1))
-            .unwrap();
-        Ok(Action::Move(MoveTransition::new(selected.clone(), None)))
+        let candidates = mdr.sorted_candidates();
+        // Lower is better, and the placements fitting flush (creating no holes) are preferred.
+        let heights = game.state.playfield.heights();
+        let spec = game.state.falling_piece.as_ref().ok_or("no falling piece")?.piece_spec;
+        let penalty = game.state.playfield.height() as f32;
+        let score = |pl: &Placement| -pl.pos.1 as f32 - if is_flush(&heights, spec, pl) { 0.0 } else { penalty };
+        let key = |pl: &Placement| (!is_flush(&heights, spec, pl), pl.pos.1);
+        let best_key = candidates.iter().map(key).min().unwrap();
+        let ties = candidates.iter().filter(|pl| key(pl) == best_key).copied().collect::<Vec<_>>();
+        let selected = self.break_tie(&ties, game, &mdr);
+        self.last_think_info = Some(ThinkInfo {
+            num_expanded_nodes: candidates.len(),
+            max_depth: 1,
+            candidate_scores: candidates.iter()
+                .map(|pl| (Action::Move(MoveTransition::new(*pl, None)), score(pl)))
+                .collect(),
+            chosen_score: Some(score(&selected)),
+        });
+        self.scratch.recycle(mdr.brute_force_search_result);
+        Ok(Action::Move(MoveTransition::new(selected, None)))
+    }
+    fn last_think_info(&self) -> Option<ThinkInfo> { self.last_think_info.clone() }
+}
+
+/// Wraps a bot to override its action by [SurvivalSolver] when the action leads to topping out.
+#[derive(Clone, Debug, Default)]
+pub struct SurvivalGuard<B: Bot> {
+    pub bot: B,
+    pub solver: SurvivalSolver,
+}
+
+impl<B: Bot> SurvivalGuard<B> {
+    pub fn new(bot: B, solver: SurvivalSolver) -> Self { Self { bot, solver } }
+    fn survives(&self, game: &Game, action: &Action) -> Result<bool, Box<dyn Error>> {
+        let mut next = game.clone();
+        let depth = match action {
+            Action::Move(mt) => {
+                let spec = next.state.falling_piece.as_ref().ok_or("no falling piece")?.piece_spec;
+                next.state.falling_piece = Some(FallingPiece::new_with_last_move_transition(spec, mt));
+                next.lock()?;
+                self.solver.depth.saturating_sub(1)
+            }
+            Action::Hold => {
+                next.hold()?;
+                self.solver.depth
+            }
+        };
+        Ok(find_survival_line(&next, depth)?.is_some())
+    }
+}
+
+impl<B: Bot> Bot for SurvivalGuard<B> {
+    fn think(&mut self, game: &Game) -> Result<Action, Box<dyn Error>> {
+        let action = self.bot.think(game)?;
+        if !self.solver.is_triggered(game) || self.survives(game, &action)? {
+            return Ok(action);
+        }
+        match self.solver.solve(game)?.and_then(|line| line.first().copied()) {
+            Some(step) if step.hold => Ok(Action::Hold),
+            Some(step) => Ok(Action::Move(step.move_transition)),
+            // No way to survive.
+            None => Ok(action),
+        }
     }
+    fn last_think_info(&self) -> Option<ThinkInfo> { self.bot.last_think_info() }
+    fn last_plan_divergence(&self) -> Option<PlanDivergence> { self.bot.last_plan_divergence() }
 }
 
 //---
@@ -38,7 +486,13 @@ pub trait SimpleBotRunnerHooks {
     fn on_start(&mut self, _game: &Game) -> Result<(), Box<dyn Error>> { Ok(()) }
     fn on_iter(&mut self, _game: &Game) -> Result<bool, Box<dyn Error>> { Ok(true) }
     fn on_action(&mut self, _game: &Game, _action: &Action) -> Result<(), Box<dyn Error>> { Ok(()) }
+    /// Called before `on_action()` if the bot provides [Bot::last_think_info].
+    fn on_think_info(&mut self, _game: &Game, _info: &ThinkInfo) -> Result<(), Box<dyn Error>> { Ok(()) }
+    /// Called before `on_action()` if the bot discarded its plan (see [Bot::last_plan_divergence]).
+    fn on_replan(&mut self, _game: &Game, _divergence: &PlanDivergence) -> Result<(), Box<dyn Error>> { Ok(()) }
     fn on_move_step(&mut self, _game: &Game) -> Result<(), Box<dyn Error>> { Ok(()) }
+    /// Called when the same position appeared too many times. The run will be terminated after this.
+    fn on_loop_detected(&mut self, _game: &Game, _position_key: u64) -> Result<(), Box<dyn Error>> { Ok(()) }
     fn on_end(&mut self, _game: &Game) -> Result<(), Box<dyn Error>> { Ok(()) }
 }
 
@@ -46,75 +500,180 @@ pub struct DefaultSimpleBotRunnerHooks;
 
 impl SimpleBotRunnerHooks for DefaultSimpleBotRunnerHooks {}
 
+/// Calls all the hooks in order, e.g. to attach a stats recorder and a visualizer to one run.
+/// The run continues only while all the `on_iter()` return `true`.
+#[derive(Default)]
+pub struct CompositeSimpleBotRunnerHooks(pub Vec<Box<dyn SimpleBotRunnerHooks>>);
+
+impl CompositeSimpleBotRunnerHooks {
+    pub fn new() -> Self { Self::default() }
+    pub fn push(&mut self, hooks: Box<dyn SimpleBotRunnerHooks>) { self.0.push(hooks); }
+}
+
+impl SimpleBotRunnerHooks for CompositeSimpleBotRunnerHooks {
+    fn on_start(&mut self, game: &Game) -> Result<(), Box<dyn Error>> {
+        self.0.iter_mut().try_for_each(|h| h.on_start(game))
+    }
+    fn on_iter(&mut self, game: &Game) -> Result<bool, Box<dyn Error>> {
+        let mut r = true;
+        for h in self.0.iter_mut() {
+            r &= h.on_iter(game)?;
+        }
+        Ok(r)
+    }
+    fn on_action(&mut self, game: &Game, action: &Action) -> Result<(), Box<dyn Error>> {
+        self.0.iter_mut().try_for_each(|h| h.on_action(game, action))
+    }
+    fn on_think_info(&mut self, game: &Game, info: &ThinkInfo) -> Result<(), Box<dyn Error>> {
+        self.0.iter_mut().try_for_each(|h| h.on_think_info(game, info))
+    }
+    fn on_replan(&mut self, game: &Game, divergence: &PlanDivergence) -> Result<(), Box<dyn Error>> {
+        self.0.iter_mut().try_for_each(|h| h.on_replan(game, divergence))
+    }
+    fn on_move_step(&mut self, game: &Game) -> Result<(), Box<dyn Error>> {
+        self.0.iter_mut().try_for_each(|h| h.on_move_step(game))
+    }
+    fn on_loop_detected(&mut self, game: &Game, position_key: u64) -> Result<(), Box<dyn Error>> {
+        self.0.iter_mut().try_for_each(|h| h.on_loop_detected(game, position_key))
+    }
+    fn on_end(&mut self, game: &Game) -> Result<(), Box<dyn Error>> {
+        self.0.iter_mut().try_for_each(|h| h.on_end(game))
+    }
+}
+
 pub struct SimpleBotRunner {
     max_iterations: usize,
     quick_action: bool,
     random_seed: Option<u64>,
     debug_print: bool,
+    max_repetitions: Option<usize>,
+    initial_garbage: Vec<X>,
+    rising_garbage: Option<RisingGarbage>,
+    piece_generator: Option<Box<dyn PieceGenerator>>,
 }
 
 impl SimpleBotRunner {
     pub fn new(max_iterations: usize, quick_action: bool, random_seed: Option<u64>, debug_print: bool) -> Self {
-        Self { max_iterations, quick_action, random_seed, debug_print }
+        Self { max_iterations, quick_action, random_seed, debug_print, max_repetitions: None, initial_garbage: Vec::new(), rising_garbage: None, piece_generator: None }
+    }
+    /// If `Some(n)` is specified, the run is terminated when the same position (see
+    /// [Game::position_key]) appears `n` times. `n` should be 2 or more. Disabled by default.
+    pub fn set_max_repetitions(&mut self, n: Option<usize>) -> Result<(), &'static str> {
+        if n.is_some_and(|n| n < 2) {
+            return Err("max repetitions should be 2 or more.");
+        }
+        self.max_repetitions = n;
+        Ok(())
+    }
+    /// Each run uses a clone of `generator` for the next pieces instead of the random one of
+    /// `random_seed`.
+    pub fn set_piece_generator(&mut self, generator: Option<Box<dyn PieceGenerator>>) {
+        self.piece_generator = generator;
+    }
+    /// Garbage rows given to the playfield at start (see [crate::Playfield::append_garbage]).
+    pub fn set_initial_garbage(&mut self, gap_x_list: &[X]) {
+        self.initial_garbage = gap_x_list.to_vec();
+    }
+    /// Garbage rows rising during each run. The gap pattern starts over every run.
+    pub fn set_rising_garbage(&mut self, rising_garbage: Option<RisingGarbage>) {
+        self.rising_garbage = rising_garbage;
+    }
+    /// Runs until all the initial garbage rows are cleared and reports the downstack speed.
+    pub fn run_downstack_benchmark(&self, bot: &mut impl Bot) -> Result<DownstackReport, Box<dyn Error>> {
+        let mut hooks = DownstackBenchmarkHooks::default();
+        let game = self.run(bot, &mut hooks)?;
+        let remaining = CheeseMetrics::new(&game.state.playfield);
+        let initial = hooks.initial.unwrap_or_default();
+        let cleared = initial.garbage_rows.saturating_sub(remaining.garbage_rows);
+        Ok(DownstackReport {
+            num_pieces: game.stats.lock as usize,
+            initial_garbage_rows: initial.garbage_rows,
+            cleared_garbage_rows: cleared,
+            remaining_garbage_holes: remaining.garbage_holes,
+            pieces_per_garbage_line: pieces_per_garbage_line(game.stats.lock as usize, cleared),
+            completed: remaining.garbage_rows == 0,
+        })
     }
     pub fn run_with_no_hooks(&self, bot: &mut impl Bot) -> Result<Game, Box<dyn Error>> {
         let mut dummy = DefaultSimpleBotRunnerHooks;
         self.run(bot, &mut dummy)
     }
-    pub fn run(&self, bot: &mut impl Bot, hook: &mut impl SimpleBotRunnerHooks) -> Result<Game, Box<dyn Error>> {
+    pub fn run(&self, bot: &mut impl Bot, hook: &mut (impl SimpleBotRunnerHooks + ?Sized)) -> Result<Game, Box<dyn Error>> {
         let mut game: Game = Default::default();
+        game.state.playfield.append_garbage(&self.initial_garbage);
 
-        let mut rpg = self.random_seed.map(|seed| RandomPieceGenerator::new(StdRng::seed_from_u64(seed)));
-        if let Some(rpg) = rpg.as_mut() {
-            game.supply_next_pieces(&rpg.generate());
+        if let Some(generator) = self.piece_generator.as_ref() {
+            game.set_piece_generator(Some(generator.clone()));
+            game.setup_falling_piece(None).unwrap();
+        } else if let Some(seed) = self.random_seed {
+            game.set_piece_generator(Some(Box::new(RandomPieceGenerator::new(StdRng::seed_from_u64(seed)))));
             game.setup_falling_piece(None).unwrap();
         }
         hook.on_start(&game)?;
 
+        let mut rising_garbage = self.rising_garbage.clone();
+        if let Some(rg) = rising_garbage.as_mut() {
+            rg.reset();
+        }
+        let mut position_counts: HashMap<u64, usize> = HashMap::new();
         for n in 0..self.max_iterations {
             if !hook.on_iter(&game)? {
                 break;
             }
             if self.debug_print { println!("===== {} =====\n{}", n, game); }
-            if game.should_supply_next_pieces() {
-                if let Some(rpg) = rpg.as_mut() {
-                    game.supply_next_pieces(&rpg.generate());
+            if let Some(max_repetitions) = self.max_repetitions {
+                let key = game.position_key();
+                let count = position_counts.entry(key).or_insert(0);
+                *count += 1;
+                if *count >= max_repetitions {
+                    if self.debug_print { println!("Loop detected: {:016x}", key); }
+                    hook.on_loop_detected(&game, key)?;
+                    break;
                 }
             }
 
             let action = bot.think(&game)?;
             if self.debug_print { println!("Action: {:?}", action); }
+            if let Some(info) = bot.last_think_info() {
+                hook.on_think_info(&game, &info)?;
+            }
+            if let Some(divergence) = bot.last_plan_divergence() {
+                if self.debug_print { println!("Replan: {:?}", divergence); }
+                hook.on_replan(&game, &divergence)?;
+            }
             hook.on_action(&game, &action)?;
 
-            match action {
-                Action::Move(mt) => {
-                    if self.quick_action {
-                        let fp = FallingPiece::new_with_last_move_transition(
-                            game.state.falling_piece.unwrap().piece_spec,
-                            &mt,
-                        );
-                        game.state.falling_piece = Some(fp);
+            let mt = match action {
+                Action::Move(mt) => Some(mt),
+                Action::Hold => None,
+            };
+            match mt {
+                Some(mt) if self.quick_action => {
+                    let mt = game.establish_move_hint(&mt)?;
+                    let fp = FallingPiece::new_with_last_move_transition(
+                        game.state.falling_piece.unwrap().piece_spec,
+                        &mt,
+                    );
+                    game.state.falling_piece = Some(fp);
+                    if self.debug_print { println!("{}", game); }
+                    hook.on_move_step(&game)?;
+                }
+                _ => {
+                    let path = game.get_action_path(mt.is_none(), mt.as_ref())?;
+                    let mut mp = MovePlayer::new(path);
+                    while mp.step(&mut game)? {
                         if self.debug_print { println!("{}", game); }
                         hook.on_move_step(&game)?;
-                    } else {
-                        let path = game.get_almost_good_move_path(&mt)?;
-
-                        let mut mp = MovePlayer::new(path);
-                        while mp.step(&mut game)? {
-                            if self.debug_print { println!("{}", game); }
-                            hook.on_move_step(&game)?;
-                        }
-                    }
-
-                    game.lock().unwrap();
-                    if game.state.is_game_over() {
-                        break;
                     }
                 }
-                Action::Hold => {
-                    game.hold()?;
-                    if self.debug_print { println!("{}", game); }
-                    hook.on_move_step(&game)?;
+            }
+            if mt.is_some() {
+                game.lock().unwrap();
+                if let Some(rg) = rising_garbage.as_mut() {
+                    rg.on_lock(&mut game)?;
+                }
+                if game.state.is_game_over() {
+                    break;
                 }
             }
         }
@@ -125,9 +684,36 @@ impl SimpleBotRunner {
     }
 }
 
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct DownstackReport {
+    pub num_pieces: usize,
+    pub initial_garbage_rows: usize,
+    pub cleared_garbage_rows: usize,
+    pub remaining_garbage_holes: usize,
+    pub pieces_per_garbage_line: Option<f32>,
+    /// `true` if all the garbage rows were cleared.
+    pub completed: bool,
+}
+
+#[derive(Default)]
+struct DownstackBenchmarkHooks {
+    initial: Option<CheeseMetrics>,
+}
+
+impl SimpleBotRunnerHooks for DownstackBenchmarkHooks {
+    fn on_start(&mut self, game: &Game) -> Result<(), Box<dyn Error>> {
+        self.initial = Some(CheeseMetrics::new(&game.state.playfield));
+        Ok(())
+    }
+    fn on_iter(&mut self, game: &Game) -> Result<bool, Box<dyn Error>> {
+        Ok(CheeseMetrics::new(&game.state.playfield).garbage_rows > 0)
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::Piece;
 
     #[test]
     fn test_simple_bot_runner() {
@@ -137,4 +723,273 @@ mod test {
         // println!("{}", game);
         assert_eq!(20, game.stats.lock);
     }
+
+    #[test]
+    fn test_simple_bot_runner_rising_garbage() {
+        let mut runner = SimpleBotRunner::new(100, true, Some(0), false);
+        runner.set_rising_garbage(Some(RisingGarbage::new(1, 2, vec![0, 9]).unwrap()));
+        let mut bot = SimpleBot::default();
+        let game = runner.run_with_no_hooks(&mut bot).unwrap();
+        assert!(game.state.is_game_over());
+        assert!(game.stats.lock < 100);
+    }
+
+    #[test]
+    fn test_downstack_benchmark() {
+        let mut runner = SimpleBotRunner::new(100, true, Some(0), false);
+        runner.set_initial_garbage(&[0, 1, 2, 3]);
+        let mut bot = SimpleBot::default();
+        let report = runner.run_downstack_benchmark(&mut bot).unwrap();
+        assert_eq!(4, report.initial_garbage_rows);
+        assert!(report.num_pieces > 0);
+        assert!(report.completed, "{:?}", report);
+        assert_eq!(4, report.cleared_garbage_rows);
+        assert_eq!(0, report.remaining_garbage_holes);
+        assert_eq!(Some(report.num_pieces as f32 / 4.0), report.pieces_per_garbage_line);
+    }
+
+    #[test]
+    fn test_simple_bot_runner_loop_detection() {
+        struct Hooks { num_loops: usize }
+        impl SimpleBotRunnerHooks for Hooks {
+            fn on_loop_detected(&mut self, _game: &Game, _position_key: u64) -> Result<(), Box<dyn Error>> {
+                self.num_loops += 1;
+                Ok(())
+            }
+        }
+        let mut runner = SimpleBotRunner::new(100, true, None, false);
+        assert!(runner.set_max_repetitions(Some(0)).is_err());
+        assert!(runner.set_max_repetitions(Some(1)).is_err());
+        runner.set_max_repetitions(Some(2)).unwrap();
+        // Only O pieces: the bot keeps perfect-clearing and the empty playfield comes back.
+        runner.set_piece_generator(Some(Box::new(crate::StaticPieceGenerator::new(&[Piece::O], true))));
+        let mut bot = SimpleBot::default();
+        let mut hooks = Hooks { num_loops: 0 };
+        let game = runner.run(&mut bot, &mut hooks).unwrap();
+        assert_eq!(1, hooks.num_loops);
+        assert!(game.stats.lock > 0);
+        assert!(game.stats.lock < 100);
+    }
+
+    #[test]
+    fn test_survival_guard() {
+        let mut game: Game = Game::default();
+        game.state.playfield.set_rows_with_strs((0, 0).into(), &["@@@@@@@@@ "; 20]);
+        game.supply_next_pieces(&[crate::Piece::O, crate::Piece::I, crate::Piece::O]);
+        game.setup_falling_piece(None).unwrap();
+        let mut bot = SimpleBot::default();
+        assert!(matches!(bot.think(&game).unwrap(), Action::Move(_)));
+        let mut guarded = SurvivalGuard::new(bot, SurvivalSolver::new(15, 2));
+        assert_eq!(Action::Hold, guarded.think(&game).unwrap());
+    }
+
+    #[test]
+    fn test_apply_action() {
+        let mut game: Game = Game::default();
+        game.state.playfield.set_rows_with_strs((0, 0).into(), &[
+            "@@@@      ",
+            "@@@   @@@@",
+            "@@@@ @@@@@",
+        ]);
+        game.supply_next_pieces(&[Piece::T, Piece::O]);
+        game.setup_falling_piece(None).unwrap();
+        let resource = MoveDecisionResource::with_game(&game).unwrap();
+        let tsd = crate::helper::MoveDecisionHelper::with_game(&game, &resource).unwrap().tspin_moves().unwrap()
+            .into_iter()
+            .find(|(_, lc)| *lc == crate::LineClear::tsd())
+            .unwrap().0;
+        let hintless = MoveTransition::new(tsd.placement, None);
+        assert!(game.establish_move_hint(&hintless).unwrap().hint.is_some());
+
+        let mut played = game.clone();
+        assert_eq!(None, played.apply_action(&Action::Hold).unwrap());
+        assert_eq!(Some(Piece::T), played.state.hold_piece);
+
+        let outcome = game.apply_action(&Action::Move(hintless)).unwrap().unwrap();
+        assert_eq!(crate::LineClear::tsd(), outcome.line_clear);
+        assert_eq!(1, game.stats.line_clear.get(&crate::LineClear::tsd()));
+        // Not a spin for the other pieces.
+        let dropped = MoveTransition::new(Placement::new(crate::Orientation::Orientation0, (4, 0).into()), None);
+        assert_eq!(dropped, game.establish_move_hint(&dropped).unwrap());
+    }
+
+    #[test]
+    fn test_plan() {
+        let mut game: Game = Game::default();
+        game.supply_next_pieces(&[Piece::T, Piece::O, Piece::I, Piece::J]);
+        game.setup_falling_piece(None).unwrap();
+        let dropped = |g: &Game| {
+            let mut g = g.clone();
+            g.firm_drop().unwrap();
+            MoveTransition::new(g.state.falling_piece.unwrap().placement, None)
+        };
+        let mut plan = Plan::default();
+        plan.push(PlanStep::new(false, Piece::T, dropped(&game)));
+        let mut next = game.clone();
+        next.firm_drop().unwrap();
+        next.lock().unwrap();
+        next.hold().unwrap();
+        plan.push(PlanStep::new(true, Piece::I, dropped(&next)));
+        plan.validate(&game).unwrap();
+        let mut wrong = plan.clone();
+        wrong.steps[1].piece = Piece::O;
+        assert_eq!(Err("piece mismatch"), wrong.validate(&game));
+
+        let mut executed = game.clone();
+        plan.execute(&mut executed).unwrap();
+        assert_eq!(2, executed.stats.lock);
+        assert_eq!(Some(Piece::O), executed.state.hold_piece);
+
+        let mut executor = PlanExecutor::new(plan.clone());
+        let mut replayed = game.clone();
+        let mut actions = Vec::new();
+        while let Some(action) = executor.next_action(&replayed) {
+            match action {
+                Action::Move(mt) => {
+                    let path = replayed.get_action_path(false, Some(&mt)).unwrap();
+                    MovePlayer::new(path).fast_forward(&mut replayed).unwrap();
+                    replayed.lock().unwrap();
+                }
+                Action::Hold => { replayed.hold().unwrap(); }
+            }
+            actions.push(action);
+        }
+        assert_eq!(3, actions.len());
+        assert_eq!(Action::Hold, actions[1]);
+        assert!(executor.is_end());
+        assert_eq!(executed.state.playfield, replayed.state.playfield);
+        assert_eq!(plan, Plan::from_actions(&game, &actions).unwrap());
+
+        let rows = |s: &str| crate::parse::parse_compact(s, 10).unwrap();
+        let pages = [vec![], rows("4T5/3TTT4"), rows("3IIII3/4T5/3TTT4")];
+        assert_eq!(crate::parse::encode_fumen(&pages), plan.to_fumen(&game));
+        assert_eq!(Err("piece mismatch"), wrong.to_fumen(&game));
+
+        // The queue differs from the plan.
+        let mut other: Game = Game::default();
+        other.supply_next_pieces(&[Piece::S, Piece::O]);
+        other.setup_falling_piece(None).unwrap();
+        let mut executor = PlanExecutor::new(plan.clone());
+        assert_eq!(Err(PlanDivergence::Piece { expected: Piece::T, actual: Some(Piece::S) }), executor.poll(&other));
+        assert!(executor.is_end());
+        assert!(executor.remaining().is_empty());
+        assert_eq!(Ok(None), executor.poll(&other));
+
+        // Garbage arrived after the first piece.
+        let mut executor = PlanExecutor::new(plan);
+        let mut attacked = game.clone();
+        assert!(matches!(executor.poll(&attacked), Ok(Some(Action::Move(_)))));
+        attacked.firm_drop().unwrap();
+        attacked.lock().unwrap();
+        attacked.receive_garbage(&[0]);
+        assert_eq!(Err(PlanDivergence::Playfield), executor.poll(&attacked));
+    }
+
+    #[test]
+    fn test_simple_bot_tie_break() {
+        let mut game = Game::default();
+        game.supply_next_pieces(&[Piece::O]);
+        game.setup_falling_piece(None).unwrap();
+        let think = |tie_break: TieBreak| match SimpleBot::with_tie_break(tie_break).think(&game).unwrap() {
+            Action::Move(mt) => mt.placement,
+            Action::Hold => unreachable!(),
+        };
+        let spec = Piece::O.default_spec();
+        let left = |pl: Placement| pl.pos.0 + spec.grid(pl.orientation).left_padding();
+        assert_eq!(0, left(think(TieBreak::Order)));
+        assert_eq!(4, left(think(TieBreak::Center)));
+        // No moves from the spawn.
+        assert_eq!(left(game.state.falling_piece.as_ref().unwrap().placement), left(think(TieBreak::FewestInputs)));
+        let mut bot1 = SimpleBot::with_tie_break(TieBreak::Random(1));
+        let mut bot2 = SimpleBot::with_tie_break(TieBreak::Random(1));
+        for _ in 0..3 {
+            assert_eq!(bot1.think(&game).unwrap(), bot2.think(&game).unwrap());
+        }
+        assert_eq!(Ok(TieBreak::Random(7)), "random:7".parse());
+        assert_eq!(Ok(TieBreak::LowestLanding), "landing".parse());
+        assert!("random".parse::<TieBreak>().is_err());
+    }
+
+    #[test]
+    fn test_suggest() {
+        let mut game = Game::default();
+        game.supply_next_pieces(&[Piece::O]);
+        game.setup_falling_piece(None).unwrap();
+        let mut bot = SimpleBot::default();
+        let best = bot.think(&game).unwrap();
+        let s = suggest(&mut bot, &game, &best).unwrap();
+        assert!(s.is_played);
+        assert_eq!(Some(0.0), s.score_delta());
+
+        let mut played = game.clone();
+        played.firm_drop().unwrap();
+        let mt = played.state.falling_piece.as_ref().unwrap().last_move_transition(true).unwrap();
+        let s = suggest(&mut bot, &game, &Action::Move(mt)).unwrap();
+        assert_eq!(best, s.suggested);
+        assert!(s.played_score.is_some());
+        assert!(s.score_delta().unwrap() >= 0.0);
+
+        let s = suggest(&mut bot, &game, &Action::Hold).unwrap();
+        assert!(!s.is_played);
+        assert_eq!(None, s.played_score);
+        assert_eq!(None, s.score_delta());
+
+        let info = bot.last_think_info().unwrap();
+        let mapped = info.clone().map_actions(|a| a.is_same_decision(&best));
+        assert_eq!(info.candidate_scores.len(), mapped.candidate_scores.len());
+        assert_eq!(1, mapped.candidate_scores.iter().filter(|(same, _)| *same).count());
+        assert_eq!(info.chosen_score, mapped.chosen_score);
+    }
+
+    #[test]
+    fn test_composite_hooks() {
+        use std::cell::Cell;
+        use std::rc::Rc;
+        struct Counter(Rc<Cell<usize>>);
+        impl SimpleBotRunnerHooks for Counter {
+            fn on_action(&mut self, _game: &Game, _action: &Action) -> Result<(), Box<dyn Error>> {
+                self.0.set(self.0.get() + 1);
+                Ok(())
+            }
+        }
+        struct Limit(usize);
+        impl SimpleBotRunnerHooks for Limit {
+            fn on_iter(&mut self, _game: &Game) -> Result<bool, Box<dyn Error>> {
+                self.0 = self.0.saturating_sub(1);
+                Ok(self.0 > 0)
+            }
+        }
+        let count = Rc::new(Cell::new(0));
+        let mut hooks = CompositeSimpleBotRunnerHooks::new();
+        hooks.push(Box::new(Counter(count.clone())));
+        hooks.push(Box::new(Limit(3)));
+        hooks.push(Box::new(Counter(count.clone())));
+        let hooks: &mut dyn SimpleBotRunnerHooks = &mut hooks;
+        let runner = SimpleBotRunner::new(10, true, Some(0), false);
+        let game = runner.run(&mut SimpleBot::default(), hooks).unwrap();
+        assert_eq!(2, game.stats.lock);
+        assert_eq!(4, count.get());
+    }
+
+    #[test]
+    fn test_think_info_hook() {
+        #[derive(Default)]
+        struct Hooks { infos: Vec<ThinkInfo> }
+        impl SimpleBotRunnerHooks for Hooks {
+            fn on_think_info(&mut self, _game: &Game, info: &ThinkInfo) -> Result<(), Box<dyn Error>> {
+                self.infos.push(info.clone());
+                Ok(())
+            }
+        }
+        let runner = SimpleBotRunner::new(3, true, Some(0), false);
+        let mut bot = SimpleBot::default();
+        let mut hooks = Hooks::default();
+        runner.run(&mut bot, &mut hooks).unwrap();
+        assert_eq!(3, hooks.infos.len());
+        for info in &hooks.infos {
+            assert!(!info.candidate_scores.is_empty());
+            let best = info.candidate_scores.iter().map(|(_, s)| *s).fold(f32::MIN, f32::max);
+            assert_eq!(Some(best), info.chosen_score);
+        }
+    }
 }
@@ -0,0 +1,124 @@
+//! Imperfect play, e.g. to generate datasets like human play or to test recovery from misdrops.
+
+use std::error::Error;
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use crate::{Game, MoveTransition, Orientation, Placement};
+use crate::helper::MoveDecisionResource;
+use super::{Action, Bot, PlanDivergence, ThinkInfo};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Noise {
+    /// The piece is placed at the next column.
+    Shift,
+    /// The last rotation from the spawn orientation is missed.
+    SkippedRotation,
+}
+
+impl Noise {
+    pub const ALL: [Noise; 2] = [Noise::Shift, Noise::SkippedRotation];
+}
+
+/// Returns a reachable placement perturbed from `placement` by `noise`,
+/// or `None` if no such placements.
+pub fn perturb_placement(game: &Game, placement: Placement, noise: Noise, rng: &mut impl Rng) -> Option<Placement> {
+    let resource = MoveDecisionResource::with_game(game).ok()?;
+    let candidates = resource.sorted_candidates();
+    let x = placement.pos.0;
+    let perturbed = match noise {
+        Noise::Shift => candidates.into_iter()
+            .filter(|pl| pl.orientation == placement.orientation && (pl.pos.0 - x).abs() == 1)
+            .collect::<Vec<_>>(),
+        Noise::SkippedRotation => {
+            let orientations: &[Orientation] = match placement.orientation {
+                Orientation::Orientation0 => &[],
+                Orientation::Orientation1 | Orientation::Orientation3 => &[Orientation::Orientation0],
+                Orientation::Orientation2 => &[Orientation::Orientation1, Orientation::Orientation3],
+            };
+            let rotated = candidates.into_iter()
+                .filter(|pl| orientations.contains(&pl.orientation))
+                .collect::<Vec<_>>();
+            let min_dx = rotated.iter().map(|pl| (pl.pos.0 - x).abs()).min();
+            rotated.into_iter().filter(|pl| Some((pl.pos.0 - x).abs()) == min_dx).collect()
+        }
+    };
+    perturbed.choose(rng).copied()
+}
+
+/// Wraps a bot to perturb its placements by [Noise] with the probability of `rate`.
+#[derive(Clone, Debug)]
+pub struct NoisyBot<B: Bot> {
+    pub bot: B,
+    pub rate: f32,
+    /// The kinds of noise chosen uniformly.
+    pub noises: Vec<Noise>,
+    rng: StdRng,
+    last_noise: Option<Noise>,
+}
+
+impl<B: Bot> NoisyBot<B> {
+    pub fn new(bot: B, rate: f32, seed: u64) -> Self {
+        Self { bot, rate, noises: Noise::ALL.to_vec(), rng: StdRng::seed_from_u64(seed), last_noise: None }
+    }
+    /// Returns the noise applied in the last `think()`.
+    pub fn last_noise(&self) -> Option<Noise> { self.last_noise }
+}
+
+impl<B: Bot> Bot for NoisyBot<B> {
+    fn think(&mut self, game: &Game) -> Result<Action, Box<dyn Error>> {
+        self.last_noise = None;
+        let action = self.bot.think(game)?;
+        let mt = match action {
+            Action::Move(mt) => mt,
+            Action::Hold => return Ok(action),
+        };
+        if self.rng.gen::<f32>() >= self.rate {
+            return Ok(action);
+        }
+        let mut noises = self.noises.clone();
+        noises.shuffle(&mut self.rng);
+        for noise in noises {
+            if let Some(pl) = perturb_placement(game, mt.placement, noise, &mut self.rng) {
+                self.last_noise = Some(noise);
+                return Ok(Action::Move(MoveTransition::new(pl, None)));
+            }
+        }
+        Ok(action)
+    }
+    fn last_think_info(&self) -> Option<ThinkInfo> { self.bot.last_think_info() }
+    fn last_plan_divergence(&self) -> Option<PlanDivergence> { self.bot.last_plan_divergence() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Piece;
+    use crate::bot::SimpleBot;
+
+    #[test]
+    fn test_noisy_bot() {
+        let mut game = Game::default();
+        game.supply_next_pieces(&[Piece::T]);
+        game.setup_falling_piece(None).unwrap();
+        let mut rng = StdRng::seed_from_u64(0);
+        let pl = Placement::new(Orientation::Orientation2, (3, 0).into());
+        let shifted = perturb_placement(&game, pl, Noise::Shift, &mut rng).unwrap();
+        assert_eq!(Orientation::Orientation2, shifted.orientation);
+        assert_eq!(1, (shifted.pos.0 - 3).abs());
+        let rotated = perturb_placement(&game, pl, Noise::SkippedRotation, &mut rng).unwrap();
+        assert!(matches!(rotated.orientation, Orientation::Orientation1 | Orientation::Orientation3));
+        let pl = Placement::new(Orientation::Orientation0, (3, 0).into());
+        assert_eq!(None, perturb_placement(&game, pl, Noise::SkippedRotation, &mut rng));
+
+        let expected = SimpleBot::default().think(&game).unwrap();
+        let mut bot = NoisyBot::new(SimpleBot::default(), 0.0, 0);
+        assert_eq!(expected, bot.think(&game).unwrap());
+        assert_eq!(None, bot.last_noise());
+        let mut bot = NoisyBot::new(SimpleBot::default(), 1.0, 0);
+        let action = bot.think(&game).unwrap();
+        assert!(bot.last_noise().is_some());
+        assert!(!action.is_same_decision(&expected));
+        assert!(bot.last_think_info().is_some());
+    }
+}
@@ -0,0 +1,106 @@
+//! Success criteria of solvers and puzzles, composable with [Goal::All] and [Goal::Any].
+//!
+//! A goal is judged by the game reached from the start, where the start is given as its statistics.
+
+use deep_trinity_grid::{Cell as CellTrait, Grid};
+use crate::{Count, Game, LineClear, Playfield, Statistics, TSpin};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Goal {
+    PerfectClear,
+    /// At least the number of lines are cleared in total.
+    LinesCleared(Count),
+    /// Same as `LineClear(LineClear::tsd())`.
+    TSpinDouble,
+    /// Any lock clears the lines in this way.
+    LineClear(LineClear),
+    /// All the blocks of the pattern are filled. The other cells can be anything.
+    BoardMatches(Playfield<'static>),
+    All(Vec<Goal>),
+    Any(Vec<Goal>),
+}
+
+impl Goal {
+    /// Parses `pc`, `lines<n>` (e.g. `lines4`) or a line clear (e.g. `tsd`, `tetris`).
+    pub fn parse(s: &str) -> Result<Self, &'static str> {
+        if s == "pc" {
+            return Ok(Self::PerfectClear);
+        }
+        if let Some(n) = s.strip_prefix("lines") {
+            return Ok(Self::LinesCleared(n.parse().map_err(|_| "invalid number of lines")?));
+        }
+        for tspin in [None, Some(TSpin::Standard), Some(TSpin::Mini)] {
+            for num_lines in 0..=4 {
+                let lc = LineClear::new(num_lines, tspin);
+                if lc.to_string() == s {
+                    return Ok(Self::LineClear(lc));
+                }
+            }
+        }
+        Err("unknown goal")
+    }
+    /// `start` is the statistics of the game at the start.
+    pub fn is_achieved(&self, start: &Statistics, game: &Game) -> bool {
+        let stats = &game.stats;
+        match self {
+            Self::PerfectClear => stats.perfect_clear > start.perfect_clear,
+            Self::LinesCleared(n) => num_cleared_lines(stats) - num_cleared_lines(start) >= *n,
+            Self::TSpinDouble => Self::LineClear(LineClear::tsd()).is_achieved(start, game),
+            Self::LineClear(lc) => stats.line_clear.get(lc) > start.line_clear.get(lc),
+            Self::BoardMatches(pattern) => {
+                let grid = &game.state.playfield.grid;
+                (0..pattern.height()).all(|y| (0..pattern.width()).all(|x| {
+                    pattern.grid.cell((x, y).into()).is_empty() || grid.cell((x, y).into()).is_filled()
+                }))
+            }
+            Self::All(goals) => goals.iter().all(|g| g.is_achieved(start, game)),
+            Self::Any(goals) => goals.iter().any(|g| g.is_achieved(start, game)),
+        }
+    }
+}
+
+fn num_cleared_lines(stats: &Statistics) -> Count {
+    stats.line_clear.data.iter().map(|(lc, n)| lc.num_lines as Count * n).sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Piece;
+
+    #[test]
+    fn test_goal() {
+        let mut game: Game = Game::default();
+        game.state.playfield.set_rows_with_strs((0, 0).into(), &[
+            "@@@@@@@@  ",
+            "@@@@@@@@  ",
+        ]);
+        game.supply_next_pieces(&[Piece::O, Piece::I]);
+        game.setup_falling_piece(None).unwrap();
+        let start = game.stats.clone();
+        let pattern = Playfield::parse("8OO/8OO").unwrap();
+        let goals = [Goal::PerfectClear, Goal::LinesCleared(2), Goal::BoardMatches(pattern.clone())];
+        assert!(goals.iter().all(|g| !g.is_achieved(&start, &game)));
+
+        game.state.playfield.set_rows_with_strs((8, 0).into(), &["@@", "@@"]);
+        assert!(Goal::BoardMatches(pattern).is_achieved(&start, &game));
+        game.state.playfield.set_rows_with_strs((8, 0).into(), &["  ", "  "]);
+
+        game.shift(4, true).unwrap();
+        game.firm_drop().unwrap();
+        game.lock().unwrap();
+        assert!(Goal::PerfectClear.is_achieved(&start, &game));
+        assert!(Goal::LinesCleared(2).is_achieved(&start, &game));
+        assert!(!Goal::LinesCleared(3).is_achieved(&start, &game));
+        assert!(Goal::LineClear(LineClear::new(2, None)).is_achieved(&start, &game));
+        assert!(!Goal::TSpinDouble.is_achieved(&start, &game));
+        assert!(!Goal::All(vec![Goal::PerfectClear, Goal::TSpinDouble]).is_achieved(&start, &game));
+        assert!(Goal::Any(vec![Goal::PerfectClear, Goal::TSpinDouble]).is_achieved(&start, &game));
+        assert!(!Goal::PerfectClear.is_achieved(&game.stats, &game));
+
+        assert_eq!(Goal::PerfectClear, Goal::parse("pc").unwrap());
+        assert_eq!(Goal::LinesCleared(4), Goal::parse("lines4").unwrap());
+        assert_eq!(Goal::LineClear(LineClear::tsd()), Goal::parse("tsd").unwrap());
+        assert!(Goal::parse("foo").is_err());
+    }
+}
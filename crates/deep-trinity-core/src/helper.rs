@@ -1,9 +1,10 @@
-use std::collections::{HashSet, VecDeque};
-use crate::{Game, MoveTransition, FallingPiece, Playfield, GameRules, Piece, MovePathItem, Move, MovePath, LineClear, RotationMode, Placement, Orientation::*, NUM_PIECES};
-use crate::move_search::{MoveSearcher, SearchConfiguration, SearchResult};
-use crate::move_search::heuristic_bruteforce::HeuristicBruteForceMoveSearcher;
+use std::collections::HashSet;
+use crate::{MoveTransition, FallingPiece, Playfield, GameRules, Piece, MovePathItem, Move, MovePath, RotationMode, Placement, Orientation::*};
+use crate::move_search::{MoveSearcher, SearchConfiguration};
 use crate::move_search::bruteforce::BruteForceMoveSearcher;
+use crate::move_search::heuristic_bruteforce;
 use crate::move_search::humanly_optimized::HumanlyOptimizedMoveSearcher;
+#[cfg(feature = "search-astar")]
 use crate::move_search::astar::AStarMoveSearcher;
 
 pub fn get_alternative_placements(piece: Piece, placement: &Placement) -> Vec<Placement> {
@@ -82,100 +83,38 @@ pub fn get_nearest_alternative_placement(piece: Piece, target: &Placement, src:
     candidate
 }
 
-//---
+mod cheese;
+pub use cheese::{CheeseMetrics, pieces_per_garbage_line, generate_cheese_gaps};
 
-#[derive(Clone)]
-pub struct MoveDecisionResource {
-    /// Reachable and lockable placements including all alternative placements.
-    pub dst_candidates: HashSet<Placement>,
-    /// The result of the search by [HeuristicBruteForceMoveSearcher].
-    pub brute_force_search_result: SearchResult,
-}
+#[cfg(feature = "bot")]
+mod survival;
+#[cfg(feature = "bot")]
+pub use survival::{SurvivalStep, SurvivalSolver, find_survival_line};
 
-impl MoveDecisionResource {
-    pub fn new<'a>(pf: &Playfield<'a>, fp: &FallingPiece<'a>, rules: &GameRules) -> Self {
-        let mut searcher: HeuristicBruteForceMoveSearcher = Default::default();
-        let conf = SearchConfiguration::new(pf, fp.piece_spec, fp.placement, rules.rotation_mode);
-        let search_result = searcher.search(&conf);
-        let dst_candidates = pf.search_lockable_placements(fp.piece_spec).iter()
-            .filter(|&p| search_result.contains(p))
-            .copied()
-            .collect::<HashSet<_>>();
-        Self {
-            dst_candidates,
-            brute_force_search_result: search_result,
-        }
-    }
-    pub fn with_game<'a>(game: &Game<'a>) -> Result<Self, &'static str> {
-        if matches!(game.state.falling_piece, None) {
-            return Err("The falling_piece should not be None.");
-        }
-        Ok(Self::new(&game.state.playfield, game.state.falling_piece.as_ref().unwrap(), &game.rules))
-    }
-}
+mod contour;
+pub use contour::{is_flush, flush_placements, SkylineFingerprint, SKYLINE_DELTA_LIMIT, skyline_fingerprint};
 
-pub struct MoveDecisionHelper<'a> {
-    pub falling_piece: &'a FallingPiece<'a>,
-    pub playfield: &'a Playfield<'a>,
-    pub rules: &'a GameRules,
-    pub resource: &'a MoveDecisionResource,
-}
+mod stacking;
+pub use stacking::{StackingFeatures, st_notches, sz_chain_len};
 
-impl<'a> MoveDecisionHelper<'a> {
-    pub fn new(pf: &'a Playfield<'a>, fp: &'a FallingPiece<'a>, rules: &'a GameRules, resource: &'a MoveDecisionResource) -> Self {
-        Self {
-            playfield: pf,
-            falling_piece: fp,
-            rules,
-            resource,
-        }
-    }
-    pub fn with_game(game: &'a Game<'a>, resource: &'a MoveDecisionResource) -> Result<Self, &'static str> {
-        Ok(Self::new(&game.state.playfield, game.state.falling_piece.as_ref().unwrap(), &game.rules, resource))
-    }
-    pub fn tspin_moves(&self) -> Result<Vec<(MoveTransition, LineClear)>, &'static str> {
-        if self.falling_piece.piece() != Piece::T {
-            return Err("This helper is not for T piece.");
-        }
-        let mut r = vec![];
-        for dst in self.resource.dst_candidates.iter() {
-            let fp = FallingPiece::new(self.falling_piece.piece_spec, *dst);
-            for cw in &[true, false] {
-                for src in self.playfield.check_reverse_rotation(self.rules.rotation_mode, &fp, *cw).iter() {
-                    if !self.resource.brute_force_search_result.contains(src) {
-                        continue;
-                    }
-                    let mt = MoveTransition::new(*dst, Some(MovePathItem::new(Move::Rotate(if *cw { 1 } else { -1 }), *src)));
-                    let line_clear = self.playfield.check_line_clear(
-                        &FallingPiece::new_with_last_move_transition(self.falling_piece.piece_spec, &mt),
-                        self.rules.tspin_judgement_mode);
-                    if line_clear.tspin.is_none() {
-                        continue;
-                    }
-                    r.push((mt, line_clear));
-                }
-            }
-        }
-        Ok(r)
-    }
-    pub fn tetris_destinations(&self) -> Result<Vec<Placement>, &'static str> {
-        if self.falling_piece.piece() != Piece::I {
-            return Err("This helper is not for I piece.");
-        }
-        let r = self.resource.dst_candidates.iter()
-            .filter(|&p| {
-                if p.orientation.is_even() {
-                    return false;
-                }
-                let fp = FallingPiece::new(self.falling_piece.piece_spec, *p);
-                let line_clear = self.playfield.check_line_clear(&fp, self.rules.tspin_judgement_mode);
-                line_clear.is_tetris()
-            })
-            .copied()
-            .collect::<Vec<_>>();
-        Ok(r)
-    }
-}
+mod four_wide;
+pub use four_wide::{ComboWell, FOUR_WIDE, find_four_wide_well};
+
+mod bag;
+pub use bag::{BagCandidate, RemainingBag, PieceHistory, infer_bag_state};
+
+mod pruning;
+pub use pruning::{PlacementMetrics, prune_dominated, prune_dominated_transitions};
+
+#[cfg(feature = "bot")]
+mod decision;
+#[cfg(feature = "bot")]
+pub use decision::{MoveDecisionResource, MoveDecisionHelper, NextPiecePredictor, derive_spin_hint, derive_tspin_hint};
+
+#[cfg(feature = "bot")]
+mod repair;
+#[cfg(feature = "bot")]
+pub use repair::{find_repair_plan, pieces_to_repair};
 
 //---
 
@@ -222,6 +161,18 @@ pub fn get_move_candidates(pf: &Playfield, fp: &FallingPiece, rules: &GameRules)
     r
 }
 
+/// Returns the lockable placements reachable only if the last move is a rotation, e.g. genuine
+/// spins, in a deterministic order.
+pub fn spin_only_destinations(pf: &Playfield, fp: &FallingPiece, rules: &GameRules) -> Vec<Placement> {
+    let conf = SearchConfiguration::new(pf, fp.piece_spec, fp.placement, rules.rotation_mode);
+    let spin_only = heuristic_bruteforce::search_spin_only_placements(&conf);
+    let mut r = pf.search_lockable_placements(fp.piece_spec).into_iter()
+        .filter(|p| spin_only.contains(p))
+        .collect::<Vec<_>>();
+    r.sort();
+    r
+}
+
 pub fn get_almost_good_move_path(rotation_mode: RotationMode, pf: &Playfield, fp: &FallingPiece, dst: &Placement) -> Option<MovePath> {
     let search_conf = SearchConfiguration::new(pf, fp.piece_spec, fp.placement, rotation_mode);
 
@@ -232,8 +183,11 @@ pub fn get_almost_good_move_path(rotation_mode: RotationMode, pf: &Playfield, fp
         return Some(path);
     }
 
-    // Search moves by A* searcher.
+    // Search moves by A* searcher, or by brute force searcher if A* is not available.
+    #[cfg(feature = "search-astar")]
     let r = AStarMoveSearcher::new(*dst, false).search(&search_conf);
+    #[cfg(not(feature = "search-astar"))]
+    let r = BruteForceMoveSearcher::default().search(&search_conf);
     let path_by_aster = if let Some(path) = r.get(dst) {
         path
     } else {
@@ -263,6 +217,7 @@ pub fn get_almost_good_move_path(rotation_mode: RotationMode, pf: &Playfield, fp
     Some(path_by_aster)
 }
 
+#[cfg(feature = "search-astar")]
 #[deprecated]
 pub fn get_almost_good_move_path_old(pf: &Playfield, fp: &FallingPiece, last_transition: &MoveTransition, rotation_mode: RotationMode) -> Option<MovePath> {
     enum Searcher {
@@ -310,173 +265,10 @@ pub fn get_almost_good_move_path_old(pf: &Playfield, fp: &FallingPiece, last_tra
 
 //---
 
-// const FACTORIALS: [u64; 8] = [1, 1, 2, 6, 24, 120, 720, 5040];
-
-/// ```txt
-///            ┌ current_idx = 2
-/// pieces = SZLJITOSZLJITO
-///            └───┘
-///            num_visible_pieces = 5
-/// ```
-pub struct NextPiecePredictor {
-    pieces: VecDeque<Piece>,
-    current_idx: usize,
-    num_visible_pieces: usize,
-}
-
-impl NextPiecePredictor {
-    pub fn new(num_visible_pieces: usize) -> Self {
-        Self {
-            pieces: VecDeque::new(),
-            current_idx: 0,
-            num_visible_pieces,
-        }
-    }
-    pub fn append(&mut self, pieces: &[Piece]) {
-        self.pieces.extend(pieces.iter());
-    }
-    pub fn predict(&self, piece: Piece, offset: usize, span: usize) -> f32 {
-        debug_assert!(self.current_idx < NUM_PIECES);
-
-        if span == 0 {
-            return 0f32;
-        }
-
-        let target_idx = self.current_idx + offset;
-        let end_idx = target_idx + span;
-        if (NUM_PIECES - target_idx % NUM_PIECES) % NUM_PIECES + NUM_PIECES <= span {
-            return 1f32;
-        }
-        debug_assert!(0 < span && span < NUM_PIECES * 2);
-
-        let invisible_idx = (self.current_idx + self.num_visible_pieces).min(self.pieces.len());
-
-        let is_target_span_in_one_bag = (target_idx % NUM_PIECES) + span <= NUM_PIECES;
-        if is_target_span_in_one_bag {
-            let target_bag_idx = (target_idx / NUM_PIECES) * NUM_PIECES;
-            // LJSZITO
-            // └─┘ previous target span
-            for i in target_bag_idx..target_idx {
-                if i < invisible_idx {
-                    if self.pieces[i] == piece {
-                        return 0f32;
-                    }
-                } else {
-                    return span as f32 / (NUM_PIECES - (i - target_bag_idx)) as f32;
-                }
-            }
-            // LJSZITO
-            //    └─┘ target span
-            for i in target_idx..end_idx {
-                if i < invisible_idx {
-                    if self.pieces[i] == piece {
-                        return 1f32;
-                    }
-                } else {
-                    return (end_idx - i) as f32 / (NUM_PIECES - (i - target_bag_idx)) as f32;
-                }
-            }
-            return 0f32;
-        }
-
-        let first_bag_idx = (target_idx / NUM_PIECES) * NUM_PIECES;
-        let second_bag_idx = first_bag_idx + NUM_PIECES;
-        let mut prob = 0f32;
-        let mut is_first_prob_resolved = false;
-        for i in first_bag_idx..target_idx {
-            if i < invisible_idx {
-                if self.pieces[i] == piece {
-                    break;
-                }
-            } else {
-                prob = span as f32 / (NUM_PIECES - (i - first_bag_idx)) as f32;
-                is_first_prob_resolved = true;
-                break;
-            }
-        }
-        if !is_first_prob_resolved {
-            for i in target_idx..second_bag_idx {
-                if i < invisible_idx {
-                    if self.pieces[i] == piece {
-                        return 1f32;
-                    }
-                } else {
-                    prob = (i - target_idx) as f32 / (NUM_PIECES - (i - first_bag_idx)) as f32;
-                    break;
-                }
-            }
-        }
-        debug_assert!(!is_first_prob_resolved && prob == 0f32);
-        for i in second_bag_idx..end_idx {
-            if i < invisible_idx {
-                if self.pieces[i] == piece {
-                    return 1f32;
-                }
-            } else {
-                prob += (end_idx - i) as f32 / (NUM_PIECES - (i - second_bag_idx)) as f32;
-                break;
-            }
-        }
-        prob
-    }
-    pub fn num_comsumable(&self) -> usize { self.pieces.len() - self.current_idx }
-    pub fn consume(&mut self, limit: usize) -> usize {
-        if self.pieces.is_empty() {
-            self.current_idx = 0;
-            return 0;
-        }
-        let prev = self.current_idx;
-        self.current_idx += limit;
-        if self.current_idx >= self.pieces.len() {
-            self.current_idx = self.pieces.len() - 1;
-        }
-        let num_consumed = self.current_idx - prev;
-        if self.current_idx >= NUM_PIECES {
-            let num_removed = (self.current_idx / NUM_PIECES) * NUM_PIECES;
-            for _ in 0..num_removed {
-                self.pieces.pop_front();
-            }
-            self.current_idx -= num_removed;
-        }
-        num_consumed
-    }
-}
-
-//---
-
-#[cfg(test)]
+#[cfg(all(test, feature = "search-astar"))]
 mod tests {
     use super::*;
 
-    #[test]
-    fn test_move_decision_helper() {
-        let mut pf: Playfield<'static> = Default::default();
-        pf.set_rows_with_strs((0, 0).into(), &[
-            "  @@  @@@@",
-            "@@@   @@@@",
-            "@@@@ @@@@@",
-            "@@@@ @@@@@",
-            "@@@@ @@@@@",
-            "@@@@ @@@@@",
-            "@@@@ @@@@@",
-        ]);
-        let rules: GameRules = Default::default();
-        {
-            let fp = FallingPiece::spawn(Piece::T.default_spec(), Some(&pf));
-            let m = MoveDecisionResource::new(&pf, &fp, &rules);
-            let h = MoveDecisionHelper::new(&pf, &fp, &rules, &m);
-            let moves = h.tspin_moves().unwrap();
-            assert_eq!(10, moves.len());
-        }
-        {
-            let fp = FallingPiece::spawn(Piece::I.default_spec(), Some(&pf));
-            let m = MoveDecisionResource::new(&pf, &fp, &rules);
-            let h = MoveDecisionHelper::new(&pf, &fp, &rules, &m);
-            let dsts = h.tetris_destinations().unwrap();
-            assert_eq!(2, dsts.len());
-        }
-    }
-
     #[test]
     fn test_get_almost_good_move_path() {
         let mut pf: Playfield<'static> = Default::default();
@@ -502,41 +294,4 @@ mod tests {
         // MovePathItem { by: Rotate(1), placement: Placement { orientation: Orientation(2), pos: Vec2(1, 0) } }
         assert_eq!(6, path.len());
     }
-
-    #[test]
-    fn test_next_piece_predictor() {
-        struct Case {
-            num_visible: usize,
-            pieces: Vec<Piece>,
-            num_consumed: usize,
-            piece: Piece,
-            offset: usize,
-            span: usize,
-            prob_permil: u16,
-        }
-        impl Case {
-            fn new(num_visible: usize, pieces_str: &'static str, num_consumed: usize, piece: Piece, offset: usize, span: usize, prob_permil: u16) -> Self {
-                let pieces = pieces_str.chars().map(|c| Piece::try_from_char(c).unwrap()).collect::<Vec<_>>();
-                Self { num_visible, pieces, num_consumed, piece, offset, span, prob_permil }
-            }
-        }
-        for (i, c) in [
-            Case::new(5, "", 0, Piece::I, 0, 0, 0),
-            Case::new(5, "", 0, Piece::I, 0, 1, 142 /* 1/7 */),
-            Case::new(5, "", 0, Piece::I, 0, 2, 285 /* 2/7 */),
-            Case::new(5, "LJSZITO", 0, Piece::T, 0, 6, 500),
-            Case::new(5, "LJSZITO", 0, Piece::T, 0, 7, 1000),
-            Case::new(5, "LJSZITO", 1, Piece::T, 0, 5, 1000),
-            Case::new(5, "LJSZITO", 1, Piece::O, 0, 5, 0),
-            Case::new(5, "LJSZITOLJSZITO", 4, Piece::J, 0, 7, 1000),
-            Case::new(5, "LJSZITOLJSZITO", 4, Piece::S, 0, 7, 400),
-        ].iter().enumerate() {
-            let mut predictor = NextPiecePredictor::new(c.num_visible);
-            predictor.append(c.pieces.as_slice());
-            let n = predictor.consume(c.num_consumed);
-            assert_eq!(c.num_consumed, n, "test #{}", i);
-            let prob = predictor.predict(c.piece, c.offset, c.span);
-            assert_eq!(c.prob_permil, (prob * 1000f32) as u16, "test #{}", i);
-        }
-    }
 }
@@ -0,0 +1,152 @@
+//! Inference of the 7-bag randomizer state from the observed pieces.
+
+use crate::{Piece, NUM_PIECES, PIECES};
+
+/// A state of the current bag for a bag alignment consistent with the observation.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BagCandidate {
+    /// The number of pieces of the bag consumed before the observation.
+    pub phase: usize,
+    /// The pieces of the current bag not observed yet, in the order of [PIECES].
+    pub unseen: Vec<Piece>,
+    /// The number of pieces remaining in the current bag. This is less than `unseen.len()` when
+    /// the current bag started before the observation.
+    pub num_remaining: usize,
+}
+
+impl BagCandidate {
+    pub fn is_determined(&self) -> bool { self.unseen.len() == self.num_remaining }
+}
+
+/// Result of [infer_bag_state].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RemainingBag {
+    pub candidates: Vec<BagCandidate>,
+}
+
+impl RemainingBag {
+    /// `false` if the observation is not of a 7-bag randomizer.
+    pub fn is_consistent(&self) -> bool { !self.candidates.is_empty() }
+    /// Returns the remaining pieces of the current bag if all the candidates agree.
+    pub fn remaining(&self) -> Option<&[Piece]> {
+        let first = self.candidates.first()?;
+        if self.candidates.iter().all(|c| c.is_determined() && c.unseen == first.unseen) {
+            Some(&first.unseen)
+        } else {
+            None
+        }
+    }
+    /// Returns the probability that `piece` comes next, assuming all the candidates are equally
+    /// likely. `1 / 7` if inconsistent.
+    pub fn probability(&self, piece: Piece) -> f32 {
+        if !self.is_consistent() {
+            return 1.0 / NUM_PIECES as f32;
+        }
+        // Even if the remaining pieces are not determined, each unseen piece is equally likely.
+        let sum = self.candidates.iter()
+            .map(|c| if c.unseen.contains(&piece) { 1.0 / c.unseen.len() as f32 } else { 0.0 })
+            .sum::<f32>();
+        sum / self.candidates.len() as f32
+    }
+}
+
+/// Reconstructs the remaining pieces of the current 7-bag from the pieces observed so far in the
+/// order of appearance (e.g. the queue history including the current preview).
+/// The observation doesn't have to start at a bag boundary.
+pub fn infer_bag_state(observed_pieces: &[Piece]) -> RemainingBag {
+    let n = observed_pieces.len();
+    let mut candidates = Vec::new();
+    for phase in 0..NUM_PIECES {
+        // The rest of the bag started before the observation.
+        let first_len = if phase == 0 { 0 } else { (NUM_PIECES - phase).min(n) };
+        let (first, rest) = observed_pieces.split_at(first_len);
+        if has_duplicates(first) || rest.chunks(NUM_PIECES).any(has_duplicates) {
+            continue;
+        }
+        let consumed = (phase + n) % NUM_PIECES;
+        let current = &observed_pieces[n - consumed.min(n)..];
+        candidates.push(BagCandidate {
+            phase,
+            unseen: PIECES.iter().filter(|p| !current.contains(p)).copied().collect(),
+            num_remaining: NUM_PIECES - consumed,
+        });
+    }
+    RemainingBag { candidates }
+}
+
+/// Accumulates the observed pieces from the snapshots of the visible next pieces for
+/// [infer_bag_state].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PieceHistory {
+    pieces: Vec<Piece>,
+    last_visible: Vec<Piece>,
+    max_len: usize,
+}
+
+impl PieceHistory {
+    /// Keeps the last `max_len` pieces at most.
+    pub fn new(max_len: usize) -> Self { Self { max_len, ..Default::default() } }
+    pub fn pieces(&self) -> &[Piece] { &self.pieces }
+    /// Appends the newly appeared pieces by aligning `visible` with the last one.
+    pub fn observe(&mut self, visible: &[Piece]) {
+        let n = self.last_visible.len();
+        let shift = (0..=n).find(|&k| visible.starts_with(&self.last_visible[k..])).unwrap_or(n);
+        self.pieces.extend_from_slice(&visible[(n - shift).min(visible.len())..]);
+        if self.pieces.len() > self.max_len {
+            self.pieces.drain(..self.pieces.len() - self.max_len);
+        }
+        self.last_visible = visible.to_vec();
+    }
+    pub fn clear(&mut self) {
+        self.pieces.clear();
+        self.last_visible.clear();
+    }
+}
+
+fn has_duplicates(pieces: &[Piece]) -> bool {
+    pieces.iter().enumerate().any(|(i, p)| pieces[..i].contains(p))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pieces(s: &str) -> Vec<Piece> {
+        s.chars().map(|c| Piece::try_from_char(c).unwrap()).collect()
+    }
+
+    #[test]
+    fn test_infer_bag_state() {
+        // LJZS | LOJTZSI | LTI
+        let bag = infer_bag_state(&pieces("LJZSLOJTZSILTI"));
+        assert_eq!(1, bag.candidates.len());
+        assert_eq!(3, bag.candidates[0].phase);
+        assert_eq!(Some(pieces("SZJO").as_slice()), bag.remaining());
+        assert_eq!(0.25, bag.probability(Piece::O));
+        assert_eq!(0.0, bag.probability(Piece::T));
+
+        // A bag just finished if aligned.
+        let bag = infer_bag_state(&pieces("SZLJITO"));
+        assert_eq!(Some(&PIECES[..]), bag.candidates.iter().find(|c| c.phase == 0).map(|c| c.unseen.as_slice()));
+
+        // Short observations are ambiguous.
+        let bag = infer_bag_state(&pieces("SZ"));
+        assert_eq!(NUM_PIECES, bag.candidates.len());
+        assert_eq!(None, bag.remaining());
+        assert!(!bag.candidates.iter().find(|c| c.phase == 1).unwrap().is_determined());
+        assert!(bag.probability(Piece::S) < bag.probability(Piece::T));
+
+        let mut history = PieceHistory::new(10);
+        history.observe(&pieces("LJZSL"));
+        history.observe(&pieces("JZSLO"));
+        history.observe(&pieces("SLOJT"));
+        history.observe(&pieces("SLOJT"));
+        history.observe(&pieces("OJTZS"));
+        history.observe(&pieces("ILTIZ"));
+        assert_eq!(pieces("SLOJTZSILTIZ")[2..], *history.pieces());
+
+        let bag = infer_bag_state(&pieces("SSSSSSSS"));
+        assert!(!bag.is_consistent());
+        assert_eq!(1.0 / 7.0, bag.probability(Piece::S));
+    }
+}
@@ -0,0 +1,104 @@
+//! Metrics of "cheese" (garbage rows with scattered holes) for downstacking.
+//!
+//! Garbage rows are the ones tracked by [Playfield::num_garbage_rows], which works also in
+//! [crate::Game::performance_mode], and the ones with [Cell::Garbage] cells, e.g. parsed boards.
+
+use rand::Rng;
+use crate::{Cell, Playfield};
+use deep_trinity_grid::{Grid, X, Y};
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct CheeseMetrics {
+    /// The number of rows including at least one garbage cell.
+    pub garbage_rows: usize,
+    /// The number of empty cells in the garbage rows.
+    pub garbage_holes: usize,
+}
+
+impl CheeseMetrics {
+    pub fn new(pf: &Playfield) -> Self {
+        let mut r = Self::default();
+        for y in 0..pf.stack_height().max(pf.num_garbage_rows()) {
+            let mut num_garbage = 0;
+            let mut num_empty = 0;
+            for x in 0..pf.width() {
+                match pf.grid.cell((x, y).into()) {
+                    Cell::Garbage => num_garbage += 1,
+                    Cell::Empty => num_empty += 1,
+                    _ => {}
+                }
+            }
+            if y < pf.num_garbage_rows() || num_garbage > 0 {
+                r.garbage_rows += 1;
+                r.garbage_holes += num_empty;
+            }
+        }
+        r
+    }
+}
+
+/// Returns `None` if no garbage lines were cleared.
+pub fn pieces_per_garbage_line(num_pieces: usize, num_cleared_garbage_lines: usize) -> Option<f32> {
+    if num_cleared_garbage_lines == 0 {
+        return None;
+    }
+    Some(num_pieces as f32 / num_cleared_garbage_lines as f32)
+}
+
+/// Generates gap positions for [Playfield::append_garbage] so that adjacent rows have different gaps.
+pub fn generate_cheese_gaps(rng: &mut impl Rng, num_rows: Y, width: X) -> Vec<X> {
+    let mut gaps: Vec<X> = Vec::with_capacity(num_rows as usize);
+    for _ in 0..num_rows {
+        let x = loop {
+            let x = rng.gen_range(0..width);
+            if width == 1 || gaps.last() != Some(&x) {
+                break x;
+            }
+        };
+        gaps.push(x);
+    }
+    gaps
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+
+    #[test]
+    fn test_cheese_metrics() {
+        let mut pf = Playfield::default();
+        assert_eq!(CheeseMetrics::default(), CheeseMetrics::new(&pf));
+        let gaps = generate_cheese_gaps(&mut StdRng::seed_from_u64(0), 4, pf.width());
+        assert_eq!(4, gaps.len());
+        assert!(gaps.windows(2).all(|w| w[0] != w[1]));
+        pf.append_garbage(&gaps);
+        // Not taken into account in comparison.
+        let same = Playfield { grid: pf.grid.clone(), ..Default::default() };
+        assert_eq!(0, same.num_garbage_rows());
+        assert_eq!(pf, same);
+        pf.grid.set_cell((gaps[3], 3).into(), Cell::T);
+        pf.grid.set_cell((0, 4).into(), Cell::T);
+        assert_eq!(CheeseMetrics { garbage_rows: 4, garbage_holes: 3 }, CheeseMetrics::new(&pf));
+
+        // Tracked without the piece types of the cells.
+        let mut game: crate::Game = crate::Game::default();
+        game.performance_mode();
+        game.state.playfield.append_garbage(&[0, 0, 0]);
+        assert_eq!(Cell::Any, game.state.playfield.grid.cell((1, 0).into()));
+        assert_eq!(CheeseMetrics { garbage_rows: 3, garbage_holes: 3 }, CheeseMetrics::new(&game.state.playfield));
+        game.supply_next_pieces(&[crate::Piece::I]);
+        game.setup_falling_piece(None).unwrap();
+        game.rotate(1).unwrap();
+        game.shift(-1, true).unwrap();
+        game.firm_drop().unwrap();
+        game.lock().unwrap();
+        assert_eq!(1, game.state.playfield.stack_height());
+        assert_eq!(CheeseMetrics::default(), CheeseMetrics::new(&game.state.playfield));
+        game.state.playfield.append_garbage(&[1]);
+        assert_eq!(CheeseMetrics { garbage_rows: 1, garbage_holes: 1 }, CheeseMetrics::new(&game.state.playfield));
+        assert_eq!(None, pieces_per_garbage_line(3, 0));
+        assert_eq!(Some(2.5), pieces_per_garbage_line(5, 2));
+    }
+}
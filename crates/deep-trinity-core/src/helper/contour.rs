@@ -0,0 +1,125 @@
+//! Helpers about the surface of the stack.
+//!
+//! - Placements fitting flush on the surface, i.e. creating no holes beneath. This is a fast
+//!   pre-filter for evaluators punishing overhangs. Reachability is not checked.
+//! - Skyline fingerprints to classify the shape of the surface regardless of its height and side.
+
+use crate::{Placement, PieceSpec, Playfield, ORIENTATIONS};
+use deep_trinity_grid::{Grid, Cell as _, X, Y};
+
+/// Returns `true` if the bottom profile of the piece matches `heights` (see [Playfield::heights]).
+pub fn is_flush(heights: &[Y], spec: &PieceSpec, placement: &Placement) -> bool {
+    let g = &spec.grids[placement.orientation.to_usize()];
+    let mut has_block = false;
+    for c in 0..g.width() {
+        if let Some(b) = (0..g.height()).find(|y| !g.cell((c, *y).into()).is_empty()) {
+            has_block = true;
+            let x = placement.pos.0 + c;
+            if x < 0 || x as usize >= heights.len() || placement.pos.1 + b != heights[x as usize] {
+                return false;
+            }
+        }
+    }
+    has_block
+}
+
+/// Returns the placements fitting flush on the stack.
+/// The equivalent placements of different orientations (e.g. O) are all included.
+pub fn flush_placements(pf: &Playfield, spec: &PieceSpec) -> Vec<Placement> {
+    let heights = pf.heights();
+    let drop_heights = pf.drop_heights(spec);
+    let mut r = Vec::new();
+    for o in &ORIENTATIONS {
+        for &(x, y) in drop_heights[o.to_usize()].iter() {
+            let placement = Placement::new(*o, (x as X, y).into());
+            if is_flush(&heights, spec, &placement) {
+                r.push(placement);
+            }
+        }
+    }
+    r
+}
+
+/// Height differences between adjacent columns larger than this are regarded as the same.
+pub const SKYLINE_DELTA_LIMIT: Y = 4;
+
+/// A canonical form of the surface of the stack: the height differences between adjacent columns
+/// (clamped by [SKYLINE_DELTA_LIMIT]) normalized with its mirror image.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct SkylineFingerprint {
+    pub deltas: Vec<Y>,
+    /// `true` if `deltas` is of the mirrored surface.
+    /// Always `false` for symmetric surfaces.
+    pub mirrored: bool,
+}
+
+impl SkylineFingerprint {
+    /// `heights` are the ones of [Playfield::heights].
+    pub fn from_heights(heights: &[Y]) -> Self {
+        let deltas = heights.windows(2)
+            .map(|w| (w[1] - w[0]).clamp(-SKYLINE_DELTA_LIMIT, SKYLINE_DELTA_LIMIT))
+            .collect::<Vec<_>>();
+        let mirrored_deltas = deltas.iter().rev().map(|d| -d).collect::<Vec<_>>();
+        if mirrored_deltas < deltas {
+            Self { deltas: mirrored_deltas, mirrored: true }
+        } else {
+            Self { deltas, mirrored: false }
+        }
+    }
+    /// Returns `true` if the surfaces have the same shape but are mirror images of each other.
+    pub fn is_mirror_of(&self, other: &Self) -> bool {
+        self.deltas == other.deltas && self.mirrored != other.mirrored
+    }
+}
+
+pub fn skyline_fingerprint(pf: &Playfield) -> SkylineFingerprint {
+    SkylineFingerprint::from_heights(&pf.heights())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Piece, Orientation::*};
+
+    #[test]
+    fn test_flush_placements() {
+        let mut pf = Playfield::default();
+        pf.set_rows_with_strs((0, 0).into(), &["@@ @@@@@@ "]);
+        let heights = pf.heights();
+        let o = Piece::O.default_spec();
+        let placements = flush_placements(&pf, o);
+        assert!(placements.iter().all(|p| is_flush(&heights, o, p)));
+        // Columns (0, 1), (3, 4), ..., (7, 8)
+        assert_eq!(6, placements.iter().filter(|p| p.orientation == Orientation0).count());
+        assert!(!placements.contains(&Placement::new(Orientation0, (1, 0).into())));
+        let t = Piece::T.default_spec();
+        assert!(flush_placements(&pf, t).contains(&Placement::new(Orientation2, (1, 0).into())));
+        assert!(!is_flush(&heights, t, &Placement::new(Orientation0, (1, 0).into())));
+    }
+
+    #[test]
+    fn test_skyline_fingerprint() {
+        let mut pf = Playfield::default();
+        pf.set_rows_with_strs((0, 0).into(), &[
+            "@         ",
+            "@@@       ",
+            "@@@@@@@ @@",
+        ]);
+        let fp = skyline_fingerprint(&pf);
+        let mut mirrored = Playfield::default();
+        mirrored.set_rows_with_strs((0, 1).into(), &[
+            "         @",
+            "       @@@",
+            "@@ @@@@@@@",
+            "@@@@@@@@@@",
+        ]);
+        let mirrored_fp = skyline_fingerprint(&mirrored);
+        assert!(fp.is_mirror_of(&mirrored_fp));
+        assert_eq!(fp.deltas, mirrored_fp.deltas);
+        assert!(!fp.is_mirror_of(&fp));
+
+        let fp = SkylineFingerprint::from_heights(&[10, 0, 1, 1, 0, 10]);
+        assert_eq!(vec![-4, 1, 0, -1, 4], fp.deltas);
+        assert!(!fp.mirrored);
+    }
+}
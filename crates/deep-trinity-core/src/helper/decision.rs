@@ -0,0 +1,429 @@
+//! Bot-oriented helpers, available with the `bot` feature.
+
+use std::collections::{HashSet, VecDeque};
+use crate::{Game, MoveTransition, FallingPiece, Playfield, GameRules, Piece, MovePathItem, Move, LineClear, Placement, TSpin, NUM_PIECES};
+use crate::move_search::{MoveSearcher, SearchConfiguration, SearchResult, SearchScratch};
+use crate::move_search::heuristic_bruteforce::HeuristicBruteForceMoveSearcher;
+use crate::move_search::instant_gravity::InstantGravityMoveSearcher;
+
+#[derive(Clone)]
+pub struct MoveDecisionResource {
+    /// Reachable and lockable placements including all alternative placements.
+    pub dst_candidates: HashSet<Placement>,
+    /// The result of the search by [HeuristicBruteForceMoveSearcher], or by
+    /// [InstantGravityMoveSearcher] if [GameRules::instant_gravity] is enabled.
+    pub brute_force_search_result: SearchResult,
+}
+
+impl MoveDecisionResource {
+    pub fn new<'a>(pf: &Playfield<'a>, fp: &FallingPiece<'a>, rules: &GameRules) -> Self {
+        Self::new_with_scratch(pf, fp, rules, &mut SearchScratch::new())
+    }
+    /// Same as [Self::new], but the buffers of `scratch` are reused for the search.
+    /// Give back [Self::brute_force_search_result] by [SearchScratch::recycle] after use.
+    pub fn new_with_scratch<'a>(pf: &Playfield<'a>, fp: &FallingPiece<'a>, rules: &GameRules, scratch: &mut SearchScratch) -> Self {
+        let conf = SearchConfiguration::new(pf, fp.piece_spec, fp.placement, rules.rotation_mode);
+        let search_result = if rules.instant_gravity {
+            InstantGravityMoveSearcher.search_with_scratch(&conf, scratch)
+        } else {
+            HeuristicBruteForceMoveSearcher::default().search_with_scratch(&conf, scratch)
+        };
+        let dst_candidates = pf.search_lockable_placements(fp.piece_spec).iter()
+            .filter(|&p| search_result.contains(p))
+            .copied()
+            .collect::<HashSet<_>>();
+        Self {
+            dst_candidates,
+            brute_force_search_result: search_result,
+        }
+    }
+    pub fn with_game<'a>(game: &Game<'a>) -> Result<Self, &'static str> {
+        if matches!(game.state.falling_piece, None) {
+            return Err("The falling_piece should not be None.");
+        }
+        Ok(Self::new(&game.state.playfield, game.state.falling_piece.as_ref().unwrap(), &game.rules))
+    }
+    /// See [Self::new_with_scratch].
+    pub fn with_game_and_scratch<'a>(game: &Game<'a>, scratch: &mut SearchScratch) -> Result<Self, &'static str> {
+        let fp = game.state.falling_piece.as_ref().ok_or("The falling_piece should not be None.")?;
+        Ok(Self::new_with_scratch(&game.state.playfield, fp, &game.rules, scratch))
+    }
+    /// Returns `dst_candidates` in a deterministic order.
+    /// Use this instead of iterating `dst_candidates` directly when reproducibility matters.
+    pub fn sorted_candidates(&self) -> Vec<Placement> {
+        let mut r = self.dst_candidates.iter().copied().collect::<Vec<_>>();
+        r.sort();
+        r
+    }
+}
+
+pub struct MoveDecisionHelper<'a> {
+    pub falling_piece: &'a FallingPiece<'a>,
+    pub playfield: &'a Playfield<'a>,
+    pub rules: &'a GameRules,
+    pub resource: &'a MoveDecisionResource,
+}
+
+impl<'a> MoveDecisionHelper<'a> {
+    pub fn new(pf: &'a Playfield<'a>, fp: &'a FallingPiece<'a>, rules: &'a GameRules, resource: &'a MoveDecisionResource) -> Self {
+        Self {
+            playfield: pf,
+            falling_piece: fp,
+            rules,
+            resource,
+        }
+    }
+    pub fn with_game(game: &'a Game<'a>, resource: &'a MoveDecisionResource) -> Result<Self, &'static str> {
+        Ok(Self::new(&game.state.playfield, game.state.falling_piece.as_ref().unwrap(), &game.rules, resource))
+    }
+    pub fn tspin_moves(&self) -> Result<Vec<(MoveTransition, LineClear)>, &'static str> {
+        if self.falling_piece.piece() != Piece::T {
+            return Err("This helper is not for T piece.");
+        }
+        let mut r = vec![];
+        for dst in self.resource.dst_candidates.iter() {
+            let fp = FallingPiece::new(self.falling_piece.piece_spec, *dst);
+            for cw in &[true, false] {
+                for src in self.playfield.check_reverse_rotation(self.rules.rotation_mode, &fp, *cw).iter() {
+                    if !self.resource.brute_force_search_result.contains(src) {
+                        continue;
+                    }
+                    let mt = MoveTransition::new(*dst, Some(MovePathItem::new(Move::Rotate(if *cw { 1 } else { -1 }), *src)));
+                    let line_clear = self.playfield.check_line_clear(
+                        &FallingPiece::new_with_last_move_transition(self.falling_piece.piece_spec, &mt),
+                        self.rules.tspin_judgement_mode);
+                    if line_clear.tspin.is_none() {
+                        continue;
+                    }
+                    r.push((mt, line_clear));
+                }
+            }
+        }
+        Ok(r)
+    }
+    pub fn tetris_destinations(&self) -> Result<Vec<Placement>, &'static str> {
+        if self.falling_piece.piece() != Piece::I {
+            return Err("This helper is not for I piece.");
+        }
+        let r = self.resource.dst_candidates.iter()
+            .filter(|&p| {
+                if p.orientation.is_even() {
+                    return false;
+                }
+                let fp = FallingPiece::new(self.falling_piece.piece_spec, *p);
+                let line_clear = self.playfield.check_line_clear(&fp, self.rules.tspin_judgement_mode);
+                line_clear.is_tetris()
+            })
+            .copied()
+            .collect::<Vec<_>>();
+        Ok(r)
+    }
+}
+
+/// The final rotations reaching `placement` from the placements in `search_result`, with the
+/// T-spins made by them.
+fn final_rotations(pf: &Playfield, fp: &FallingPiece, placement: Placement, rules: &GameRules, search_result: &SearchResult) -> Vec<(MovePathItem, Option<TSpin>)> {
+    let dst = FallingPiece::new(fp.piece_spec, placement);
+    let mut r = Vec::new();
+    for cw in [true, false] {
+        for src in pf.check_reverse_rotation(rules.rotation_mode, &dst, cw) {
+            if !search_result.contains(&src) {
+                continue;
+            }
+            let item = MovePathItem::new(Move::Rotate(if cw { 1 } else { -1 }), src);
+            let mt = MoveTransition::new(placement, Some(item));
+            let tspin = pf.check_tspin(&FallingPiece::new_with_last_move_transition(fp.piece_spec, &mt), rules.tspin_judgement_mode);
+            r.push((item, tspin));
+        }
+    }
+    r
+}
+
+/// The rank of the final rotation to be preferred, where the standard T-spins are the best.
+fn tspin_rank(tspin: Option<TSpin>) -> u8 {
+    match tspin {
+        Some(TSpin::Standard) => 2,
+        Some(TSpin::Mini) => 1,
+        None => 0,
+    }
+}
+
+/// Returns the hint of the final rotation if `placement` is reachable only by rotations, i.e. not by
+/// a shift or a drop from any placement in `search_result` (see [MoveDecisionResource]).
+/// The rotation making the better T-spin is preferred.
+pub fn derive_spin_hint(pf: &Playfield, fp: &FallingPiece, placement: Placement, rules: &GameRules, search_result: &SearchResult) -> Option<MovePathItem> {
+    if !search_result.contains(&placement) {
+        return None;
+    }
+    let by_shift_or_drop = [(0, 1), (1, 0), (-1, 0)].into_iter()
+        .any(|d| search_result.contains(&Placement::new(placement.orientation, placement.pos + d.into())));
+    if by_shift_or_drop {
+        return None;
+    }
+    let mut rotations = final_rotations(pf, fp, placement, rules, search_result);
+    rotations.sort_by_key(|(item, tspin)| (std::cmp::Reverse(tspin_rank(*tspin)), item.placement));
+    rotations.first().map(|(item, _)| *item)
+}
+
+/// Returns the hint of the final rotation making the best T-spin at `placement`, or `None` if no
+/// rotation from the placements in `search_result` makes a T-spin.
+pub fn derive_tspin_hint(pf: &Playfield, fp: &FallingPiece, placement: Placement, rules: &GameRules, search_result: &SearchResult) -> Option<MovePathItem> {
+    final_rotations(pf, fp, placement, rules, search_result).into_iter()
+        .filter(|(_, tspin)| tspin.is_some())
+        .min_by_key(|(item, tspin)| (std::cmp::Reverse(tspin_rank(*tspin)), item.placement))
+        .map(|(item, _)| item)
+}
+//---
+
+// const FACTORIALS: [u64; 8] = [1, 1, 2, 6, 24, 120, 720, 5040];
+
+/// ```txt
+///            ┌ current_idx = 2
+/// pieces = SZLJITOSZLJITO
+///            └───┘
+///            num_visible_pieces = 5
+/// ```
+pub struct NextPiecePredictor {
+    pieces: VecDeque<Piece>,
+    current_idx: usize,
+    num_visible_pieces: usize,
+}
+
+impl NextPiecePredictor {
+    pub fn new(num_visible_pieces: usize) -> Self {
+        Self {
+            pieces: VecDeque::new(),
+            current_idx: 0,
+            num_visible_pieces,
+        }
+    }
+    pub fn append(&mut self, pieces: &[Piece]) {
+        self.pieces.extend(pieces.iter());
+    }
+    pub fn predict(&self, piece: Piece, offset: usize, span: usize) -> f32 {
+        debug_assert!(self.current_idx < NUM_PIECES);
+
+        if span == 0 {
+            return 0f32;
+        }
+
+        let target_idx = self.current_idx + offset;
+        let end_idx = target_idx + span;
+        if (NUM_PIECES - target_idx % NUM_PIECES) % NUM_PIECES + NUM_PIECES <= span {
+            return 1f32;
+        }
+        debug_assert!(0 < span && span < NUM_PIECES * 2);
+
+        let invisible_idx = (self.current_idx + self.num_visible_pieces).min(self.pieces.len());
+
+        let is_target_span_in_one_bag = (target_idx % NUM_PIECES) + span <= NUM_PIECES;
+        if is_target_span_in_one_bag {
+            let target_bag_idx = (target_idx / NUM_PIECES) * NUM_PIECES;
+            // LJSZITO
+            // └─┘ previous target span
+            for i in target_bag_idx..target_idx {
+                if i < invisible_idx {
+                    if self.pieces[i] == piece {
+                        return 0f32;
+                    }
+                } else {
+                    return span as f32 / (NUM_PIECES - (i - target_bag_idx)) as f32;
+                }
+            }
+            // LJSZITO
+            //    └─┘ target span
+            for i in target_idx..end_idx {
+                if i < invisible_idx {
+                    if self.pieces[i] == piece {
+                        return 1f32;
+                    }
+                } else {
+                    return (end_idx - i) as f32 / (NUM_PIECES - (i - target_bag_idx)) as f32;
+                }
+            }
+            return 0f32;
+        }
+
+        let first_bag_idx = (target_idx / NUM_PIECES) * NUM_PIECES;
+        let second_bag_idx = first_bag_idx + NUM_PIECES;
+        let mut prob = 0f32;
+        let mut is_first_prob_resolved = false;
+        for i in first_bag_idx..target_idx {
+            if i < invisible_idx {
+                if self.pieces[i] == piece {
+                    break;
+                }
+            } else {
+                prob = span as f32 / (NUM_PIECES - (i - first_bag_idx)) as f32;
+                is_first_prob_resolved = true;
+                break;
+            }
+        }
+        if !is_first_prob_resolved {
+            for i in target_idx..second_bag_idx {
+                if i < invisible_idx {
+                    if self.pieces[i] == piece {
+                        return 1f32;
+                    }
+                } else {
+                    prob = (i - target_idx) as f32 / (NUM_PIECES - (i - first_bag_idx)) as f32;
+                    break;
+                }
+            }
+        }
+        debug_assert!(!is_first_prob_resolved && prob == 0f32);
+        for i in second_bag_idx..end_idx {
+            if i < invisible_idx {
+                if self.pieces[i] == piece {
+                    return 1f32;
+                }
+            } else {
+                prob += (end_idx - i) as f32 / (NUM_PIECES - (i - second_bag_idx)) as f32;
+                break;
+            }
+        }
+        prob
+    }
+    pub fn num_comsumable(&self) -> usize { self.pieces.len() - self.current_idx }
+    pub fn consume(&mut self, limit: usize) -> usize {
+        if self.pieces.is_empty() {
+            self.current_idx = 0;
+            return 0;
+        }
+        let prev = self.current_idx;
+        self.current_idx += limit;
+        if self.current_idx >= self.pieces.len() {
+            self.current_idx = self.pieces.len() - 1;
+        }
+        let num_consumed = self.current_idx - prev;
+        if self.current_idx >= NUM_PIECES {
+            let num_removed = (self.current_idx / NUM_PIECES) * NUM_PIECES;
+            for _ in 0..num_removed {
+                self.pieces.pop_front();
+            }
+            self.current_idx -= num_removed;
+        }
+        num_consumed
+    }
+}
+//---
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_move_decision_helper() {
+        let mut pf: Playfield<'static> = Default::default();
+        pf.set_rows_with_strs((0, 0).into(), &[
+            "  @@  @@@@",
+            "@@@   @@@@",
+            "@@@@ @@@@@",
+            "@@@@ @@@@@",
+            "@@@@ @@@@@",
+            "@@@@ @@@@@",
+            "@@@@ @@@@@",
+        ]);
+        let rules: GameRules = Default::default();
+        {
+            let fp = FallingPiece::spawn(Piece::T.default_spec(), Some(&pf));
+            let m = MoveDecisionResource::new(&pf, &fp, &rules);
+            let h = MoveDecisionHelper::new(&pf, &fp, &rules, &m);
+            let moves = h.tspin_moves().unwrap();
+            assert_eq!(10, moves.len());
+        }
+        {
+            let fp = FallingPiece::spawn(Piece::I.default_spec(), Some(&pf));
+            let m = MoveDecisionResource::new(&pf, &fp, &rules);
+            let h = MoveDecisionHelper::new(&pf, &fp, &rules, &m);
+            let dsts = h.tetris_destinations().unwrap();
+            assert_eq!(2, dsts.len());
+        }
+        {
+            let fp = FallingPiece::spawn(Piece::L.default_spec(), Some(&pf));
+            let m1 = MoveDecisionResource::new(&pf, &fp, &rules);
+            let m2 = MoveDecisionResource::new(&pf, &fp, &rules);
+            let sorted = m1.sorted_candidates();
+            assert_eq!(m1.dst_candidates.len(), sorted.len());
+            assert!(sorted.windows(2).all(|w| w[0] < w[1]));
+            assert_eq!(sorted, m2.sorted_candidates());
+            // The same with the reused buffers.
+            let mut scratch = SearchScratch::new();
+            let m = MoveDecisionResource::new_with_scratch(&pf, &fp, &rules, &mut scratch);
+            scratch.recycle(m.brute_force_search_result);
+            let m3 = MoveDecisionResource::new_with_scratch(&pf, &fp, &rules, &mut scratch);
+            assert_eq!(sorted, m3.sorted_candidates());
+            assert_eq!(m1.brute_force_search_result.found, m3.brute_force_search_result.found);
+        }
+    }
+
+    #[test]
+    fn test_derive_spin_hint() {
+        let mut pf: Playfield<'static> = Default::default();
+        pf.set_rows_with_strs((0, 0).into(), &[
+            "@@@@      ",
+            "@@@   @@@@",
+            "@@@@ @@@@@",
+        ]);
+        let rules: GameRules = Default::default();
+        let fp = FallingPiece::spawn(Piece::T.default_spec(), Some(&pf));
+        let m = MoveDecisionResource::new(&pf, &fp, &rules);
+        let sr = &m.brute_force_search_result;
+        let tsd = m.sorted_candidates().into_iter().find(|&p| {
+            let mt = MoveTransition::new(p, derive_tspin_hint(&pf, &fp, p, &rules, sr));
+            pf.check_line_clear(&FallingPiece::new_with_last_move_transition(fp.piece_spec, &mt), rules.tspin_judgement_mode) == LineClear::tsd()
+        }).unwrap();
+        let hint = derive_spin_hint(&pf, &fp, tsd, &rules, sr).unwrap();
+        assert_eq!(Some(hint), derive_tspin_hint(&pf, &fp, tsd, &rules, sr));
+        assert!(matches!(hint.by, Move::Rotate(_)));
+
+        // On the surface, reachable by a drop.
+        let dropped = Placement::new(crate::Orientation::Orientation0, (5, 1).into());
+        assert!(m.dst_candidates.contains(&dropped));
+        assert_eq!(None, derive_spin_hint(&pf, &fp, dropped, &rules, sr));
+
+        let spin_only = crate::helper::spin_only_destinations(&pf, &fp, &rules);
+        assert!(spin_only.contains(&tsd));
+        assert!(!spin_only.contains(&dropped));
+        for p in m.sorted_candidates() {
+            assert_eq!(spin_only.contains(&p), derive_spin_hint(&pf, &fp, p, &rules, sr).is_some(), "{:?}", p);
+        }
+    }
+
+    #[test]
+    fn test_next_piece_predictor() {
+        struct Case {
+            num_visible: usize,
+            pieces: Vec<Piece>,
+            num_consumed: usize,
+            piece: Piece,
+            offset: usize,
+            span: usize,
+            prob_permil: u16,
+        }
+        impl Case {
+            fn new(num_visible: usize, pieces_str: &'static str, num_consumed: usize, piece: Piece, offset: usize, span: usize, prob_permil: u16) -> Self {
+                let pieces = pieces_str.chars().map(|c| Piece::try_from_char(c).unwrap()).collect::<Vec<_>>();
+                Self { num_visible, pieces, num_consumed, piece, offset, span, prob_permil }
+            }
+        }
+        for (i, c) in [
+            Case::new(5, "", 0, Piece::I, 0, 0, 0),
+            Case::new(5, "", 0, Piece::I, 0, 1, 142 /* 1/7 */),
+            Case::new(5, "", 0, Piece::I, 0, 2, 285 /* 2/7 */),
+            Case::new(5, "LJSZITO", 0, Piece::T, 0, 6, 500),
+            Case::new(5, "LJSZITO", 0, Piece::T, 0, 7, 1000),
+            Case::new(5, "LJSZITO", 1, Piece::T, 0, 5, 1000),
+            Case::new(5, "LJSZITO", 1, Piece::O, 0, 5, 0),
+            Case::new(5, "LJSZITOLJSZITO", 4, Piece::J, 0, 7, 1000),
+            Case::new(5, "LJSZITOLJSZITO", 4, Piece::S, 0, 7, 400),
+        ].iter().enumerate() {
+            let mut predictor = NextPiecePredictor::new(c.num_visible);
+            predictor.append(c.pieces.as_slice());
+            let n = predictor.consume(c.num_consumed);
+            assert_eq!(c.num_consumed, n, "test #{}", i);
+            let prob = predictor.predict(c.piece, c.offset, c.span);
+            assert_eq!(c.prob_permil, (prob * 1000f32) as u16, "test #{}", i);
+        }
+    }
+}
@@ -0,0 +1,82 @@
+//! Detection of 4-wide combo wells.
+
+use crate::Playfield;
+use deep_trinity_grid::{Grid, Cell as _, X, Y};
+
+pub const FOUR_WIDE: X = 4;
+
+/// A well of 4 columns surrounded by the walls of the other columns.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ComboWell {
+    /// The leftmost column of the well.
+    pub x: X,
+    /// The lowest row having an empty cell in the well.
+    pub floor: Y,
+    /// The lowest height of the walls. The height of the playfield if no walls.
+    pub wall_top: Y,
+    /// The number of blocks in the well above the floor.
+    pub residual: usize,
+}
+
+impl ComboWell {
+    /// Returns `None` if the well at `x` is out of the playfield.
+    pub fn at(pf: &Playfield, x: X) -> Option<Self> {
+        if x < 0 || x + FOUR_WIDE > pf.width() {
+            return None;
+        }
+        let heights = pf.heights();
+        let wall_top = heights.iter().enumerate()
+            .filter(|(i, _)| (*i as X) < x || (*i as X) >= x + FOUR_WIDE)
+            .map(|(_, h)| *h)
+            .min()
+            .unwrap_or_else(|| pf.height());
+        let row_of_well = |y: Y| (x..x + FOUR_WIDE).filter(|&cx| !pf.grid.cell((cx, y).into()).is_empty()).count();
+        let floor = (0..pf.height()).find(|&y| row_of_well(y) < FOUR_WIDE as usize).unwrap_or_else(|| pf.height());
+        let residual = (floor..pf.stack_height()).map(row_of_well).sum();
+        Some(Self { x, floor, wall_top, residual })
+    }
+    /// The number of lines which can be cleared in the well.
+    pub fn depth(&self) -> Y { (self.wall_top - self.floor).max(0) }
+    /// The standard shape to continue the combo with any piece.
+    pub fn is_three_residual(&self) -> bool { self.residual == 3 }
+}
+
+/// Returns the deepest well, preferring the one with 3 residual blocks and then the left one.
+/// `None` if no wells.
+pub fn find_four_wide_well(pf: &Playfield) -> Option<ComboWell> {
+    let mut r: Option<ComboWell> = None;
+    for x in 0..=(pf.width() - FOUR_WIDE) {
+        let well = match ComboWell::at(pf, x) {
+            Some(w) if w.depth() > 0 => w,
+            _ => continue,
+        };
+        let key = |w: &ComboWell| (w.depth(), w.is_three_residual());
+        if r.as_ref().map_or(true, |best| key(&well) > key(best)) {
+            r = Some(well);
+        }
+    }
+    r
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_four_wide_well() {
+        let mut pf = Playfield::default();
+        assert_eq!(None, find_four_wide_well(&pf));
+        pf.set_rows_with_strs((0, 0).into(), &[
+            "@@@    @@@",
+            "@@@    @@@",
+            "@@@@@  @@@",
+            "@@@@   @@@",
+        ]);
+        let well = find_four_wide_well(&pf).unwrap();
+        assert_eq!(ComboWell { x: 3, floor: 0, wall_top: 4, residual: 3 }, well);
+        assert_eq!(4, well.depth());
+        assert!(well.is_three_residual());
+        assert_eq!(None, ComboWell::at(&pf, 7));
+        assert_eq!(0, ComboWell::at(&pf, 0).unwrap().depth());
+    }
+}
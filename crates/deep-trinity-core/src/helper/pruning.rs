@@ -0,0 +1,120 @@
+//! Pruning of candidate placements obviously worse than another one before evaluation.
+
+use std::collections::HashMap;
+use crate::{MoveTransition, PieceSpec, Placement, Playfield};
+use deep_trinity_grid::{Grid, Cell as _, Y};
+
+/// Metrics of a placement cheap enough to compute for all the candidates.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct PlacementMetrics {
+    pub placement: Placement,
+    /// Bit mask of the columns covered by the blocks.
+    pub columns: u64,
+    /// The bottom row of the blocks.
+    pub landing_height: Y,
+    /// The empty cells newly covered by the blocks.
+    pub num_new_holes: usize,
+    pub num_cleared_lines: usize,
+}
+
+impl PlacementMetrics {
+    /// `heights` are the ones of [Playfield::heights].
+    pub fn new(pf: &Playfield, heights: &[Y], spec: &PieceSpec, placement: Placement) -> Self {
+        let g = spec.grid(placement.orientation);
+        let pos = placement.pos;
+        let mut columns = 0;
+        let mut num_new_holes = 0;
+        for x in 0..g.width() {
+            if let Some(bottom) = (0..g.height()).find(|&y| !g.cell((x, y).into()).is_empty()) {
+                let px = pos.0 + x;
+                columns |= 1 << px;
+                num_new_holes += (pos.1 + bottom - heights[px as usize]).max(0) as usize;
+            }
+        }
+        let num_cleared_lines = (0..g.height())
+            .filter(|&y| {
+                let n = g.num_blocks_of_row(y);
+                n > 0 && pf.grid.num_blocks_of_row(pos.1 + y) + n == pf.width() as usize
+            })
+            .count();
+        Self {
+            placement,
+            columns,
+            landing_height: pos.1 + g.bottom_padding(),
+            num_new_holes,
+            num_cleared_lines,
+        }
+    }
+    /// Returns `true` if `other` covers the same columns, lands lower with no more holes and
+    /// clears no fewer lines.
+    pub fn is_dominated_by(&self, other: &Self) -> bool {
+        self.columns == other.columns
+            && self.landing_height > other.landing_height
+            && self.num_new_holes >= other.num_new_holes
+            && self.num_cleared_lines <= other.num_cleared_lines
+    }
+}
+
+/// Returns the placements not dominated by any other one (see [PlacementMetrics::is_dominated_by])
+/// keeping the order.
+pub fn prune_dominated(pf: &Playfield, spec: &PieceSpec, placements: &[Placement]) -> Vec<Placement> {
+    let heights = pf.heights();
+    let metrics = placements.iter()
+        .map(|pl| PlacementMetrics::new(pf, &heights, spec, *pl))
+        .collect::<Vec<_>>();
+    let mut by_columns: HashMap<u64, Vec<&PlacementMetrics>> = HashMap::new();
+    for m in metrics.iter() {
+        by_columns.entry(m.columns).or_default().push(m);
+    }
+    metrics.iter()
+        .filter(|m| !by_columns[&m.columns].iter().any(|other| m.is_dominated_by(other)))
+        .map(|m| m.placement)
+        .collect()
+}
+
+/// Same as [prune_dominated] for move transitions. The ones with hints are kept since the last
+/// move matters (e.g. T-spins).
+pub fn prune_dominated_transitions(pf: &Playfield, spec: &PieceSpec, transitions: &[MoveTransition]) -> Vec<MoveTransition> {
+    let placements = transitions.iter().map(|mt| mt.placement).collect::<Vec<_>>();
+    let kept = prune_dominated(pf, spec, &placements);
+    transitions.iter()
+        .filter(|mt| mt.hint.is_some() || kept.contains(&mt.placement))
+        .copied()
+        .collect()
+}
+
+#[cfg(all(test, feature = "bot"))]
+mod tests {
+    use super::*;
+    use crate::{Piece, Orientation::*};
+    use crate::helper::MoveDecisionResource;
+    use crate::{FallingPiece, GameRules};
+
+    #[test]
+    fn test_prune_dominated() {
+        let mut pf = Playfield::default();
+        pf.set_rows_with_strs((0, 0).into(), &[
+            "@         ",
+            "@@@  @@@  ",
+        ]);
+        let heights = pf.heights();
+        let spec = Piece::O.default_spec();
+        let on_floor = PlacementMetrics::new(&pf, &heights, spec, Placement::new(Orientation0, (2, -1).into()));
+        assert_eq!(0b11000, on_floor.columns);
+        assert_eq!(0, on_floor.num_new_holes);
+        let lifted = PlacementMetrics::new(&pf, &heights, spec, Placement::new(Orientation0, (2, 0).into()));
+        assert_eq!(2, lifted.num_new_holes);
+        assert!(lifted.is_dominated_by(&on_floor));
+        assert!(!on_floor.is_dominated_by(&lifted));
+
+        let spec = Piece::L.default_spec();
+        let rules = GameRules::default();
+        let fp = FallingPiece::spawn(spec, Some(&pf));
+        let candidates = MoveDecisionResource::new(&pf, &fp, &rules).sorted_candidates();
+        let pruned = prune_dominated(&pf, spec, &candidates);
+        assert!(pruned.len() < candidates.len());
+        assert!(pruned.windows(2).all(|w| w[0] < w[1]));
+        let metrics = pruned.iter().map(|pl| PlacementMetrics::new(&pf, &heights, spec, *pl)).collect::<Vec<_>>();
+        assert!(metrics.iter().all(|m| metrics.iter().all(|other| !m.is_dominated_by(other))));
+    }
+}
@@ -0,0 +1,112 @@
+//! Search for piece sequences to uncover holes, e.g. after a misdrop.
+//!
+//! A hole is an empty cell covered by blocks (see [deep_trinity_grid::Grid::num_covered_empty_cells]),
+//! and it's repaired when the blocks above it are cleared.
+
+use crate::{Game, FallingPiece, MoveTransition};
+use crate::bot::{Plan, PlanStep};
+use crate::helper::MoveDecisionResource;
+use deep_trinity_grid::Grid;
+
+fn num_holes(game: &Game) -> usize {
+    game.state.playfield.grid.num_covered_empty_cells()
+}
+
+/// Returns the shortest plan up to `max_depth` pieces which reduces the holes, `Some` of the empty
+/// plan if no holes, or `None` if not found.
+/// Placements making new holes are not searched.
+pub fn find_repair_plan(game: &Game, max_depth: usize) -> Result<Option<Plan>, &'static str> {
+    let target = num_holes(game);
+    if target == 0 {
+        return Ok(Some(Plan::default()));
+    }
+    let mut plan = Plan::default();
+    for depth in 1..=max_depth {
+        if search(game, target, depth, &mut plan)? {
+            return Ok(Some(plan));
+        }
+    }
+    Ok(None)
+}
+
+/// Returns the number of pieces to repair the holes (see [find_repair_plan]).
+pub fn pieces_to_repair(game: &Game, max_depth: usize) -> Result<Option<usize>, &'static str> {
+    Ok(find_repair_plan(game, max_depth)?.map(|plan| plan.len()))
+}
+
+fn search(game: &Game, target: usize, depth: usize, plan: &mut Plan) -> Result<bool, &'static str> {
+    if depth == 0 || game.state.is_game_over() || game.state.falling_piece.is_none() {
+        return Ok(false);
+    }
+    if search_moves(game, false, target, depth, plan)? {
+        return Ok(true);
+    }
+    if game.can_hold() {
+        let mut held = game.clone();
+        held.hold()?;
+        if held.state.falling_piece.is_some() && search_moves(&held, true, target, depth, plan)? {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+fn search_moves(game: &Game, hold: bool, target: usize, depth: usize, plan: &mut Plan) -> Result<bool, &'static str> {
+    let spec = game.state.falling_piece.as_ref().unwrap().piece_spec;
+    let resource = MoveDecisionResource::with_game(game)?;
+    for placement in resource.sorted_candidates() {
+        let mt = MoveTransition::new(placement, None);
+        let mut next = game.clone();
+        next.state.falling_piece = Some(FallingPiece::new_with_last_move_transition(spec, &mt));
+        next.lock()?;
+        if next.state.is_game_over() {
+            continue;
+        }
+        let n = num_holes(&next);
+        if n > target {
+            continue;
+        }
+        plan.push(PlanStep::new(hold, spec.piece, mt));
+        if n < target || search(&next, target, depth - 1, plan)? {
+            return Ok(true);
+        }
+        plan.truncate(plan.len() - 1);
+    }
+    Ok(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Piece;
+
+    #[test]
+    fn test_find_repair_plan() {
+        let mut game: Game = Game::default();
+        game.state.playfield.set_rows_with_strs((0, 0).into(), &[
+            "@@@@@@@@@ ",
+            "@@@ @@@@@@",
+        ]);
+        game.supply_next_pieces(&[Piece::O, Piece::I, Piece::T]);
+        game.setup_falling_piece(None).unwrap();
+        assert_eq!(1, num_holes(&game));
+
+        // The I piece in the well clears the row covering the hole.
+        let plan = find_repair_plan(&game, 2).unwrap().unwrap();
+        assert_eq!(1, plan.len());
+        assert!(plan.steps[0].hold);
+        assert_eq!(Piece::I, plan.steps[0].piece);
+        let mut repaired = game.clone();
+        plan.execute(&mut repaired).unwrap();
+        assert_eq!(0, num_holes(&repaired));
+
+        game.rules.enable_hold = false;
+        assert_eq!(Some(2), pieces_to_repair(&game, 2).unwrap());
+        assert_eq!(None, pieces_to_repair(&game, 1).unwrap());
+
+        let mut game: Game = Game::default();
+        game.supply_next_pieces(&[Piece::O]);
+        game.setup_falling_piece(None).unwrap();
+        assert_eq!(Some(0), pieces_to_repair(&game, 1).unwrap());
+    }
+}
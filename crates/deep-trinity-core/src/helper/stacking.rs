@@ -0,0 +1,109 @@
+//! Pattern-based features of ST stacking (stacking S/Z and T pieces on an overhang to repeat
+//! T-spins).
+
+use crate::Playfield;
+use deep_trinity_grid::{Grid, Cell as _, X, Y};
+
+/// The walls and the floor are regarded as filled.
+fn is_filled(pf: &Playfield, x: X, y: Y) -> bool {
+    if x < 0 || x >= pf.width() || y < 0 {
+        return true;
+    }
+    y < pf.height() && !pf.grid.bit_grid.cell((x, y).into()).is_empty()
+}
+
+/// Returns the positions of the bottom cells of ST notches, i.e. T slots roofed on one side:
+///
+/// ```text
+/// @..    ..@
+/// ...    ...
+/// @.@    @.@
+/// ```
+///
+/// The notch is kept by stacking S/Z on the roof side while a T fills the slot.
+pub fn st_notches(pf: &Playfield) -> Vec<(X, Y)> {
+    let mut r = Vec::new();
+    for y in 0..pf.stack_height() {
+        for x in 0..pf.width() {
+            let f = |dx: X, dy: Y| is_filled(pf, x + dx, y + dy);
+            if f(0, 0) || !f(-1, 0) || !f(1, 0) || f(-1, 1) || f(0, 1) || f(1, 1) || f(0, 2) {
+                continue;
+            }
+            if f(-1, 2) != f(1, 2) {
+                r.push((x, y));
+            }
+        }
+    }
+    r
+}
+
+/// Returns the number of column steps in S/Z dependency chains, i.e. staircases of 2 or more
+/// consecutive unit steps in the same direction, which only vertical S/Z fill flush without
+/// breaking the staircase.
+/// `heights` are the ones of [Playfield::heights].
+pub fn sz_chain_len(heights: &[Y]) -> usize {
+    let mut r = 0;
+    let mut run = 0;
+    let mut prev = 0;
+    for w in heights.windows(2) {
+        let d = w[1] - w[0];
+        if d.abs() == 1 && d == prev {
+            run += 1;
+        } else {
+            if run >= 2 {
+                r += run;
+            }
+            run = if d.abs() == 1 { 1 } else { 0 };
+        }
+        prev = d;
+    }
+    if run >= 2 {
+        r += run;
+    }
+    r
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct StackingFeatures {
+    pub num_st_notches: usize,
+    pub sz_chain_len: usize,
+}
+
+impl StackingFeatures {
+    pub fn new(pf: &Playfield) -> Self {
+        Self {
+            num_st_notches: st_notches(pf).len(),
+            sz_chain_len: sz_chain_len(&pf.heights()),
+        }
+    }
+    pub fn has_st_overhang(&self) -> bool { self.num_st_notches > 0 }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stacking_features() {
+        let mut pf = Playfield::default();
+        pf.set_rows_with_strs((0, 0).into(), &[
+            "  @@      ",
+            "          ",
+            "@ @@@@@   ",
+            "@@@@@@@@  ",
+        ]);
+        assert_eq!(vec![(1, 1)], st_notches(&pf));
+        let features = StackingFeatures::new(&pf);
+        assert!(features.has_st_overhang());
+        // The staircase of columns 6..=8.
+        assert_eq!(2, features.sz_chain_len);
+
+        // Roofed on both sides.
+        pf.set_rows_with_strs((0, 3).into(), &["@ @@      "]);
+        assert!(st_notches(&pf).is_empty());
+
+        assert_eq!(3, sz_chain_len(&[0, 1, 2, 3, 3, 2, 0]));
+        assert_eq!(4, sz_chain_len(&[3, 2, 1, 1, 2, 3]));
+        assert_eq!(0, sz_chain_len(&[0, 1, 0, 1]));
+    }
+}
@@ -0,0 +1,132 @@
+//! Exhaustive search for a non-topout continuation in constrained boards.
+//!
+//! Scores are ignored entirely. This is intended as a safety net of bots near the top.
+
+use crate::{Game, FallingPiece, MoveTransition};
+use crate::helper::{MoveDecisionResource, derive_spin_hint, derive_tspin_hint};
+use deep_trinity_grid::Y;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct SurvivalStep {
+    /// Hold before the move.
+    pub hold: bool,
+    pub move_transition: MoveTransition,
+}
+
+/// Returns the steps for the next `depth` pieces which don't top out, or `None` if all the
+/// continuations top out. The line can be shorter than `depth` when the known pieces run out.
+pub fn find_survival_line(game: &Game, depth: usize) -> Result<Option<Vec<SurvivalStep>>, &'static str> {
+    let mut line = Vec::with_capacity(depth);
+    if search(game, depth, &mut line)? {
+        Ok(Some(line))
+    } else {
+        Ok(None)
+    }
+}
+
+fn search(game: &Game, depth: usize, line: &mut Vec<SurvivalStep>) -> Result<bool, &'static str> {
+    if game.state.is_game_over() {
+        return Ok(false);
+    }
+    if depth == 0 || game.state.falling_piece.is_none() {
+        return Ok(true);
+    }
+    if search_moves(game, false, depth, line)? {
+        return Ok(true);
+    }
+    if game.can_hold() {
+        let mut held = game.clone();
+        held.hold()?;
+        if held.state.falling_piece.is_some() && search_moves(&held, true, depth, line)? {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+fn search_moves(game: &Game, hold: bool, depth: usize, line: &mut Vec<SurvivalStep>) -> Result<bool, &'static str> {
+    let mdr = MoveDecisionResource::with_game(game)?;
+    let (pf, fp) = (&game.state.playfield, game.state.falling_piece.as_ref().unwrap());
+    let sr = &mdr.brute_force_search_result;
+    let mut candidates = mdr.sorted_candidates().into_iter()
+        .map(|pl| {
+            let hint = derive_tspin_hint(pf, fp, pl, &game.rules, sr)
+                .or_else(|| derive_spin_hint(pf, fp, pl, &game.rules, sr));
+            MoveTransition::new(pl, hint)
+        })
+        .collect::<Vec<_>>();
+    // Lower placements are more likely to survive.
+    candidates.sort_by_key(|mt| mt.placement.pos.1);
+    let spec = fp.piece_spec;
+    for mt in candidates {
+        let mut next = game.clone();
+        next.state.falling_piece = Some(FallingPiece::new_with_last_move_transition(spec, &mt));
+        next.lock()?;
+        line.push(SurvivalStep { hold, move_transition: mt });
+        if search(&next, depth - 1, line)? {
+            return Ok(true);
+        }
+        line.pop();
+    }
+    Ok(false)
+}
+
+/// Runs [find_survival_line] only when the stack is higher than `trigger_height`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct SurvivalSolver {
+    pub trigger_height: Y,
+    pub depth: usize,
+}
+
+impl Default for SurvivalSolver {
+    fn default() -> Self { Self { trigger_height: 16, depth: 3 } }
+}
+
+impl SurvivalSolver {
+    pub fn new(trigger_height: Y, depth: usize) -> Self { Self { trigger_height, depth } }
+    pub fn is_triggered(&self, game: &Game) -> bool {
+        game.state.playfield.stack_height() > self.trigger_height
+    }
+    /// Returns `Ok(None)` also when not triggered.
+    pub fn solve(&self, game: &Game) -> Result<Option<Vec<SurvivalStep>>, &'static str> {
+        if !self.is_triggered(game) {
+            return Ok(None);
+        }
+        find_survival_line(game, self.depth)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Piece;
+
+    #[test]
+    fn test_find_survival_line() {
+        let mut game: Game = Game::default();
+        let rows = ["@@@@@@@@@ "; 20];
+        game.state.playfield.set_rows_with_strs((0, 0).into(), &rows);
+        game.supply_next_pieces(&[Piece::O, Piece::I, Piece::O]);
+        game.setup_falling_piece(None).unwrap();
+        let solver = SurvivalSolver::new(15, 2);
+        assert!(solver.is_triggered(&game));
+        let line = solver.solve(&game).unwrap().unwrap();
+        assert_eq!(2, line.len());
+        // Holding O and dropping I into the well is the only way.
+        assert!(line[0].hold);
+
+        let mut replayed = game.clone();
+        for step in &line {
+            if step.hold {
+                replayed.hold().unwrap();
+            }
+            let spec = replayed.state.falling_piece.as_ref().unwrap().piece_spec;
+            replayed.state.falling_piece = Some(FallingPiece::new_with_last_move_transition(spec, &step.move_transition));
+            replayed.lock().unwrap();
+        }
+        assert!(!replayed.state.is_game_over());
+
+        game.rules.enable_hold = false;
+        assert_eq!(None, find_survival_line(&game, 2).unwrap());
+    }
+}
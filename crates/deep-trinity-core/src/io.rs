@@ -0,0 +1,179 @@
+//! Sources of board states from outside (e.g. screen capture or OCR), to run the engine on
+//! games played elsewhere.
+//!
+//! [TextBoardSource] reads the compact text protocol: one update per line of space separated
+//! fields, where all the fields are optional.
+//!
+//! - `board=<board>`: The playfield in the compact format of [crate::parse] (`-` for empty).
+//! - `queue=<pieces>`: The current piece followed by the next pieces, e.g. `TIOSZ`.
+//! - `hold=<piece>`: The held piece (`-` for none).
+//!
+//! Empty lines and lines starting with `#` are skipped.
+//! Sources producing updates in other threads (e.g. async tasks) can send them to
+//! [ChannelBoardSource].
+
+use std::error::Error;
+use std::io::BufRead;
+use std::sync::mpsc::{Receiver, TryRecvError};
+use crate::{Game, NextPieces, Piece, Playfield};
+
+/// The fields are `None` if unchanged.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct BoardUpdate {
+    pub playfield: Option<Playfield<'static>>,
+    /// The current piece followed by the next pieces.
+    pub queue: Option<Vec<Piece>>,
+    /// `Some(None)` if the hold is empty.
+    pub hold: Option<Option<Piece>>,
+}
+
+impl BoardUpdate {
+    /// Parses a line of the compact text protocol.
+    pub fn parse(line: &str) -> Result<Self, &'static str> {
+        let mut r = Self::default();
+        for field in line.split_whitespace() {
+            let (key, value) = field.split_once('=').ok_or("invalid field")?;
+            match key {
+                "board" => {
+                    r.playfield = Some(if value == "-" { Playfield::default() } else { Playfield::parse(value)? });
+                }
+                "queue" => {
+                    r.queue = Some(value.chars().map(Piece::try_from_char).collect::<Result<_, _>>()?);
+                }
+                "hold" => {
+                    r.hold = Some(if value == "-" { None } else { Some(value.parse()?) });
+                }
+                _ => return Err("unknown field"),
+            }
+        }
+        Ok(r)
+    }
+    /// Applies the update to `game`. The falling piece is respawned when the queue is given.
+    pub fn apply(&self, game: &mut Game<'static>) -> Result<(), &'static str> {
+        if let Some(pf) = self.playfield.as_ref() {
+            game.state.playfield = pf.clone();
+        }
+        if let Some(hold) = self.hold {
+            game.state.hold_piece = hold;
+        }
+        if let Some(queue) = self.queue.as_ref() {
+            let (current, next) = queue.split_first().ok_or("empty queue")?;
+            let visible_num = game.state.next_pieces.visible_num;
+            game.state.next_pieces = NextPieces::new(visible_num);
+            game.state.next_pieces.supply(next);
+            game.state.falling_piece = None;
+            game.setup_falling_piece(Some(*current))?;
+        }
+        Ok(())
+    }
+}
+
+pub trait BoardSource {
+    /// Waits for the next update. `Ok(None)` is returned when the source is closed.
+    fn next_update(&mut self) -> Result<Option<BoardUpdate>, Box<dyn Error>>;
+    /// Same as [Self::next_update] but returns `Ok(None)` if no update is ready.
+    /// The default implementation waits for sources which can't tell it.
+    fn try_next_update(&mut self) -> Result<Option<BoardUpdate>, Box<dyn Error>> {
+        self.next_update()
+    }
+}
+
+/// Reads updates of the compact text protocol, e.g. from stdin.
+pub struct TextBoardSource<R: BufRead> {
+    reader: R,
+    line: String,
+}
+
+impl<R: BufRead> TextBoardSource<R> {
+    pub fn new(reader: R) -> Self { Self { reader, line: String::new() } }
+}
+
+impl TextBoardSource<std::io::StdinLock<'static>> {
+    pub fn stdin() -> Self { Self::new(std::io::stdin().lock()) }
+}
+
+impl<R: BufRead> BoardSource for TextBoardSource<R> {
+    fn next_update(&mut self) -> Result<Option<BoardUpdate>, Box<dyn Error>> {
+        loop {
+            self.line.clear();
+            if self.reader.read_line(&mut self.line)? == 0 {
+                return Ok(None);
+            }
+            let line = self.line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            return Ok(Some(BoardUpdate::parse(line)?));
+        }
+    }
+}
+
+/// Receives updates sent from other threads.
+pub struct ChannelBoardSource {
+    receiver: Receiver<BoardUpdate>,
+}
+
+impl ChannelBoardSource {
+    pub fn new(receiver: Receiver<BoardUpdate>) -> Self { Self { receiver } }
+}
+
+impl BoardSource for ChannelBoardSource {
+    fn next_update(&mut self) -> Result<Option<BoardUpdate>, Box<dyn Error>> {
+        Ok(self.receiver.recv().ok())
+    }
+    fn try_next_update(&mut self) -> Result<Option<BoardUpdate>, Box<dyn Error>> {
+        match self.receiver.try_recv() {
+            Ok(update) => Ok(Some(update)),
+            Err(TryRecvError::Empty) | Err(TryRecvError::Disconnected) => Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use deep_trinity_grid::Grid;
+
+    #[test]
+    fn test_text_board_source() {
+        let input = "# comment\n\nboard=3T6/2TTT5 queue=IOS hold=T\nqueue=ZL hold=-\nboard=- \n";
+        let mut source = TextBoardSource::new(input.as_bytes());
+        let mut game = Game::default();
+
+        let update = source.next_update().unwrap().unwrap();
+        assert_eq!(Some(vec![Piece::I, Piece::O, Piece::S]), update.queue);
+        update.apply(&mut game).unwrap();
+        assert_eq!(4, game.state.playfield.grid.num_blocks());
+        assert_eq!(Piece::I, game.state.falling_piece.as_ref().unwrap().piece());
+        assert_eq!(vec![Piece::O, Piece::S], game.state.next_pieces.iter().copied().collect::<Vec<_>>());
+        assert_eq!(Some(Piece::T), game.state.hold_piece);
+
+        let update = source.next_update().unwrap().unwrap();
+        assert_eq!(None, update.playfield);
+        update.apply(&mut game).unwrap();
+        assert_eq!(4, game.state.playfield.grid.num_blocks());
+        assert_eq!(Piece::Z, game.state.falling_piece.as_ref().unwrap().piece());
+        assert_eq!(None, game.state.hold_piece);
+
+        source.next_update().unwrap().unwrap().apply(&mut game).unwrap();
+        assert!(game.state.playfield.is_empty());
+        assert!(source.next_update().unwrap().is_none());
+
+        assert!(BoardUpdate::parse("board").is_err());
+        assert!(BoardUpdate::parse("foo=1").is_err());
+        assert!(BoardUpdate::parse("queue=X").is_err());
+        assert!(BoardUpdate::parse("queue=").unwrap().apply(&mut game).is_err());
+    }
+
+    #[test]
+    fn test_channel_board_source() {
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let mut source = ChannelBoardSource::new(receiver);
+        assert_eq!(None, source.try_next_update().unwrap());
+        std::thread::spawn(move || {
+            sender.send(BoardUpdate::parse("queue=T").unwrap()).unwrap();
+        }).join().unwrap();
+        assert_eq!(Some(vec![Piece::T]), source.next_update().unwrap().unwrap().queue);
+        assert_eq!(None, source.next_update().unwrap());
+    }
+}
@@ -0,0 +1,242 @@
+//! Validation of input streams from external clients, e.g. on servers.
+//!
+//! [RulesJudge] consumes the timestamped input events of a client together with the moves the
+//! client claims, and reports the moves which can't be done by the inputs under the timing model
+//! of [InputTimingProfile], where the profile gives the fastest timings allowed.
+
+use std::collections::HashMap;
+use crate::{InputEvent, InputKey, InputTimingProfile, Move};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum ViolationKind {
+    /// The event is older than the previous one. It's ignored.
+    OutOfOrder(InputEvent),
+    /// A key was pressed while pressed, or released while not pressed. It's ignored.
+    InvalidKeyState(InputEvent),
+    /// A key was pressed again before [InputTimingProfile::key_interval] from the release.
+    TooFastRepeat(InputEvent),
+    /// The move needs more inputs than given, e.g. faster shifts than DAS and ARR.
+    TooFastMove(Move),
+    /// The move was made after the lock before the next piece.
+    MoveAfterLock(Move),
+    /// A hold without the input.
+    TooFastHold,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Violation {
+    pub frame: u32,
+    pub kind: ViolationKind,
+}
+
+#[derive(Copy, Clone, Debug, Default)]
+struct KeyLog {
+    /// The frame of the press if pressed.
+    pressed: Option<u32>,
+    released: Option<u32>,
+    /// The number of the moves granted by the released presses.
+    granted: u32,
+    /// The number of the moves done.
+    used: u32,
+}
+
+#[derive(Clone, Debug)]
+pub struct RulesJudge {
+    profile: InputTimingProfile,
+    last_frame: u32,
+    keys: HashMap<InputKey, KeyLog>,
+    locked: bool,
+    violations: Vec<Violation>,
+}
+
+impl RulesJudge {
+    pub fn new(profile: InputTimingProfile) -> Self {
+        Self { profile, last_frame: 0, keys: HashMap::new(), locked: false, violations: Vec::new() }
+    }
+    pub fn violations(&self) -> &[Violation] { &self.violations }
+    /// Returns the violations found since the last call.
+    pub fn take_violations(&mut self) -> Vec<Violation> { std::mem::take(&mut self.violations) }
+    pub fn input(&mut self, event: InputEvent) {
+        if !self.advance(event.frame, ViolationKind::OutOfOrder(event)) {
+            return;
+        }
+        let moves = self.moves_by_press(event.key);
+        let log = self.keys.entry(event.key).or_default();
+        match (event.pressed, log.pressed) {
+            (true, None) => {
+                if matches!(log.released, Some(f) if event.frame < f + self.profile.key_interval) {
+                    self.violations.push(Violation { frame: event.frame, kind: ViolationKind::TooFastRepeat(event) });
+                }
+                log.pressed = Some(event.frame);
+            }
+            (false, Some(start)) => {
+                log.granted = log.granted.saturating_add(moves(event.frame - start));
+                log.pressed = None;
+                log.released = Some(event.frame);
+            }
+            _ => self.violations.push(Violation { frame: event.frame, kind: ViolationKind::InvalidKeyState(event) }),
+        }
+    }
+    /// Judges the move of the falling piece done at `frame`.
+    pub fn apply_move(&mut self, mv: Move, frame: u32) {
+        if !self.advance(frame, ViolationKind::TooFastMove(mv)) {
+            return;
+        }
+        if self.locked {
+            self.violations.push(Violation { frame, kind: ViolationKind::MoveAfterLock(mv) });
+            return;
+        }
+        let (key, n) = match mv {
+            Move::Shift(n) => (if n < 0 { InputKey::Left } else { InputKey::Right }, n.unsigned_abs() as u32),
+            Move::Drop(n) => (InputKey::SoftDrop, n.unsigned_abs() as u32),
+            Move::Rotate(n) => (if n < 0 { InputKey::RotateCcw } else { InputKey::RotateCw }, n.unsigned_abs() as u32),
+        };
+        if !self.consume(key, n, frame) {
+            self.violations.push(Violation { frame, kind: ViolationKind::TooFastMove(mv) });
+        }
+    }
+    pub fn hold(&mut self, frame: u32) {
+        if !self.advance(frame, ViolationKind::TooFastHold) {
+            return;
+        }
+        if !self.consume(InputKey::Hold, 1, frame) {
+            self.violations.push(Violation { frame, kind: ViolationKind::TooFastHold });
+        }
+        self.spawn(frame);
+    }
+    /// The falling piece is locked. Moves are violations until [Self::spawn].
+    pub fn lock(&mut self, frame: u32) {
+        self.last_frame = self.last_frame.max(frame);
+        self.locked = true;
+    }
+    /// The next piece appears. The inputs for the previous piece can't be used for it, but the
+    /// keys held over the spawn keep their DAS.
+    pub fn spawn(&mut self, frame: u32) {
+        self.last_frame = self.last_frame.max(frame);
+        self.locked = false;
+        let keys = self.keys.keys().copied().collect::<Vec<_>>();
+        for key in keys {
+            let moves = self.moves_by_press(key);
+            let log = self.keys.get_mut(&key).unwrap();
+            log.granted = 0;
+            // The moves already granted by a held key were for the previous piece.
+            log.used = log.pressed.map_or(0, |start| moves(frame.max(start) - start));
+        }
+    }
+    /// Returns `false` and records `kind` if `frame` goes back.
+    fn advance(&mut self, frame: u32, kind: ViolationKind) -> bool {
+        if frame < self.last_frame {
+            self.violations.push(Violation { frame, kind });
+            return false;
+        }
+        self.last_frame = frame;
+        true
+    }
+    /// Returns the function giving the number of the moves allowed by a press of `key` for a duration.
+    fn moves_by_press(&self, key: InputKey) -> impl Fn(u32) -> u32 {
+        let InputTimingProfile { das, arr, soft_drop_interval, .. } = self.profile;
+        move |duration: u32| match key {
+            InputKey::Left | InputKey::Right => {
+                if duration < das {
+                    1
+                } else {
+                    (duration - das).checked_div(arr).map_or(u32::MAX, |n| n.saturating_add(2))
+                }
+            }
+            InputKey::SoftDrop => duration.checked_div(soft_drop_interval).unwrap_or(u32::MAX),
+            InputKey::RotateCw | InputKey::RotateCcw | InputKey::Hold => 1,
+        }
+    }
+    fn consume(&mut self, key: InputKey, n: u32, frame: u32) -> bool {
+        let moves = self.moves_by_press(key);
+        let log = self.keys.entry(key).or_default();
+        let available = log.granted.saturating_add(log.pressed.map_or(0, |start| moves(frame - start)));
+        // Rejected moves are not counted to report each of them once.
+        if log.used.saturating_add(n) > available {
+            return false;
+        }
+        log.used += n;
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{MovePath, MovePathItem, Orientation::*, Placement};
+
+    #[test]
+    fn test_rules_judge() {
+        let profile = InputTimingProfile { das: 10, arr: 2, soft_drop_interval: 1, key_interval: 1 };
+        let mut path = MovePath::new(Placement::new(Orientation0, (3, 18).into()));
+        path.push(MovePathItem::new(Move::Shift(-3), Placement::new(Orientation0, (0, 18).into())));
+        path.push(MovePathItem::new(Move::Rotate(-2), Placement::new(Orientation2, (0, 18).into())));
+        path.push(MovePathItem::new(Move::Drop(5), Placement::new(Orientation2, (0, 13).into())));
+
+        // The inputs generated by the same profile are valid, where the moves are done on the releases.
+        let mut judge = RulesJudge::new(profile);
+        let mut frame = 0;
+        for item in path.iter() {
+            let mut events = Vec::new();
+            frame = profile.append_events(item.by, frame, &mut events);
+            events.into_iter().for_each(|e| judge.input(e));
+            judge.apply_move(item.by, frame - profile.key_interval);
+        }
+        assert!(judge.violations().is_empty(), "{:?}", judge.violations());
+
+        // Faster than DAS.
+        let mut judge = RulesJudge::new(profile);
+        judge.input(InputEvent::new(InputKey::Left, true, 0));
+        judge.apply_move(Move::Shift(-1), 0);
+        judge.apply_move(Move::Shift(-1), 9);
+        judge.apply_move(Move::Shift(-1), 10);
+        judge.apply_move(Move::Shift(-1), 12);
+        judge.apply_move(Move::Shift(-1), 13);
+        assert_eq!(vec![
+            Violation { frame: 9, kind: ViolationKind::TooFastMove(Move::Shift(-1)) },
+            Violation { frame: 13, kind: ViolationKind::TooFastMove(Move::Shift(-1)) },
+        ], judge.take_violations());
+
+        // Rotations after the lock and a held rotation key over the spawn.
+        judge.input(InputEvent::new(InputKey::RotateCw, true, 20));
+        judge.apply_move(Move::Rotate(1), 20);
+        judge.lock(21);
+        judge.apply_move(Move::Rotate(1), 21);
+        judge.spawn(22);
+        judge.apply_move(Move::Rotate(1), 22);
+        judge.input(InputEvent::new(InputKey::RotateCw, false, 23));
+        judge.input(InputEvent::new(InputKey::RotateCw, true, 23));
+        judge.input(InputEvent::new(InputKey::RotateCw, true, 24));
+        judge.hold(25);
+        assert_eq!(vec![
+            Violation { frame: 21, kind: ViolationKind::MoveAfterLock(Move::Rotate(1)) },
+            Violation { frame: 22, kind: ViolationKind::TooFastMove(Move::Rotate(1)) },
+            Violation { frame: 23, kind: ViolationKind::TooFastRepeat(InputEvent::new(InputKey::RotateCw, true, 23)) },
+            Violation { frame: 24, kind: ViolationKind::InvalidKeyState(InputEvent::new(InputKey::RotateCw, true, 24)) },
+            Violation { frame: 25, kind: ViolationKind::TooFastHold },
+        ], judge.take_violations());
+
+        judge.input(InputEvent::new(InputKey::Hold, true, 24));
+        assert_eq!(1, judge.violations().len());
+    }
+
+    #[test]
+    fn test_rules_judge_held_over_spawn() {
+        let profile = InputTimingProfile { das: 10, arr: 2, soft_drop_interval: 1, key_interval: 1 };
+        let mut judge = RulesJudge::new(profile);
+        judge.input(InputEvent::new(InputKey::Left, true, 0));
+        judge.apply_move(Move::Shift(-3), 20);
+        judge.lock(100);
+        judge.spawn(100);
+        // Only the repeats after the spawn are allowed.
+        judge.apply_move(Move::Shift(-1), 100);
+        judge.apply_move(Move::Shift(-1), 102);
+        judge.apply_move(Move::Shift(-1), 103);
+        assert_eq!(vec![
+            Violation { frame: 100, kind: ViolationKind::TooFastMove(Move::Shift(-1)) },
+            Violation { frame: 103, kind: ViolationKind::TooFastMove(Move::Shift(-1)) },
+        ], judge.take_violations());
+        judge.apply_move(Move::Shift(-40), 110);
+        assert_eq!(1, judge.take_violations().len());
+    }
+}
@@ -1,9 +1,21 @@
 pub mod move_search;
 pub mod helper;
 pub mod prelude;
+pub mod attack;
+pub mod theme;
+#[cfg(feature = "render")]
+pub mod render;
+pub mod parse;
+pub mod randomizer;
+pub mod io;
+pub mod goal;
+pub mod judge;
+pub mod mode;
+#[cfg(feature = "bot")]
 pub mod bot;
 
 use std::collections::{HashMap, VecDeque, BTreeMap, HashSet};
+use std::collections::hash_map::DefaultHasher;
 use std::error::Error;
 use std::fmt;
 use std::fmt::{Debug, Display, Formatter};
@@ -16,7 +28,7 @@ use rand::seq::SliceRandom;
 use bitflags::bitflags;
 use num_traits::PrimInt;
 use once_cell::sync::Lazy;
-use deep_trinity_grid::{Cell as CellTrait, Grid, X, Y, Vec2};
+use deep_trinity_grid::{Cell as CellTrait, Grid, X, Y, Vec2, Rect, SetRowsReport};
 use deep_trinity_grid::bitgrid::BitGridTrait;
 
 //--------------------------------------------------------------------------------------------------
@@ -86,6 +98,7 @@ pub enum Piece {
 
 impl Piece {
     pub fn to_u8(&self) -> u8 { *self as u8 }
+    pub fn to_usize(&self) -> usize { self.to_u8() as usize }
     pub fn try_from_u8(v: u8) -> Result<Self, &'static str> {
         if v <= NUM_PIECES as u8 {
             Ok(Self::from_u8_unchecked(v))
@@ -266,6 +279,21 @@ impl<'a, BitGrid: BitGridTrait<'a, BitGridInt, Cell>> HybridGrid<'a, BitGrid> {
     pub fn disable_basic_grid(&mut self) {
         self.basic_grid = None;
     }
+    /// Reconstructs the basic grid from the bit grid if disabled. The filled cells become
+    /// [Cell::Any] since their piece types are lost.
+    pub fn enable_basic_grid(&mut self) {
+        if self.basic_grid.is_some() {
+            return;
+        }
+        let mut grid = BasicGrid::new(self.bit_grid.size());
+        for y in 0..grid.height() {
+            for x in 0..grid.width() {
+                let pos = Vec2(x, y);
+                grid.set_cell(pos, self.bit_grid.cell(pos));
+            }
+        }
+        self.basic_grid = Some(grid);
+    }
     pub fn put_fast<'b>(&mut self, pos: Vec2, other: &HybridGrid<'b, PrimBitGrid<'b>>) {
         if let Some(grid) = self.basic_grid.as_mut() {
             if let Some(other_grid) = other.basic_grid.as_ref() {
@@ -332,18 +360,39 @@ impl<'a, BitGrid: BitGridTrait<'a, BitGridInt, Cell>> Grid<Cell> for HybridGrid<
         self.basic_grid.as_mut().map(|g| g.swap_rows(y1, y2));
         self.bit_grid.swap_rows(y1, y2);
     }
+    /// If only this grid has the basic grid, the copied blocks are [Cell::Any].
+    fn copy_from(&mut self, other: &Self, src: Rect, dst: Vec2) {
+        match (self.basic_grid.as_mut(), other.basic_grid.as_ref()) {
+            (Some(g), Some(o)) => g.copy_from(o, src, dst),
+            (Some(g), None) => {
+                for dy in 0..src.size.1 {
+                    for dx in 0..src.size.0 {
+                        let (s, d) = (src.pos + Vec2(dx, dy), dst + Vec2(dx, dy));
+                        if other.bit_grid.is_inside(s) && g.is_inside(d) {
+                            g.set_cell(d, other.bit_grid.cell(s));
+                        }
+                    }
+                }
+            }
+            (None, _) => {}
+        }
+        self.bit_grid.copy_from(&other.bit_grid, src, dst);
+    }
 }
 
 impl<'a, BitGrid: BitGridTrait<'a, BitGridInt, Cell>> Display for HybridGrid<'a, BitGrid> {
     fn fmt(&self, f: &mut Formatter) -> fmt::Result { self.format(f) }
 }
 
-impl<'a, BitGrid: BitGridTrait<'a, BitGridInt, Cell>> PartialEq for HybridGrid<'a, BitGrid> {
-    fn eq(&self, other: &Self) -> bool { self.basic_grid == other.basic_grid }
+/// Only the bit grids are compared, i.e. the piece types of the cells are ignored, so that the
+/// equality is transitive among grids with and without the basic grid.
+impl<'a, BitGrid: BitGridTrait<'a, BitGridInt, Cell> + PartialEq> PartialEq for HybridGrid<'a, BitGrid> {
+    fn eq(&self, other: &Self) -> bool { self.bit_grid == other.bit_grid }
 }
 
-impl<'a, BitGrid: BitGridTrait<'a, BitGridInt, Cell>> Hash for HybridGrid<'a, BitGrid> {
-    fn hash<H: Hasher>(&self, state: &mut H) { self.basic_grid.hash(state); }
+/// Only the bit grid is hashed so that grids with and without the basic grid are consistent.
+impl<'a, BitGrid: BitGridTrait<'a, BitGridInt, Cell> + Hash> Hash for HybridGrid<'a, BitGrid> {
+    fn hash<H: Hasher>(&self, state: &mut H) { self.bit_grid.hash(state); }
 }
 
 //--------------------------------------------------------------------------------------------------
@@ -422,7 +471,7 @@ impl From<Orientation> for usize {
 // Placement
 //--------------------------------------------------------------------------------------------------
 
-#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub struct Placement {
     pub orientation: Orientation,
     pub pos: Vec2,
@@ -461,7 +510,7 @@ impl Placement {
 // Move
 //--------------------------------------------------------------------------------------------------
 
-#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub enum Move {
     Shift(i8),
     Drop(i8),
@@ -505,7 +554,7 @@ impl Move {
     }
 }
 
-#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub struct MovePathItem {
     pub by: Move,
     pub placement: Placement,
@@ -517,7 +566,7 @@ impl MovePathItem {
     }
 }
 
-#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub struct MoveTransition {
     pub placement: Placement,
     pub hint: Option<MovePathItem>,
@@ -706,11 +755,92 @@ impl Default for LossConditions {
     }
 }
 
-#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+/// How a piece is moved when its spawn position is blocked.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SpawnNudge {
+    /// The piece is never moved, so it blocks out if the spawn position is blocked.
+    Disabled,
+    /// The piece is moved up by up to the given number of rows until it can be put.
+    UpTo(Y),
+    /// The piece is spawned one row above the default position (the 21st and 22nd rows) and
+    /// immediately dropped by one row if possible. It blocks out if the upper position is blocked
+    /// even when the default position is free.
+    Guideline,
+}
+
+impl Default for SpawnNudge {
+    fn default() -> Self { Self::UpTo(1) }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct GameRules {
     pub rotation_mode: RotationMode,
     pub tspin_judgement_mode: TSpinJudgementMode,
     pub loss_conds: LossConditions,
+    pub spawn_nudge: SpawnNudge,
+    /// 20G. If `true`, the falling piece is dropped to the bottom right after spawned and after
+    /// every shift and rotation, so it always rests on the stack.
+    pub instant_gravity: bool,
+    pub enable_hold: bool,
+    pub attack_table: attack::AttackTable,
+    /// The default timings to convert moves into inputs, e.g. for [MovePlayer::with_input_profile].
+    pub input_timing: InputTimingProfile,
+}
+
+impl Default for GameRules {
+    fn default() -> Self {
+        Self {
+            rotation_mode: Default::default(),
+            tspin_judgement_mode: Default::default(),
+            loss_conds: Default::default(),
+            spawn_nudge: Default::default(),
+            instant_gravity: false,
+            enable_hold: true,
+            attack_table: Default::default(),
+            input_timing: Default::default(),
+        }
+    }
+}
+
+/// Presets bundling the loss conditions, the spawn nudge, the gravity, the attack table and the
+/// input timings. Only SRS and the T-Spin judgement of Puyo Puyo Tetris are supported for now, so
+/// they are the same in all presets.
+impl GameRules {
+    /// Tetris Guideline with the guideline attack table.
+    pub fn guideline() -> Self {
+        Self {
+            rotation_mode: RotationMode::Srs,
+            tspin_judgement_mode: TSpinJudgementMode::PuyoPuyoTetris,
+            loss_conds: LossConditions::BLOCK_OUT | LossConditions::LOCK_OUT | LossConditions::GARBAGE_OUT,
+            spawn_nudge: SpawnNudge::Guideline,
+            instant_gravity: false,
+            enable_hold: true,
+            attack_table: attack::AttackTable::GUIDELINE,
+            input_timing: InputTimingProfile { das: 10, arr: 2, soft_drop_interval: 2, key_interval: 1 },
+        }
+    }
+    /// TETR.IO like rules. The piece is nudged up instead of blocking out and the game is over only
+    /// when it can't be spawned or by garbage. Combos multiply attacks and B2B chains are charged.
+    pub fn tetrio() -> Self {
+        Self {
+            loss_conds: LossConditions::BLOCK_OUT | LossConditions::GARBAGE_OUT,
+            spawn_nudge: SpawnNudge::UpTo(2),
+            attack_table: attack::AttackTable::TETRIO,
+            input_timing: InputTimingProfile { das: 10, arr: 2, soft_drop_interval: 1, key_interval: 1 },
+            ..Self::guideline()
+        }
+    }
+    /// TGM like 20G rules. The attack table is the guideline one since TGM has no versus attacks.
+    pub fn tgm() -> Self {
+        Self {
+            loss_conds: LossConditions::BLOCK_OUT,
+            spawn_nudge: SpawnNudge::Disabled,
+            instant_gravity: true,
+            attack_table: attack::AttackTable::GUIDELINE,
+            input_timing: InputTimingProfile { das: 14, arr: 1, soft_drop_interval: 1, key_interval: 1 },
+            ..Self::guideline()
+        }
+    }
 }
 
 //--------------------------------------------------------------------------------------------------
@@ -787,6 +917,16 @@ impl<'a> PieceSpec<'a> {
     pub fn grid(&self, o: Orientation) -> &HybridGrid<'a, PrimBitGrid<'a>> {
         self.grids.get(o.to_usize()).unwrap()
     }
+    /// Returns the grid of [Orientation0] cropped to the blocks (e.g. 4x1 for I and 3x2 for T)
+    /// to render the piece in the next and hold previews.
+    pub fn preview_grid(&self) -> BasicGrid {
+        let g = self.grid(Orientation0).basic_grid.as_ref().unwrap();
+        let (left, bottom) = (g.left_padding(), g.bottom_padding());
+        let size = Vec2(g.width() - left - g.right_padding(), g.height() - bottom - g.top_padding());
+        let mut r = BasicGrid::new(size);
+        r.copy_from(g, Rect::new(Vec2(left, bottom), size), Vec2(0, 0));
+        r
+    }
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
@@ -983,14 +1123,32 @@ impl<'a> FallingPiece<'a> {
         }
     }
     pub fn spawn(piece_spec: &'a PieceSpec, pf: Option<&Playfield>) -> Self {
-        let mut fp = Self::new(piece_spec, piece_spec.initial_placement);
+        Self::spawn_with_nudge(piece_spec, pf, SpawnNudge::default())
+    }
+    pub fn spawn_with_nudge(piece_spec: &'a PieceSpec, pf: Option<&Playfield>, nudge: SpawnNudge) -> Self {
+        let mut placement = piece_spec.initial_placement;
         if let Some(pf) = pf {
-            if !pf.can_put(&fp) {
-                fp.placement.pos.1 += 1;
-                fp.move_path.initial_placement.pos.1 += 1;
+            let can_put = |p: Placement| pf.can_put(&Self::new(piece_spec, p));
+            match nudge {
+                SpawnNudge::Disabled => {}
+                SpawnNudge::UpTo(n) => {
+                    for _ in 0..n {
+                        if can_put(placement) {
+                            break;
+                        }
+                        placement.pos.1 += 1;
+                    }
+                }
+                SpawnNudge::Guideline => {
+                    let mut upper = placement;
+                    upper.pos.1 += 1;
+                    if !can_put(upper) || !can_put(placement) {
+                        placement = upper;
+                    }
+                }
             }
         }
-        fp
+        Self::new(piece_spec, placement)
     }
     pub fn piece(&self) -> Piece { self.piece_spec.piece }
     pub fn grid(&self) -> &'a HybridGrid<PrimBitGrid<'a>> {
@@ -1052,26 +1210,108 @@ impl<'a> FallingPiece<'a> {
 // Playfield
 //--------------------------------------------------------------------------------------------------
 
+/// See [Playfield::num_garbage_rows]. It depends on how the rows were given rather than the cells,
+/// so it's not taken into account in comparison and hashing.
+#[derive(Copy, Clone, Debug, Default)]
+struct GarbageRowCount(Y);
+
+impl PartialEq for GarbageRowCount {
+    fn eq(&self, _other: &Self) -> bool { true }
+}
+
+impl Eq for GarbageRowCount {}
+
+impl Hash for GarbageRowCount {
+    fn hash<H: Hasher>(&self, _state: &mut H) {}
+}
+
+/// See [Playfield::grid_hash].
+fn bit_grid_hash(grid: &BasicBitGrid) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    grid.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Result of [Playfield::lock_without_clear].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PendingLineClear<'a> {
+    pub line_clear: LineClear,
+    /// The rows to be cleared in the ascending order.
+    pub rows: Vec<Y>,
+    /// The grid before the rows are cleared.
+    pub snapshot: HybridGrid<'a, BasicBitGrid<'a>>,
+}
+
+impl PendingLineClear<'_> {
+    /// Bit mask of [Self::rows] (the lowest bit is y = 0).
+    pub fn row_mask(&self) -> u64 {
+        self.rows.iter().fold(0, |m, y| m | (1 << y))
+    }
+}
+
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub struct Playfield<'a> {
     pub grid: HybridGrid<'a, BasicBitGrid<'a>>,
     pub visible_height: Y,
+    garbage_rows: GarbageRowCount,
 }
 
 impl<'a> Playfield<'a> {
     pub fn new(store: &'a PrimBitGridConstantsStore, size: Vec2, with_basic_grid: bool, visible_height: Y) -> Option<Self> {
-        HybridGrid::with_store(store, size, with_basic_grid).map(|grid| Self { grid, visible_height })
+        HybridGrid::with_store(store, size, with_basic_grid)
+            .map(|grid| Self { grid, visible_height, garbage_rows: Default::default() })
     }
     pub fn width(&self) -> X { self.grid.width() }
     pub fn height(&self) -> Y { self.grid.height() }
     pub fn is_empty(&self) -> bool { self.grid.is_empty() }
-    pub fn stack_height(&self) -> Y { self.grid.height() - self.grid.top_padding() }
+    /// The number of the rows from the bottom given by [Self::append_garbage] and not cleared yet.
+    /// It's tracked regardless of the cell types, e.g. without the basic grid, but direct
+    /// modifications of [Self::grid] are not taken into account.
+    /// Playfields having the same cells are equal regardless of this value.
+    pub fn num_garbage_rows(&self) -> Y { self.garbage_rows.0 }
+    /// Deterministic hash of the bit grid, i.e. the piece types of cells are ignored.
+    /// The same boards have the same hash.
+    pub fn grid_hash(&self) -> u64 { bit_grid_hash(&self.grid.bit_grid) }
+    /// Returns the positions of the cells different from `other` from the bottom row.
+    /// Both should have the same size.
+    pub fn diff_cells(&self, other: &Playfield) -> Vec<Vec2> {
+        debug_assert_eq!((self.width(), self.height()), (other.width(), other.height()));
+        // The rows above both stacks are empty.
+        let h = self.stack_height().max(other.stack_height());
+        (0..h).flat_map(|y| (0..self.width()).map(move |x| Vec2(x, y)))
+            .filter(|pos| self.grid.cell(*pos) != other.grid.cell(*pos))
+            .collect()
+    }
+    /// Returns the top filled row + 1 (0 if empty), including the hidden rows above
+    /// [Self::visible_height].
+    pub fn stack_height(&self) -> Y { self.grid.bit_grid.stack_height() }
+    /// [Self::stack_height] capped by [Self::visible_height].
+    pub fn visible_stack_height(&self) -> Y { self.stack_height().min(self.visible_height) }
+    /// Empty rows at the top of the visible region.
+    pub fn visible_top_padding(&self) -> Y { self.grid.bit_grid.visible_top_padding(self.visible_height) }
+    /// Density of the visible region.
+    pub fn visible_density(&self) -> f32 { self.grid.bit_grid.visible_density(self.visible_height) }
+    /// Contour of the visible region. Blocks in the hidden rows are ignored.
+    pub fn visible_contour(&self) -> Vec<Y> { self.grid.bit_grid.visible_contour(self.visible_height) }
+    /// Returns the height of the column, i.e. the top filled row + 1 (0 if empty).
+    pub fn height_of(&self, x: X) -> Y { self.grid.bit_grid.col_height(x) }
+    /// Returns the height of each column, i.e. the top filled row + 1 (0 if empty).
+    pub fn heights(&self) -> Vec<Y> {
+        (0..self.width()).map(|x| self.grid.bit_grid.col_height(x)).collect()
+    }
+    pub fn set_cell(&mut self, pos: Vec2, cell: Cell) { self.grid.set_cell(pos, cell); }
     pub fn set_rows_with_strs(&mut self, pos: Vec2, rows: &[&str]) {
         self.grid.set_rows_with_strs(pos, rows);
     }
+    /// See [Grid::try_set_rows_with_strs].
+    pub fn try_set_rows_with_strs(&mut self, pos: Vec2, rows: &[&str]) -> Result<SetRowsReport, &'static str> {
+        self.grid.try_set_rows_with_strs(pos, rows)
+    }
     // If garbage out, `true` will be returned.
     pub fn append_garbage(&mut self, gap_x_list: &[X]) -> bool {
-        let ok = self.grid.insert_rows(0, Cell::Garbage, gap_x_list.len() as Y);
+        let n = gap_x_list.len() as Y;
+        let ok = self.grid.insert_rows(0, Cell::Garbage, n);
+        self.garbage_rows.0 = (self.garbage_rows.0 + n).min(self.height());
         for (y, x) in gap_x_list.iter().enumerate() {
             self.grid.set_cell((*x, y as Y).into(), Cell::Empty);
         }
@@ -1243,10 +1483,51 @@ impl<'a> Playfield<'a> {
             return None;
         }
         let tspin = self.check_tspin(fp, mode);
-        self.grid.put_fast(fp.placement.pos, fp.grid());
-        let num_cleared_line = self.grid.drop_filled_rows();
+        self.put_locked(fp);
+        let num_cleared_line = self.finish_pending_clear();
         Some(LineClear::new(num_cleared_line as u8, tspin))
     }
+    /// The first phase of [Self::lock] for line clear animations.
+    /// The filled rows are kept until [Self::finish_pending_clear] is called.
+    pub fn lock_without_clear(&mut self, fp: &FallingPiece, mode: TSpinJudgementMode) -> Option<PendingLineClear<'a>> {
+        if !self.can_lock(fp) {
+            return None;
+        }
+        let tspin = self.check_tspin(fp, mode);
+        let rows = self.rows_cleared_by(fp);
+        self.put_locked(fp);
+        Some(PendingLineClear {
+            line_clear: LineClear::new(rows.len() as u8, tspin),
+            rows,
+            snapshot: self.grid.clone(),
+        })
+    }
+    /// Drops the filled rows left by [Self::lock_without_clear] and returns the number of them.
+    pub fn finish_pending_clear(&mut self) -> Y {
+        // The garbage rows stay at the bottom since the rows above them are never moved below.
+        self.garbage_rows.0 -= (0..self.garbage_rows.0).filter(|y| self.grid.is_row_filled(*y)).count() as Y;
+        self.grid.drop_filled_rows()
+    }
+    pub fn has_pending_clear(&self) -> bool { self.grid.num_filled_rows() > 0 }
+    fn put_locked(&mut self, fp: &FallingPiece) {
+        self.grid.put_fast(fp.placement.pos, fp.grid());
+    }
+    /// Returns the rows which would be cleared by locking `fp` in the ascending order.
+    pub fn rows_cleared_by(&self, fp: &FallingPiece) -> Vec<Y> {
+        let g = fp.grid();
+        let pos = fp.placement.pos;
+        (0..g.height())
+            .filter(|dy| {
+                let y = pos.1 + dy;
+                y >= 0 && y < self.height() && (0..self.width()).all(|x| {
+                    let dx = x - pos.0;
+                    !self.grid.cell((x, y).into()).is_empty()
+                        || (dx >= 0 && dx < g.width() && !g.cell((dx, *dy).into()).is_empty())
+                })
+            })
+            .map(|dy| pos.1 + dy)
+            .collect()
+    }
     /// The return placements can include unreachable placements.
     /// These also includes all alternative placements.
     pub fn search_lockable_placements(&self, spec: &PieceSpec) -> Vec<Placement> {
@@ -1254,18 +1535,13 @@ impl<'a> Playfield<'a> {
             Piece::I => 2,
             _ => 1,
         };
-        let yend = (self.grid.height() - self.grid.top_padding()) as Y;
-        let piece_grids = [
-            &spec.grids[Orientation0.to_usize()],
-            &spec.grids[Orientation1.to_usize()],
-            &spec.grids[Orientation2.to_usize()],
-            &spec.grids[Orientation3.to_usize()],
-        ];
+        let drop_heights = self.drop_heights(spec);
         let mut r: Vec<Placement> = Vec::new();
-        for y in -max_padding..=yend {
-            for x in -max_padding..=(self.grid.width() as X - max_padding) {
-                for o in &ORIENTATIONS {
-                    let g = piece_grids[o.to_usize()];
+        for o in &ORIENTATIONS {
+            let g = &spec.grids[o.to_usize()];
+            for &(x, landing_y) in drop_heights[o.to_usize()].iter() {
+                // Above the landing position, the piece can always be dropped.
+                for y in -max_padding..=landing_y {
                     let can_put = self.grid.can_put_fast((x, y).into(), g);
                     if !can_put {
                         continue;
@@ -1278,8 +1554,56 @@ impl<'a> Playfield<'a> {
                 }
             }
         }
+        r.sort_by_key(|p| (p.pos.1, p.pos.0, p.orientation.to_usize()));
         r
     }
+    /// Returns the landing positions when the piece is dropped from above the stack, as the list
+    /// of `(x, y)` for each orientation. All `x` where the piece is in the playfield are included.
+    pub fn drop_heights(&self, spec: &PieceSpec) -> [Vec<(X, Y)>; 4] {
+        let heights = self.heights();
+        let mut r: [Vec<(X, Y)>; 4] = Default::default();
+        for o in &ORIENTATIONS {
+            let g = &spec.grids[o.to_usize()];
+            let bottoms = (0..g.width())
+                .map(|c| (0..g.height()).find(|y| !g.cell((c, *y).into()).is_empty()))
+                .collect::<Vec<_>>();
+            let left = bottoms.iter().position(|b| b.is_some()).unwrap_or(0) as X;
+            let right = bottoms.iter().rposition(|b| b.is_some()).unwrap_or(0) as X;
+            for x in -left..(self.width() - right) {
+                let y = bottoms.iter().enumerate()
+                    .filter_map(|(c, b)| b.map(|b| heights[(x + c as X) as usize] - b))
+                    .max()
+                    .unwrap_or(0);
+                r[o.to_usize()].push((x, y));
+            }
+        }
+        r
+    }
+    /// Returns the max number of lines cleared by locking the piece once at any lockable
+    /// placement (see [Self::search_lockable_placements]), e.g. to detect a well ready for a tetris.
+    pub fn count_clearable_lines_with(&self, spec: &PieceSpec) -> usize {
+        let width = self.width() as usize;
+        let max_blocks = spec.grids[0].num_blocks();
+        let nums = (0..self.stack_height())
+            .map(|y| self.grid.num_blocks_of_row(y))
+            .collect::<Vec<_>>();
+        if nums.iter().all(|n| n + max_blocks < width) {
+            return 0;
+        }
+        let mut max = 0;
+        for pl in self.search_lockable_placements(spec) {
+            let g = &spec.grids[pl.orientation.to_usize()];
+            let n = (0..g.height())
+                .filter(|&dy| {
+                    let n = g.num_blocks_of_row(dy);
+                    let y = pl.pos.1 + dy;
+                    n > 0 && y >= 0 && (y as usize) < nums.len() && nums[y as usize] + n == width
+                })
+                .count();
+            max = max.max(n);
+        }
+        max
+    }
 }
 
 impl Default for Playfield<'static> {
@@ -1342,12 +1666,85 @@ impl<R: rand::Rng + Sized> RandomPieceGenerator<R> {
     }
 }
 
+/// Derives a seed from a string, e.g. shared between users. Unlike [std::hash::Hash], the result
+/// is stable across platforms and versions (64-bit FNV-1a).
+pub fn seed_from_str(s: &str) -> u64 {
+    s.bytes().fold(0xcbf29ce484222325, |h, b| (h ^ b as u64).wrapping_mul(0x100000001b3))
+}
+
+/// The seed of the daily games of the date in `YYYY-MM-DD`.
+pub fn daily_seed(date: &str) -> Result<u64, &'static str> {
+    let parts = date.split('-').collect::<Vec<_>>();
+    if parts.len() != 3 || !parts.iter().zip([4, 2, 2]).all(|(p, n)| p.len() == n && p.bytes().all(|b| b.is_ascii_digit())) {
+        return Err("invalid date");
+    }
+    let (year, month, day) = (parts[0].parse::<u32>().unwrap(), parts[1].parse::<u32>().unwrap(), parts[2].parse::<u32>().unwrap());
+    let is_leap_year = year % 4 == 0 && (year % 100 != 0 || year % 400 == 0);
+    let num_days = match month {
+        1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+        4 | 6 | 9 | 11 => 30,
+        2 => if is_leap_year { 29 } else { 28 },
+        _ => return Err("invalid date"),
+    };
+    if !(1..=num_days).contains(&day) {
+        return Err("invalid date");
+    }
+    Ok(seed_from_str(&format!("daily:{}", date)))
+}
+
+/// A source of next pieces which can be owned by [Game] (see [Game::set_piece_generator]).
+pub trait PieceGenerator: Debug + Send {
+    /// Returns the pieces to be appended to the next pieces. Should not be empty.
+    fn generate(&mut self) -> Vec<Piece>;
+    fn clone_box(&self) -> Box<dyn PieceGenerator>;
+}
+
+impl Clone for Box<dyn PieceGenerator> {
+    fn clone(&self) -> Self { self.clone_box() }
+}
+
+impl<R: rand::Rng + Clone + Debug + Send + 'static> PieceGenerator for RandomPieceGenerator<R> {
+    fn generate(&mut self) -> Vec<Piece> { RandomPieceGenerator::generate(self) }
+    fn clone_box(&self) -> Box<dyn PieceGenerator> { Box::new(self.clone()) }
+}
+
+/// Generates the pre-loaded pieces. If not looping, nothing is generated after the first time.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StaticPieceGenerator {
+    pieces: Vec<Piece>,
+    looping: bool,
+    exhausted: bool,
+}
+
+impl StaticPieceGenerator {
+    pub fn new(pieces: &[Piece], looping: bool) -> Self {
+        Self { pieces: pieces.to_vec(), looping, exhausted: false }
+    }
+    pub fn is_exhausted(&self) -> bool { self.exhausted }
+}
+
+impl PieceGenerator for StaticPieceGenerator {
+    fn generate(&mut self) -> Vec<Piece> {
+        if self.exhausted {
+            return Vec::new();
+        }
+        if !self.looping {
+            self.exhausted = true;
+        }
+        self.pieces.clone()
+    }
+    fn clone_box(&self) -> Box<dyn PieceGenerator> { Box::new(self.clone()) }
+}
+
 //--------------------------------------------------------------------------------------------------
 // Statistics
 //--------------------------------------------------------------------------------------------------
 
 pub type Count = u32;
 
+/// The number of locks to sum up attacks for spike statistics.
+pub const SPIKE_WINDOW_SIZE: usize = 5;
+
 #[derive(Clone, Debug, Default, PartialEq, Eq)]
 pub struct LineClearCounter {
     pub data: HashMap<LineClear, Count>,
@@ -1364,6 +1761,11 @@ impl LineClearCounter {
     pub fn get(&self, lc: &LineClear) -> Count {
         self.data.get(lc).copied().unwrap_or(0)
     }
+    pub fn merge(&mut self, other: &Self) {
+        for (lc, count) in other.data.iter() {
+            self.add(lc, *count);
+        }
+    }
 }
 
 impl ops::Sub for LineClearCounter {
@@ -1399,6 +1801,11 @@ impl ConsecutiveCountCounter {
     pub fn max(&self) -> Count {
         self.data.iter().next_back().map_or(0, |v| { *v.0 })
     }
+    pub fn merge(&mut self, other: &Self) {
+        for (cont_count, count) in other.data.iter() {
+            self.add(*cont_count, *count);
+        }
+    }
 }
 
 impl ops::Sub for ConsecutiveCountCounter {
@@ -1415,6 +1822,39 @@ impl ops::Sub for ConsecutiveCountCounter {
     }
 }
 
+/// Counts indexed by [Piece].
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct PieceCounter {
+    pub data: [Count; NUM_PIECES],
+}
+
+impl PieceCounter {
+    pub fn add(&mut self, p: Piece, n: Count) {
+        self.data[p.to_usize()] += n;
+    }
+    pub fn get(&self, p: Piece) -> Count {
+        self.data[p.to_usize()]
+    }
+    pub fn total(&self) -> Count {
+        self.data.iter().sum()
+    }
+    pub fn merge(&mut self, other: &Self) {
+        for (c, n) in self.data.iter_mut().zip(other.data.iter()) {
+            *c += *n;
+        }
+    }
+}
+
+impl ops::Sub for PieceCounter {
+    type Output = Self;
+    fn sub(mut self, other: Self) -> Self {
+        for (c, n) in self.data.iter_mut().zip(other.data.iter()) {
+            *c -= *n;
+        }
+        self
+    }
+}
+
 #[derive(Copy, Clone, Debug)]
 pub enum StatisticsEntryType {
     LineClear(LineClear),
@@ -1422,9 +1862,20 @@ pub enum StatisticsEntryType {
     MaxCombos,
     Btb(Count),
     MaxBtbs,
+    /// Attacks sent within [SPIKE_WINDOW_SIZE] locks.
+    Spike(Count),
+    MaxSpike,
     PerfectClear,
     Hold,
     Lock,
+    PieceHold(Piece),
+    PieceLock(Piece),
+    /// The sum of the landing heights (the bottom rows of the blocks) of the locked pieces.
+    PieceLandingHeight(Piece),
+    /// T pieces clearing lines without T-spins.
+    WastedT,
+    /// I pieces clearing lines other than tetrises.
+    MisusedI,
 }
 
 impl Display for StatisticsEntryType {
@@ -1435,9 +1886,16 @@ impl Display for StatisticsEntryType {
             StatisticsEntryType::MaxCombos => write!(f, "max combos"),
             StatisticsEntryType::Btb(n) => write!(f, "btb[{}]", n),
             StatisticsEntryType::MaxBtbs => write!(f, "max btbs"),
+            StatisticsEntryType::Spike(n) => write!(f, "spike[{}]", n),
+            StatisticsEntryType::MaxSpike => write!(f, "max spike"),
             StatisticsEntryType::PerfectClear => write!(f, "pc"),
             StatisticsEntryType::Hold => write!(f, "hold"),
             StatisticsEntryType::Lock => write!(f, "lock"),
+            StatisticsEntryType::PieceHold(p) => write!(f, "hold[{}]", p.to_char()),
+            StatisticsEntryType::PieceLock(p) => write!(f, "lock[{}]", p.to_char()),
+            StatisticsEntryType::PieceLandingHeight(p) => write!(f, "landing height[{}]", p.to_char()),
+            StatisticsEntryType::WastedT => write!(f, "wasted T"),
+            StatisticsEntryType::MisusedI => write!(f, "misused I"),
         }
     }
 }
@@ -1447,9 +1905,19 @@ pub struct Statistics {
     pub line_clear: LineClearCounter,
     pub combo: ConsecutiveCountCounter,
     pub btb: ConsecutiveCountCounter,
+    pub spike: ConsecutiveCountCounter,
     pub perfect_clear: Count,
     pub hold: Count,
     pub lock: Count,
+    /// Held pieces, i.e. the ones moved into the hold.
+    pub hold_by_piece: PieceCounter,
+    pub lock_by_piece: PieceCounter,
+    /// See [StatisticsEntryType::PieceLandingHeight].
+    pub landing_height_by_piece: PieceCounter,
+    /// See [StatisticsEntryType::WastedT].
+    pub wasted_t: Count,
+    /// See [StatisticsEntryType::MisusedI].
+    pub misused_i: Count,
 }
 
 impl Statistics {
@@ -1460,11 +1928,40 @@ impl Statistics {
             StatisticsEntryType::MaxCombos => self.combo.max(),
             StatisticsEntryType::Btb(n) => self.btb.get(n),
             StatisticsEntryType::MaxBtbs => self.btb.max(),
+            StatisticsEntryType::Spike(n) => self.spike.get(n),
+            StatisticsEntryType::MaxSpike => self.spike.max(),
             StatisticsEntryType::PerfectClear => self.perfect_clear,
             StatisticsEntryType::Hold => self.hold,
             StatisticsEntryType::Lock => self.lock,
+            StatisticsEntryType::PieceHold(p) => self.hold_by_piece.get(p),
+            StatisticsEntryType::PieceLock(p) => self.lock_by_piece.get(p),
+            StatisticsEntryType::PieceLandingHeight(p) => self.landing_height_by_piece.get(p),
+            StatisticsEntryType::WastedT => self.wasted_t,
+            StatisticsEntryType::MisusedI => self.misused_i,
+        }
+    }
+    /// Returns `None` if no `piece` has been locked.
+    pub fn average_landing_height(&self, piece: Piece) -> Option<f32> {
+        match self.lock_by_piece.get(piece) {
+            0 => None,
+            n => Some(self.landing_height_by_piece.get(piece) as f32 / n as f32),
         }
     }
+    /// Adds all counts of `other` to `self`.
+    pub fn merge(&mut self, other: &Self) {
+        self.line_clear.merge(&other.line_clear);
+        self.combo.merge(&other.combo);
+        self.btb.merge(&other.btb);
+        self.spike.merge(&other.spike);
+        self.perfect_clear += other.perfect_clear;
+        self.hold += other.hold;
+        self.lock += other.lock;
+        self.hold_by_piece.merge(&other.hold_by_piece);
+        self.lock_by_piece.merge(&other.lock_by_piece);
+        self.landing_height_by_piece.merge(&other.landing_height_by_piece);
+        self.wasted_t += other.wasted_t;
+        self.misused_i += other.misused_i;
+    }
 }
 
 impl ops::Sub for Statistics {
@@ -1474,13 +1971,64 @@ impl ops::Sub for Statistics {
             line_clear: self.line_clear - other.line_clear,
             combo: self.combo - other.combo,
             btb: self.btb - other.btb,
+            spike: self.spike - other.spike,
             perfect_clear: self.perfect_clear - other.perfect_clear,
             hold: self.hold - other.hold,
             lock: self.lock - other.lock,
+            hold_by_piece: self.hold_by_piece - other.hold_by_piece,
+            lock_by_piece: self.lock_by_piece - other.lock_by_piece,
+            landing_height_by_piece: self.landing_height_by_piece - other.landing_height_by_piece,
+            wasted_t: self.wasted_t - other.wasted_t,
+            misused_i: self.misused_i - other.misused_i,
         }
     }
 }
 
+/// Keeps the statistics of the last `size` pieces.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct StatisticsWindow {
+    size: usize,
+    deltas: VecDeque<Statistics>,
+    last: Statistics,
+}
+
+impl StatisticsWindow {
+    pub fn new(size: usize) -> Self {
+        Self { size, deltas: VecDeque::with_capacity(size), last: Default::default() }
+    }
+    pub fn size(&self) -> usize { self.size }
+    pub fn len(&self) -> usize { self.deltas.len() }
+    pub fn is_empty(&self) -> bool { self.deltas.is_empty() }
+    /// Records the difference from the previous call.
+    /// `stats` should be the whole statistics of a game and this method should be called after every lock.
+    pub fn update(&mut self, stats: &Statistics) {
+        self.push(stats.clone() - self.last.clone());
+        self.last = stats.clone();
+    }
+    /// Pushes the statistics of one piece. The oldest one is discarded if the window is full.
+    pub fn push(&mut self, delta: Statistics) {
+        if self.size == 0 {
+            return;
+        }
+        if self.deltas.len() >= self.size {
+            self.deltas.pop_front();
+        }
+        self.deltas.push_back(delta);
+    }
+    /// Returns the merged statistics in the window.
+    pub fn stats(&self) -> Statistics {
+        let mut r = Statistics::default();
+        for delta in self.deltas.iter() {
+            r.merge(delta);
+        }
+        r
+    }
+    pub fn clear(&mut self) {
+        self.deltas.clear();
+        self.last = Default::default();
+    }
+}
+
 //--------------------------------------------------------------------------------------------------
 // GameState
 //--------------------------------------------------------------------------------------------------
@@ -1494,11 +2042,33 @@ pub struct GameState<'a> {
     pub can_hold: bool,
     pub num_combos: Option<Count>,
     pub num_btbs: Option<Count>,
+    /// Attacks of the last [SPIKE_WINDOW_SIZE] locks.
+    pub recent_attacks: VecDeque<Count>,
     pub game_over_reason: LossConditions,
 }
 
 impl<'a> GameState<'a> {
     pub fn is_game_over(&self) -> bool { !self.game_over_reason.is_empty() }
+    /// The key to deduplicate states in searches, available in the performance mode.
+    /// The playfield (ignoring piece types of cells), the placement of the falling piece, the hold
+    /// state and the visible next pieces are taken into account. Combos and back-to-backs are not.
+    pub fn canonical_key(&self) -> u64 { self.position_hash(true) }
+    /// The hash shared by [Self::canonical_key] and [Game::position_key].
+    fn position_hash(&self, with_placement: bool) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.playfield.grid_hash().hash(&mut hasher);
+        self.falling_piece.as_ref().map(|fp| fp.piece()).hash(&mut hasher);
+        if with_placement {
+            self.falling_piece.as_ref().map(|fp| fp.placement).hash(&mut hasher);
+        }
+        self.hold_piece.hash(&mut hasher);
+        self.can_hold.hash(&mut hasher);
+        for p in self.next_pieces.iter().take(self.next_pieces.visible_num) {
+            p.hash(&mut hasher);
+        }
+        hasher.finish()
+    }
+    pub fn current_spike(&self) -> Count { self.recent_attacks.iter().sum() }
     /// Return the cell of `pos` from the playfield or the falling piece.
     pub fn get_cell(&self, pos: Vec2) -> Cell {
         let mut cell = if let Some(fp) = self.falling_piece.as_ref() {
@@ -1517,6 +2087,28 @@ impl<'a> GameState<'a> {
         }
         cell
     }
+    /// Returns the bit grid hash and the contour after locking `fp` only with a copy of the bit grid.
+    /// `None` if `fp` can't be locked.
+    pub fn lock_preview(&self, fp: &FallingPiece) -> Option<LockPreview> {
+        if !self.playfield.can_lock(fp) {
+            return None;
+        }
+        let mut grid = self.playfield.grid.bit_grid.clone();
+        grid.put_prim_bit_grid(fp.placement.pos, &fp.grid().bit_grid);
+        let num_cleared_lines = grid.drop_filled_rows();
+        let heights = (0..grid.width()).map(|x| grid.col_height(x)).collect();
+        Some(LockPreview { grid_hash: bit_grid_hash(&grid), heights, num_cleared_lines })
+    }
+}
+
+/// Result of [GameState::lock_preview].
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct LockPreview {
+    /// Deterministic hash of the bit grid after the lock. The same grids have the same hash.
+    pub grid_hash: u64,
+    /// See [Playfield::heights].
+    pub heights: Vec<Y>,
+    pub num_cleared_lines: Y,
 }
 
 impl Default for GameState<'static> {
@@ -1529,6 +2121,7 @@ impl Default for GameState<'static> {
             can_hold: true,
             num_combos: None,
             num_btbs: None,
+            recent_attacks: VecDeque::with_capacity(SPIKE_WINDOW_SIZE),
             game_over_reason: LossConditions::empty(),
         }
     }
@@ -1538,14 +2131,69 @@ impl Default for GameState<'static> {
 // Game
 //--------------------------------------------------------------------------------------------------
 
-#[derive(Clone, Debug, Default, PartialEq, Eq)]
+/// Notable changes of a game for frontends to trigger effects.
+/// Recorded only if enabled by [Game::set_event_recording].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum GameEvent {
+    PieceLocked { piece: Piece, placement: Placement },
+    /// `rows` are the rows before cleared in the ascending order.
+    LinesCleared { rows: Vec<Y>, line_clear: LineClear },
+    GarbageReceived { rows: Y },
+    /// A chain of back-to-backs (`num_btbs` > 0) was broken by a line clear.
+    BtbBroken { num_btbs: Count },
+    /// Emitted when `num_combos` > 0.
+    ComboExtended { num_combos: Count },
+}
+
+/// The result of [Game::lock_ex].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct LockOutcome {
+    pub line_clear: LineClear,
+    pub attack_sent: Count,
+    pub perfect_clear: bool,
+    /// The number of combos after the lock. `None` if no combo continues.
+    pub num_combos: Option<Count>,
+    /// The number of back-to-backs after the lock. `None` if no back-to-back continues.
+    pub num_btbs: Option<Count>,
+    pub game_over: bool,
+    /// Whether the next falling piece was set up. If `false`, supply next pieces then call
+    /// [Game::setup_falling_piece].
+    pub next_piece_ready: bool,
+}
+
+/// The result of [Game::peek_hold_result].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct HoldPreview {
+    /// The piece which would become the falling piece.
+    pub piece: Piece,
+    /// `true` if `piece` would be taken from the next pieces, i.e. nothing is held yet.
+    pub from_next_pieces: bool,
+    /// `true` if the spawned piece would immediately block out.
+    pub blocks_out: bool,
+}
+
+#[derive(Clone, Debug, Default)]
 pub struct Game<'a> {
     pub piece_specs: &'a PieceSpecCollection<'a>,
     pub rules: GameRules,
     pub state: GameState<'a>,
     pub stats: Statistics,
+    piece_generator: Option<Box<dyn PieceGenerator>>,
+    events: Option<Vec<GameEvent>>,
+}
+
+/// The piece generator is not taken into account.
+impl<'a> PartialEq for Game<'a> {
+    fn eq(&self, other: &Self) -> bool {
+        self.piece_specs == other.piece_specs
+            && self.rules == other.rules
+            && self.state == other.state
+            && self.stats == other.stats
+    }
 }
 
+impl<'a> Eq for Game<'a> {}
+
 impl<'a> Game<'a> {
     pub fn new(piece_specs: &'a PieceSpecCollection<'a>, rules: GameRules, state: GameState<'a>, stats: Statistics) -> Self {
         Self {
@@ -1553,12 +2201,26 @@ impl<'a> Game<'a> {
             rules,
             state,
             stats,
+            piece_generator: None,
+            events: None,
         }
     }
     /// Makes the performance better but discards piece information in the playfield.
+    /// Same as `set_color_tracking(false)`.
     pub fn performance_mode(&mut self) {
-        self.state.playfield.grid.disable_basic_grid();
+        self.set_color_tracking(false);
+    }
+    /// Whether the piece types of the cells in the playfield are managed (enabled by default).
+    /// Disabling it makes the performance better. On re-enabling, the existing cells become
+    /// [Cell::Any] since their piece types are lost.
+    pub fn set_color_tracking(&mut self, enabled: bool) {
+        if enabled {
+            self.state.playfield.grid.enable_basic_grid();
+        } else {
+            self.state.playfield.grid.disable_basic_grid();
+        }
     }
+    pub fn is_color_tracking(&self) -> bool { self.state.playfield.grid.basic_grid.is_some() }
     pub fn get_cell(&self, pos: Vec2) -> Cell {
         self.state.get_cell(pos)
     }
@@ -1568,15 +2230,70 @@ impl<'a> Game<'a> {
     pub fn supply_next_pieces(&mut self, pieces: &[Piece]) {
         self.state.next_pieces.supply(pieces);
     }
+    /// Overrides the default number of visible next pieces (see [configure_default_values]).
+    pub fn set_num_visible_next_pieces(&mut self, n: usize) {
+        self.state.next_pieces.visible_num = n;
+        self.refill_next_pieces();
+    }
+    /// If a piece generator is set, the next pieces are supplied from it whenever needed, so that
+    /// `supply_next_pieces()` is not required anymore.
+    /// Note that the generator is cloned with the game, so clones draw the same pieces.
+    pub fn set_piece_generator(&mut self, generator: Option<Box<dyn PieceGenerator>>) {
+        self.piece_generator = generator;
+        self.refill_next_pieces();
+    }
+    pub fn take_piece_generator(&mut self) -> Option<Box<dyn PieceGenerator>> {
+        self.piece_generator.take()
+    }
+    pub fn has_piece_generator(&self) -> bool { self.piece_generator.is_some() }
+    /// Returns a copy of the piece generator in the current state. The copy generates the same
+    /// pieces as the original from now on, so another game (e.g. the opponent) is given the
+    /// identical piece sequence if it also copies the current next pieces.
+    pub fn fork_piece_generator(&self) -> Option<Box<dyn PieceGenerator>> {
+        self.piece_generator.clone()
+    }
+    /// If enabled, [GameEvent]s are recorded until drained by [Self::drain_events].
+    pub fn set_event_recording(&mut self, enabled: bool) {
+        self.events = if enabled { Some(Vec::new()) } else { None };
+    }
+    pub fn drain_events(&mut self) -> Vec<GameEvent> {
+        self.events.as_mut().map(std::mem::take).unwrap_or_default()
+    }
+    fn push_event(&mut self, event: GameEvent) {
+        if let Some(events) = self.events.as_mut() {
+            events.push(event);
+        }
+    }
+    /// Appends garbage rows to the playfield (see [Playfield::append_garbage]).
+    /// Returns `true` if the game is over by garbage out.
+    pub fn receive_garbage(&mut self, gap_x_list: &[X]) -> bool {
+        let garbage_out = self.state.playfield.append_garbage(gap_x_list);
+        if garbage_out && self.rules.loss_conds.contains(LossConditions::GARBAGE_OUT) {
+            self.state.game_over_reason |= LossConditions::GARBAGE_OUT;
+        }
+        self.push_event(GameEvent::GarbageReceived { rows: gap_x_list.len() as Y });
+        self.state.is_game_over()
+    }
+    fn refill_next_pieces(&mut self) {
+        if let Some(g) = self.piece_generator.as_mut() {
+            while self.state.next_pieces.should_supply() {
+                let pieces = g.generate();
+                if pieces.is_empty() {
+                    break;
+                }
+                self.state.next_pieces.supply(&pieces);
+            }
+        }
+    }
     /// This method should be called right after `new()`.
     /// `Err` will be returned when there are no next pieces.
     pub fn setup_falling_piece(&mut self, next: Option<Piece>) -> Result<(), &'static str> {
-        let s = &mut self.state;
-
-        if s.falling_piece.is_some() {
+        if self.state.falling_piece.is_some() {
             return Err("falling piece already exists");
         }
 
+        self.refill_next_pieces();
+        let s = &mut self.state;
         let p = if let Some(next) = next {
             next
         } else {
@@ -1586,12 +2303,17 @@ impl<'a> Game<'a> {
             s.next_pieces.pop().unwrap()
         };
 
-        let fp = FallingPiece::spawn(self.piece_specs.get(p), Some(&s.playfield));
+        let mut fp = FallingPiece::spawn_with_nudge(self.piece_specs.get(p), Some(&s.playfield), self.rules.spawn_nudge);
         if !s.playfield.can_put(&fp) {
             s.game_over_reason |= LossConditions::BLOCK_OUT;
+        } else if self.rules.instant_gravity {
+            let mut placement = fp.placement;
+            placement.pos.1 -= s.playfield.num_droppable_rows(&fp);
+            fp = FallingPiece::new(fp.piece_spec, placement);
         }
         s.falling_piece = Some(fp);
         s.can_hold = true;
+        self.refill_next_pieces();
         Ok(())
     }
     /// `Err` will be returned when an invalid move was specified.
@@ -1601,6 +2323,17 @@ impl<'a> Game<'a> {
         }
         let fp = self.state.falling_piece.as_mut().unwrap();
         if fp.apply_move(mv, &self.state.playfield, self.rules.rotation_mode) {
+            if self.rules.instant_gravity {
+                // The drop is a part of the move rather than a path item, so that the last move is
+                // kept for T-spin judgement and each move is one item as in the searched paths.
+                let n = self.state.playfield.num_droppable_rows(fp);
+                if n > 0 {
+                    fp.placement.pos.1 -= n;
+                    if let Some(item) = fp.move_path.items.last_mut() {
+                        item.placement = fp.placement;
+                    }
+                }
+            }
             Ok(())
         } else {
             Err("invalid move specified")
@@ -1651,6 +2384,10 @@ impl<'a> Game<'a> {
     /// If `Ok(false)` was returned, you should supply next pieces then call `setup_next_piece()`.
     /// `Err` will be returned when the process fails.
     pub fn lock(&mut self) -> Result<bool, &'static str> {
+        self.lock_ex().map(|outcome| outcome.next_piece_ready)
+    }
+    /// Same as [Self::lock] but returns the details of the lock.
+    pub fn lock_ex(&mut self) -> Result<LockOutcome, &'static str> {
         let s = &mut self.state;
         if s.falling_piece.is_none() {
             return Err("falling_piece is none");
@@ -1674,17 +2411,32 @@ impl<'a> Game<'a> {
                 }
             }
         }
+        let locked = (fp.piece(), fp.placement);
+        let landing_height = fp.placement.pos.1 + fp.grid().bottom_padding();
+        let cleared_rows = if self.events.is_some() { pf.rows_cleared_by(fp) } else { Vec::new() };
         let line_clear = pf.lock(fp, self.rules.tspin_judgement_mode);
         s.falling_piece = None;
         debug_assert!(line_clear.is_some());
         let line_clear = line_clear.unwrap();
         self.stats.lock += 1;
+        self.stats.lock_by_piece.add(locked.0, 1);
+        self.stats.landing_height_by_piece.add(locked.0, landing_height.max(0) as Count);
         self.stats.line_clear.add(&line_clear, 1);
+        if line_clear.num_lines > 0 {
+            match locked.0 {
+                Piece::T if line_clear.tspin.is_none() => self.stats.wasted_t += 1,
+                Piece::I if !line_clear.is_tetris() => self.stats.misused_i += 1,
+                _ => {}
+            }
+        }
+        let prev_num_btbs = s.num_btbs;
+        let mut is_perfect_clear = false;
         if line_clear.num_lines > 0 {
             s.num_combos = Some(s.num_combos.map_or(0, |n| { n + 1 }));
             self.stats.combo.add(s.num_combos.unwrap(), 1);
             if pf.is_empty() {
                 self.stats.perfect_clear += 1;
+                is_perfect_clear = true;
             }
             if line_clear.is_tetris() || line_clear.is_tspin() || line_clear.is_tspin_mini() {
                 s.num_btbs = Some(s.num_btbs.map_or(0, |n| { n + 1 }));
@@ -1696,12 +2448,52 @@ impl<'a> Game<'a> {
             s.num_btbs = None;
             s.num_combos = None;
         }
-        Ok(self.setup_falling_piece(None).is_ok())
+        let table = &self.rules.attack_table;
+        let mut attack = attack::calc_attack(&line_clear, s.num_combos, s.num_btbs, is_perfect_clear, table);
+        // Only a line clear breaks the chain to release the charge.
+        if line_clear.num_lines > 0 {
+            attack += attack::calc_btb_release(prev_num_btbs, s.num_btbs, table);
+        }
+        if s.recent_attacks.len() >= SPIKE_WINDOW_SIZE {
+            s.recent_attacks.pop_front();
+        }
+        s.recent_attacks.push_back(attack);
+        if attack > 0 {
+            self.stats.spike.add(s.current_spike(), 1);
+        }
+        let (num_combos, num_btbs) = (s.num_combos, s.num_btbs);
+        if let Some(events) = self.events.as_mut() {
+            events.push(GameEvent::PieceLocked { piece: locked.0, placement: locked.1 });
+            if !cleared_rows.is_empty() {
+                events.push(GameEvent::LinesCleared { rows: cleared_rows, line_clear });
+            }
+            if let Some(n) = prev_num_btbs.filter(|n| *n > 0 && num_btbs.is_none() && line_clear.num_lines > 0) {
+                events.push(GameEvent::BtbBroken { num_btbs: n });
+            }
+            if let Some(n) = num_combos.filter(|n| *n > 0) {
+                events.push(GameEvent::ComboExtended { num_combos: n });
+            }
+        }
+        let next_piece_ready = self.setup_falling_piece(None).is_ok();
+        Ok(LockOutcome {
+            line_clear,
+            attack_sent: attack,
+            perfect_clear: is_perfect_clear,
+            num_combos,
+            num_btbs,
+            game_over: self.state.is_game_over(),
+            next_piece_ready,
+        })
     }
+    /// Returns `true` if hold is enabled by the rules and not used yet for the falling piece.
+    pub fn can_hold(&self) -> bool { self.rules.enable_hold && self.state.can_hold }
     /// `Ok(true)` will be returned if the process is totally succeeded.
     /// If `Ok(false)` was returned, you should supply next pieces then call `setup_next_piece()`.
     /// `Err` will be returned when the process fails.
     pub fn hold(&mut self) -> Result<bool, &'static str> {
+        if !self.rules.enable_hold {
+            return Err("hold is disabled");
+        }
         let s = &mut self.state;
         if s.falling_piece.is_none() {
             return Err("no falling piece");
@@ -1715,9 +2507,50 @@ impl<'a> Game<'a> {
         self.state.hold_piece = Some(p);
         self.state.can_hold = false;
         self.stats.hold += 1;
+        self.stats.hold_by_piece.add(p, 1);
         Ok(r.is_ok())
     }
+    /// Returns what would happen if [Game::hold] were called, without changing the game.
+    /// The errors are the same as [Game::hold] and additionally `Err` is returned when nothing is
+    /// held and no next pieces are supplied.
+    pub fn peek_hold_result(&self) -> Result<HoldPreview, &'static str> {
+        if !self.rules.enable_hold {
+            return Err("hold is disabled");
+        }
+        let s = &self.state;
+        if s.falling_piece.is_none() {
+            return Err("no falling piece");
+        }
+        if !s.can_hold {
+            return Err("already held once");
+        }
+        let (piece, from_next_pieces) = if let Some(p) = s.hold_piece {
+            (p, false)
+        } else if let Some(p) = s.next_pieces.iter().next() {
+            (*p, true)
+        } else {
+            return Err("no next pieces");
+        };
+        let fp = FallingPiece::spawn_with_nudge(self.piece_specs.get(piece), Some(&s.playfield), self.rules.spawn_nudge);
+        Ok(HoldPreview { piece, from_next_pieces, blocks_out: !s.playfield.can_put(&fp) })
+    }
+    /// Returns a hash value to identify the position for the purpose of duplicate detection.
+    /// The playfield (ignoring piece types of cells), the falling piece, the hold state and
+    /// the visible next pieces are taken into account. Statistics and combo/btb states are not.
+    pub fn position_key(&self) -> u64 { self.state.position_hash(false) }
+    /// Returns `placement` followed by its alternative placements for the falling piece, which all
+    /// fill the same cells (see [helper::get_alternative_placements]).
+    pub fn alternative_placements(&self, placement: &Placement) -> Result<Vec<Placement>, &'static str> {
+        let piece = self.state.falling_piece.as_ref().ok_or("no falling piece")?.piece();
+        let mut r = vec![*placement];
+        r.extend(helper::get_alternative_placements(piece, placement));
+        Ok(r)
+    }
     pub fn search_moves(&self, searcher: &mut impl move_search::MoveSearcher) -> Result<move_search::SearchResult, &'static str> {
+        self.search_moves_with_scratch(searcher, &mut move_search::SearchScratch::new())
+    }
+    /// Same as [Self::search_moves], but the buffers of `scratch` are reused.
+    pub fn search_moves_with_scratch(&self, searcher: &mut impl move_search::MoveSearcher, scratch: &mut move_search::SearchScratch) -> Result<move_search::SearchResult, &'static str> {
         let s = &self.state;
         if s.falling_piece.is_none() {
             return Err("no falling piece");
@@ -1725,7 +2558,56 @@ impl<'a> Game<'a> {
         let fp = s.falling_piece.as_ref().unwrap();
         let pf = &s.playfield;
         let conf = move_search::SearchConfiguration::new(pf, fp.piece_spec, fp.placement, self.rules.rotation_mode);
-        Ok(searcher.search(&conf))
+        Ok(searcher.search_with_scratch(&conf, scratch))
+    }
+    /// Counts the distinct states (see [Self::position_key]) reached by locking `depth` pieces
+    /// with holding allowed, to validate move generation against known counts.
+    /// The states which can't lock `depth` pieces (e.g. game over or the queue runs out) are not
+    /// counted.
+    pub fn perft(&self, depth: usize) -> Result<usize, &'static str> {
+        self.perft_with(depth, &mut move_search::bruteforce::BruteForceMoveSearcher::default())
+    }
+    /// Same as [Self::perft] with the searcher to compare searchers for correctness and speed.
+    pub fn perft_with(&self, depth: usize, searcher: &mut impl move_search::MoveSearcher) -> Result<usize, &'static str> {
+        if self.state.falling_piece.is_none() {
+            return Err("no falling piece");
+        }
+        let mut scratch = move_search::SearchScratch::new();
+        let mut states = HashMap::new();
+        states.insert(self.position_key(), self.clone());
+        for d in 0..depth {
+            let mut next_states = HashMap::new();
+            for game in states.values() {
+                let mut sources = vec![game.clone()];
+                if game.can_hold() {
+                    let mut held = game.clone();
+                    if held.hold()? {
+                        sources.push(held);
+                    }
+                }
+                for src in sources.iter() {
+                    let fp = src.state.falling_piece.as_ref().unwrap();
+                    let pf = &src.state.playfield;
+                    let r = src.search_moves_with_scratch(searcher, &mut scratch)?;
+                    for placement in r.found.keys().chain(std::iter::once(&r.src)) {
+                        let dst = FallingPiece::new(fp.piece_spec, *placement);
+                        if !pf.can_lock(&dst) {
+                            continue;
+                        }
+                        let mut next = src.clone();
+                        next.state.falling_piece = Some(dst);
+                        let ready = next.lock()?;
+                        if next.state.is_game_over() || (!ready && d + 1 < depth) {
+                            continue;
+                        }
+                        next_states.entry(next.position_key()).or_insert(next);
+                    }
+                    scratch.recycle(r);
+                }
+            }
+            states = next_states;
+        }
+        Ok(states.len())
     }
     #[deprecated(note = "Use helper::MoveDecisionHelper.")]
     pub fn get_move_candidates(&self) -> Result<HashSet<MoveTransition>, &'static str> {
@@ -1742,6 +2624,10 @@ impl<'a> Game<'a> {
         } else {
             return Err("no falling piece");
         };
+        if self.rules.instant_gravity {
+            let r = self.search_moves(&mut move_search::instant_gravity::InstantGravityMoveSearcher)?;
+            return r.get_unmerged(&last_transition.placement).ok_or("move path not found");
+        }
         let dst = if let Some(hint) = last_transition.hint.as_ref() { &hint.placement } else { &last_transition.placement };
         if let Some(mut path) = helper::get_almost_good_move_path(self.rules.rotation_mode, &self.state.playfield, fp, dst) {
             if let Some(hint) = last_transition.hint {
@@ -1802,6 +2688,20 @@ impl<'a> Display for Game<'a> {
                 12 => {
                     write!(f, "  {:6}  {}", "LOCK", self.stats.get(StatisticsEntryType::Lock))?;
                 }
+                13 => {
+                    write!(f, "  {:6}  {}/{}", "SPIKE", s.current_spike(), self.stats.get(StatisticsEntryType::MaxSpike))?;
+                }
+                14 | 15 => {
+                    let (label, counter) = if i == 14 { ("HOLD/P", &self.stats.hold_by_piece) } else { ("LOCK/P", &self.stats.lock_by_piece) };
+                    let counts = PIECES.iter().map(|p| format!("{}{}", p.to_char(), counter.get(*p))).collect::<Vec<_>>();
+                    write!(f, "  {:6}  {}", label, counts.join(" "))?;
+                }
+                16 => {
+                    let heights = PIECES.iter()
+                        .map(|p| format!("{}{}", p.to_char(), self.stats.average_landing_height(*p).map_or("-".into(), |h| format!("{:.1}", h))))
+                        .collect::<Vec<_>>();
+                    write!(f, "  {:6}  {}", "LAND/P", heights.join(" "))?;
+                }
                 _ => {}
             }
             writeln!(f)?;
@@ -1822,17 +2722,183 @@ pub type StdGame = Game<'static>;
 // MovePlayer
 //--------------------------------------------------------------------------------------------------
 
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum InputKey {
+    Left,
+    Right,
+    SoftDrop,
+    RotateCw,
+    RotateCcw,
+    Hold,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct InputEvent {
+    pub key: InputKey,
+    pub pressed: bool,
+    pub frame: u32,
+}
+
+impl InputEvent {
+    pub fn new(key: InputKey, pressed: bool, frame: u32) -> Self { Self { key, pressed, frame } }
+}
+
+/// The timings (in frames) to convert moves into input events.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct InputTimingProfile {
+    /// Delayed auto shift.
+    pub das: u32,
+    /// Auto repeat rate. 0 means that the piece is shifted to the destination at once after DAS.
+    pub arr: u32,
+    /// Frames per one row of soft drop. 0 means that the piece is dropped at once.
+    pub soft_drop_interval: u32,
+    /// Frames between a release and the next press.
+    pub key_interval: u32,
+}
+
+impl Default for InputTimingProfile {
+    fn default() -> Self {
+        Self { das: 10, arr: 2, soft_drop_interval: 2, key_interval: 1 }
+    }
+}
+
+impl InputTimingProfile {
+    /// Appends the events of `mv` starting at `frame` to `events`, and returns the frame where the
+    /// next input can be started.
+    pub fn append_events(&self, mv: Move, frame: u32, events: &mut Vec<InputEvent>) -> u32 {
+        let mut press = |key: InputKey, start: u32, duration: u32| self.press(key, start, duration, events);
+        match mv {
+            Move::Shift(n) => {
+                let key = if n < 0 { InputKey::Left } else { InputKey::Right };
+                let n = n.unsigned_abs() as u32;
+                if n <= 1 {
+                    press(key, frame, 1)
+                } else {
+                    press(key, frame, self.das + (n - 2) * self.arr + 1)
+                }
+            }
+            Move::Drop(n) => press(InputKey::SoftDrop, frame, n.unsigned_abs() as u32 * self.soft_drop_interval),
+            Move::Rotate(n) => {
+                let key = if n < 0 { InputKey::RotateCcw } else { InputKey::RotateCw };
+                let mut f = frame;
+                for _ in 0..n.unsigned_abs() {
+                    f = press(key, f, 1);
+                }
+                f
+            }
+        }
+    }
+    /// Same as [Self::append_events] for a hold.
+    pub fn append_hold_events(&self, frame: u32, events: &mut Vec<InputEvent>) -> u32 {
+        self.press(InputKey::Hold, frame, 1, events)
+    }
+    fn press(&self, key: InputKey, start: u32, duration: u32, events: &mut Vec<InputEvent>) -> u32 {
+        let end = start + duration.max(1);
+        events.push(InputEvent::new(key, true, start));
+        events.push(InputEvent::new(key, false, end));
+        end + self.key_interval
+    }
+}
+
+impl MovePath {
+    /// Converts the path into input events starting from frame 0.
+    pub fn to_input_events(&self, profile: &InputTimingProfile) -> Vec<InputEvent> {
+        let mut events = Vec::new();
+        let mut frame = 0;
+        for item in self.iter() {
+            frame = profile.append_events(item.by, frame, &mut events);
+        }
+        events
+    }
+}
+
+/// An optional hold followed by the moves of the falling piece after it, which can express any
+/// action of bots.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ActionPath {
+    pub hold: bool,
+    /// `None` if only holding.
+    pub path: Option<MovePath>,
+}
+
+impl ActionPath {
+    pub fn new(hold: bool, path: Option<MovePath>) -> Self { Self { hold, path } }
+    /// The number of the steps including the hold.
+    pub fn len(&self) -> usize {
+        self.hold as usize + self.path.as_ref().map_or(0, |p| p.len())
+    }
+    pub fn is_empty(&self) -> bool { self.len() == 0 }
+    /// Converts the path into input events starting from frame 0.
+    pub fn to_input_events(&self, profile: &InputTimingProfile) -> Vec<InputEvent> {
+        let mut events = Vec::new();
+        let mut frame = 0;
+        if self.hold {
+            frame = profile.append_hold_events(frame, &mut events);
+        }
+        for item in self.path.iter().flat_map(|p| p.iter()) {
+            frame = profile.append_events(item.by, frame, &mut events);
+        }
+        events
+    }
+}
+
+impl From<MovePath> for ActionPath {
+    fn from(path: MovePath) -> Self { Self::new(false, Some(path)) }
+}
+
+impl<'a> Game<'a> {
+    /// Returns the path to hold if `hold` and then to move the falling piece by `last_transition`.
+    pub fn get_action_path(&self, hold: bool, last_transition: Option<&MoveTransition>) -> Result<ActionPath, &'static str> {
+        if !hold {
+            let mt = last_transition.ok_or("no action")?;
+            return Ok(self.get_almost_good_move_path(mt)?.into());
+        }
+        let mut game = self.clone();
+        game.hold()?;
+        let path = last_transition.map(|mt| game.get_almost_good_move_path(mt)).transpose()?;
+        Ok(ActionPath::new(true, path))
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct MovePlayer {
-    path: MovePath,
+    path: ActionPath,
     i: usize,
+    input: Option<(InputTimingProfile, u32)>,
+    input_events: Vec<InputEvent>,
 }
 
 impl MovePlayer {
-    pub fn new(path: MovePath) -> Self {
-        Self { path, i: 0 }
+    /// `path` is either [MovePath] or [ActionPath].
+    pub fn new(path: impl Into<ActionPath>) -> Self {
+        Self { path: path.into(), i: 0, input: None, input_events: Vec::new() }
+    }
+    /// Input events are recorded on each step. See [Self::take_input_events].
+    pub fn with_input_profile(path: impl Into<ActionPath>, profile: InputTimingProfile) -> Self {
+        let mut r = Self::new(path);
+        r.input = Some((profile, 0));
+        r
+    }
+    /// Returns the input events recorded since the last call.
+    pub fn take_input_events(&mut self) -> Vec<InputEvent> {
+        std::mem::take(&mut self.input_events)
+    }
+    /// The frame where the next input starts if the input profile is specified.
+    pub fn current_frame(&self) -> Option<u32> { self.input.map(|(_, frame)| frame) }
+    pub fn is_end(&self) -> bool { self.i >= self.len() }
+    /// The number of steps done. The hold is the first step if any.
+    pub fn current_index(&self) -> usize { self.i }
+    pub fn len(&self) -> usize { self.path.len() }
+    fn num_hold_steps(&self) -> usize { self.path.hold as usize }
+    /// `i` is the index of the steps excluding the hold.
+    fn placement_at(&self, i: usize) -> Placement {
+        let path = self.path.path.as_ref().unwrap();
+        if i == 0 {
+            path.initial_placement
+        } else {
+            path.items[i - 1].placement
+        }
     }
-    pub fn is_end(&self) -> bool { self.i >= self.path.len() }
     pub fn step(&mut self, game: &mut Game) -> Result<bool, &'static str> {
         if self.is_end() {
             return Ok(false);
@@ -1840,20 +2906,63 @@ impl MovePlayer {
         if game.state.falling_piece.is_none() {
             return Err("no falling piece");
         }
+        if self.i < self.num_hold_steps() {
+            game.hold()?;
+            if let Some((profile, frame)) = self.input.as_mut() {
+                *frame = profile.append_hold_events(*frame, &mut self.input_events);
+            }
+            self.i += 1;
+            return Ok(true);
+        }
+        let j = self.i - self.num_hold_steps();
         let fp = game.state.falling_piece.as_ref().unwrap();
-        let placement = if self.i == 0 {
-            self.path.initial_placement
-        } else {
-            self.path.items[self.i - 1].placement
-        };
-        if fp.placement != placement {
+        if fp.placement != self.placement_at(j) {
             return Err("invalid placement");
         }
-        let item = self.path.items[self.i];
+        let item = self.path.path.as_ref().unwrap().items[j];
         game.do_move(item.by)?;
+        if let Some((profile, frame)) = self.input.as_mut() {
+            *frame = profile.append_events(item.by, *frame, &mut self.input_events);
+        }
         self.i += 1;
         Ok(true)
     }
+    /// Executes the remaining steps and returns the number of them.
+    pub fn fast_forward(&mut self, game: &mut Game) -> Result<usize, &'static str> {
+        let mut n = 0;
+        while self.step(game)? {
+            n += 1;
+        }
+        Ok(n)
+    }
+    /// Makes the falling piece be in the state after `i` steps.
+    /// Seeking backward undoes the moves of the falling piece, but doesn't rewind recorded input events.
+    /// The hold can't be undone.
+    pub fn seek(&mut self, game: &mut Game, i: usize) -> Result<(), &'static str> {
+        if i > self.len() {
+            return Err("out of range");
+        }
+        while self.i < i {
+            self.step(game)?;
+        }
+        if self.i > i {
+            let h = self.num_hold_steps();
+            if i < h {
+                return Err("hold can't be undone");
+            }
+            let (cur, dst) = (self.i - h, i - h);
+            let fp = game.state.falling_piece.as_mut().ok_or("no falling piece")?;
+            if fp.placement != self.placement_at(cur) || fp.move_path.len() < cur - dst {
+                return Err("invalid placement");
+            }
+            for _ in dst..cur {
+                fp.move_path.pop();
+            }
+            fp.placement = self.placement_at(dst);
+            self.i = i;
+        }
+        Ok(())
+    }
 }
 
 //---
@@ -1861,6 +2970,8 @@ impl MovePlayer {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
 
     macro_rules! assert_ok {
         ($r:expr) => {
@@ -1880,6 +2991,43 @@ mod tests {
         assert_eq!(5, grid.num_covered_empty_cells());
     }
 
+    #[test]
+    fn test_hybrid_grid_copy_from_without_basic_grid() {
+        let mut src = Playfield::default().grid;
+        src.set_rows_with_strs((0, 0).into(), &["S@ ", "@ @"]);
+        src.disable_basic_grid();
+        let mut dst = Playfield::default().grid;
+        dst.set_cell((1, 1).into(), Cell::T);
+        dst.copy_from(&src, Rect::new(Vec2(0, 0), Vec2(2, 2)), Vec2(0, 0));
+        let basic = dst.basic_grid.as_ref().unwrap();
+        assert_eq!(Cell::Any, basic.cell((0, 0).into()));
+        assert_eq!(Cell::Empty, basic.cell((1, 0).into()));
+        assert_eq!(Cell::Any, basic.cell((1, 1).into()));
+        assert_eq!(3, basic.num_blocks());
+        assert_eq!(3, dst.bit_grid.num_blocks());
+
+        // Only the bit grid is copied to the grid without the basic grid.
+        let mut src = Playfield::default().grid;
+        src.set_cell((0, 0).into(), Cell::I);
+        let mut dst = Playfield::default().grid;
+        dst.disable_basic_grid();
+        dst.copy_from(&src, Rect::new(Vec2(0, 0), Vec2(1, 1)), Vec2(2, 0));
+        assert_eq!(Cell::Any, dst.cell((2, 0).into()));
+    }
+
+    #[test]
+    fn test_piece_spec_preview_grid() {
+        let sizes = [(3, 2), (3, 2), (3, 2), (3, 2), (4, 1), (3, 2), (2, 2)];
+        for (p, size) in PIECES.iter().zip(sizes) {
+            let g = p.default_spec().preview_grid();
+            assert_eq!(Vec2(size.0, size.1), g.size());
+            assert_eq!(4, g.num_blocks());
+        }
+        let t = Piece::T.default_spec().preview_grid();
+        assert_eq!(Cell::T, t.cell((1, 1).into()));
+        assert_eq!(Cell::Empty, t.cell((0, 1).into()));
+    }
+
     #[test]
     fn test_falling_piece() {
         let pf = Playfield::default();
@@ -1914,6 +3062,141 @@ mod tests {
         assert_eq!(NUM_PIECES, piece_set.len());
     }
 
+    #[test]
+    fn test_seed_from_str() {
+        assert_eq!(0xcbf29ce484222325, seed_from_str(""));
+        assert_eq!(0xaf63dc4c8601ec8c, seed_from_str("a"));
+        assert_ne!(seed_from_str("foo"), seed_from_str("bar"));
+        assert_eq!(seed_from_str("daily:2026-10-16"), daily_seed("2026-10-16").unwrap());
+        for date in ["2026-1-16", "2026-13-01", "2026-10-00", "20261016", "2026-10-16x", "2026-02-31", "2026-04-31", "2026-02-29", "1900-02-29"] {
+            assert!(daily_seed(date).is_err(), "{}", date);
+        }
+        for date in ["2026-01-31", "2028-02-29", "2000-02-29", "2026-12-31"] {
+            assert!(daily_seed(date).is_ok(), "{}", date);
+        }
+    }
+
+    #[test]
+    fn test_piece_generator() {
+        let mut game: Game = Default::default();
+        game.set_piece_generator(Some(Box::new(RandomPieceGenerator::new(StdRng::seed_from_u64(0)))));
+        assert!(!game.should_supply_next_pieces());
+        for _ in 0..20 {
+            assert_ok!(game.setup_falling_piece(None));
+            assert!(!game.should_supply_next_pieces());
+            let cloned = game.clone();
+            assert_eq!(cloned, game);
+            game.state.falling_piece = None;
+        }
+        let mut other: Game = Default::default();
+        other.state.next_pieces = game.state.next_pieces.clone();
+        other.set_piece_generator(game.fork_piece_generator());
+        for _ in 0..20 {
+            assert_ok!(game.setup_falling_piece(None));
+            assert_ok!(other.setup_falling_piece(None));
+            assert_eq!(game.state.falling_piece.as_ref().unwrap().piece(), other.state.falling_piece.as_ref().unwrap().piece());
+            game.state.falling_piece = None;
+            other.state.falling_piece = None;
+        }
+        assert!(game.take_piece_generator().is_some());
+        assert!(!game.has_piece_generator());
+    }
+
+    #[test]
+    fn test_static_piece_generator() {
+        let pieces = [Piece::T, Piece::I];
+        let mut g = StaticPieceGenerator::new(&pieces, false);
+        assert_eq!(pieces.to_vec(), g.generate());
+        assert!(g.is_exhausted());
+        assert!(g.generate().is_empty());
+
+        let mut game: Game = Default::default();
+        game.set_piece_generator(Some(Box::new(StaticPieceGenerator::new(&pieces, true))));
+        for p in pieces.iter().cycle().take(6) {
+            assert_ok!(game.setup_falling_piece(None));
+            assert_eq!(*p, game.state.falling_piece.as_ref().unwrap().piece());
+            game.state.falling_piece = None;
+        }
+
+        let mut game: Game = Default::default();
+        game.set_piece_generator(Some(Box::new(StaticPieceGenerator::new(&pieces, false))));
+        assert_ok!(game.setup_falling_piece(None));
+        game.state.falling_piece = None;
+        assert_ok!(game.setup_falling_piece(None));
+        game.state.falling_piece = None;
+        assert!(game.setup_falling_piece(None).is_err());
+    }
+
+    #[test]
+    fn test_try_set_rows_with_strs() {
+        let mut pf = Playfield::default();
+        let report = pf.try_set_rows_with_strs((8, 0).into(), &["SZ@", "@@ "]).unwrap();
+        assert_eq!(vec![Vec2(10, 1)], report.clipped);
+        assert_eq!(Cell::S, pf.grid.cell((8, 1).into()));
+        assert_eq!(vec![2, 2], pf.heights()[8..]);
+        assert!(pf.try_set_rows_with_strs((0, 0).into(), &["@x"]).is_err());
+        assert!(pf.grid.cell((0, 0).into()).is_empty());
+    }
+
+    #[test]
+    fn test_height_of() {
+        let mut pf = Playfield::default();
+        assert_eq!(0, pf.stack_height());
+        pf.set_rows_with_strs((0, 0).into(), &["  @", "@@@"]);
+        assert_eq!(vec![1, 1, 2], (0..3).map(|x| pf.height_of(x)).collect::<Vec<_>>());
+        // Hidden rows above the visible height are included.
+        pf.set_cell((9, 30).into(), Cell::Garbage);
+        assert_eq!(31, pf.height_of(9));
+        assert_eq!(31, pf.stack_height());
+        assert_eq!(pf.heights(), (0..pf.width()).map(|x| pf.height_of(x)).collect::<Vec<_>>());
+        assert_eq!(20, pf.visible_stack_height());
+        assert_eq!(18, pf.visible_top_padding());
+        assert_eq!(vec![0, 0, 1, 0, 0, 0, 0, 0, 0, 0], pf.visible_contour());
+        assert_eq!(4.0 / 200.0, pf.visible_density());
+    }
+
+    #[test]
+    fn test_diff_cells() {
+        let mut pf = Playfield::default();
+        pf.set_rows_with_strs((0, 0).into(), &["  @", "@@@"]);
+        let prev = pf.clone();
+        assert!(pf.diff_cells(&prev).is_empty());
+        pf.set_cell((1, 1).into(), Cell::Garbage);
+        pf.set_cell((0, 0).into(), Cell::Empty);
+        pf.set_cell((5, 3).into(), Cell::Garbage);
+        assert_eq!(vec![Vec2(0, 0), Vec2(1, 1), Vec2(5, 3)], pf.diff_cells(&prev));
+        assert_eq!(pf.diff_cells(&prev), prev.diff_cells(&pf));
+    }
+
+    #[test]
+    fn test_heights() {
+        let mut pf = Playfield::default();
+        let check = |pf: &Playfield| {
+            let hs = pf.heights();
+            assert_eq!(hs.iter().copied().max().unwrap(), pf.stack_height());
+            for x in 0..pf.width() {
+                assert_eq!(hs[x as usize], pf.height_of(x));
+            }
+        };
+        assert_eq!(vec![0; 10], pf.heights());
+        pf.append_garbage(&[0, 1]);
+        assert_eq!(vec![2, 1, 2, 2, 2, 2, 2, 2, 2, 2], pf.heights());
+        check(&pf);
+        pf.set_cell((0, 5).into(), Cell::Garbage);
+        check(&pf);
+        pf.set_cell((0, 5).into(), Cell::Empty);
+        check(&pf);
+        let fp = FallingPiece::new(Piece::T.default_spec(), Placement::new(Orientation0, (3, 1).into()));
+        assert!(pf.lock(&fp, TSpinJudgementMode::PuyoPuyoTetris).is_some());
+        assert_eq!(vec![2, 1, 2, 3, 4, 3, 2, 2, 2, 2], pf.heights());
+        check(&pf);
+        pf.append_garbage(&[3]);
+        check(&pf);
+        pf.grid.set_cell((9, 10).into(), Cell::Garbage);
+        assert_eq!(11, pf.height_of(9));
+        check(&pf);
+    }
+
     #[test]
     fn test_spawn_and_lock_out() {
         let mut pf = Playfield::default();
@@ -1940,6 +3223,63 @@ mod tests {
         assert!(!pf.can_lock(&fp));
     }
 
+    #[test]
+    fn test_game_rules_presets() {
+        let mut game: Game = Default::default();
+        game.rules = GameRules::tgm();
+        game.supply_next_pieces(&[Piece::T]);
+        assert_ok!(game.setup_falling_piece(None));
+        assert_eq!(-1, game.state.falling_piece.as_ref().unwrap().placement.pos.1);
+        assert_ne!(GameRules::guideline(), GameRules::tetrio());
+        assert_eq!(attack::AttackTable::GUIDELINE, GameRules::tgm().attack_table);
+        assert_eq!(InputTimingProfile::default(), GameRules::guideline().input_timing);
+        assert!(GameRules::tgm().input_timing.arr < GameRules::guideline().input_timing.arr);
+    }
+
+    #[test]
+    fn test_hold_disabled_and_visible_next_pieces() {
+        let mut game: Game = Default::default();
+        game.rules.enable_hold = false;
+        game.set_num_visible_next_pieces(2);
+        game.set_piece_generator(Some(Box::new(StaticPieceGenerator::new(&[Piece::T, Piece::I], true))));
+        assert_ok!(game.setup_falling_piece(None));
+        assert!(game.state.can_hold);
+        assert!(!game.can_hold());
+        assert!(game.hold().is_err());
+        assert!(game.peek_hold_result().is_err());
+        assert_eq!(2, game.state.next_pieces.visible_num);
+        assert!(game.state.next_pieces.len() > 2);
+    }
+
+    #[test]
+    fn test_spawn_nudge() {
+        let spawn_y = |pf: &Playfield, nudge: SpawnNudge| {
+            FallingPiece::spawn_with_nudge(Piece::O.default_spec(), Some(pf), nudge).placement.pos.1
+        };
+        let mut pf = Playfield::default();
+        pf.append_garbage(&[0].repeat(19));
+        assert_eq!(18, spawn_y(&pf, SpawnNudge::Disabled));
+        assert_eq!(18, spawn_y(&pf, SpawnNudge::UpTo(1)));
+        assert_eq!(18, spawn_y(&pf, SpawnNudge::Guideline));
+        pf.append_garbage(&[0, 0]);
+        assert_eq!(18, spawn_y(&pf, SpawnNudge::Disabled));
+        assert_eq!(19, spawn_y(&pf, SpawnNudge::UpTo(1)));
+        assert_eq!(20, spawn_y(&pf, SpawnNudge::UpTo(3)));
+        assert_eq!(19, spawn_y(&pf, SpawnNudge::Guideline));
+
+        // Overhang just above the default position.
+        let mut pf = Playfield::default();
+        pf.grid.set_cell((4, 21).into(), Cell::Garbage);
+        assert_eq!(18, spawn_y(&pf, SpawnNudge::UpTo(1)));
+        assert_eq!(19, spawn_y(&pf, SpawnNudge::Guideline));
+        let mut game: Game = Default::default();
+        game.rules.spawn_nudge = SpawnNudge::Guideline;
+        game.state.playfield = pf;
+        game.supply_next_pieces(&[Piece::O]);
+        assert_ok!(game.setup_falling_piece(None));
+        assert!(game.state.is_game_over());
+    }
+
     #[test]
     fn test_reverse_rotation_by_srs() {
         let mut pf = Playfield::default();
@@ -2021,6 +3361,58 @@ mod tests {
         assert!(ps.contains(&Placement::new(Orientation3, (-2, -1).into())));
     }
 
+    #[test]
+    fn test_drop_heights() {
+        let mut pf = Playfield::default();
+        pf.set_rows_with_strs((0, 0).into(), &[
+            "@@@       ",
+            "@    @@   ",
+            "@ @@@@@ @@",
+        ]);
+        let hs = pf.drop_heights(Piece::T.default_spec());
+        // T of Orientation0 has its bottom at the row 1 of the piece grid.
+        assert_eq!(8, hs[Orientation0.to_usize()].len());
+        assert_eq!((0, 2), hs[Orientation0.to_usize()][0]);
+        assert_eq!((3, 1), hs[Orientation0.to_usize()][3]);
+        assert_eq!(9, hs[Orientation1.to_usize()].len());
+        assert_eq!((-1, 3), hs[Orientation1.to_usize()][0]);
+        for piece in PIECES.iter() {
+            let spec = piece.default_spec();
+            let ps = pf.search_lockable_placements(spec);
+            for (o, xys) in pf.drop_heights(spec).iter().enumerate() {
+                for (x, y) in xys.iter() {
+                    assert!(ps.contains(&Placement::new(ORIENTATIONS[o], (*x, *y).into())));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_count_clearable_lines_with() {
+        let mut pf = Playfield::default();
+        assert_eq!(0, pf.count_clearable_lines_with(Piece::I.default_spec()));
+        pf.set_rows_with_strs((0, 0).into(), &[
+            "@@@@@@@@@ ",
+            "@@@@@@@@@ ",
+            "@@@@@@@@@ ",
+            "@@@@@@@@@ ",
+        ]);
+        assert_eq!(4, pf.count_clearable_lines_with(Piece::I.default_spec()));
+        // The other block of L has to be above the stack.
+        assert_eq!(2, pf.count_clearable_lines_with(Piece::L.default_spec()));
+        assert_eq!(0, pf.count_clearable_lines_with(Piece::O.default_spec()));
+        // The tsd position.
+        let mut pf = Playfield::default();
+        pf.set_rows_with_strs((0, 0).into(), &[
+            "@         ",
+            "   @@@@@@@",
+            "@ @@@@@@@@",
+        ]);
+        assert_eq!(2, pf.count_clearable_lines_with(Piece::T.default_spec()));
+        assert_eq!(0, pf.count_clearable_lines_with(Piece::O.default_spec()));
+    }
+
+    #[cfg(feature = "search-astar")]
     #[test]
     fn test_search_moves() {
         let mut game: Game = Default::default();
@@ -2070,6 +3462,7 @@ mod tests {
         }
     }
 
+    #[cfg(feature = "search-astar")]
     #[test]
     fn test_search_moves_2() {
         let mut game: Game = Default::default();
@@ -2108,7 +3501,12 @@ mod tests {
         assert_ok!(game.lock());
         // T
         assert_eq!(Piece::T, game.state.falling_piece.as_ref().unwrap().piece_spec.piece);
+        assert_eq!(
+            HoldPreview { piece: Piece::I, from_next_pieces: true, blocks_out: false },
+            assert_ok!(game.peek_hold_result()),
+        );
         assert_ok!(game.hold());
+        assert!(game.peek_hold_result().is_err());
         // I
         assert_eq!(Piece::I, game.state.falling_piece.as_ref().unwrap().piece_spec.piece);
         assert_ok!(game.firm_drop());
@@ -2144,7 +3542,16 @@ mod tests {
         assert_ok!(game.rotate(-1));
         assert_ok!(game.firm_drop());
         assert_ok!(game.rotate(-1));
-        assert_ok!(game.lock());
+        let outcome = assert_ok!(game.lock_ex());
+        assert_eq!(LockOutcome {
+            line_clear: LineClear::tsd(),
+            attack_sent: 4,
+            perfect_clear: false,
+            num_combos: Some(0),
+            num_btbs: Some(0),
+            game_over: false,
+            next_piece_ready: true,
+        }, outcome);
 
         assert_eq!(r#"[O]   (T)IJLSZ
 --+----------+
@@ -2161,10 +3568,10 @@ mod tests {
 09|          |  BTB     0/0
 08|          |  HOLD    2
 07|          |  LOCK    7
-06|          |
-05|          |
-04|          |
-03|          |
+06|          |  SPIKE   4/4
+05|          |  HOLD/P  S0 Z0 L0 J0 I0 T1 O1
+04|          |  LOCK/P  S1 Z1 L1 J1 I1 T1 O1
+03|          |  LAND/P  S1.0 Z0.0 L2.0 J0.0 I0.0 T0.0 O0.0
 02|L         |
 01|L         |
 00|LL Z SS  J|
@@ -2172,6 +3579,307 @@ mod tests {
 ##|0123456789|"#, format!("{}", game));
     }
 
+    #[test]
+    fn test_lock_preview() {
+        let mut game: Game = Game::default();
+        game.state.playfield.set_rows_with_strs((0, 0).into(), &[
+            "@@@@@@@@  ",
+        ]);
+        game.supply_next_pieces(&[Piece::O, Piece::O]);
+        game.setup_falling_piece(None).unwrap();
+        let candidates = game.get_move_candidates().unwrap();
+        let spec = game.state.falling_piece.as_ref().unwrap().piece_spec;
+        let mut hashes = HashMap::new();
+        for mt in candidates.iter() {
+            let fp = FallingPiece::new_with_last_move_transition(spec, mt);
+            let preview = game.state.lock_preview(&fp).unwrap();
+            let mut locked = game.state.playfield.clone();
+            locked.lock(&fp, TSpinJudgementMode::PuyoPuyoTetris).unwrap();
+            let grid = hashes.entry(preview.grid_hash).or_insert_with(|| locked.grid.bit_grid.clone());
+            assert_eq!(grid, &locked.grid.bit_grid);
+            assert_eq!(locked.heights(), preview.heights);
+            assert_eq!(locked.grid.num_blocks() == 2, preview.num_cleared_lines == 1);
+        }
+        // Locking O at the right-most and then the left-most is the same as the opposite.
+        let at = |x| FallingPiece::new(spec, Placement::new(Orientation0, (x, -1).into()));
+        let mut state = GameState::default();
+        state.playfield.lock(&at(7), TSpinJudgementMode::PuyoPuyoTetris).unwrap();
+        let a = state.lock_preview(&at(-1)).unwrap();
+        let mut state = GameState::default();
+        state.playfield.lock(&at(-1), TSpinJudgementMode::PuyoPuyoTetris).unwrap();
+        let b = state.lock_preview(&at(7)).unwrap();
+        assert_eq!(a, b);
+        assert_ne!(a, state.lock_preview(&at(3)).unwrap());
+    }
+
+    #[test]
+    fn test_lock_without_clear() {
+        let mut pf = Playfield::default();
+        pf.set_rows_with_strs((0, 0).into(), &[
+            "@@@@@@@   ",
+            "@@@@@@@@  ",
+            "@@@@@@@@  ",
+        ]);
+        let fp = FallingPiece::new(Piece::O.default_spec(), Placement::new(Orientation0, (7, -1).into()));
+        let mut expected = pf.clone();
+        let lc = expected.lock(&fp, TSpinJudgementMode::PuyoPuyoTetris).unwrap();
+        let pending = pf.lock_without_clear(&fp, TSpinJudgementMode::PuyoPuyoTetris).unwrap();
+        assert_eq!(lc, pending.line_clear);
+        assert_eq!(vec![0, 1], pending.rows);
+        assert_eq!(0b11, pending.row_mask());
+        assert_eq!(pending.snapshot, pf.grid);
+        assert!(pf.has_pending_clear());
+        assert_eq!(3, pf.stack_height());
+        assert_eq!(2, pf.finish_pending_clear());
+        assert!(!pf.has_pending_clear());
+        assert_eq!(expected, pf);
+        assert_eq!(1, pf.stack_height());
+    }
+
+    #[test]
+    fn test_game_events() {
+        let mut game: Game = Game::default();
+        game.set_event_recording(true);
+        game.state.playfield.set_rows_with_strs((0, 0).into(), &[
+            "@@@@@@@@  ",
+            "@@@@@@@@  ",
+        ]);
+        game.supply_next_pieces(&[Piece::O, Piece::O, Piece::O, Piece::O]);
+        game.setup_falling_piece(None).unwrap();
+        game.shift(1, true).unwrap();
+        game.firm_drop().unwrap();
+        let placement = game.state.falling_piece.as_ref().unwrap().placement;
+        game.lock().unwrap();
+        assert_eq!(vec![
+            GameEvent::PieceLocked { piece: Piece::O, placement },
+            GameEvent::LinesCleared { rows: vec![0, 1], line_clear: LineClear::new(2, None) },
+        ], game.drain_events());
+        assert!(game.drain_events().is_empty());
+
+        assert!(!game.receive_garbage(&[0, 0]));
+        assert_eq!(vec![GameEvent::GarbageReceived { rows: 2 }], game.drain_events());
+        assert_eq!(2, game.state.playfield.stack_height());
+
+        // The chain is broken only by a line clear.
+        game.state.playfield.set_rows_with_strs((0, 0).into(), &["          ", "@@@@@@@@  "]);
+        game.state.num_btbs = Some(2);
+        game.firm_drop().unwrap();
+        game.lock().unwrap();
+        assert!(!game.drain_events().iter().any(|e| matches!(e, GameEvent::BtbBroken { .. })));
+        game.state.num_btbs = Some(2);
+        game.shift(1, true).unwrap();
+        game.firm_drop().unwrap();
+        game.lock().unwrap();
+        assert!(game.drain_events().contains(&GameEvent::BtbBroken { num_btbs: 2 }));
+
+        game.set_event_recording(false);
+        game.receive_garbage(&[0]);
+        assert!(game.drain_events().is_empty());
+    }
+
+    #[test]
+    fn test_btb_release() {
+        let mut game: Game = Game::default();
+        game.rules = GameRules::tetrio();
+        game.state.playfield.set_rows_with_strs((0, 0).into(), &["@@@@@@@@  "]);
+        game.supply_next_pieces(&[Piece::O, Piece::O, Piece::O]);
+        assert_ok!(game.setup_falling_piece(None));
+        // A lock without line clears doesn't release the charge.
+        game.state.num_btbs = Some(5);
+        assert_ok!(game.firm_drop());
+        let outcome = assert_ok!(game.lock_ex());
+        assert_eq!(0, outcome.line_clear.num_lines);
+        assert_eq!(0, outcome.attack_sent);
+
+        game.state.num_btbs = Some(5);
+        assert_ok!(game.shift(1, true));
+        assert_ok!(game.firm_drop());
+        let outcome = assert_ok!(game.lock_ex());
+        assert_eq!(1, outcome.line_clear.num_lines);
+        assert_eq!(2, outcome.attack_sent);
+    }
+
+    #[test]
+    fn test_wasted_pieces() {
+        let mut game: Game = Game::default();
+        game.state.playfield.set_rows_with_strs((0, 0).into(), &["@@@@@@@   "]);
+        game.supply_next_pieces(&[Piece::T, Piece::I, Piece::O]);
+        assert_ok!(game.setup_falling_piece(None));
+        assert_ok!(game.shift(1, true));
+        assert_ok!(game.firm_drop());
+        assert_ok!(game.lock());
+        assert_eq!(1, game.stats.get(StatisticsEntryType::WastedT));
+        assert_eq!(0, game.stats.get(StatisticsEntryType::MisusedI));
+
+        game.state.playfield.set_rows_with_strs((0, 0).into(), &["@@@@@@@@@ ", "@@@@@@@@@ "]);
+        assert_ok!(game.rotate(1));
+        assert_ok!(game.shift(1, true));
+        assert_ok!(game.firm_drop());
+        assert_ok!(game.lock());
+        assert_eq!(1, game.stats.line_clear.get(&LineClear::new(2, None)));
+        assert_eq!(1, game.stats.get(StatisticsEntryType::MisusedI));
+        assert_eq!("wasted T", StatisticsEntryType::WastedT.to_string());
+    }
+
+    #[test]
+    fn test_statistics_merge_and_window() {
+        let mut s1 = Statistics::default();
+        s1.line_clear.add(&LineClear::tsd(), 1);
+        s1.combo.add(1, 1);
+        s1.lock = 2;
+        let mut s2 = Statistics::default();
+        s2.line_clear.add(&LineClear::tsd(), 2);
+        s2.line_clear.add(&LineClear::tetris(), 1);
+        s2.combo.add(3, 1);
+        s2.lock = 3;
+        let mut merged = s1.clone();
+        merged.merge(&s2);
+        assert_eq!(3, merged.get(StatisticsEntryType::LineClear(LineClear::tsd())));
+        assert_eq!(1, merged.get(StatisticsEntryType::LineClear(LineClear::tetris())));
+        assert_eq!(3, merged.get(StatisticsEntryType::MaxCombos));
+        assert_eq!(5, merged.lock);
+        assert_eq!(s2, merged.clone() - s1.clone());
+
+        let mut s3 = Statistics::default();
+        s3.lock_by_piece.add(Piece::T, 2);
+        s3.landing_height_by_piece.add(Piece::T, 5);
+        s3.hold_by_piece.add(Piece::I, 1);
+        let mut merged = s3.clone();
+        merged.merge(&s3);
+        assert_eq!(4, merged.get(StatisticsEntryType::PieceLock(Piece::T)));
+        assert_eq!(2, merged.get(StatisticsEntryType::PieceHold(Piece::I)));
+        assert_eq!(4, merged.lock_by_piece.total());
+        assert_eq!(Some(2.5), merged.average_landing_height(Piece::T));
+        assert_eq!(None, merged.average_landing_height(Piece::O));
+        assert_eq!(s3, merged - s3.clone());
+        assert_eq!("landing height[T]", StatisticsEntryType::PieceLandingHeight(Piece::T).to_string());
+
+        let mut window = StatisticsWindow::new(2);
+        let mut total = Statistics::default();
+        for _ in 0..3 {
+            total.lock += 1;
+            window.update(&total);
+        }
+        total.lock += 1;
+        total.line_clear.add(&LineClear::tetris(), 1);
+        window.update(&total);
+        assert_eq!(2, window.len());
+        let stats = window.stats();
+        assert_eq!(2, stats.lock);
+        assert_eq!(1, stats.get(StatisticsEntryType::LineClear(LineClear::tetris())));
+    }
+
+    #[test]
+    fn test_position_key() {
+        let mut game: Game<'static> = Game::default();
+        game.supply_next_pieces(&[Piece::O, Piece::T, Piece::I, Piece::J, Piece::L, Piece::S, Piece::Z]);
+        assert_ok!(game.setup_falling_piece(None));
+        let key = game.position_key();
+
+        let mut g = game.clone();
+        g.stats.hold += 1;
+        assert_eq!(key, g.position_key());
+        g.performance_mode();
+        assert_eq!(key, g.position_key());
+
+        let mut g = game.clone();
+        assert_ok!(g.hold());
+        assert_ne!(key, g.position_key());
+
+        let mut g = game.clone();
+        assert_ok!(g.firm_drop());
+        assert_ok!(g.lock());
+        assert_ne!(key, g.position_key());
+    }
+
+    #[test]
+    fn test_canonical_key() {
+        let mut game: Game<'static> = Game::default();
+        game.performance_mode();
+        game.supply_next_pieces(&[Piece::O, Piece::T, Piece::I, Piece::J, Piece::L, Piece::S, Piece::Z]);
+        assert_ok!(game.setup_falling_piece(None));
+        let locked = |g: &Game<'static>| {
+            let mut g = g.clone();
+            assert_ok!(g.firm_drop());
+            assert_ok!(g.lock());
+            g
+        };
+        let mut shifted = game.clone();
+        assert_ok!(shifted.shift(1, false));
+        assert_eq!(game.position_key(), shifted.position_key());
+        assert_ne!(game.state.canonical_key(), shifted.state.canonical_key());
+        assert_ne!(locked(&game).state.canonical_key(), locked(&shifted).state.canonical_key());
+
+        let mut g = locked(&game);
+        g.state.num_combos = Some(1);
+        assert_eq!(locked(&game).state.canonical_key(), g.state.canonical_key());
+
+        // Grids without the basic grids are distinguished by the blocks.
+        let states = [locked(&game).state, locked(&game).state, locked(&shifted).state, game.state.clone()];
+        assert_eq!(3, states.iter().collect::<HashSet<_>>().len());
+        // Consistent with the grids with the basic grids.
+        let mut colored = locked(&game).state.playfield;
+        colored.grid.enable_basic_grid();
+        assert_eq!(locked(&game).state.playfield, colored);
+        // The piece types are ignored also between the grids with the basic grids.
+        let mut recolored = colored.clone();
+        let pos = (0..colored.width()).map(|x| Vec2(x, 0)).find(|p| !colored.grid.cell(*p).is_empty()).unwrap();
+        recolored.grid.set_cell(pos, Cell::Z);
+        assert_ne!(colored.grid.cell(pos), recolored.grid.cell(pos));
+        assert_eq!(colored, recolored);
+    }
+
+    #[test]
+    fn test_color_tracking() {
+        let mut game = Game::default();
+        game.supply_next_pieces(&[Piece::T, Piece::O]);
+        assert_ok!(game.setup_falling_piece(None));
+        assert_ok!(game.firm_drop());
+        assert_ok!(game.lock());
+        assert!(game.is_color_tracking());
+        let key = game.position_key();
+        let cell_of_t = |g: &Game| g.state.playfield.grid.cell((4, 1).into());
+        assert_eq!(Cell::T, cell_of_t(&game));
+
+        game.set_color_tracking(false);
+        assert!(!game.is_color_tracking());
+        assert_eq!(key, game.position_key());
+        assert_ok!(game.firm_drop());
+        assert_ok!(game.lock());
+        let num_blocks = game.state.playfield.grid.bit_grid.num_blocks();
+
+        game.set_color_tracking(true);
+        assert!(game.is_color_tracking());
+        assert_eq!(Cell::Any, cell_of_t(&game));
+        assert_eq!(num_blocks, game.state.playfield.grid.num_blocks());
+        // Re-enabling doesn't change the tracked cells.
+        let pf = game.state.playfield.clone();
+        game.set_color_tracking(true);
+        assert_eq!(pf, game.state.playfield);
+    }
+
+    #[test]
+    fn test_perft() {
+        let mut game = Game::default();
+        game.supply_next_pieces(&[Piece::O]);
+        assert_ok!(game.setup_falling_piece(None));
+        assert_eq!(1, assert_ok!(game.perft(0)));
+        // All the orientations of O result in the same playfields.
+        assert_eq!(9, assert_ok!(game.perft(1)));
+        // Out of pieces.
+        assert_eq!(0, assert_ok!(game.perft(2)));
+
+        let mut game = Game::default();
+        game.supply_next_pieces(&[Piece::O, Piece::I, Piece::T]);
+        assert_ok!(game.setup_falling_piece(None));
+        // O or I (holding O) with the horizontal and vertical placements.
+        assert_eq!(9 + 7 + 10, assert_ok!(game.perft(1)));
+        let n = assert_ok!(game.perft(2));
+        assert!(n > 26);
+        let mut searcher = move_search::heuristic_bruteforce::HeuristicBruteForceMoveSearcher::default();
+        assert_eq!(n, assert_ok!(game.perft_with(2, &mut searcher)));
+    }
+
     #[test]
     fn test_move_player() {
         let mut game = Game::default();
@@ -2195,4 +3903,116 @@ mod tests {
         }
         assert_eq!(dst, game.state.falling_piece.as_ref().unwrap().placement);
     }
+
+    #[test]
+    fn test_move_player_seek() {
+        let mut game = Game::default();
+        game.supply_next_pieces(&[Piece::T]);
+        assert_ok!(game.setup_falling_piece(None));
+        let initial = game.clone();
+        let dst = Placement::new(Orientation1, (0, 0).into());
+        let path = assert_ok!(game.get_almost_good_move_path(&MoveTransition::new(dst, None)));
+        assert!(path.len() >= 2);
+
+        let mut player = MovePlayer::new(path.clone());
+        assert_eq!(path.len(), assert_ok!(player.fast_forward(&mut game)));
+        assert!(player.is_end());
+        assert_eq!(dst, game.state.falling_piece.as_ref().unwrap().placement);
+
+        assert_ok!(player.seek(&mut game, 0));
+        assert_eq!(0, player.current_index());
+        assert_eq!(initial, game);
+
+        assert_ok!(player.seek(&mut game, 1));
+        assert_eq!(path.items[0].placement, game.state.falling_piece.as_ref().unwrap().placement);
+        assert_ok!(player.seek(&mut game, path.len()));
+        assert_eq!(dst, game.state.falling_piece.as_ref().unwrap().placement);
+        assert!(player.seek(&mut game, path.len() + 1).is_err());
+    }
+
+    #[test]
+    fn test_move_player_action_path() {
+        let mut game = Game::default();
+        game.supply_next_pieces(&[Piece::T, Piece::O]);
+        assert_ok!(game.setup_falling_piece(None));
+        let dst = Placement::new(Orientation0, (-1, -1).into());
+        let mt = MoveTransition::new(dst, None);
+        assert!(game.get_action_path(false, None).is_err());
+
+        let path = assert_ok!(game.get_action_path(true, Some(&mt)));
+        assert!(path.hold);
+        let mut player = MovePlayer::new(path.clone());
+        assert_eq!(path.len(), assert_ok!(player.fast_forward(&mut game)));
+        assert_eq!(Some(Piece::T), game.state.hold_piece);
+        let fp = game.state.falling_piece.as_ref().unwrap();
+        assert_eq!(Piece::O, fp.piece());
+        assert_eq!(dst, fp.placement);
+        assert_ok!(player.seek(&mut game, 1));
+        assert!(player.seek(&mut game, 0).is_err());
+        assert!(game.get_action_path(true, None).is_err());
+
+        let mut game = Game::default();
+        game.supply_next_pieces(&[Piece::T, Piece::O]);
+        assert_ok!(game.setup_falling_piece(None));
+        let profile = InputTimingProfile::default();
+        let path = assert_ok!(game.get_action_path(true, None));
+        assert_eq!(ActionPath::new(true, None), path);
+        let mut player = MovePlayer::with_input_profile(path.clone(), profile);
+        assert!(assert_ok!(player.step(&mut game)));
+        assert!(!assert_ok!(player.step(&mut game)));
+        assert_eq!(Piece::O, game.state.falling_piece.as_ref().unwrap().piece());
+        assert_eq!(path.to_input_events(&profile), player.take_input_events());
+    }
+
+    #[test]
+    fn test_input_events() {
+        let profile = InputTimingProfile { das: 10, arr: 2, soft_drop_interval: 1, key_interval: 1 };
+        let mut path = MovePath::new(Placement::new(Orientation0, (3, 18).into()));
+        path.push(MovePathItem::new(Move::Shift(-3), Placement::new(Orientation0, (0, 18).into())));
+        path.push(MovePathItem::new(Move::Rotate(-1), Placement::new(Orientation3, (0, 18).into())));
+        path.push(MovePathItem::new(Move::Drop(5), Placement::new(Orientation3, (0, 13).into())));
+        path.push(MovePathItem::new(Move::Shift(1), Placement::new(Orientation3, (1, 13).into())));
+        assert_eq!(vec![
+            InputEvent::new(InputKey::Left, true, 0),
+            InputEvent::new(InputKey::Left, false, 13),
+            InputEvent::new(InputKey::RotateCcw, true, 14),
+            InputEvent::new(InputKey::RotateCcw, false, 15),
+            InputEvent::new(InputKey::SoftDrop, true, 16),
+            InputEvent::new(InputKey::SoftDrop, false, 21),
+            InputEvent::new(InputKey::Right, true, 22),
+            InputEvent::new(InputKey::Right, false, 23),
+        ], path.to_input_events(&profile));
+
+        let mut game = Game::default();
+        game.supply_next_pieces(&[Piece::T]);
+        assert_ok!(game.setup_falling_piece(None));
+        let fp = game.state.falling_piece.as_ref().unwrap();
+        let mut path = MovePath::new(fp.placement);
+        path.push(MovePathItem::new(Move::Shift(-2), Placement::new(Orientation0, fp.placement.pos + Vec2(-2, 0))));
+        let mut player = MovePlayer::with_input_profile(path.clone(), profile);
+        while assert_ok!(player.step(&mut game)) {}
+        assert_eq!(path.to_input_events(&profile), player.take_input_events());
+        assert!(player.take_input_events().is_empty());
+        assert_eq!(Some(12), player.current_frame());
+    }
+
+    #[test]
+    fn test_alternative_placements() {
+        let mut game = Game::default();
+        game.supply_next_pieces(&[Piece::S]);
+        assert_ok!(game.setup_falling_piece(None));
+        let dst = Placement::new(Orientation0, (3, 0).into());
+        let alts = assert_ok!(game.alternative_placements(&dst));
+        assert_eq!(vec![dst, Placement::new(Orientation2, (3, 1).into())], alts);
+
+        // Only the orientation 0 is stored.
+        let fp = game.state.falling_piece.as_ref().unwrap();
+        let mut found = move_search::MoveDestinations::new();
+        found.insert(dst, MovePathItem::new(Move::Drop(fp.placement.pos.1 - dst.pos.1), fp.placement));
+        let r = move_search::SearchResult::new(Piece::S, fp.placement, found);
+        assert!(!r.contains(&alts[1]));
+        assert_eq!(Some(dst), r.find_alternative(&alts[1]));
+        assert_eq!(Some(dst), r.get(&alts[1]).map(|path| path.last().unwrap().placement));
+        assert_eq!(None, r.get(&Placement::new(Orientation1, (3, 0).into())));
+    }
 }
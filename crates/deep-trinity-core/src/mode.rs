@@ -0,0 +1,102 @@
+//! Game modes changing the game as it's played, applied after each lock by the runner of the game.
+
+use deep_trinity_grid::{X, Y};
+use crate::{Count, Game};
+
+/// Garbage rows rise automatically every `interval` locks, e.g. to practice survival.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RisingGarbage {
+    interval: Count,
+    rows: Y,
+    /// The gap positions of the rising rows from the bottom, repeated in order.
+    gap_pattern: Vec<X>,
+    next_gap: usize,
+}
+
+impl RisingGarbage {
+    /// `rows` rows rise at once every `interval` locks.
+    pub fn new(interval: Count, rows: Y, gap_pattern: Vec<X>) -> Result<Self, &'static str> {
+        if interval == 0 || rows <= 0 {
+            return Err("interval and rows should be positive");
+        }
+        if gap_pattern.is_empty() {
+            return Err("empty gap pattern");
+        }
+        Ok(Self { interval, rows, gap_pattern, next_gap: 0 })
+    }
+    pub fn interval(&self) -> Count { self.interval }
+    pub fn rows(&self) -> Y { self.rows }
+    pub fn gap_pattern(&self) -> &[X] { &self.gap_pattern }
+    /// Starts the gap pattern over, e.g. for a new game.
+    pub fn reset(&mut self) { self.next_gap = 0; }
+    /// Should be called after each lock with the next piece spawned. Returns `true` if the game is over.
+    pub fn on_lock(&mut self, game: &mut Game) -> Result<bool, &'static str> {
+        if game.stats.lock == 0 || !game.stats.lock.is_multiple_of(self.interval) {
+            return Ok(game.state.is_game_over());
+        }
+        let width = game.state.playfield.width();
+        let mut gaps = Vec::with_capacity(self.rows as usize);
+        for _ in 0..self.rows {
+            let x = self.gap_pattern[self.next_gap];
+            if x < 0 || width <= x {
+                return Err("gap out of the playfield");
+            }
+            gaps.push(x);
+            self.next_gap = (self.next_gap + 1) % self.gap_pattern.len();
+        }
+        // The latest row is the bottom one.
+        gaps.reverse();
+        if game.receive_garbage(&gaps) {
+            return Ok(true);
+        }
+        // The rows rise before the next piece in effect, so it's spawned again above them.
+        if let Some(fp) = game.state.falling_piece.take() {
+            game.setup_falling_piece(Some(fp.piece()))?;
+        }
+        Ok(game.state.is_game_over())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Cell, Piece};
+    use deep_trinity_grid::{Cell as _, Grid};
+
+    fn gap_x(game: &Game, y: Y) -> Option<X> {
+        (0..game.state.playfield.width()).find(|x| game.state.playfield.grid.cell((*x, y).into()).is_empty())
+    }
+
+    #[test]
+    fn test_rising_garbage() {
+        assert!(RisingGarbage::new(0, 1, vec![0]).is_err());
+        assert!(RisingGarbage::new(1, 1, vec![]).is_err());
+
+        let mut game: Game = Game::default();
+        game.supply_next_pieces(&[Piece::O; 4]);
+        game.setup_falling_piece(None).unwrap();
+        let mut rising = RisingGarbage::new(2, 1, vec![3, 5]).unwrap();
+        let mut heights = Vec::new();
+        for _ in 0..4 {
+            game.shift(1, true).unwrap();
+            game.firm_drop().unwrap();
+            game.lock().unwrap();
+            assert!(!rising.on_lock(&mut game).unwrap());
+            heights.push(game.state.playfield.stack_height());
+        }
+        assert_eq!(vec![2, 5, 7, 10], heights);
+        assert_eq!(Some(5), gap_x(&game, 0));
+        assert_eq!(Some(3), gap_x(&game, 1));
+        assert_eq!(Cell::Garbage, game.state.playfield.grid.cell((9, 1).into()));
+
+        // Multiple rows at once rise in the order of the pattern.
+        let mut game: Game = Game::default();
+        game.supply_next_pieces(&[Piece::O; 2]);
+        game.setup_falling_piece(None).unwrap();
+        game.firm_drop().unwrap();
+        game.lock().unwrap();
+        let mut rising = RisingGarbage::new(1, 3, vec![0, 9]).unwrap();
+        rising.on_lock(&mut game).unwrap();
+        assert_eq!(vec![Some(0), Some(9), Some(0)], (0..3).map(|y| gap_x(&game, y)).collect::<Vec<_>>());
+    }
+}
@@ -1,10 +1,12 @@
-use std::collections::HashMap;
-use crate::{Playfield, Placement, RotationMode, MovePathItem, MovePath, PieceSpec};
+use std::collections::{HashMap, BTreeMap, VecDeque};
+use crate::{Playfield, Piece, Placement, RotationMode, MovePathItem, MovePath, PieceSpec};
 
+#[cfg(feature = "search-astar")]
 pub mod astar;
 pub mod bruteforce;
 pub mod humanly_optimized;
 pub mod heuristic_bruteforce;
+pub mod instant_gravity;
 
 #[derive(Clone)]
 pub struct SearchConfiguration<'a> {
@@ -26,15 +28,50 @@ pub type MoveDestinations = HashMap<Placement, MovePathItem>;
 
 #[derive(Clone, Debug)]
 pub struct SearchResult {
+    pub piece: Piece,
     pub src: Placement,
     pub found: MoveDestinations,
 }
 
 impl SearchResult {
-    pub fn new(src: Placement, found: MoveDestinations) -> Self { Self { src, found } }
+    pub fn new(piece: Piece, src: Placement, found: MoveDestinations) -> Self { Self { piece, src, found } }
     pub fn len(&self) -> usize { self.found.len() }
+    /// Unlike [Self::get], the alternative placements are not considered.
     pub fn contains(&self, dst: &Placement) -> bool { self.found.contains_key(dst) }
+    /// Returns `dst` if found, or the first found one of the alternative placements of `dst` (see
+    /// [crate::helper::get_alternative_placements]), which fill the same cells.
+    pub fn find_alternative(&self, dst: &Placement) -> Option<Placement> {
+        if self.contains(dst) {
+            return Some(*dst);
+        }
+        crate::helper::get_alternative_placements(self.piece, dst).into_iter().find(|p| self.contains(p))
+    }
+    /// Returns the found placements in a deterministic order unlike the iteration of `found`.
+    pub fn sorted_placements(&self) -> Vec<Placement> {
+        let mut r = self.found.keys().copied().collect::<Vec<_>>();
+        r.sort();
+        r
+    }
+    /// Returns the path to `dst`, or to any alternative placement of it if `dst` itself is not found.
     pub fn get(&self, dst: &Placement) -> Option<MovePath> {
+        let items = self.collect_items(&self.find_alternative(dst)?)?;
+        let mut path = MovePath::new(self.src);
+        for item in items.iter().rev() {
+            path.merge_or_push(*item);
+        }
+        Some(path)
+    }
+    /// Same as `get()`, but successive moves of the same kind are not merged.
+    pub fn get_unmerged(&self, dst: &Placement) -> Option<MovePath> {
+        let items = self.collect_items(&self.find_alternative(dst)?)?;
+        let mut path = MovePath::new(self.src);
+        for item in items.iter().rev() {
+            path.push(*item);
+        }
+        Some(path)
+    }
+    /// Returns the items from `dst` to `src` in the reverse order.
+    fn collect_items(&self, dst: &Placement) -> Option<Vec<MovePathItem>> {
         let mut placement = *dst;
         let mut items: Vec<MovePathItem> = Vec::new();
         let mut i = 0;
@@ -54,14 +91,57 @@ impl SearchResult {
         if items.is_empty() {
             return None;
         }
-        let mut path = MovePath::new(self.src);
-        for item in items.iter().rev() {
-            path.merge_or_push(*item);
-        }
-        Some(path)
+        Some(items)
     }
 }
 
 pub trait MoveSearcher {
     fn search(&mut self, conf: &SearchConfiguration) -> SearchResult;
+    /// Same as `search()`, but the buffers of `scratch` are reused.
+    /// The default implementation ignores `scratch`.
+    fn search_with_scratch(&mut self, conf: &SearchConfiguration, _scratch: &mut SearchScratch) -> SearchResult {
+        self.search(conf)
+    }
+}
+
+/// Reusable buffers of searchers to avoid repeated allocations in tight loops.
+/// Give back results by `recycle()` to reuse their buffers too.
+#[derive(Clone, Debug, Default)]
+pub struct SearchScratch {
+    found: MoveDestinations,
+    pub(crate) queue: VecDeque<Placement>,
+    pub(crate) costs: HashMap<Placement, StateEntry>,
+    pub(crate) open_list: BTreeMap<i16, VecDeque<Placement>>,
+}
+
+/// The state of a placement in the A* search.
+#[derive(Copy, Clone, Debug)]
+#[cfg_attr(not(feature = "search-astar"), allow(dead_code))]
+pub(crate) struct StateEntry {
+    pub(crate) f: i16,
+    pub(crate) is_checked: bool,
+}
+
+#[cfg_attr(not(feature = "search-astar"), allow(dead_code))]
+impl StateEntry {
+    pub(crate) fn new(f: i16, is_checked: bool) -> Self { Self { f, is_checked } }
+}
+
+impl SearchScratch {
+    pub fn new() -> Self { Default::default() }
+    /// Takes back the buffer of the result for the next search.
+    pub fn recycle(&mut self, r: SearchResult) {
+        if r.found.capacity() > self.found.capacity() {
+            self.found = r.found;
+        }
+    }
+    /// Returns the cleared buffers except for `found`, which is moved out for the result.
+    pub(crate) fn prepare(&mut self) -> MoveDestinations {
+        self.queue.clear();
+        self.costs.clear();
+        self.open_list.clear();
+        let mut found = std::mem::take(&mut self.found);
+        found.clear();
+        found
+    }
 }
@@ -1,21 +1,15 @@
 /// Move searcher by A* algorithm.
 /// By using this, we can get mostly good moves to a specific placement.
-use std::collections::{HashMap, BTreeMap, VecDeque};
+use std::collections::VecDeque;
 use crate::{Move, FallingPiece, MovePathItem, Placement};
-use super::{SearchConfiguration, MoveDestinations, SearchResult, MoveSearcher};
+use super::{SearchConfiguration, SearchResult, MoveSearcher, SearchScratch, StateEntry};
 
 pub fn search_moves(conf: &SearchConfiguration, dst: Placement, debug: bool) -> SearchResult {
-    type F = i16;
-    type OpenList = BTreeMap<F, VecDeque<Placement>>;
+    search_moves_with_scratch(conf, dst, debug, &mut SearchScratch::new())
+}
 
-    #[derive(Copy, Clone, Debug)]
-    struct StateEntry {
-        f: F,
-        is_checked: bool,
-    }
-    impl StateEntry {
-        fn new(f: F, is_checked: bool) -> Self { Self { f, is_checked } }
-    }
+pub fn search_moves_with_scratch(conf: &SearchConfiguration, dst: Placement, debug: bool, scratch: &mut SearchScratch) -> SearchResult {
+    type F = i16;
 
     fn heuristic_func(current: &Placement, target: &Placement) -> F {
         current.distance(target, Some((1, 1, 1))) as F
@@ -42,9 +36,9 @@ pub fn search_moves(conf: &SearchConfiguration, dst: Placement, debug: bool) ->
         Move::Drop(1), Move::Shift(1), Move::Shift(-1), Move::Rotate(1), Move::Rotate(-1),
     ];
 
-    let mut found = MoveDestinations::new();
-    let mut open_list = OpenList::new();
-    let mut state: HashMap<Placement, StateEntry> = HashMap::new();
+    let mut found = scratch.prepare();
+    let open_list = &mut scratch.open_list;
+    let state = &mut scratch.costs;
 
     open_list.insert(0, VecDeque::from(vec![conf.src]));
     state.insert(conf.src, StateEntry::new(0, false));
@@ -103,7 +97,7 @@ pub fn search_moves(conf: &SearchConfiguration, dst: Placement, debug: bool) ->
         }
     }
 
-    SearchResult { src: conf.src, found }
+    SearchResult::new(conf.piece_spec.piece, conf.src, found)
 }
 
 #[derive(Copy, Clone, Debug)]
@@ -122,6 +116,9 @@ impl MoveSearcher for AStarMoveSearcher {
     fn search(&mut self, conf: &SearchConfiguration) -> SearchResult {
         search_moves(conf, self.dst, self.debug)
     }
+    fn search_with_scratch(&mut self, conf: &SearchConfiguration, scratch: &mut SearchScratch) -> SearchResult {
+        search_moves_with_scratch(conf, self.dst, self.debug, scratch)
+    }
 }
 
 #[cfg(test)]
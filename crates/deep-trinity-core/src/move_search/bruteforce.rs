@@ -3,15 +3,33 @@
 ///
 /// Remarks: Since this searcher doesn't search all move transitions,
 /// the result will lack some meaningful special rotations (e.g. T-Spin Mini).
-use crate::{Move, FallingPiece, MovePathItem};
-use super::{SearchConfiguration, MoveDestinations, SearchResult, MoveSearcher};
+use std::collections::HashSet;
+use crate::{Move, FallingPiece, MovePathItem, Placement};
+use super::{SearchConfiguration, MoveDestinations, SearchResult, MoveSearcher, SearchScratch};
 
 const MOVES: [Move; 5] = [Move::Drop(1), Move::Shift(1), Move::Shift(-1), Move::Rotate(1), Move::Rotate(-1)];
 
 pub fn search_moves(conf: &SearchConfiguration, debug: bool) -> SearchResult {
-    let mut found = MoveDestinations::new();
+    search_moves_with_scratch(conf, debug, &mut SearchScratch::new())
+}
+
+pub fn search_moves_with_scratch(conf: &SearchConfiguration, debug: bool, scratch: &mut SearchScratch) -> SearchResult {
+    search_all(conf, debug, scratch, None)
+}
 
-    fn search(conf: &SearchConfiguration, fp: &FallingPiece, depth: usize, found: &mut MoveDestinations, debug: bool) {
+/// Returns the found placements which are reachable only if the last move is a rotation, i.e. not
+/// by any shift or drop, e.g. spins and kicks under overhangs.
+pub fn search_spin_only_placements(conf: &SearchConfiguration) -> HashSet<Placement> {
+    let mut by_others = HashSet::new();
+    let r = search_all(conf, false, &mut SearchScratch::new(), Some(&mut by_others));
+    r.found.into_keys().filter(|p| !by_others.contains(p)).collect()
+}
+
+/// `by_others` collects the placements reached by moves other than rotations if given.
+fn search_all(conf: &SearchConfiguration, debug: bool, scratch: &mut SearchScratch, by_others: Option<&mut HashSet<Placement>>) -> SearchResult {
+    let mut found = scratch.prepare();
+
+    fn search(conf: &SearchConfiguration, fp: &FallingPiece, depth: usize, found: &mut MoveDestinations, mut by_others: Option<&mut HashSet<Placement>>, debug: bool) {
         macro_rules! debug_println {
             ($e:expr $(, $es:expr)*) => {
                 if debug {
@@ -24,6 +42,11 @@ pub fn search_moves(conf: &SearchConfiguration, debug: bool) -> SearchResult {
         }
 
         debug_println!("search_all: {:?} {}", fp.placement.orientation, fp.placement.pos);
+        if let (Some(by_others), Some(last)) = (by_others.as_deref_mut(), fp.move_path.last()) {
+            if !matches!(last.by, Move::Rotate(_)) {
+                by_others.insert(fp.placement);
+            }
+        }
         if depth > 0 && fp.placement == conf.src {
             debug_println!("=> initial placement.");
             return;
@@ -43,16 +66,16 @@ pub fn search_moves(conf: &SearchConfiguration, debug: bool) -> SearchResult {
         for mv in &MOVES {
             debug_println!("├ {:?}", mv);
             if fp.apply_move(*mv, conf.pf, conf.mode) {
-                search(conf, &fp, depth + 1, found, debug);
+                search(conf, &fp, depth + 1, found, by_others.as_deref_mut(), debug);
                 fp.rollback();
             }
         }
         debug_println!("=> checked.");
     }
 
-    search(conf, &FallingPiece::new(conf.piece_spec, conf.src), 0, &mut found, debug);
+    search(conf, &FallingPiece::new(conf.piece_spec, conf.src), 0, &mut found, by_others, debug);
 
-    SearchResult { src: conf.src, found }
+    SearchResult::new(conf.piece_spec.piece, conf.src, found)
 }
 
 #[derive(Copy, Clone, Debug, Default)]
@@ -68,11 +91,14 @@ impl MoveSearcher for BruteForceMoveSearcher {
     fn search(&mut self, conf: &SearchConfiguration) -> SearchResult {
         search_moves(conf, self.debug)
     }
+    fn search_with_scratch(&mut self, conf: &SearchConfiguration, scratch: &mut SearchScratch) -> SearchResult {
+        search_moves_with_scratch(conf, self.debug, scratch)
+    }
 }
 
 #[cfg(test)]
 mod test {
-    use crate::{Game, Piece, Placement, RotationMode, MovePlayer, Orientation1, Orientation3};
+    use crate::{Game, Piece, Placement, RotationMode, MovePlayer, Orientation1, Orientation2, Orientation3};
     use super::*;
 
     #[test]
@@ -104,4 +130,43 @@ mod test {
         }
         // println!("{}", game);
     }
+
+    #[test]
+    fn test_search_spin_only_placements() {
+        let mut game: Game = Game::default();
+        game.supply_next_pieces(&[Piece::T]);
+        game.setup_falling_piece(None).unwrap();
+        let pf = &mut game.state.playfield;
+        pf.set_rows_with_strs((0, 0).into(), &[
+            "@@@@      ",
+            "@@@   @@@@",
+            "@@@@ @@@@@",
+        ]);
+        let fp = game.state.falling_piece.as_ref().unwrap();
+        let conf = SearchConfiguration::new(&pf, fp.piece_spec, fp.placement, RotationMode::Srs);
+        let r = search_spin_only_placements(&conf);
+        // The T pointing down in the slot.
+        assert!(r.contains(&Placement::new(Orientation2, (3, 0).into())));
+        assert!(!r.contains(&Placement::new(Orientation2, (3, 2).into())));
+        let found = search_moves(&conf, false);
+        assert!(r.iter().all(|p| found.contains(p)));
+        assert_eq!(r, crate::move_search::heuristic_bruteforce::search_spin_only_placements(&conf));
+    }
+
+    #[test]
+    fn test_search_moves_with_scratch() {
+        let mut game: Game = Game::default();
+        game.supply_next_pieces(&[Piece::T]);
+        game.setup_falling_piece(None).unwrap();
+        let expected = game.search_moves(&mut BruteForceMoveSearcher::default()).unwrap();
+        let pf = &game.state.playfield;
+        let fp = game.state.falling_piece.as_ref().unwrap();
+        let conf = SearchConfiguration::new(pf, fp.piece_spec, fp.placement, RotationMode::Srs);
+        let mut scratch = SearchScratch::new();
+        for _ in 0..2 {
+            let r = BruteForceMoveSearcher::default().search_with_scratch(&conf, &mut scratch);
+            assert_eq!(expected.sorted_placements(), r.sorted_placements());
+            scratch.recycle(r);
+        }
+    }
 }
@@ -1,17 +1,27 @@
 /// Heuristic move searcher by brute force approach.
 /// By using this, we can get at least all lockable placements.
+use std::collections::HashSet;
 use deep_trinity_grid::Grid;
-use crate::{Move, MovePathItem};
-use super::{SearchConfiguration, SearchResult, MoveSearcher};
+use crate::{Move, MovePathItem, Placement};
+use super::{SearchConfiguration, SearchResult, MoveSearcher, SearchScratch};
 
 pub fn search_moves(conf: &SearchConfiguration, debug: bool) -> SearchResult {
-    let highest = conf.pf.grid.height() - conf.pf.grid.top_padding();
-    let piece_height = conf.piece_spec.grid(conf.src.orientation).height();
-    let safe_y = conf.src.pos.1.min(highest + piece_height);
+    search_moves_with_scratch(conf, debug, &mut SearchScratch::new())
+}
 
+/// The configuration starting from the height where no blocks are above the piece.
+fn safe_conf<'a>(conf: &SearchConfiguration<'a>) -> SearchConfiguration<'a> {
+    let highest = conf.pf.stack_height();
+    let piece_height = conf.piece_spec.grid(conf.src.orientation).height();
     let mut conf2 = conf.clone();
-    conf2.src.pos.1 = safe_y;
-    let mut r = super::bruteforce::search_moves(&conf2, debug);
+    conf2.src.pos.1 = conf.src.pos.1.min(highest + piece_height);
+    conf2
+}
+
+pub fn search_moves_with_scratch(conf: &SearchConfiguration, debug: bool, scratch: &mut SearchScratch) -> SearchResult {
+    let conf2 = safe_conf(conf);
+    let safe_y = conf2.src.pos.1;
+    let mut r = super::bruteforce::search_moves_with_scratch(&conf2, debug, scratch);
 
     if safe_y != conf.src.pos.1 {
         r.found.insert(conf2.src, MovePathItem::new(Move::Drop(conf.src.pos.1 - safe_y), conf.src));
@@ -21,6 +31,12 @@ pub fn search_moves(conf: &SearchConfiguration, debug: bool) -> SearchResult {
     r
 }
 
+/// Same as [super::bruteforce::search_spin_only_placements], but faster as [search_moves].
+/// The placements above the stack, which are reachable by drops, are not searched.
+pub fn search_spin_only_placements(conf: &SearchConfiguration) -> HashSet<Placement> {
+    super::bruteforce::search_spin_only_placements(&safe_conf(conf))
+}
+
 #[derive(Copy, Clone, Debug, Default)]
 pub struct HeuristicBruteForceMoveSearcher {
     debug: bool,
@@ -34,6 +50,9 @@ impl MoveSearcher for HeuristicBruteForceMoveSearcher {
     fn search(&mut self, conf: &SearchConfiguration) -> SearchResult {
         search_moves(conf, self.debug)
     }
+    fn search_with_scratch(&mut self, conf: &SearchConfiguration, scratch: &mut SearchScratch) -> SearchResult {
+        search_moves_with_scratch(conf, self.debug, scratch)
+    }
 }
 
 #[cfg(test)]
@@ -87,7 +87,7 @@ fn search_moves(conf: &SearchConfiguration, plan: &[Vec<Move>]) -> SearchResult
         }
     }
 
-    SearchResult::new(conf.src, found)
+    SearchResult::new(conf.piece_spec.piece, conf.src, found)
 }
 
 /// This searcher covers most of moves the last of which is hard drop.
@@ -125,7 +125,7 @@ impl MoveSearcher for HumanlyOptimizedMoveSearcher {
                 return r;
             }
         }
-        SearchResult::new(conf.src, MoveDestinations::new())
+        SearchResult::new(conf.piece_spec.piece, conf.src, MoveDestinations::new())
     }
 }
 
@@ -0,0 +1,129 @@
+/// Move searcher for 20G (see [crate::GameRules::instant_gravity]).
+/// Every shift and rotation is immediately followed by dropping to the bottom, so the found
+/// placements always rest on the stack.
+///
+/// Remarks: Since the drops are implicit, paths should be obtained by
+/// [SearchResult::get_unmerged] instead of [SearchResult::get].
+use crate::{Move, FallingPiece, MovePathItem, Placement};
+use super::{SearchConfiguration, SearchResult, MoveSearcher, SearchScratch};
+
+const MOVES: [Move; 4] = [Move::Shift(1), Move::Shift(-1), Move::Rotate(1), Move::Rotate(-1)];
+
+fn drop_to_bottom(conf: &SearchConfiguration, placement: Placement) -> Placement {
+    let fp = FallingPiece::new(conf.piece_spec, placement);
+    let mut r = placement;
+    r.pos.1 -= conf.pf.num_droppable_rows(&fp);
+    r
+}
+
+pub fn search_moves(conf: &SearchConfiguration) -> SearchResult {
+    search_moves_with_scratch(conf, &mut SearchScratch::new())
+}
+
+pub fn search_moves_with_scratch(conf: &SearchConfiguration, scratch: &mut SearchScratch) -> SearchResult {
+    let mut found = scratch.prepare();
+    let start = drop_to_bottom(conf, conf.src);
+    if start != conf.src {
+        found.insert(start, MovePathItem::new(Move::Drop(conf.src.pos.1 - start.pos.1), conf.src));
+    }
+    let queue = &mut scratch.queue;
+    queue.push_back(start);
+    while let Some(placement) = queue.pop_front() {
+        for mv in &MOVES {
+            let mut fp = FallingPiece::new(conf.piece_spec, placement);
+            if !fp.apply_move(*mv, conf.pf, conf.mode) {
+                continue;
+            }
+            let dst = drop_to_bottom(conf, fp.placement);
+            if dst == conf.src || dst == start || found.contains_key(&dst) {
+                continue;
+            }
+            found.insert(dst, MovePathItem::new(*mv, placement));
+            queue.push_back(dst);
+        }
+    }
+    SearchResult::new(conf.piece_spec.piece, conf.src, found)
+}
+
+#[derive(Copy, Clone, Debug, Default)]
+pub struct InstantGravityMoveSearcher;
+
+impl MoveSearcher for InstantGravityMoveSearcher {
+    fn search(&mut self, conf: &SearchConfiguration) -> SearchResult {
+        search_moves(conf)
+    }
+    fn search_with_scratch(&mut self, conf: &SearchConfiguration, scratch: &mut SearchScratch) -> SearchResult {
+        search_moves_with_scratch(conf, scratch)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{Game, Piece, MovePlayer, LineClear, TSpin};
+    use super::*;
+
+    #[test]
+    fn test_search_moves() {
+        let mut game: Game = Game::default();
+        game.rules.instant_gravity = true;
+        game.state.playfield.set_rows_with_strs((0, 0).into(), &[
+            "@@@  @@@@@",
+            "@@@  @@@@@",
+            "@@@  @@@@@",
+            "@@@@ @@@@@",
+        ]);
+        game.supply_next_pieces(&[Piece::O]);
+        game.setup_falling_piece(None).unwrap();
+        let fp = game.state.falling_piece.as_ref().unwrap();
+        // The piece rests on the stack right after spawned.
+        assert!(!game.state.playfield.can_drop(fp));
+        let r = game.search_moves(&mut InstantGravityMoveSearcher).unwrap();
+        for p in r.found.keys() {
+            assert!(!game.state.playfield.can_drop(&FallingPiece::new(fp.piece_spec, *p)));
+        }
+        // The O piece falls into the well when passing over it, so can't go to the left side.
+        assert!(r.contains(&Placement::new(crate::Orientation0, (2, 0).into())));
+        assert!(!r.contains(&Placement::new(crate::Orientation0, (-1, 3).into())));
+        let r2 = game.search_moves(&mut crate::move_search::bruteforce::BruteForceMoveSearcher::default()).unwrap();
+        assert!(r2.contains(&Placement::new(crate::Orientation0, (-1, 3).into())));
+
+        let dst = *r.sorted_placements().first().unwrap();
+        let path = r.get_unmerged(&dst).unwrap();
+        let mut mp = MovePlayer::new(path);
+        mp.fast_forward(&mut game).unwrap();
+        assert_eq!(dst, game.state.falling_piece.as_ref().unwrap().placement);
+    }
+
+    #[test]
+    fn test_tspin_double() {
+        let mut game: Game = Game::default();
+        game.rules.instant_gravity = true;
+        game.state.playfield.set_rows_with_strs((0, 0).into(), &[
+            "@@@       ",
+            "@@   @@@@@",
+            "@@@ @@@@@@",
+        ]);
+        game.supply_next_pieces(&[Piece::T, Piece::O]);
+        game.setup_falling_piece(None).unwrap();
+        let initial = game.clone();
+        let r = game.search_moves(&mut InstantGravityMoveSearcher).unwrap();
+        let dst = Placement::new(crate::Orientation2, (2, 0).into());
+        let path = r.get_unmerged(&dst).unwrap();
+        assert!(matches!(path.last().unwrap().by, Move::Rotate(_)));
+
+        let mut mp = MovePlayer::new(path.clone());
+        assert_eq!(path.len(), mp.fast_forward(&mut game).unwrap());
+        let fp = game.state.falling_piece.as_ref().unwrap();
+        assert_eq!(dst, fp.placement);
+        assert_eq!(path.len(), fp.move_path.len());
+        assert!(fp.is_last_move_rotation());
+
+        // Each move can be undone.
+        mp.seek(&mut game, 0).unwrap();
+        assert_eq!(initial, game);
+        mp.seek(&mut game, path.len()).unwrap();
+
+        let outcome = game.lock_ex().unwrap();
+        assert_eq!(LineClear::new(2, Some(TSpin::Standard)), outcome.line_clear);
+    }
+}
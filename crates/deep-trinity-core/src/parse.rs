@@ -0,0 +1,256 @@
+//! Parsers of playfields from text formats.
+//!
+//! - Fumen (`v115@...`, e.g. in a URL): The field of the first page. Pages of fields can be
+//!   encoded by [encode_fumen].
+//! - Compact: Rows from the top separated by `/`, where numbers are runs of empty cells
+//!   (e.g. `4T5/3TTT4`). Short rows are padded with empty cells.
+//! - ASCII art: Lines from the top, where ` `, `.` and `_` are empty cells.
+//!   Short lines are padded with empty cells.
+
+use crate::{Cell, Playfield};
+use deep_trinity_grid::Cell as _;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum BoardFormat {
+    Fumen,
+    Compact,
+    Ascii,
+}
+
+impl BoardFormat {
+    pub fn detect(input: &str) -> Self {
+        if input.contains(FUMEN_PREFIX) {
+            Self::Fumen
+        } else if input.trim().contains('\n') {
+            Self::Ascii
+        } else if input.contains('/') || input.chars().any(|c| c.is_ascii_digit()) {
+            Self::Compact
+        } else {
+            Self::Ascii
+        }
+    }
+}
+
+const FUMEN_PREFIX: &str = "v115@";
+const FUMEN_CHARS: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+const FUMEN_WIDTH: usize = 10;
+/// The field rows and the garbage row below them.
+const FUMEN_HEIGHT: usize = 24;
+const FUMEN_CELLS: [Cell; 9] = [
+    Cell::Empty, Cell::I, Cell::L, Cell::O, Cell::Z, Cell::T, Cell::J, Cell::S, Cell::Garbage,
+];
+
+/// Returns the rows of the field of the first page from the top, excluding the garbage row.
+pub fn decode_fumen_field(input: &str) -> Result<Vec<Vec<Cell>>, &'static str> {
+    let start = input.find(FUMEN_PREFIX).ok_or("not a v115 fumen")? + FUMEN_PREFIX.len();
+    let mut values = input[start..].chars()
+        .filter(|&c| c != '?')
+        .map(|c| FUMEN_CHARS.find(c).ok_or("invalid fumen char"));
+    let num_cells = FUMEN_WIDTH * FUMEN_HEIGHT;
+    let mut cells = Vec::with_capacity(num_cells);
+    while cells.len() < num_cells {
+        let (v0, v1) = match (values.next(), values.next()) {
+            (Some(v0), Some(v1)) => (v0?, v1?),
+            _ => return Err("too short fumen"),
+        };
+        let v = v0 + v1 * 64;
+        // The differences from the empty field since this is the first page.
+        let block = v / num_cells;
+        let run = v % num_cells + 1;
+        if !(8..=16).contains(&block) || cells.len() + run > num_cells {
+            return Err("invalid fumen field");
+        }
+        cells.extend(std::iter::repeat_n(FUMEN_CELLS[block - 8], run));
+    }
+    Ok(cells.chunks(FUMEN_WIDTH).take(FUMEN_HEIGHT - 1).map(|row| row.to_vec()).collect())
+}
+
+fn push_fumen_value(s: &mut String, mut v: usize, num_chars: usize) {
+    for _ in 0..num_chars {
+        s.push(FUMEN_CHARS.as_bytes()[v % 64] as char);
+        v /= 64;
+    }
+}
+
+/// Encodes the fields of the pages, each given as the rows from the top aligned to the bottom of the
+/// field, e.g. as returned by [decode_fumen_field].
+/// As in fumen, the filled rows of a page are cleared in the next page.
+pub fn encode_fumen(pages: &[Vec<Vec<Cell>>]) -> Result<String, &'static str> {
+    let num_rows = FUMEN_HEIGHT - 1;
+    let num_cells = FUMEN_WIDTH * FUMEN_HEIGHT;
+    let mut r = String::from(FUMEN_PREFIX);
+    let mut prev = vec![0; num_cells];
+    for (i, rows) in pages.iter().enumerate() {
+        if rows.len() > num_rows {
+            return Err("too many rows");
+        }
+        let mut field = vec![0; num_cells];
+        for (y, row) in rows.iter().enumerate() {
+            if row.len() > FUMEN_WIDTH {
+                return Err("too wide row");
+            }
+            let offset = (num_rows - rows.len() + y) * FUMEN_WIDTH;
+            for (x, cell) in row.iter().enumerate() {
+                // Any block is garbage.
+                field[offset + x] = FUMEN_CELLS.iter().position(|c| c == cell).unwrap_or(FUMEN_CELLS.len() - 1);
+            }
+        }
+        let mut runs: Vec<(usize, usize)> = Vec::new();
+        for (v, prev_v) in field.iter().zip(prev.iter()) {
+            let diff = v + 8 - prev_v;
+            match runs.last_mut() {
+                Some((d, n)) if *d == diff => *n += 1,
+                _ => runs.push((diff, 1)),
+            }
+        }
+        for &(diff, n) in runs.iter() {
+            push_fumen_value(&mut r, diff * num_cells + n - 1, 2);
+        }
+        if runs == [(8, num_cells)] {
+            // The following pages with no changes are not folded.
+            push_fumen_value(&mut r, 0, 1);
+        }
+        // No piece and the lock flag on, with the color flag on the first page.
+        push_fumen_value(&mut r, if i == 0 { 128 * num_cells } else { 0 }, 3);
+        // The next page starts from the field with the filled rows cleared.
+        let visible = &field[..num_rows * FUMEN_WIDTH];
+        let mut cleared = visible.chunks(FUMEN_WIDTH)
+            .filter(|row| row.contains(&0))
+            .flatten()
+            .copied()
+            .collect::<Vec<_>>();
+        let mut next = vec![0; num_rows * FUMEN_WIDTH - cleared.len()];
+        next.append(&mut cleared);
+        next.extend_from_slice(&field[num_rows * FUMEN_WIDTH..]);
+        prev = next;
+    }
+    Ok(r)
+}
+
+/// Returns the rows from the top. Rows wider than `width` are rejected.
+pub fn parse_compact(input: &str, width: usize) -> Result<Vec<Vec<Cell>>, &'static str> {
+    let mut rows = Vec::new();
+    for s in input.trim().split('/') {
+        let mut row = Vec::new();
+        let mut run = 0usize;
+        for c in s.chars() {
+            if let Some(d) = c.to_digit(10) {
+                run = run.checked_mul(10).and_then(|r| r.checked_add(d as usize)).ok_or("too wide row")?;
+                if run > width - row.len() {
+                    return Err("too wide row");
+                }
+                continue;
+            }
+            row.extend(std::iter::repeat_n(Cell::Empty, run));
+            run = 0;
+            if row.len() == width {
+                return Err("too wide row");
+            }
+            row.push(Cell::try_from_char(c)?);
+        }
+        row.extend(std::iter::repeat_n(Cell::Empty, run));
+        rows.push(row);
+    }
+    Ok(rows)
+}
+
+/// Returns the rows from the top. The empty first and last lines are ignored.
+pub fn parse_ascii(input: &str) -> Result<Vec<Vec<Cell>>, &'static str> {
+    let mut lines = input.lines().map(|l| l.trim_end_matches('\r')).collect::<Vec<_>>();
+    if lines.first() == Some(&"") {
+        lines.remove(0);
+    }
+    if lines.last() == Some(&"") {
+        lines.pop();
+    }
+    lines.iter()
+        .map(|l| l.chars().map(|c| match c {
+            '.' | '_' => Ok(Cell::Empty),
+            c => Cell::try_from_char(c),
+        }).collect())
+        .collect()
+}
+
+impl Playfield<'static> {
+    /// Parses the playfield in the format detected by [BoardFormat::detect].
+    /// The size of the playfield is the default one.
+    ///
+    /// ```
+    /// use deep_trinity_core::Playfield;
+    /// let pf = Playfield::parse("3T6/2TTT5/@@@@ @@@@@").unwrap();
+    /// assert_eq!(pf, Playfield::parse("   T\n  TTT\n@@@@.@@@@@").unwrap());
+    /// assert_eq!(vec![1, 1, 2, 3, 2, 1, 1, 1, 1, 1], pf.heights());
+    /// ```
+    pub fn parse(input: &str) -> Result<Self, &'static str> {
+        let rows = match BoardFormat::detect(input) {
+            BoardFormat::Fumen => decode_fumen_field(input)?,
+            BoardFormat::Compact => parse_compact(input, Self::default().width() as usize)?,
+            BoardFormat::Ascii => parse_ascii(input)?,
+        };
+        let mut pf = Self::default();
+        if rows.iter().any(|row| row.len() > pf.width() as usize) {
+            return Err("too wide row");
+        }
+        // Drop the empty rows above the stack so that a fumen field fits in smaller playfields.
+        let top = rows.iter().position(|row| row.iter().any(|c| !c.is_empty())).unwrap_or(rows.len());
+        let rows = rows[top..].iter().map(|row| row.iter().map(|c| c.to_char()).collect::<String>()).collect::<Vec<_>>();
+        let rows = rows.iter().map(|s| s.as_str()).collect::<Vec<_>>();
+        let report = pf.try_set_rows_with_strs((0, 0).into(), &rows)?;
+        if !report.clipped.is_empty() {
+            return Err("too many rows");
+        }
+        Ok(pf)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use deep_trinity_grid::Grid;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(BoardFormat::Fumen, BoardFormat::detect("https://harddrop.com/fumen/?v115@vhAAgH"));
+        assert_eq!(BoardFormat::Compact, BoardFormat::detect("10/@9"));
+        assert_eq!(BoardFormat::Ascii, BoardFormat::detect("@@@ @@@@@@"));
+        assert_eq!(BoardFormat::Ascii, BoardFormat::detect("\n  T\n @@@\n"));
+
+        assert!(Playfield::parse("v115@vhAAgH").unwrap().is_empty());
+        // I blocks at the left bottom.
+        let pf = Playfield::parse("v115@bhzhPeAgH").unwrap();
+        assert_eq!(vec![1, 1, 1, 1, 0, 0, 0, 0, 0, 0], pf.heights());
+        assert_eq!(Cell::I, pf.grid.cell((0, 0).into()));
+        assert_eq!(pf, Playfield::parse("IIII6").unwrap());
+        assert_eq!(pf, Playfield::parse("\nIIII\n").unwrap());
+
+        let pf = Playfield::parse("..........\n####.#####").unwrap();
+        assert_eq!(9, pf.grid.num_blocks());
+        assert_eq!(Cell::Garbage, pf.grid.cell((0, 0).into()));
+
+        assert_eq!(Err("too wide row"), Playfield::parse("@@@@@@@@@@@"));
+        for input in ["99999999999999999999T", "1000000000T", "10T", "9TT", "11", "T10/10"] {
+            assert_eq!(Err("too wide row"), Playfield::parse(input), "{}", input);
+        }
+        assert_eq!(Ok(vec![vec![Cell::Empty; 10], [vec![Cell::Empty; 9], vec![Cell::T]].concat()]), parse_compact("10/9T", 10));
+        assert_eq!(Err("too many rows"), Playfield::parse(&["@"; 41].join("/")));
+        assert!(Playfield::parse("4X5").is_err());
+        assert_eq!(Err("too short fumen"), Playfield::parse("v115@bhzh"));
+        assert_eq!(Err("invalid fumen field"), Playfield::parse("v115@//"));
+    }
+
+    #[test]
+    fn test_encode_fumen() {
+        let rows = |s: &str| parse_compact(s, FUMEN_WIDTH).unwrap();
+        assert_eq!(Ok("v115@vhAAgH".into()), encode_fumen(&[vec![]]));
+        let fumen = encode_fumen(&[vec![], rows("IIII6")]).unwrap();
+        assert_eq!("v115@vhAAgHbhzhPeAAA", fumen);
+        // The first page can be decoded.
+        let fumen = encode_fumen(&[rows("T9/#########1"), vec![]]).unwrap();
+        assert_eq!(rows("T9/#########1"), decode_fumen_field(&fumen).unwrap()[21..]);
+        // The filled row is cleared in the next page.
+        let fumen = encode_fumen(&[rows("IIIIIIIIII"), vec![]]).unwrap();
+        assert!(fumen.ends_with("vhAAAA"));
+        assert_eq!(Err("too many rows"), encode_fumen(&[vec![vec![]; 24]]));
+        assert_eq!(Err("too wide row"), encode_fumen(&[vec![vec![Cell::Garbage; FUMEN_WIDTH + 1]]]));
+    }
+}
@@ -5,17 +5,22 @@ pub use crate::{
     Placement,
     MoveTransition,
     RandomPieceGenerator,
+    PieceGenerator,
+    StaticPieceGenerator,
     FallingPiece,
     Playfield,
     Game, StdGame,
 };
 
+#[cfg(feature = "bot")]
 pub use crate::helper::{
     MoveDecisionResource,
     MoveDecisionHelper,
 };
 
+#[cfg(feature = "bot")]
 pub use crate::bot::{
     Action,
     Bot,
+    ThinkInfo,
 };
@@ -0,0 +1,131 @@
+//! Random plausible playfields to sample diverse states (e.g. for evaluator tuning) without
+//! running full games.
+
+use rand::Rng;
+use deep_trinity_grid::{Grid, X, Y};
+use crate::{Cell, Playfield};
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct RandomPlayfieldProfile {
+    /// The height of the leftmost column above the garbage rows is chosen from
+    /// `min_height..=max_height`, and the others are random walks from it within the range.
+    pub min_height: Y,
+    pub max_height: Y,
+    /// The max height difference between adjacent columns.
+    pub max_height_step: Y,
+    /// The probability of each cell under the surface to be empty.
+    pub hole_rate: f32,
+    /// The number of garbage rows at the bottom is chosen from `min_garbage_rows..=max_garbage_rows`.
+    pub min_garbage_rows: Y,
+    pub max_garbage_rows: Y,
+    /// The probability of a garbage row to have the same gap as the one below.
+    pub garbage_gap_repeat_rate: f32,
+}
+
+impl Default for RandomPlayfieldProfile {
+    fn default() -> Self {
+        Self {
+            min_height: 0,
+            max_height: 8,
+            max_height_step: 2,
+            hole_rate: 0.05,
+            min_garbage_rows: 0,
+            max_garbage_rows: 4,
+            garbage_gap_repeat_rate: 0.3,
+        }
+    }
+}
+
+impl Playfield<'static> {
+    /// Generates a playfield of the default size. The stack has no filled rows.
+    /// `Err` is returned if the profile has negative values or more garbage rows than the playfield.
+    pub fn random(rng: &mut impl Rng, profile: &RandomPlayfieldProfile) -> Result<Self, &'static str> {
+        let mut pf = Self::default();
+        if profile.min_height < 0 || profile.max_height_step < 0 || profile.min_garbage_rows < 0 {
+            return Err("negative values in the profile");
+        }
+        if profile.max_garbage_rows.max(profile.min_garbage_rows) > pf.height() {
+            return Err("too many garbage rows");
+        }
+        let width = pf.width();
+        let num_garbage_rows = rng.gen_range(profile.min_garbage_rows..=profile.max_garbage_rows.max(profile.min_garbage_rows));
+        let mut gaps: Vec<X> = Vec::with_capacity(num_garbage_rows as usize);
+        for _ in 0..num_garbage_rows {
+            let gap = match gaps.last() {
+                Some(&gap) if rng.gen::<f32>() < profile.garbage_gap_repeat_rate => gap,
+                _ => rng.gen_range(0..width),
+            };
+            gaps.push(gap);
+        }
+        pf.append_garbage(&gaps);
+
+        let (min, max) = (profile.min_height, profile.max_height.max(profile.min_height));
+        let max_height = max.min(pf.height() - num_garbage_rows);
+        let mut h = rng.gen_range(min..=max).min(max_height);
+        let mut heights = Vec::with_capacity(width as usize);
+        for x in 0..width {
+            if x > 0 {
+                let step = rng.gen_range(-profile.max_height_step..=profile.max_height_step);
+                h = (h + step).clamp(min.min(max_height), max_height);
+            }
+            for dy in 0..h {
+                // Keep the top cell to make the surface height as chosen.
+                if dy + 1 < h && rng.gen::<f32>() < profile.hole_rate {
+                    continue;
+                }
+                pf.set_cell((x, num_garbage_rows + dy).into(), Cell::Any);
+            }
+            heights.push(num_garbage_rows + h);
+        }
+        for y in num_garbage_rows..pf.stack_height() {
+            if pf.grid.num_blocks_of_row(y) < width as usize {
+                continue;
+            }
+            // Prefer the cells under the surface to keep the heights.
+            let covered = (0..width).filter(|&x| heights[x as usize] > y + 1).collect::<Vec<_>>();
+            let x = if covered.is_empty() { rng.gen_range(0..width) } else { covered[rng.gen_range(0..covered.len())] };
+            pf.set_cell((x, y).into(), Cell::Empty);
+        }
+        Ok(pf)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+
+    #[test]
+    fn test_random_playfield() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let profile = RandomPlayfieldProfile {
+            min_height: 3,
+            max_height: 6,
+            min_garbage_rows: 2,
+            max_garbage_rows: 2,
+            ..Default::default()
+        };
+        for _ in 0..20 {
+            let pf = Playfield::random(&mut rng, &profile).unwrap();
+            let heights = pf.heights();
+            assert!(heights.iter().all(|&h| (5..=8).contains(&h)), "{:?}", heights);
+            assert!(heights.windows(2).all(|w| (w[0] - w[1]).abs() <= 2));
+            assert_eq!(9, pf.grid.num_blocks_of_row(0));
+            assert_eq!(9, pf.grid.num_blocks_of_row(1));
+            assert!((0..10).any(|x| pf.grid.cell((x, 0).into()) == Cell::Garbage));
+            assert!((0..pf.height()).all(|y| pf.grid.num_blocks_of_row(y) < 10));
+        }
+
+        let mut a = StdRng::seed_from_u64(1);
+        let mut b = StdRng::seed_from_u64(1);
+        assert_eq!(Playfield::random(&mut a, &Default::default()), Playfield::random(&mut b, &Default::default()));
+        let empty = RandomPlayfieldProfile { max_height: 0, max_garbage_rows: 0, ..Default::default() };
+        assert!(Playfield::random(&mut a, &empty).unwrap().is_empty());
+
+        let invalid = RandomPlayfieldProfile { max_height_step: -1, ..Default::default() };
+        assert!(Playfield::random(&mut a, &invalid).is_err());
+        let invalid = RandomPlayfieldProfile { max_garbage_rows: Playfield::default().height() + 1, ..Default::default() };
+        assert!(Playfield::random(&mut a, &invalid).is_err());
+    }
+}
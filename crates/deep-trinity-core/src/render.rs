@@ -0,0 +1,186 @@
+//! SVG images of playfields and games to embed positions in analysis tools, docs and bug reports.
+
+use std::fmt::Write;
+use deep_trinity_grid::{Grid, Cell as _, Vec2, X, Y};
+use crate::{Cell, Game, Piece, Playfield};
+use crate::theme::{Rgba, Theme};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct SvgOptions {
+    /// The size of a cell in pixels.
+    pub cell_size: u32,
+    pub theme: Theme,
+    pub background: Rgba,
+    /// The alpha of the cells of the ghost piece.
+    pub ghost_alpha: u8,
+    /// The number of the next pieces to render at most. The visible ones are rendered anyway.
+    pub max_next_pieces: usize,
+}
+
+impl Default for SvgOptions {
+    fn default() -> Self {
+        Self {
+            cell_size: 16,
+            theme: Theme::DEFAULT,
+            background: Rgba::rgb(24, 24, 24),
+            ghost_alpha: 96,
+            max_next_pieces: 5,
+        }
+    }
+}
+
+/// The hold and the next pieces are rendered in the panels of this width (in cells).
+const PANEL_WIDTH: u32 = 5;
+/// The height of a piece in the panels (in cells).
+const PANEL_PIECE_HEIGHT: u32 = 3;
+
+/// e.g. `fill="#ffd700"`, with `fill-opacity` if translucent.
+fn fill_attrs(color: Rgba) -> String {
+    let mut r = format!("fill=\"#{:02x}{:02x}{:02x}\"", color.0, color.1, color.2);
+    if color.3 != 255 {
+        write!(r, " fill-opacity=\"{:.3}\"", color.3 as f32 / 255.0).unwrap();
+    }
+    r
+}
+
+struct Canvas {
+    svg: String,
+    cell_size: u32,
+}
+
+impl Canvas {
+    fn new(width: u32, height: u32, cell_size: u32) -> Self {
+        let (w, h) = (width * cell_size, height * cell_size);
+        let svg = format!("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" viewBox=\"0 0 {} {}\">\n", w, h, w, h);
+        Self { svg, cell_size }
+    }
+    /// `x` and `y` are in pixels from the top left.
+    fn rect(&mut self, x: u32, y: u32, w: u32, h: u32, color: Rgba) {
+        writeln!(self.svg, "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" {}/>", x, y, w, h, fill_attrs(color)).unwrap();
+    }
+    fn cell(&mut self, x: u32, y: u32, color: Rgba) {
+        self.rect(x, y, self.cell_size, self.cell_size, color);
+    }
+    /// Renders the preview of `piece` in the panel whose top left is the cell `(x, y)`.
+    fn piece_preview(&mut self, x: u32, y: u32, piece: Piece, theme: &Theme) {
+        let grid = piece.default_spec().preview_grid();
+        let cs = self.cell_size;
+        let (w, h) = (grid.width() as u32, grid.height() as u32);
+        let left = x * cs + (PANEL_WIDTH - w) * cs / 2;
+        let top = y * cs + (PANEL_PIECE_HEIGHT - h) * cs / 2;
+        for gy in 0..grid.height() {
+            for gx in 0..grid.width() {
+                let cell = grid.cell((gx, gy).into());
+                if !cell.is_empty() {
+                    self.cell(left + gx as u32 * cs, top + (h - 1 - gy as u32) * cs, theme.color(cell));
+                }
+            }
+        }
+    }
+    fn finish(mut self) -> String {
+        self.svg.push_str("</svg>\n");
+        self.svg
+    }
+}
+
+/// Renders the board of `width` x `height` cells at the cell column `left`.
+/// `get_cell` returns the cell and its alpha.
+fn board(canvas: &mut Canvas, left: u32, width: X, height: Y, opts: &SvgOptions,
+         get_cell: impl Fn(X, Y) -> Option<(Cell, u8)>) {
+    let cs = opts.cell_size;
+    canvas.rect(left * cs, 0, width as u32 * cs, height as u32 * cs, opts.background);
+    for y in 0..height {
+        for x in 0..width {
+            if let Some((cell, alpha)) = get_cell(x, y) {
+                let mut color = opts.theme.color(cell);
+                color.3 = ((color.3 as u32 * alpha as u32) / 255) as u8;
+                canvas.cell((left + x as u32) * cs, (height - 1 - y) as u32 * cs, color);
+            }
+        }
+    }
+}
+
+/// Renders the visible rows of the playfield.
+pub fn playfield_to_svg(pf: &Playfield, opts: &SvgOptions) -> String {
+    let mut canvas = Canvas::new(pf.width() as u32, pf.visible_height as u32, opts.cell_size);
+    board(&mut canvas, 0, pf.width(), pf.visible_height, opts, |x, y| {
+        let cell = pf.grid.cell((x, y).into());
+        if cell.is_empty() { None } else { Some((cell, 255)) }
+    });
+    canvas.finish()
+}
+
+/// Renders the visible rows of the playfield with the falling piece and its ghost, the hold piece
+/// on the left and the next pieces on the right.
+pub fn game_to_svg(game: &Game, opts: &SvgOptions) -> String {
+    let s = &game.state;
+    let pf = &s.playfield;
+    let num_next = s.next_pieces.len().min(s.next_pieces.visible_num.max(opts.max_next_pieces));
+    let height = (pf.visible_height as u32).max(num_next as u32 * PANEL_PIECE_HEIGHT);
+    let mut canvas = Canvas::new(PANEL_WIDTH * 2 + pf.width() as u32, height, opts.cell_size);
+    if let Some(p) = s.hold_piece {
+        let mut theme = opts.theme;
+        if !s.can_hold {
+            theme = Theme { cell_colors: [opts.theme.color(Cell::Garbage); crate::theme::NUM_CELLS] };
+        }
+        canvas.piece_preview(0, 0, p, &theme);
+    }
+    let ghost_y = s.falling_piece.as_ref().map(|fp| fp.placement.pos.1 - pf.num_droppable_rows(fp));
+    board(&mut canvas, PANEL_WIDTH, pf.width(), pf.visible_height, opts, |x, y| {
+        let cell = pf.grid.cell((x, y).into());
+        if !cell.is_empty() {
+            return Some((cell, 255));
+        }
+        let fp = s.falling_piece.as_ref()?;
+        let g = fp.grid();
+        let piece_cell = |pos: Vec2| g.is_inside(pos).then(|| g.cell(pos)).filter(|c| !c.is_empty());
+        if let Some(cell) = piece_cell(Vec2(x, y) - fp.placement.pos) {
+            return Some((cell, 255));
+        }
+        if let Some(cell) = piece_cell(Vec2(x - fp.placement.pos.0, y - ghost_y.unwrap())) {
+            return Some((cell, opts.ghost_alpha));
+        }
+        None
+    });
+    for (i, p) in s.next_pieces.iter().take(num_next).enumerate() {
+        canvas.piece_preview(PANEL_WIDTH + pf.width() as u32, i as u32 * PANEL_PIECE_HEIGHT, *p, &opts.theme);
+    }
+    canvas.finish()
+}
+
+impl<'a> Playfield<'a> {
+    pub fn to_svg(&self) -> String { playfield_to_svg(self, &SvgOptions::default()) }
+}
+
+impl<'a> Game<'a> {
+    pub fn to_svg(&self) -> String { game_to_svg(self, &SvgOptions::default()) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_svg() {
+        let mut pf = Playfield::default();
+        pf.set_rows_with_strs((0, 0).into(), &["@@@@ #####"]);
+        let svg = pf.to_svg();
+        assert!(svg.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"160\" height=\"320\""));
+        // The background and the blocks.
+        assert_eq!(1 + 9, svg.matches("<rect").count());
+        assert_eq!(5, svg.matches("fill=\"#808080\"").count());
+
+        let mut game = Game::default();
+        game.supply_next_pieces(&[Piece::T, Piece::O, Piece::I]);
+        game.setup_falling_piece(None).unwrap();
+        game.hold().unwrap();
+        game.drop(3).unwrap();
+        let svg = game.to_svg();
+        // The board, the hold (T), the falling piece and its ghost (O) and the next piece (I).
+        assert_eq!(1 + 4 + 4 * 2 + 4, svg.matches("<rect").count());
+        assert_eq!(4, svg.matches("fill-opacity=\"0.376\"").count());
+        // The hold piece is grayed out until the next lock.
+        assert_eq!(4, svg.matches("fill=\"#808080\"").count());
+        assert!(svg.ends_with("</svg>\n"));
+    }
+}
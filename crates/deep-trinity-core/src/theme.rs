@@ -0,0 +1,106 @@
+//! Colors of cells shared by frontends (web, TUI, etc.).
+
+use std::collections::BTreeMap;
+use crate::Cell;
+
+pub const NUM_CELLS: usize = 10;
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Rgba(pub u8, pub u8, pub u8, pub u8);
+
+impl Rgba {
+    pub const fn rgb(r: u8, g: u8, b: u8) -> Self { Self(r, g, b, 255) }
+    /// `0xRRGGBBAA`
+    pub fn to_u32(&self) -> u32 { u32::from_be_bytes([self.0, self.1, self.2, self.3]) }
+    pub fn from_u32(v: u32) -> Self {
+        let [r, g, b, a] = v.to_be_bytes();
+        Self(r, g, b, a)
+    }
+    /// e.g. `rgba(255, 215, 0, 1)`
+    pub fn to_css(&self) -> String {
+        let a = (self.3 as f32 / 255.0 * 1000.0).round() / 1000.0;
+        format!("rgba({}, {}, {}, {})", self.0, self.1, self.2, a)
+    }
+    /// 24-bit ANSI escape sequence to set the background color.
+    pub fn to_ansi_bg(&self) -> String { format!("\x1b[48;2;{};{};{}m", self.0, self.1, self.2) }
+}
+
+/// Colors indexed by [Cell].
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Theme {
+    pub cell_colors: [Rgba; NUM_CELLS],
+}
+
+impl Theme {
+    pub const DEFAULT: Self = Self {
+        cell_colors: [
+            Rgba(0, 0, 0, 0),
+            // darkslategray
+            Rgba::rgb(47, 79, 79),
+            // darkolivegreen
+            Rgba::rgb(85, 107, 47),
+            // sienna
+            Rgba::rgb(160, 82, 45),
+            // darkorange
+            Rgba::rgb(255, 140, 0),
+            // darkblue
+            Rgba::rgb(0, 0, 139),
+            // turquoise
+            Rgba::rgb(64, 224, 208),
+            // purple
+            Rgba::rgb(128, 0, 128),
+            // gold
+            Rgba::rgb(255, 215, 0),
+            // gray
+            Rgba::rgb(128, 128, 128),
+        ],
+    };
+    pub fn color(&self, cell: Cell) -> Rgba { self.cell_colors[cell.to_u8() as usize] }
+    pub fn set_color(&mut self, cell: Cell, color: Rgba) { self.cell_colors[cell.to_u8() as usize] = color; }
+}
+
+impl Default for Theme {
+    fn default() -> Self { Self::DEFAULT }
+}
+
+/// Themes by name. `ThemeRegistry::default()` has [Theme::DEFAULT] as "default".
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ThemeRegistry {
+    themes: BTreeMap<String, Theme>,
+}
+
+impl Default for ThemeRegistry {
+    fn default() -> Self {
+        let mut r = Self { themes: BTreeMap::new() };
+        r.register("default", Theme::DEFAULT);
+        r
+    }
+}
+
+impl ThemeRegistry {
+    /// The existing one is replaced.
+    pub fn register(&mut self, name: &str, theme: Theme) { self.themes.insert(name.to_string(), theme); }
+    pub fn get(&self, name: &str) -> Option<&Theme> { self.themes.get(name) }
+    pub fn names(&self) -> impl Iterator<Item=&str> { self.themes.keys().map(|s| s.as_str()) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_theme() {
+        let mut theme = Theme::default();
+        assert_eq!(Rgba::rgb(255, 215, 0), theme.color(Cell::O));
+        assert_eq!("rgba(255, 215, 0, 1)", theme.color(Cell::O).to_css());
+        assert_eq!("rgba(0, 0, 0, 0)", theme.color(Cell::Empty).to_css());
+        assert_eq!(0xFFD700FF, theme.color(Cell::O).to_u32());
+        assert_eq!(Rgba::rgb(1, 2, 3), Rgba::from_u32(0x010203FF));
+        theme.set_color(Cell::O, Rgba::rgb(255, 255, 0));
+        let mut registry = ThemeRegistry::default();
+        registry.register("custom", theme);
+        assert_eq!(vec!["custom", "default"], registry.names().collect::<Vec<_>>());
+        assert_eq!(Some(&Theme::DEFAULT), registry.get("default"));
+        assert_eq!(Rgba::rgb(255, 255, 0), registry.get("custom").unwrap().color(Cell::O));
+    }
+}
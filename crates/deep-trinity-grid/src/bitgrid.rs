@@ -1,9 +1,10 @@
 use std::collections::HashMap;
 use std::hash::{Hash, Hasher};
 use std::marker::PhantomData;
+use std::sync::RwLock;
 use std::fmt;
 use num_traits::PrimInt;
-use crate::{Grid, BinaryCell, Cell, Vec2, X, Y};
+use crate::{Grid, BinaryCell, Cell, Rect, Vec2, X, Y};
 
 /// This struct contains many constant values to be used by [PrimBitGrid].
 #[derive(Clone, Debug)]
@@ -156,22 +157,30 @@ impl<Int: PrimInt> PrimBitGridConstants<Int> {
 
 /// Generally, all [PrimBitGridConstants] instances are global (static) data.
 /// This struct helps us generate, store and get these constants.
+/// The constants not prepared are generated on demand, so the store can be shared (e.g. as a
+/// static) without preparing all the sizes in advance.
 pub struct PrimBitGridConstantsStore<Int: PrimInt> {
     pub stride: X,
     pub prim_max_height: Y,
-    constants_map: HashMap<Vec2, PrimBitGridConstants<Int>>,
+    /// The entries are boxed and never removed so that the references to them are valid while
+    /// the store is alive.
+    constants_map: RwLock<HashMap<Vec2, Box<PrimBitGridConstants<Int>>>>,
 }
 
 impl<Int: PrimInt> PrimBitGridConstantsStore<Int> {
     pub fn new(stride: X) -> Self {
         let prim_num_bits = Int::zero().count_zeros();
         let prim_max_height = prim_num_bits as Y / stride;
-        Self { stride, prim_max_height, constants_map: HashMap::new() }
+        Self { stride, prim_max_height, constants_map: RwLock::new(HashMap::new()) }
+    }
+    /// Returns `true` if the constants of `size` can be generated for a [PrimBitGrid].
+    pub fn is_valid_size(&self, size: Vec2) -> bool {
+        0 < size.0 && size.0 <= self.stride && 0 < size.1 && size.1 <= self.prim_max_height
     }
     pub fn prepare(&mut self, size: Vec2) {
-        if !self.constants_map.contains_key(&size) {
-            self.constants_map.insert(size, PrimBitGridConstants::new(size.0, Some(size.1), Some(self.stride)));
-        }
+        let stride = self.stride;
+        self.constants_map.get_mut().unwrap().entry(size)
+            .or_insert_with(|| Box::new(PrimBitGridConstants::new(size.0, Some(size.1), Some(stride))));
     }
     pub fn prepare_for_prim_bit_grid(&mut self, size: Vec2) {
         self.prepare(size);
@@ -187,7 +196,31 @@ impl<Int: PrimInt> PrimBitGridConstantsStore<Int> {
             }
         }
     }
-    pub fn get(&self, size: Vec2) -> Option<&PrimBitGridConstants<Int>> { self.constants_map.get(&size) }
+    /// Returns the prepared constants only.
+    pub fn get(&self, size: Vec2) -> Option<&PrimBitGridConstants<Int>> {
+        self.constants_map.read().unwrap().get(&size).map(|c| self.extend_lifetime(c))
+    }
+    /// Returns the constants of `size`, generating them if not prepared yet.
+    /// `None` if `size` is invalid (see [Self::is_valid_size]).
+    pub fn get_or_prepare(&self, size: Vec2) -> Option<&PrimBitGridConstants<Int>> {
+        if let Some(c) = self.get(size) {
+            return Some(c);
+        }
+        if !self.is_valid_size(size) {
+            return None;
+        }
+        let mut map = self.constants_map.write().unwrap();
+        let c = map.entry(size)
+            .or_insert_with(|| Box::new(PrimBitGridConstants::new(size.0, Some(size.1), Some(self.stride))));
+        Some(self.extend_lifetime(c))
+    }
+    pub fn len(&self) -> usize { self.constants_map.read().unwrap().len() }
+    pub fn is_empty(&self) -> bool { self.len() == 0 }
+    fn extend_lifetime(&self, c: &PrimBitGridConstants<Int>) -> &PrimBitGridConstants<Int> {
+        // SAFETY: The boxed constants are never moved, replaced nor dropped until the store is
+        // dropped, and they are not mutated after the insertion.
+        unsafe { &*(c as *const PrimBitGridConstants<Int>) }
+    }
 }
 
 //---
@@ -226,6 +259,29 @@ impl<'a, Int: PrimInt, C: Cell> PrimBitGrid<'a, Int, C> {
         Self { constants, cells, phantom: PhantomData }
     }
     pub fn constants(&self) -> &'a PrimBitGridConstants<Int> { self.constants }
+    /// Returns the top filled row + 1 of the column (0 if empty) by counting leading zeros.
+    pub fn col_height(&self, x: X) -> Y { self.height_of_bits(self.cells & self.constants.col_mask(x)) }
+    /// Returns the top filled row + 1 (0 if empty) by counting leading zeros.
+    pub fn stack_height(&self) -> Y { self.height_of_bits(self.cells) }
+    /// Returns the bits of `[x, x + w)` of the row `y` as the lowest `w` bits.
+    fn row_bits(&self, y: Y, x: X, w: X) -> Int {
+        self.cells.unsigned_shr(self.bit_index((x, y).into()) as u32) & self.low_mask(w)
+    }
+    fn set_row_bits(&mut self, y: Y, x: X, w: X, bits: Int) {
+        let i = self.bit_index((x, y).into()) as usize;
+        let m = self.low_mask(w) << i;
+        self.cells = (self.cells & !m) | (bits << i & m);
+    }
+    fn low_mask(&self, w: X) -> Int {
+        if w as u32 >= self.constants.num_bits { !Int::zero() } else { (Int::one() << w as usize) - Int::one() }
+    }
+    fn height_of_bits(&self, bits: Int) -> Y {
+        if bits == Int::zero() {
+            return 0;
+        }
+        let top_bit = self.constants.num_bits - 1 - bits.leading_zeros();
+        (top_bit / self.constants.stride as u32) as Y + 1
+    }
     fn bit_index(&self, pos: Vec2) -> i32 { self.constants.stride as i32 * pos.1 as i32 + pos.0 as i32 }
     fn cell_mask(&self, pos: Vec2) -> Int {
         let i = self.bit_index(pos);
@@ -240,11 +296,11 @@ impl<'a, Int: PrimInt, C: Cell> PrimBitGrid<'a, Int, C> {
             if pos.0 == 0 {
                 other_cells
             } else if pos.0 < 0 {
-                other_cells & self.constants.left_side_empty_cols_mask(-pos.0)
+                other_cells & !other.constants.left_side_cols_mask(-pos.0)
             } else {
                 let n = pos.0 + other.width() - self.width();
                 if n > 0 {
-                    other_cells & self.constants.right_side_empty_cols_mask(n)
+                    other_cells & !other.constants.right_side_cols_mask(n)
                 } else {
                     other_cells
                 }
@@ -303,7 +359,7 @@ impl<'a, Int: PrimInt, C: Cell> PrimBitGrid<'a, Int, C> {
         while self.can_put_same_stride((pos.0, pos.1 - n).into(), sub) {
             n += 1;
         }
-        n
+        (n - 1).max(0)
     }
     pub fn swap_row_with_other<OtherCell: Cell>(&mut self, y: Y, other: &mut PrimBitGrid<Int, OtherCell>, other_y: Y) {
         assert_eq!(self.constants.stride, other.constants.stride);
@@ -354,7 +410,7 @@ impl<'a, Int: PrimInt, C: Cell> PrimBitGrid<'a, Int, C> {
 
 impl<'a, Int: PrimInt, C: Cell> BitGridTrait<'a, Int, C> for PrimBitGrid<'a, Int, C> {
     fn with_store(store: &'a PrimBitGridConstantsStore<Int>, size: Vec2) -> Option<Self> {
-        store.get(size).map(|c| Self::new(c))
+        store.get_or_prepare(size).map(|c| Self::new(c))
     }
     fn put_prim_bit_grid(&mut self, pos: Vec2, other: &PrimBitGrid<Int, C>) {
         if self.constants.stride == other.constants.stride {
@@ -418,7 +474,7 @@ impl<'a, Int: PrimInt, C: Cell> Grid<C> for PrimBitGrid<'a, Int, C> {
         self.cells = if cell.is_empty() {
             self.cells & self.constants.top_side_empty_rows_mask(n)
         } else {
-            self.cells & self.constants.top_side_rows_mask(n)
+            self.cells | self.constants.top_side_rows_mask(n)
         };
     }
     fn fill_bottom(&mut self, n: Y, cell: C) {
@@ -431,8 +487,25 @@ impl<'a, Int: PrimInt, C: Cell> Grid<C> for PrimBitGrid<'a, Int, C> {
         self.cells = if cell.is_empty() {
             self.cells & self.constants.bottom_side_empty_rows_mask(n)
         } else {
-            self.cells & self.constants.bottom_side_rows_mask(n)
+            self.cells | self.constants.bottom_side_rows_mask(n)
+        };
+    }
+    /// Copies by masking and shifting the integer at once.
+    fn copy_from(&mut self, other: &Self, src: Rect, dst: Vec2) {
+        if self.constants.stride != other.constants.stride {
+            return super::copy_cells(self, other, src, dst);
+        }
+        let (src, dst) = match super::clip_copy(other.size(), self.size(), src, dst) {
+            Some(r) => r,
+            None => return,
         };
+        let mut mask = Int::zero();
+        for dy in 0..src.size.1 {
+            mask = mask | self.low_mask(src.size.0) << self.bit_index(src.pos + Vec2(0, dy)) as usize;
+        }
+        let delta = self.bit_index(dst) - self.bit_index(src.pos);
+        let shift = |v: Int| if delta >= 0 { v << delta as usize } else { v.unsigned_shr(-delta as u32) };
+        self.cells = (self.cells & !shift(mask)) | shift(other.cells & mask);
     }
     fn set_rows_with_bits<I: PrimInt>(&mut self, pos: Vec2, stride: u32, bits: I) {
         let c = self.constants;
@@ -475,7 +548,7 @@ impl<'a, Int: PrimInt, C: Cell> Grid<C> for PrimBitGrid<'a, Int, C> {
         }
         let dy = (y2 - y1) as usize;
         debug_assert!(dy > 0);
-        let dy_shift = dy * self.width() as usize;
+        let dy_shift = dy * self.constants.stride as usize;
         let m1 = self.constants.row_mask(y1);
         let m2 = self.constants.row_mask(y2);
         self.cells = (self.cells & !m1 & !m2) | (self.cells & m1) << dy_shift | (self.cells & m2) >> dy_shift;
@@ -543,6 +616,19 @@ impl<'a, Int: PrimInt, C: Cell> BasicBitGrid<'a, Int, C> {
         }
     }
     fn first_prim_grid(&self) -> &PrimBitGrid<'a, Int, C> { self.prim_grids.first().unwrap() }
+    /// Returns the top filled row + 1 of the column (0 if empty).
+    pub fn col_height(&self, x: X) -> Y { self.height_by_prim_grids(|g| g.col_height(x)) }
+    /// Returns the top filled row + 1 (0 if empty).
+    pub fn stack_height(&self) -> Y { self.height_by_prim_grids(|g| g.stack_height()) }
+    fn height_by_prim_grids(&self, f: impl Fn(&PrimBitGrid<'a, Int, C>) -> Y) -> Y {
+        for (i, g) in self.prim_grids.iter().enumerate().rev() {
+            let h = f(g);
+            if h > 0 {
+                return i as Y * self.prim_height + h;
+            }
+        }
+        0
+    }
     pub fn first_prim_grid_info(&self, y: Y) -> (usize, Y) {
         // Example #1:
         // 0     1     2     3
@@ -568,6 +654,7 @@ impl<'a, Int: PrimInt, C: Cell> BasicBitGrid<'a, Int, C> {
     }
     fn put_same_stride_prim(&mut self, pos: Vec2, other: &PrimBitGrid<Int, C>) {
         let (mut i, mut y, last_i) = self.prim_grid_info(pos.1, other.height());
+        let last_i = last_i.min(self.prim_grids.len() - 1);
         while i <= last_i {
             self.prim_grids.get_mut(i).unwrap().put_prim_bit_grid((pos.0, y).into(), other);
             i += 1;
@@ -600,7 +687,12 @@ impl<'a, Int: PrimInt, C: Cell> BasicBitGrid<'a, Int, C> {
                     g.fill_bottom(masked_height, C::empty());
                 }
             }
-            if !self.prim_grids.get(i).unwrap().can_put_prim_bit_grid((pos.0, y).into(), &g) {
+            if let Some(prim_grid) = self.prim_grids.get(i) {
+                if !prim_grid.can_put_prim_bit_grid((pos.0, y).into(), &g) {
+                    return false;
+                }
+            } else if !g.is_empty() {
+                // Some blocks are above the top.
                 return false;
             }
             i += 1;
@@ -633,19 +725,16 @@ impl<'a, Int: PrimInt, C: Cell> BitGridTrait<'a, Int, C> for BasicBitGrid<'a, In
             return None;
         }
         if size.1 <= store.prim_max_height {
-            store.get(size).map(|c| Self::new(c, 1, None))
+            store.get_or_prepare(size).map(|c| Self::new(c, 1, None))
         } else {
-            let c1 = store.get((size.0, store.prim_max_height).into());
-            if c1.is_none() {
-                return None;
-            }
+            let c1 = store.get_or_prepare((size.0, store.prim_max_height).into())?;
             let edge_height = size.1 % store.prim_max_height;
             let c2 = if edge_height > 0 {
-                store.get((size.0, edge_height).into())
+                store.get_or_prepare((size.0, edge_height).into())
             } else {
                 None
             };
-            Some(Self::new(c1.unwrap(), size.1 / store.prim_max_height, c2))
+            Some(Self::new(c1, size.1 / store.prim_max_height, c2))
         }
     }
     fn put_prim_bit_grid(&mut self, pos: Vec2, other: &PrimBitGrid<Int, C>) {
@@ -695,6 +784,22 @@ impl<'a, Int: PrimInt, C: Cell> Grid<C> for BasicBitGrid<'a, Int, C> {
             g.fill_all(cell);
         }
     }
+    /// Copies row by row with the bits of each row.
+    fn copy_from(&mut self, other: &Self, src: Rect, dst: Vec2) {
+        if self.first_prim_grid().constants.stride != other.first_prim_grid().constants.stride {
+            return super::copy_cells(self, other, src, dst);
+        }
+        let (src, dst) = match super::clip_copy(other.size(), self.size(), src, dst) {
+            Some(r) => r,
+            None => return,
+        };
+        for dy in 0..src.size.1 {
+            let (si, sy) = other.first_prim_grid_info(src.pos.1 + dy);
+            let (di, ddy) = self.first_prim_grid_info(dst.1 + dy);
+            let bits = other.prim_grids[si].row_bits(sy, src.pos.0, src.size.0);
+            self.prim_grids[di].set_row_bits(ddy, dst.0, src.size.0, bits);
+        }
+    }
     fn set_rows_with_bits<I: PrimInt>(&mut self, pos: Vec2, stride: u32, bits: I) {
         let c = self.first_prim_grid().constants;
         let (i, y) = self.first_prim_grid_info(pos.1);
@@ -772,7 +877,7 @@ impl<'a, Int: PrimInt + Hash, C: Cell> Hash for BasicBitGrid<'a, Int, C> {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::{Vec2, Cell, Grid, TestSuite};
+    use crate::{Vec2, Cell, Grid, TestSuite, BasicGrid};
 
     #[test]
     fn test_prim_bit_grid_constants_u32_10_none_none() {
@@ -980,6 +1085,32 @@ mod tests {
         assert!(!g1.can_put_same_stride((-1, -1).into(), &g2));
     }
 
+    #[test]
+    fn test_prim_bit_grid_narrower_than_stride() {
+        let store = {
+            let mut r = PrimBitGridConstantsStore::<u64>::new(10);
+            r.prepare_for_prim_bit_grid((3, 3).into());
+            r.prepare_for_prim_bit_grid((5, 5).into());
+            r
+        };
+        let mut g = PrimBitGrid::<_, BinaryCell>::with_store(&store, (3, 3).into()).unwrap();
+        g.fill_top(1, BinaryCell::any_block());
+        assert!(g.is_row_filled(2));
+        g.fill_bottom(1, BinaryCell::any_block());
+        assert!(g.is_row_filled(0));
+        g.swap_rows(1, 2);
+        assert!(g.is_row_filled(1));
+        assert!(g.is_row_empty(2));
+
+        let mut g1 = PrimBitGrid::<_, BinaryCell>::with_store(&store, (3, 3).into()).unwrap();
+        let mut g2 = PrimBitGrid::<_, BinaryCell>::with_store(&store, (5, 5).into()).unwrap();
+        g2.set_rows_with_strs((0, 0).into(), &["  @  "]);
+        assert!(g1.can_put_same_stride((-2, 0).into(), &g2));
+        assert_eq!(0, g1.num_droppable_rows_same_stride((-2, 0).into(), &g2));
+        g1.put_same_stride((-2, 0).into(), &g2);
+        assert!(g1.cell((0, 0).into()).is_filled());
+    }
+
     #[test]
     fn test_basic_bit_grid_basic() {
         let store = {
@@ -991,6 +1122,81 @@ mod tests {
         helper.basic();
     }
 
+    #[test]
+    fn test_basic_bit_grid_col_height() {
+        let store = {
+            let mut r = PrimBitGridConstantsStore::<u32>::new(10);
+            r.prepare_for_bit_grid((10, 7).into());
+            r
+        };
+        let mut g = BasicBitGrid::<_>::with_store(&store, (10, 7).into()).unwrap();
+        assert_eq!(0, g.stack_height());
+        g.set_rows_with_strs((0, 0).into(), &["   @", "@   ", "", " @"]);
+        assert_eq!(vec![3, 1, 0, 4, 0], (0..5).map(|x| g.col_height(x)).collect::<Vec<_>>());
+        assert_eq!(4, g.stack_height());
+        g.set_cell((9, 6).into(), BinaryCell::any_block());
+        assert_eq!(7, g.col_height(9));
+        assert_eq!(7, g.stack_height());
+    }
+
+    #[test]
+    fn test_bit_grid_copy_from() {
+        let store = {
+            let mut r = PrimBitGridConstantsStore::<u32>::new(10);
+            r.prepare_for_prim_bit_grid((10, 3).into());
+            r.prepare_for_bit_grid((10, 8).into());
+            r
+        };
+        let rows = ["@ @@  @ @@", "@@ @ @  @ ", " @@@ @ @@@", "@  @@ @@ @", "  @ @@@ @ ", "@@@@  @@ @", " @ @ @@ @@"];
+        let mut basic_src = BasicGrid::<BinaryCell>::new((10, 8).into());
+        basic_src.set_rows_with_strs((0, 0).into(), &rows);
+        let mut src = BasicBitGrid::<_>::with_store(&store, (10, 8).into()).unwrap();
+        src.set_rows_with_strs((0, 0).into(), &rows);
+        let mut prim_src = PrimBitGrid::<_>::with_store(&store, (10, 3).into()).unwrap();
+        prim_src.set_rows_with_strs((0, 0).into(), &rows[4..]);
+        let mut basic_prim_src = BasicGrid::<BinaryCell>::new((10, 3).into());
+        basic_prim_src.set_rows_with_strs((0, 0).into(), &rows[4..]);
+        for (src_rect, dst) in [
+            (Rect::new(Vec2(0, 0), Vec2(10, 8)), Vec2(0, 0)),
+            (Rect::new(Vec2(1, 2), Vec2(4, 5)), Vec2(5, 1)),
+            (Rect::new(Vec2(3, 1), Vec2(6, 2)), Vec2(-2, 6)),
+            (Rect::new(Vec2(-1, -1), Vec2(3, 3)), Vec2(8, 0)),
+            (Rect::new(Vec2(2, 0), Vec2(3, 3)), Vec2(20, 0)),
+        ] {
+            let mut expected = BasicGrid::<BinaryCell>::new((10, 8).into());
+            expected.fill_all(BinaryCell::any_block());
+            let mut actual = BasicBitGrid::<_>::with_store(&store, (10, 8).into()).unwrap();
+            actual.fill_all(BinaryCell::any_block());
+            expected.copy_from(&basic_src, src_rect, dst);
+            actual.copy_from(&src, src_rect, dst);
+            assert_eq!(format!("{}", expected), format!("{}", actual), "{:?} {:?}", src_rect, dst);
+
+            let mut expected = BasicGrid::<BinaryCell>::new((10, 3).into());
+            let mut actual = PrimBitGrid::<_>::with_store(&store, (10, 3).into()).unwrap();
+            expected.copy_from(&basic_prim_src, src_rect, dst);
+            actual.copy_from(&prim_src, src_rect, dst);
+            assert_eq!(format!("{}", expected), format!("{}", actual), "{:?} {:?}", src_rect, dst);
+        }
+        assert_eq!(basic_src.rows().nth(1).unwrap().len(), 10);
+        assert_eq!(8, src.rows().count());
+    }
+
+    #[test]
+    fn test_basic_bit_grid_can_put_above_top() {
+        let store = {
+            let mut r = PrimBitGridConstantsStore::<u32>::new(10);
+            r.prepare_for_prim_bit_grid((10, 3).into());
+            r.prepare_for_bit_grid((10, 4).into());
+            r
+        };
+        let g1 = BasicBitGrid::<_>::with_store(&store, (10, 4).into()).unwrap();
+        let mut g2 = PrimBitGrid::<_>::with_store(&store, (10, 3).into()).unwrap();
+        g2.set_rows_with_strs((0, 0).into(), &["@"]);
+        assert!(g1.can_put_same_stride_prim((0, 3).into(), &g2));
+        g2.set_rows_with_strs((0, 0).into(), &["@", " "]);
+        assert!(!g1.can_put_same_stride_prim((0, 3).into(), &g2));
+    }
+
     #[test]
     fn test_basic_bit_grid_put_same_stride_prim() {
         let store = {
@@ -1062,6 +1268,19 @@ mod tests {
             assert!(c.get(Vec2(10, 6)).is_some());
             assert!(c.get(Vec2(10, 8)).is_none());
         }
+        {
+            let c = PrimBitGridConstantsStore::<u64>::new(10);
+            assert!(c.is_empty());
+            let g = BasicBitGrid::<u64, BinaryCell>::with_store(&c, (8, 15).into()).unwrap();
+            assert_eq!(Vec2(8, 15), g.size());
+            assert_eq!(2, c.len());
+            let p = PrimBitGrid::<u64, BinaryCell>::with_store(&c, (8, 6).into()).unwrap();
+            assert!(std::ptr::eq(p.constants(), c.get(Vec2(8, 6)).unwrap()));
+            assert_eq!(2, c.len());
+            assert!(c.get_or_prepare(Vec2(11, 3)).is_none());
+            assert!(c.get_or_prepare(Vec2(10, 7)).is_none());
+            assert!(BasicBitGrid::<u64, BinaryCell>::with_store(&c, (0, 10).into()).is_none());
+        }
     }
 
     #[test]
@@ -37,6 +37,42 @@ impl fmt::Display for Vec2 {
     }
 }
 
+/// An axis-aligned rectangle of cells.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Hash)]
+pub struct Rect {
+    /// The bottom-left position.
+    pub pos: Vec2,
+    pub size: Vec2,
+}
+
+impl Rect {
+    pub fn new(pos: Vec2, size: Vec2) -> Self { Self { pos, size } }
+    pub fn is_empty(&self) -> bool { self.size.0 <= 0 || self.size.1 <= 0 }
+    pub fn contains(&self, pos: Vec2) -> bool {
+        self.pos.0 <= pos.0 && pos.0 < self.pos.0 + self.size.0 && self.pos.1 <= pos.1 && pos.1 < self.pos.1 + self.size.1
+    }
+    /// Returns the intersection, which may be empty.
+    pub fn intersect(&self, other: &Rect) -> Rect {
+        let x0 = self.pos.0.max(other.pos.0);
+        let y0 = self.pos.1.max(other.pos.1);
+        let x1 = (self.pos.0 + self.size.0).min(other.pos.0 + other.size.0);
+        let y1 = (self.pos.1 + self.size.1).min(other.pos.1 + other.size.1);
+        Rect::new(Vec2(x0, y0), Vec2((x1 - x0).max(0), (y1 - y0).max(0)))
+    }
+}
+
+/// Clips the copy of `src` in a grid of `src_size` to `dst` in a grid of `dst_size`.
+/// Returns the clipped source rectangle and destination, or `None` if nothing to copy.
+pub(crate) fn clip_copy(src_size: Vec2, dst_size: Vec2, src: Rect, dst: Vec2) -> Option<(Rect, Vec2)> {
+    let src_clipped = src.intersect(&Rect::new(Vec2(0, 0), src_size));
+    let offset = dst - src.pos;
+    let dst_rect = Rect::new(src_clipped.pos + offset, src_clipped.size).intersect(&Rect::new(Vec2(0, 0), dst_size));
+    if dst_rect.is_empty() {
+        return None;
+    }
+    Some((Rect::new(dst_rect.pos - offset, dst_rect.size), dst_rect.pos))
+}
+
 pub trait Cell: Copy + Clone {
     fn empty() -> Self;
     fn any_block() -> Self;
@@ -44,6 +80,20 @@ pub trait Cell: Copy + Clone {
     fn is_filled(&self) -> bool { !self.is_empty() }
     fn to_char(&self) -> char;
     fn try_from_char(c: char) -> Result<Self, &'static str>;
+    /// Returns `false` for the chars not expected in the board definitions even if
+    /// [Self::try_from_char] accepts them.
+    fn is_valid_char(c: char) -> bool { Self::try_from_char(c).is_ok() }
+}
+
+/// The result of [Grid::try_set_rows_with_strs].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SetRowsReport {
+    /// The positions of the non-empty cells not set because they are out of the grid.
+    pub clipped: Vec<Vec2>,
+}
+
+impl SetRowsReport {
+    pub fn is_clipped(&self) -> bool { !self.clipped.is_empty() }
 }
 
 pub trait Grid<C: Cell>: Clone {
@@ -166,6 +216,43 @@ pub trait Grid<C: Cell>: Clone {
             }
         }
     }
+    /// Same as [Self::set_rows_with_strs] but validates the chars and reports the clipped cells.
+    /// Nothing is set if an invalid char (see [Cell::is_valid_char]) is found.
+    ///
+    /// Example:
+    /// ```
+    /// use deep_trinity_grid::{Grid, Cell, BasicGrid, BinaryCell, Vec2};
+    ///
+    /// let mut grid = BasicGrid::<BinaryCell>::new((3, 3).into());
+    /// let report = grid.try_set_rows_with_strs((1, 0).into(), &["@ @"]).unwrap();
+    /// assert_eq!(vec![Vec2(3, 0)], report.clipped);
+    /// assert!(grid.try_set_rows_with_strs((0, 0).into(), &["@x"]).is_err());
+    /// assert!(grid.try_set_rows_with_strs((0, 0).into(), &[&"@".repeat(200)]).is_err());
+    /// ```
+    fn try_set_rows_with_strs(&mut self, pos: Vec2, rows: &[&str]) -> Result<SetRowsReport, &'static str> {
+        if rows.iter().any(|row| row.chars().any(|c| !C::is_valid_char(c))) {
+            return Err("invalid cell char");
+        }
+        // Checked in advance so that the grid is not changed on errors.
+        let overflows = |start: X, len: usize| X::try_from(len).ok().and_then(|len| start.checked_add(len)).is_none();
+        if overflows(pos.1, rows.len()) || rows.iter().any(|row| overflows(pos.0, row.chars().count())) {
+            return Err("rows out of the coordinate range");
+        }
+        let mut report = SetRowsReport::default();
+        for (dy, row) in rows.iter().rev().enumerate() {
+            let y = pos.1 + dy as Y;
+            for (dx, c) in row.chars().enumerate() {
+                let x = pos.0 + dx as X;
+                let cell = C::try_from_char(c)?;
+                if self.is_inside((x, y).into()) {
+                    self.set_cell((x, y).into(), cell);
+                } else if !cell.is_empty() {
+                    report.clipped.push((x, y).into());
+                }
+            }
+        }
+        Ok(report)
+    }
     /// Example:
     /// ```
     /// use deep_trinity_grid::{Grid, Cell, BasicGrid, BinaryCell};
@@ -180,6 +267,26 @@ pub trait Grid<C: Cell>: Clone {
     fn set_rows_with_bits<I: PrimInt>(&mut self, pos: Vec2, stride: u32, bits: I) {
         set_rows_with_bits(self, pos, stride, bits);
     }
+    /// Copies the cells (including empty ones) of `src` in `other` to `dst` of this grid.
+    /// The cells out of either grid are ignored.
+    ///
+    /// Example:
+    /// ```
+    /// use deep_trinity_grid::{Grid, Cell, BasicGrid, BinaryCell, Rect, Vec2};
+    ///
+    /// let mut src = BasicGrid::<BinaryCell>::new((3, 3).into());
+    /// src.set_rows_with_strs((0, 0).into(), &["@ ", " @"]);
+    /// let mut dst = BasicGrid::<BinaryCell>::new((3, 3).into());
+    /// dst.copy_from(&src, Rect::new(Vec2(0, 0), Vec2(2, 2)), Vec2(1, 1));
+    /// assert!(dst.cell((2, 1).into()).is_filled());
+    /// assert!(dst.cell((1, 2).into()).is_filled());
+    /// assert_eq!(2, dst.num_blocks());
+    /// ```
+    fn copy_from(&mut self, other: &Self, src: Rect, dst: Vec2) {
+        copy_cells(self, other, src, dst);
+    }
+    /// Iterates the rows from the bottom.
+    fn rows(&self) -> Rows<'_, C, Self> where Self: Sized { Rows { grid: self, y: 0, phantom: PhantomData } }
     fn reachable_pos<G: Grid<C>>(&self, mut pos: Vec2, sub: &G, direction: Vec2) -> Vec2 {
         loop {
             let p = pos + direction;
@@ -485,10 +592,40 @@ pub trait Grid<C: Cell>: Clone {
         xs
     }
     fn density(&self) -> f32 {
-        self.num_blocks() as f32 / (self.width() * self.height()) as f32
+        self.num_blocks() as f32 / (self.width() as f32 * self.height() as f32)
     }
     fn density_without_top_padding(&self) -> f32 {
-        self.num_blocks() as f32 / (self.width() * (self.height() - self.top_padding())) as f32
+        self.num_blocks() as f32 / (self.width() as f32 * (self.height() - self.top_padding()) as f32)
+    }
+    /// [Self::top_padding] of the bottom `visible_height` rows, ignoring the hidden rows above.
+    fn visible_top_padding(&self, visible_height: Y) -> Y {
+        let h = visible_height.min(self.height());
+        (0..h).rev().take_while(|y| self.is_row_empty(*y)).count() as Y
+    }
+    /// [Self::density] of the bottom `visible_height` rows, ignoring the hidden rows above.
+    /// 0 if no rows are visible.
+    fn visible_density(&self, visible_height: Y) -> f32 {
+        let h = visible_height.min(self.height());
+        if h <= 0 {
+            return 0.0;
+        }
+        let n = (0..h).map(|y| self.num_blocks_of_row(y)).sum::<usize>();
+        n as f32 / (self.width() as f32 * h as f32)
+    }
+    /// [Self::contour] of the bottom `visible_height` rows, ignoring the hidden rows above.
+    fn visible_contour(&self, visible_height: Y) -> Vec<Y> {
+        let mut xs = vec![0; self.width() as usize];
+        for y in 0..visible_height.min(self.height()) {
+            if self.is_row_empty(y) {
+                continue;
+            }
+            for x in 0..self.width() {
+                if !self.cell((x, y).into()).is_empty() {
+                    xs[x as usize] = y;
+                }
+            }
+        }
+        xs
     }
     fn format<Writer: fmt::Write>(&self, w: &mut Writer) -> fmt::Result {
         for y in (0..self.height()).rev() {
@@ -526,6 +663,35 @@ fn set_rows_with_bits<C: Cell, G: Grid<C>, I: PrimInt>(grid: &mut G, pos: Vec2,
     }
 }
 
+fn copy_cells<C: Cell, G: Grid<C>>(grid: &mut G, other: &G, src: Rect, dst: Vec2) {
+    if let Some((src, dst)) = clip_copy(other.size(), grid.size(), src, dst) {
+        for dy in 0..src.size.1 {
+            for dx in 0..src.size.0 {
+                grid.set_cell(dst + Vec2(dx, dy), other.cell(src.pos + Vec2(dx, dy)));
+            }
+        }
+    }
+}
+
+/// The iterator returned by [Grid::rows].
+pub struct Rows<'a, C: Cell, G: Grid<C>> {
+    grid: &'a G,
+    y: Y,
+    phantom: PhantomData<fn() -> C>,
+}
+
+impl<'a, C: Cell, G: Grid<C>> Iterator for Rows<'a, C, G> {
+    type Item = Vec<C>;
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.y >= self.grid.height() {
+            return None;
+        }
+        let y = self.y;
+        self.y += 1;
+        Some((0..self.grid.width()).map(|x| self.grid.cell((x, y).into())).collect())
+    }
+}
+
 //--------------------------------------------------------------------------------------------------
 // BinaryCell
 //--------------------------------------------------------------------------------------------------
@@ -546,6 +712,9 @@ impl Cell for BinaryCell {
             _ => Self::any_block(),
         })
     }
+    /// `' '` and `'_'` for empty cells, and `'@'`, `'#'` and uppercase letters (e.g. pieces) for
+    /// blocks.
+    fn is_valid_char(c: char) -> bool { matches!(c, ' ' | '_' | '@' | '#' | 'A'..='Z') }
 }
 
 //--------------------------------------------------------------------------------------------------
@@ -709,4 +878,24 @@ mod tests {
         suite.search_space();
         suite.search_spaces();
     }
+
+    #[test]
+    fn test_visible_metrics() {
+        let mut g = BasicGrid::<BinaryCell>::new((5, 6).into());
+        g.set_rows_with_strs((0, 0).into(), &[
+            "    @",
+            "     ",
+            "     ",
+            "  @  ",
+            "@@ @@",
+        ]);
+        assert_eq!(1, g.top_padding());
+        assert_eq!(1, g.visible_top_padding(3));
+        assert_eq!(0, g.visible_top_padding(2));
+        assert_eq!(vec![0, 0, 1, 0, 4], g.contour());
+        assert_eq!(vec![0, 0, 1, 0, 0], g.visible_contour(3));
+        assert_eq!(5.0 / 15.0, g.visible_density(3));
+        assert_eq!(0.0, g.visible_density(0));
+        assert_eq!(6.0 / 30.0, g.density());
+    }
 }
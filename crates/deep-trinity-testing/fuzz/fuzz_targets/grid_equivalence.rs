@@ -0,0 +1,17 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use deep_trinity_grid::Vec2;
+use deep_trinity_testing::ByteReader;
+use deep_trinity_testing::grid::{GridOp, check_grid_ops};
+
+fuzz_target!(|data: &[u8]| {
+    if data.len() < 2 {
+        return;
+    }
+    let mut r = ByteReader::new(&data[..2]);
+    let size = Vec2(r.i8_in(1, 10), r.i8_in(1, 40));
+    let ops = GridOp::decode_all(size, &data[2..]);
+    if let Err(e) = check_grid_ops(size, &ops) {
+        panic!("{}", e);
+    }
+});
@@ -0,0 +1,20 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use deep_trinity_testing::{ByteReader, playfield_from_rows};
+use deep_trinity_testing::srs::check_srs_reversibility;
+
+fuzz_target!(|data: &[u8]| {
+    if data.is_empty() {
+        return;
+    }
+    let mut r = ByteReader::new(data);
+    let piece = r.piece();
+    let mut rows = Vec::new();
+    while !r.is_end() && rows.len() < 20 {
+        rows.push(r.u16() & 0x3ff);
+    }
+    let pf = playfield_from_rows(&rows);
+    if let Err(e) = check_srs_reversibility(&pf, piece) {
+        panic!("{}", e);
+    }
+});
@@ -0,0 +1,345 @@
+//! Golden tests of bot behaviours.
+//!
+//! The actions chosen by a bot for fixed cases (a random seed and an initial board) are recorded
+//! into a snapshot file, and compared with the recorded ones on subsequent runs. Set the
+//! `UPDATE_GOLDEN` environment variable to (re)write the snapshot files.
+//!
+//! Snapshot format:
+//!
+//! ```text
+//! # case <seed> <row>,<row>,...
+//! T 0 (3, 0)
+//! hold
+//! ...
+//! ```
+
+use std::error::Error;
+use std::fmt;
+use std::path::Path;
+use rand::SeedableRng;
+use rand::rngs::StdRng;
+use deep_trinity_core::{Game, FallingPiece, RandomPieceGenerator};
+use deep_trinity_core::bot::{Action, Bot};
+use crate::{playfield_from_rows, CheckResult};
+
+pub const UPDATE_ENV_NAME: &str = "UPDATE_GOLDEN";
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GoldenCase {
+    pub seed: u64,
+    /// The initial board (see [crate::playfield_from_rows]).
+    pub rows: Vec<u16>,
+}
+
+impl GoldenCase {
+    pub fn new(seed: u64, rows: &[u16]) -> Self { Self { seed, rows: rows.to_vec() } }
+    /// The case starting from the named position of [crate::testdata].
+    pub fn with_position(seed: u64, name: &str) -> Result<Self, String> {
+        let p = crate::testdata::get(name).ok_or_else(|| format!("unknown test position: {}", name))?;
+        Ok(Self { seed, rows: p.rows() })
+    }
+}
+
+impl fmt::Display for GoldenCase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.seed)?;
+        if !self.rows.is_empty() {
+            let rows = self.rows.iter().map(|r| r.to_string()).collect::<Vec<_>>().join(",");
+            write!(f, " {}", rows)?;
+        }
+        Ok(())
+    }
+}
+
+fn parse_case(s: &str) -> Result<GoldenCase, Box<dyn Error>> {
+    let mut it = s.split_whitespace();
+    let seed = it.next().ok_or("no seed")?.parse()?;
+    let rows = match it.next() {
+        Some(rows) => rows.split(',').map(|r| r.parse()).collect::<Result<Vec<u16>, _>>()?,
+        None => vec![],
+    };
+    Ok(GoldenCase { seed, rows })
+}
+
+fn format_action(game: &Game, action: &Action) -> String {
+    match action {
+        Action::Move(mt) => {
+            let piece = game.state.falling_piece.as_ref().map(|fp| fp.piece()).unwrap();
+            let mut s = format!("{} {} {}", piece, mt.placement.orientation, mt.placement.pos);
+            if let Some(hint) = mt.hint.as_ref() {
+                s += &format!(" by {:?} from {} {}", hint.by, hint.placement.orientation, hint.placement.pos);
+            }
+            s
+        }
+        Action::Hold => "hold".into(),
+    }
+}
+
+/// Runs `bot` from the case and returns the chosen actions in the snapshot format.
+/// The run is terminated on game over or after `max_actions` actions.
+pub fn record_actions(bot: &mut impl Bot, case: &GoldenCase, max_actions: usize) -> Result<Vec<String>, Box<dyn Error>> {
+    let mut game: Game = Default::default();
+    game.state.playfield = playfield_from_rows(&case.rows);
+    let mut rpg = RandomPieceGenerator::new(StdRng::seed_from_u64(case.seed));
+    game.supply_next_pieces(&rpg.generate());
+    game.setup_falling_piece(None)?;
+
+    let mut actions = Vec::with_capacity(max_actions);
+    while actions.len() < max_actions {
+        if game.should_supply_next_pieces() {
+            game.supply_next_pieces(&rpg.generate());
+        }
+        let action = bot.think(&game)?;
+        actions.push(format_action(&game, &action));
+        match action {
+            Action::Move(mt) => {
+                let fp = FallingPiece::new_with_last_move_transition(
+                    game.state.falling_piece.unwrap().piece_spec,
+                    &mt,
+                );
+                game.state.falling_piece = Some(fp);
+                game.lock()?;
+                if game.state.is_game_over() {
+                    break;
+                }
+            }
+            Action::Hold => {
+                game.hold()?;
+            }
+        }
+    }
+    Ok(actions)
+}
+
+//---
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Snapshot {
+    pub entries: Vec<(GoldenCase, Vec<String>)>,
+}
+
+impl Snapshot {
+    pub fn record(bot: &mut impl Bot, cases: &[GoldenCase], max_actions: usize) -> Result<Self, Box<dyn Error>> {
+        let mut entries = Vec::with_capacity(cases.len());
+        for case in cases {
+            entries.push((case.clone(), record_actions(bot, case, max_actions)?));
+        }
+        Ok(Self { entries })
+    }
+    pub fn parse(s: &str) -> Result<Self, Box<dyn Error>> {
+        let mut entries: Vec<(GoldenCase, Vec<String>)> = Vec::new();
+        for line in s.lines() {
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            if let Some(case) = line.strip_prefix("# case ") {
+                entries.push((parse_case(case)?, Vec::new()));
+                continue;
+            }
+            entries.last_mut().ok_or("action without case")?.1.push(line.to_string());
+        }
+        Ok(Self { entries })
+    }
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, Box<dyn Error>> {
+        Self::parse(&std::fs::read_to_string(path)?)
+    }
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), Box<dyn Error>> {
+        if let Some(dir) = path.as_ref().parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(path, self.to_string())?;
+        Ok(())
+    }
+}
+
+impl fmt::Display for Snapshot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (case, actions) in self.entries.iter() {
+            writeln!(f, "# case {}", case)?;
+            for action in actions.iter() {
+                writeln!(f, "{}", action)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+//---
+
+/// How many changed decisions are acceptable.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum Tolerance {
+    #[default]
+    Exact,
+    /// The number of changed decisions of the all cases.
+    MaxChanges(usize),
+    /// The ratio of changed decisions to the all decisions.
+    MaxChangeRatio(f64),
+}
+
+/// A decision which differs from the snapshot.
+/// `None` means the action doesn't exist (e.g. the game was over earlier).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DecisionChange {
+    pub case: GoldenCase,
+    pub index: usize,
+    pub expected: Option<String>,
+    pub actual: Option<String>,
+}
+
+impl fmt::Display for DecisionChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = |a: &Option<String>| a.clone().unwrap_or_else(|| "-".into());
+        write!(f, "case {} #{}: {} => {}", self.case, self.index, s(&self.expected), s(&self.actual))
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct GoldenReport {
+    pub num_decisions: usize,
+    pub changes: Vec<DecisionChange>,
+    /// Cases which exist only in either snapshot.
+    pub mismatched_cases: Vec<GoldenCase>,
+}
+
+impl GoldenReport {
+    pub fn compare(expected: &Snapshot, actual: &Snapshot) -> Self {
+        let mut r = Self::default();
+        for (case, expected_actions) in expected.entries.iter() {
+            let actual_actions = match actual.entries.iter().find(|(c, _)| c == case) {
+                Some((_, actions)) => actions,
+                None => {
+                    r.mismatched_cases.push(case.clone());
+                    continue;
+                }
+            };
+            let n = std::cmp::max(expected_actions.len(), actual_actions.len());
+            r.num_decisions += n;
+            for i in 0..n {
+                let (e, a) = (expected_actions.get(i), actual_actions.get(i));
+                if e != a {
+                    r.changes.push(DecisionChange { case: case.clone(), index: i, expected: e.cloned(), actual: a.cloned() });
+                }
+            }
+        }
+        for (case, _) in actual.entries.iter() {
+            if !expected.entries.iter().any(|(c, _)| c == case) {
+                r.mismatched_cases.push(case.clone());
+            }
+        }
+        r
+    }
+    pub fn is_acceptable(&self, tolerance: Tolerance) -> bool {
+        if !self.mismatched_cases.is_empty() {
+            return false;
+        }
+        match tolerance {
+            Tolerance::Exact => self.changes.is_empty(),
+            Tolerance::MaxChanges(n) => self.changes.len() <= n,
+            Tolerance::MaxChangeRatio(ratio) => {
+                self.num_decisions == 0 || self.changes.len() as f64 / self.num_decisions as f64 <= ratio
+            }
+        }
+    }
+}
+
+impl fmt::Display for GoldenReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{} of {} decisions changed", self.changes.len(), self.num_decisions)?;
+        for case in self.mismatched_cases.iter() {
+            writeln!(f, "case {}: not in both snapshots", case)?;
+        }
+        for change in self.changes.iter() {
+            writeln!(f, "{}", change)?;
+        }
+        Ok(())
+    }
+}
+
+/// Records the actions of `bot` and compares them with the snapshot at `path`.
+/// The snapshot is written instead if [UPDATE_ENV_NAME] is set. A missing snapshot is an error
+/// otherwise, so that a mistyped path doesn't pass silently.
+pub fn check_golden(
+    path: impl AsRef<Path>, bot: &mut impl Bot, cases: &[GoldenCase], max_actions: usize, tolerance: Tolerance,
+) -> CheckResult {
+    let path = path.as_ref();
+    let actual = Snapshot::record(bot, cases, max_actions).map_err(|e| e.to_string())?;
+    if std::env::var_os(UPDATE_ENV_NAME).is_some() {
+        return actual.save(path).map_err(|e| e.to_string());
+    }
+    if !path.exists() {
+        return Err(format!("{}: snapshot not found (set {} to write it)", path.display(), UPDATE_ENV_NAME));
+    }
+    let expected = Snapshot::load(path).map_err(|e| e.to_string())?;
+    let report = GoldenReport::compare(&expected, &actual);
+    if report.is_acceptable(tolerance) {
+        Ok(())
+    } else {
+        Err(format!("{}: {}", path.display(), report))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use deep_trinity_core::bot::SimpleBot;
+
+    fn cases() -> Vec<GoldenCase> {
+        vec![
+            GoldenCase::new(0, &[]),
+            GoldenCase::new(1, &[0b1111111110, 0b1111111100]),
+        ]
+    }
+
+    #[test]
+    fn test_snapshot() {
+        let mut bot = SimpleBot::default();
+        let snapshot = Snapshot::record(&mut bot, &cases(), 10).unwrap();
+        assert_eq!(snapshot, Snapshot::parse(&snapshot.to_string()).unwrap());
+        let report = GoldenReport::compare(&snapshot, &snapshot);
+        assert_eq!(20, report.num_decisions);
+        assert!(report.is_acceptable(Tolerance::Exact));
+
+        let mut changed = snapshot.clone();
+        changed.entries[1].1[3] = "hold".into();
+        changed.entries[1].1.pop();
+        let report = GoldenReport::compare(&snapshot, &changed);
+        assert_eq!(2, report.changes.len());
+        assert_eq!(Some("hold".to_string()), report.changes[0].actual);
+        assert_eq!(None, report.changes[1].actual);
+        assert!(!report.is_acceptable(Tolerance::Exact));
+        assert!(report.is_acceptable(Tolerance::MaxChanges(2)));
+        assert!(report.is_acceptable(Tolerance::MaxChangeRatio(0.1)));
+        assert!(!report.is_acceptable(Tolerance::MaxChangeRatio(0.05)));
+    }
+
+    #[test]
+    fn test_simple_bot_golden() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("testdata/golden/simple_bot.txt");
+        let r = check_golden(path, &mut SimpleBot::default(), &cases(), 20, Tolerance::Exact);
+        assert!(r.is_ok(), "{}", r.unwrap_err());
+    }
+
+    #[test]
+    fn test_missing_golden() {
+        if std::env::var_os(UPDATE_ENV_NAME).is_some() {
+            return;
+        }
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("testdata/golden/missing.txt");
+        let r = check_golden(&path, &mut SimpleBot::default(), &cases(), 1, Tolerance::Exact);
+        assert!(r.unwrap_err().contains("snapshot not found"));
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_simple_bot_golden_positions() {
+        let cases = ["tsd", "cheese", "near_topout"].iter()
+            .map(|name| GoldenCase::with_position(0, name).unwrap())
+            .collect::<Vec<_>>();
+        assert!(GoldenCase::with_position(0, "unknown").is_err());
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("testdata/golden/simple_bot_positions.txt");
+        let r = check_golden(path, &mut SimpleBot::default(), &cases, 10, Tolerance::Exact);
+        assert!(r.is_ok(), "{}", r.unwrap_err());
+    }
+}
@@ -0,0 +1,207 @@
+//! Equivalence checks of bit grids against [BasicGrid].
+
+use deep_trinity_core::{Cell, Piece, Orientation, ORIENTATIONS};
+use deep_trinity_grid::{BasicGrid, Grid, Vec2, X, Y, Cell as _};
+use deep_trinity_grid::bitgrid::{BasicBitGrid, BitGridTrait, PrimBitGrid, PrimBitGridConstantsStore};
+use crate::{ByteReader, CheckResult};
+
+type BitGridInt = u64;
+
+/// Pieces are put by the stride of the default playfield.
+const STRIDE: X = 10;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum GridOp {
+    SetCell(Vec2, bool),
+    FillRow(Y, bool),
+    FillTop(Y),
+    FillBottom(Y),
+    SwapRows(Y, Y),
+    InsertRows(Y, Y),
+    DropFilledRows,
+    PutPiece(Piece, Orientation, Vec2),
+}
+
+impl GridOp {
+    /// Decodes operations for a grid of `size` from raw bytes.
+    pub fn decode_all(size: Vec2, data: &[u8]) -> Vec<GridOp> {
+        let mut r = ByteReader::new(data);
+        let (w, h) = (size.0, size.1);
+        let mut ops = Vec::new();
+        while !r.is_end() {
+            let op = match r.u8() % 8 {
+                0 => GridOp::SetCell(Vec2(r.i8_in(0, w - 1), r.i8_in(0, h - 1)), r.bool()),
+                1 => GridOp::FillRow(r.i8_in(0, h - 1), r.bool()),
+                2 => GridOp::FillTop(r.i8_in(0, h)),
+                3 => GridOp::FillBottom(r.i8_in(0, h)),
+                4 => GridOp::SwapRows(r.i8_in(0, h - 1), r.i8_in(0, h - 1)),
+                5 => {
+                    let y = r.i8_in(0, h - 1);
+                    GridOp::InsertRows(y, r.i8_in(0, h - y))
+                }
+                6 => GridOp::DropFilledRows,
+                _ => GridOp::PutPiece(
+                    r.piece(),
+                    ORIENTATIONS[r.u8() as usize % ORIENTATIONS.len()],
+                    Vec2(r.i8_in(-2, w), r.i8_in(-2, h)),
+                ),
+            };
+            ops.push(op);
+        }
+        ops
+    }
+}
+
+fn to_cell(filled: bool) -> Cell { if filled { Cell::Any } else { Cell::Empty } }
+
+fn compare<G1: Grid<Cell>, G2: Grid<Cell>>(expected: &G1, actual: &G2) -> CheckResult {
+    if expected.size() != actual.size() {
+        return Err(format!("size: {} != {}", expected.size(), actual.size()));
+    }
+    for y in 0..expected.height() {
+        for x in 0..expected.width() {
+            let pos = Vec2(x, y);
+            if expected.cell(pos).is_empty() != actual.cell(pos).is_empty() {
+                return Err(format!("cell {}:\n{}\n!=\n{}", pos, expected.to_string(), actual.to_string()));
+            }
+        }
+        if expected.is_row_filled(y) != actual.is_row_filled(y) {
+            return Err(format!("is_row_filled({})", y));
+        }
+        if expected.is_row_empty(y) != actual.is_row_empty(y) {
+            return Err(format!("is_row_empty({})", y));
+        }
+        if expected.num_blocks_of_row(y) != actual.num_blocks_of_row(y) {
+            return Err(format!("num_blocks_of_row({})", y));
+        }
+    }
+    for x in 0..expected.width() {
+        if expected.is_col_filled(x) != actual.is_col_filled(x) {
+            return Err(format!("is_col_filled({})", x));
+        }
+        if expected.is_col_empty(x) != actual.is_col_empty(x) {
+            return Err(format!("is_col_empty({})", x));
+        }
+    }
+    if expected.num_blocks() != actual.num_blocks() {
+        return Err("num_blocks".into());
+    }
+    if expected.is_empty() != actual.is_empty() {
+        return Err("is_empty".into());
+    }
+    Ok(())
+}
+
+fn apply<'a, G: BitGridTrait<'a, BitGridInt, Cell>>(expected: &mut BasicGrid<Cell>, actual: &mut G, op: &GridOp) -> CheckResult {
+    match *op {
+        GridOp::SetCell(pos, filled) => {
+            expected.set_cell(pos, to_cell(filled));
+            actual.set_cell(pos, to_cell(filled));
+        }
+        GridOp::FillRow(y, filled) => {
+            expected.fill_row(y, to_cell(filled));
+            actual.fill_row(y, to_cell(filled));
+        }
+        GridOp::FillTop(n) => {
+            expected.fill_top(n, Cell::Any);
+            actual.fill_top(n, Cell::Any);
+        }
+        GridOp::FillBottom(n) => {
+            expected.fill_bottom(n, Cell::Any);
+            actual.fill_bottom(n, Cell::Any);
+        }
+        GridOp::SwapRows(y1, y2) => {
+            expected.swap_rows(y1, y2);
+            actual.swap_rows(y1, y2);
+        }
+        GridOp::InsertRows(y, n) => {
+            let r1 = expected.insert_rows(y, Cell::Garbage, n);
+            let r2 = actual.insert_rows(y, Cell::Garbage, n);
+            if r1 != r2 {
+                return Err(format!("insert_rows: {} != {}", r1, r2));
+            }
+        }
+        GridOp::DropFilledRows => {
+            let n1 = expected.drop_filled_rows();
+            let n2 = actual.drop_filled_rows();
+            if n1 != n2 {
+                return Err(format!("drop_filled_rows: {} != {}", n1, n2));
+            }
+        }
+        GridOp::PutPiece(piece, orientation, pos) => {
+            let piece_grid = piece.default_spec().grid(orientation);
+            let can_put = expected.can_put(pos, piece_grid);
+            if can_put != actual.can_put_prim_bit_grid(pos, &piece_grid.bit_grid) {
+                return Err(format!("can_put: {}", can_put));
+            }
+            let n1 = expected.num_droppable_rows(pos, piece_grid);
+            let n2 = actual.num_droppable_rows_of_prim_bit_grid(pos, &piece_grid.bit_grid);
+            if n1 != n2 {
+                return Err(format!("num_droppable_rows: {} != {}", n1, n2));
+            }
+            if can_put {
+                for direction in [Vec2(-1, 0), Vec2(1, 0), Vec2(0, -1)] {
+                    let p1 = expected.reachable_pos(pos, piece_grid, direction);
+                    let p2 = actual.reachable_pos_of_prim_bit_grid(pos, &piece_grid.bit_grid, direction);
+                    if p1 != p2 {
+                        return Err(format!("reachable_pos({}): {} != {}", direction, p1, p2));
+                    }
+                }
+                expected.put(pos, piece_grid);
+                actual.put_prim_bit_grid(pos, &piece_grid.bit_grid);
+            }
+        }
+    }
+    Ok(())
+}
+
+fn check_with<'a, G: BitGridTrait<'a, BitGridInt, Cell>>(mut actual: G, ops: &[GridOp]) -> CheckResult {
+    let mut expected = BasicGrid::<Cell>::new(actual.size());
+    for (i, op) in ops.iter().enumerate() {
+        apply(&mut expected, &mut actual, op)
+            .and_then(|_| compare(&expected, &actual))
+            .map_err(|e| format!("#{} {:?}: {}", i, op, e))?;
+    }
+    Ok(())
+}
+
+/// Checks that [BasicBitGrid] (and [PrimBitGrid] if the size is small enough) behaves the same as
+/// [BasicGrid] for `ops`.
+///
+/// ## Panics
+/// Panics if the width of `size` is larger than the stride of the default playfield.
+pub fn check_grid_ops(size: Vec2, ops: &[GridOp]) -> CheckResult {
+    assert!(0 < size.0 && size.0 <= STRIDE);
+    let mut store = PrimBitGridConstantsStore::<BitGridInt>::new(STRIDE);
+    store.prepare_for_bit_grid(size);
+    let basic_bit_grid = BasicBitGrid::<BitGridInt, Cell>::with_store(&store, size)
+        .ok_or("failed to create BasicBitGrid")?;
+    check_with(basic_bit_grid, ops).map_err(|e| format!("BasicBitGrid {}: {}", size, e))?;
+    if size.1 <= store.prim_max_height {
+        let prim_bit_grid = PrimBitGrid::<BitGridInt, Cell>::with_store(&store, size)
+            .ok_or("failed to create PrimBitGrid")?;
+        check_with(prim_bit_grid, ops).map_err(|e| format!("PrimBitGrid {}: {}", size, e))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+    use crate::strategy;
+
+    #[test]
+    fn test_decode_all() {
+        let ops = GridOp::decode_all(Vec2(10, 40), &[6, 1, 3, 1]);
+        assert_eq!(vec![GridOp::DropFilledRows, GridOp::FillRow(3, true)], ops);
+    }
+
+    proptest! {
+        #[test]
+        fn test_grid_equivalence((size, ops) in strategy::grid_size().prop_flat_map(|size| (Just(size), strategy::grid_ops(size, 32)))) {
+            let r = check_grid_ops(size, &ops);
+            prop_assert!(r.is_ok(), "{}", r.unwrap_err());
+        }
+    }
+}
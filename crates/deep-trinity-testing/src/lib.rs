@@ -0,0 +1,58 @@
+//! Testing harness of deep-trinity.
+//!
+//! The checks are implemented as plain functions returning `Err` with a description on failure,
+//! so that they can be shared by proptest based tests and fuzz targets (see `fuzz/`).
+
+use deep_trinity_core::{Cell, Piece, PIECES, Playfield};
+use deep_trinity_grid::{X, Y};
+
+pub mod golden;
+pub mod grid;
+pub mod srs;
+pub mod strategy;
+pub mod testdata;
+
+pub type CheckResult = Result<(), String>;
+
+/// Creates a playfield whose bottom rows are filled by `rows`.
+/// Each bit of the row value corresponds to a cell (LSB is the left end).
+pub fn playfield_from_rows(rows: &[u16]) -> Playfield<'static> {
+    let mut pf: Playfield<'static> = Default::default();
+    let w = pf.width();
+    for (y, row) in rows.iter().enumerate() {
+        if y as Y >= pf.height() {
+            break;
+        }
+        for x in 0..w {
+            if (row >> x) & 1 == 1 {
+                pf.set_cell((x as X, y as Y).into(), Cell::Garbage);
+            }
+        }
+    }
+    pf
+}
+
+/// Reads values from raw bytes given by fuzzers.
+pub struct ByteReader<'a> {
+    data: &'a [u8],
+    i: usize,
+}
+
+impl<'a> ByteReader<'a> {
+    pub fn new(data: &'a [u8]) -> Self { Self { data, i: 0 } }
+    pub fn is_end(&self) -> bool { self.i >= self.data.len() }
+    pub fn u8(&mut self) -> u8 {
+        let v = self.data.get(self.i).copied().unwrap_or(0);
+        self.i += 1;
+        v
+    }
+    pub fn u16(&mut self) -> u16 { u16::from_le_bytes([self.u8(), self.u8()]) }
+    pub fn bool(&mut self) -> bool { self.u8() & 1 == 1 }
+    /// Returns a value in `min..=max`.
+    pub fn i8_in(&mut self, min: i8, max: i8) -> i8 {
+        debug_assert!(min <= max);
+        let n = (max as i16 - min as i16 + 1) as u16;
+        (min as i16 + (self.u8() as u16 % n) as i16) as i8
+    }
+    pub fn piece(&mut self) -> Piece { PIECES[self.u8() as usize % PIECES.len()] }
+}
@@ -0,0 +1,68 @@
+//! Consistency checks of SRS rotations.
+
+use deep_trinity_core::{Piece, Placement, Playfield, FallingPiece, ORIENTATIONS};
+use deep_trinity_grid::{X, Y};
+use crate::CheckResult;
+
+/// The number of rows above the stack to be checked.
+const MARGIN_ROWS: Y = 4;
+
+/// Checks that every rotation accepted by [Playfield::check_rotation_by_srs] can be reversed by
+/// [Playfield::check_reverse_rotation_by_srs], and that every placement returned by the latter
+/// rotates to the original placement.
+pub fn check_srs_reversibility(pf: &Playfield, piece: Piece) -> CheckResult {
+    let spec = piece.default_spec();
+    let max_y = std::cmp::min(pf.height(), pf.stack_height() + MARGIN_ROWS);
+    for orientation in ORIENTATIONS.iter() {
+        for y in -2..max_y {
+            for x in -2..(pf.width() as X) {
+                let src = Placement::new(*orientation, (x, y).into());
+                let fp = FallingPiece::new(spec, src);
+                if !pf.can_put(&fp) {
+                    continue;
+                }
+                for cw in [true, false] {
+                    let dst = if let Some(dst) = pf.check_rotation_by_srs(&fp, cw) {
+                        dst
+                    } else {
+                        continue;
+                    };
+                    let srcs = pf.check_reverse_rotation_by_srs(&FallingPiece::new(spec, dst), cw);
+                    if !srcs.contains(&src) {
+                        return Err(format!(
+                            "{} {:?} (cw: {}) => {:?}, but the reverse rotation returned {:?}\n{}",
+                            piece, src, cw, dst, srcs, pf.grid,
+                        ));
+                    }
+                    for s in srcs.iter() {
+                        let r = pf.check_rotation_by_srs(&FallingPiece::new(spec, *s), cw);
+                        if r != Some(dst) {
+                            return Err(format!(
+                                "{} {:?} is a reverse rotation of {:?} (cw: {}), but it rotates to {:?}\n{}",
+                                piece, s, dst, cw, r, pf.grid,
+                            ));
+                        }
+                    }
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+    use crate::{playfield_from_rows, strategy};
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(64))]
+        #[test]
+        fn test_srs_reversibility(rows in strategy::rows(12), piece in strategy::piece()) {
+            let pf = playfield_from_rows(&rows);
+            let r = check_srs_reversibility(&pf, piece);
+            prop_assert!(r.is_ok(), "{}", r.unwrap_err());
+        }
+    }
+}
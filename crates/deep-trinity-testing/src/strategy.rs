@@ -0,0 +1,41 @@
+//! [proptest] strategies for the data of deep-trinity.
+
+use proptest::prelude::*;
+use deep_trinity_core::{Piece, Orientation, PIECES, ORIENTATIONS};
+use deep_trinity_grid::{Vec2, Y};
+use crate::grid::GridOp;
+
+pub fn piece() -> impl Strategy<Value=Piece> {
+    (0..PIECES.len()).prop_map(|i| PIECES[i])
+}
+
+pub fn orientation() -> impl Strategy<Value=Orientation> {
+    (0..ORIENTATIONS.len()).prop_map(|i| ORIENTATIONS[i])
+}
+
+/// Rows of the default playfield (see [crate::playfield_from_rows]).
+pub fn rows(max_height: usize) -> impl Strategy<Value=Vec<u16>> {
+    prop::collection::vec(0u16..(1 << 10), 0..=max_height)
+}
+
+pub fn grid_size() -> impl Strategy<Value=Vec2> {
+    (1..=10 as i8, 1..=40 as i8).prop_map(|(w, h)| Vec2(w, h))
+}
+
+pub fn grid_op(size: Vec2) -> impl Strategy<Value=GridOp> {
+    let (w, h) = (size.0, size.1);
+    prop_oneof![
+        (0..w, 0..h, any::<bool>()).prop_map(|(x, y, filled)| GridOp::SetCell(Vec2(x, y), filled)),
+        (0..h, any::<bool>()).prop_map(|(y, filled)| GridOp::FillRow(y, filled)),
+        (0..=h).prop_map(GridOp::FillTop),
+        (0..=h).prop_map(GridOp::FillBottom),
+        (0..h, 0..h).prop_map(|(y1, y2)| GridOp::SwapRows(y1, y2)),
+        (0..h).prop_flat_map(move |y| (Just(y), 0..=(h - y) as Y)).prop_map(|(y, n)| GridOp::InsertRows(y, n)),
+        Just(GridOp::DropFilledRows),
+        (piece(), orientation(), -2..=w, -2..=h).prop_map(|(p, o, x, y)| GridOp::PutPiece(p, o, Vec2(x, y))),
+    ]
+}
+
+pub fn grid_ops(size: Vec2, max_len: usize) -> impl Strategy<Value=Vec<GridOp>> {
+    prop::collection::vec(grid_op(size), 0..=max_len)
+}
@@ -0,0 +1,156 @@
+//! Curated board positions shared by benches, evaluator tests and golden snapshots.
+//!
+//! The positions are loadable by name (see [load]). The boards are ASCII art of
+//! [Playfield::parse] from the top.
+
+use deep_trinity_core::Playfield;
+use deep_trinity_grid::{Grid, Cell as _, X, Y};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Position {
+    pub name: &'static str,
+    pub description: &'static str,
+    /// The rows from the top.
+    pub board: &'static [&'static str],
+}
+
+impl Position {
+    pub fn playfield(&self) -> Playfield<'static> {
+        Playfield::parse(&self.board.join("\n")).expect("invalid test position")
+    }
+    /// The rows from the bottom in the format of [crate::playfield_from_rows].
+    pub fn rows(&self) -> Vec<u16> {
+        let pf = self.playfield();
+        (0..pf.stack_height())
+            .map(|y| (0..pf.width())
+                .filter(|&x| !pf.grid.cell((x as X, y as Y).into()).is_empty())
+                .fold(0, |row, x| row | 1 << x))
+            .collect()
+    }
+}
+
+pub const POSITIONS: &[Position] = &[
+    Position {
+        name: "empty",
+        description: "The empty playfield.",
+        board: &[],
+    },
+    Position {
+        name: "tsd",
+        description: "A T-spin double slot at the left with the overhang.",
+        board: &[
+            "@.........",
+            "...@@@@@@@",
+            "@.@@@@@@@@",
+        ],
+    },
+    Position {
+        name: "tst",
+        description: "A T-spin triple slot under the roof.",
+        board: &[
+            "..@@......",
+            "@@.@@@@@@@",
+            "@..@@@@@@@",
+            "@@.@@@@@@@",
+        ],
+    },
+    Position {
+        name: "dt_cannon_mid",
+        description: "The middle of building a DT cannon: a T-spin double slot over a T-spin triple slot.",
+        board: &[
+            "...@@@@@@.",
+            "@@...@@@@@",
+            "@@@.@@@@@@",
+            "@@.@@@@@@@",
+            "@..@@@@@@@",
+            "@@.@@@@@@@",
+        ],
+    },
+    Position {
+        name: "cheese",
+        description: "Garbage rows with random gaps under a low stack.",
+        board: &[
+            "@@...@@...",
+            "####.#####",
+            "#.########",
+            "########.#",
+            "###.######",
+            "######.###",
+            "#.########",
+        ],
+    },
+    Position {
+        name: "near_topout",
+        description: "A messy stack of 18 rows leaving few rows to spawn.",
+        board: &[
+            "@@@@..@@@@",
+            "@@@@.@@@@@",
+            "@@@.@@@@@@",
+            "@@@@@@.@@@",
+            "@.@@@@@@@@",
+            "@@@@@@@@.@",
+            "@@.@@@@@@@",
+            "@@@@@.@@@@",
+            "@@@@@@@.@@",
+            "@@@.@@@@@@",
+            "@.@@@@@@@@",
+            "@@@@@@@@.@",
+            "@@@@.@@@@@",
+            "@@@@@@.@@@",
+            "@@.@@@@@@@",
+            "@@@@@@@@@.",
+            "@@@@@.@@@@",
+            "@.@@@@@@@@",
+        ],
+    },
+];
+
+pub fn get(name: &str) -> Option<&'static Position> {
+    POSITIONS.iter().find(|p| p.name == name)
+}
+
+pub fn names() -> impl Iterator<Item = &'static str> {
+    POSITIONS.iter().map(|p| p.name)
+}
+
+/// Returns the playfield of the position.
+pub fn load(name: &str) -> Result<Playfield<'static>, String> {
+    get(name).map(|p| p.playfield()).ok_or_else(|| format!("unknown test position: {}", name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use deep_trinity_core::{FallingPiece, LineClear, Piece, Placement, TSpinJudgementMode, Orientation::*};
+    use deep_trinity_core::helper::CheeseMetrics;
+    use crate::playfield_from_rows;
+
+    fn lines_cleared_by_t(pf: &Playfield, placement: Placement) -> LineClear {
+        let fp = FallingPiece::new(Piece::T.default_spec(), placement);
+        assert!(pf.can_lock(&fp));
+        pf.check_line_clear(&fp, TSpinJudgementMode::PuyoPuyoTetris)
+    }
+
+    #[test]
+    fn test_positions() {
+        for p in POSITIONS {
+            let pf = p.playfield();
+            assert_eq!(p.board.len() as Y, pf.stack_height(), "{}", p.name);
+            assert_eq!(pf.grid.num_blocks(), playfield_from_rows(&p.rows()).grid.num_blocks(), "{}", p.name);
+        }
+        assert_eq!(POSITIONS.len(), names().count());
+        assert!(load("empty").unwrap().is_empty());
+        assert!(load("unknown").is_err());
+
+        let tsd = Placement::new(Orientation2, (0, 0).into());
+        assert_eq!(2, lines_cleared_by_t(&load("tsd").unwrap(), tsd).num_lines);
+        let tst = Placement::new(Orientation3, (1, 0).into());
+        assert_eq!(3, lines_cleared_by_t(&load("tst").unwrap(), tst).num_lines);
+        let dt = load("dt_cannon_mid").unwrap();
+        assert_eq!(2, lines_cleared_by_t(&dt, Placement::new(Orientation2, (2, 3).into())).num_lines);
+        assert_eq!(3, lines_cleared_by_t(&dt, tst).num_lines);
+
+        assert_eq!(6, CheeseMetrics::new(&load("cheese").unwrap()).garbage_rows);
+        assert!(load("near_topout").unwrap().stack_height() >= 18);
+    }
+}
@@ -145,10 +145,26 @@ macro_rules! impl_arena_index_ops {
     }
 }
 
+/// Limits of the size of [VecNodeArena]. `None` means unlimited.
+/// Nodes can still be created over the budget, so expansion loops should check
+/// [VecNodeArena::is_budget_exceeded] and stop.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct ArenaBudget {
+    pub max_nodes: Option<usize>,
+    /// Compared with [VecNodeArena::estimated_bytes].
+    pub max_bytes: Option<usize>,
+}
+
+impl ArenaBudget {
+    pub fn nodes(n: usize) -> Self { Self { max_nodes: Some(n), max_bytes: None } }
+    pub fn bytes(n: usize) -> Self { Self { max_nodes: None, max_bytes: Some(n) } }
+}
+
 pub struct VecNodeArena<Data> {
     handle_indices: Vec<Option<NodeHandle>>,
     recycled_indices: Vec<usize>,
     nodes: Vec<Node<Data>>,
+    budget: ArenaBudget,
 }
 
 impl<Data> Default for VecNodeArena<Data> {
@@ -157,15 +173,43 @@ impl<Data> Default for VecNodeArena<Data> {
             handle_indices: Vec::new(),
             recycled_indices: Vec::new(),
             nodes: Vec::new(),
+            budget: Default::default(),
         }
     }
 }
 
 impl<Data> VecNodeArena<Data> {
+    pub fn with_budget(budget: ArenaBudget) -> Self { Self { budget, ..Default::default() } }
     pub fn len(&self) -> usize { self.nodes.len() }
     pub fn used_len(&self) -> usize { self.nodes.len() - self.recycled_indices.len() }
     pub fn recycled_len(&self) -> usize { self.recycled_indices.len() }
     pub fn shrink(&mut self) { todo!() }
+    pub fn budget(&self) -> ArenaBudget { self.budget }
+    pub fn set_budget(&mut self, budget: ArenaBudget) { self.budget = budget; }
+    /// The bytes of the used nodes. The heap memory owned by the data is not counted.
+    pub fn estimated_bytes(&self) -> usize { self.used_len() * std::mem::size_of::<Node<Data>>() }
+    pub fn is_budget_exceeded(&self) -> bool {
+        self.budget.max_nodes.map_or(false, |n| self.used_len() > n)
+            || self.budget.max_bytes.map_or(false, |n| self.estimated_bytes() > n)
+    }
+    /// Moves the tree of `root` in `other` (e.g. built in another thread) to the last child of
+    /// `parent`, and returns the new handle of `root`. The other nodes in `other` are dropped.
+    /// ## Panics
+    /// Panics if `parent` or `root` doesn't exist.
+    pub fn append_tree(&mut self, parent: NodeHandle, other: Self, root: NodeHandle) -> NodeHandle {
+        let handle_indices = other.handle_indices;
+        let mut nodes = other.nodes.into_iter().map(Some).collect::<Vec<_>>();
+        let mut take = |h: NodeHandle| nodes[handle_indices[h].unwrap()].take().unwrap();
+        let mut new_root = None;
+        let mut open = vec![(root, parent)];
+        while let Some((h, new_parent)) = open.pop() {
+            let node = take(h);
+            let new_h = self.append_child(new_parent, node.data);
+            new_root.get_or_insert(new_h);
+            open.extend(node.children.into_iter().rev().map(|c| (c, new_h)));
+        }
+        new_root.unwrap()
+    }
 }
 
 impl<Data> NodeArena<Data> for VecNodeArena<Data> {
@@ -273,4 +317,41 @@ mod tests {
         });
         assert_eq!(&[root, n1, n1_1, n1_2, n2], handles.as_slice());
     }
+
+    #[test]
+    fn test_budget() {
+        let mut arena: VecNodeArena<u8> = VecNodeArena::with_budget(ArenaBudget::nodes(2));
+        let root = arena.create(1);
+        arena.append_child(root, 2);
+        assert!(!arena.is_budget_exceeded());
+        let n = arena.append_child(root, 3);
+        assert!(arena.is_budget_exceeded());
+        arena.destroy(n);
+        assert!(!arena.is_budget_exceeded());
+
+        arena.set_budget(ArenaBudget::bytes(std::mem::size_of::<Node<u8>>()));
+        assert_eq!(2 * std::mem::size_of::<Node<u8>>(), arena.estimated_bytes());
+        assert!(arena.is_budget_exceeded());
+        arena.set_budget(Default::default());
+        assert!(!arena.is_budget_exceeded());
+    }
+
+    #[test]
+    fn test_append_tree() {
+        let mut other: VecNodeArena<u8> = Default::default();
+        let _ = other.create(0);
+        let root = other.create(1);
+        let n1 = other.append_child(root, 10);
+        other.append_child(n1, 11);
+        other.append_child(root, 20);
+
+        let mut arena: VecNodeArena<u8> = Default::default();
+        let parent = arena.create(100);
+        let new_root = arena.append_tree(parent, other, root);
+        assert_eq!(5, arena.used_len());
+        assert_eq!(&Some(parent), arena[new_root].parent());
+        let mut visited = Vec::new();
+        arena.visit_depth_first(parent, |arena, node, _| visited.push(arena[node].data));
+        assert_eq!(vec![100, 1, 10, 11, 20], visited);
+    }
 }
@@ -0,0 +1,149 @@
+use crate::{GameSession, Action, ResetOptions};
+
+/// Multiple sessions stepped in parallel threads.
+/// Sessions are reset automatically when done like vectorized environments of gym.
+/// If seeded, the `e`-th episode of the i-th session of `n` is seeded with `rand_seed + e * n + i`
+/// (wrapping on overflow), so the episodes are reproducible and have different seeds.
+#[derive(Clone, Debug)]
+pub struct GameSessionBatch {
+    sessions: Vec<GameSession>,
+    num_threads: usize,
+    rand_seed: Option<u64>,
+    /// The number of the episodes done by each session since the last reset.
+    episodes: Vec<u64>,
+}
+
+impl GameSessionBatch {
+    /// If `rand_seed` is specified, the i-th session is seeded with `rand_seed + i`.
+    pub fn new(n: usize, rand_seed: Option<u64>) -> Result<Self, &'static str> {
+        let mut sessions = Vec::with_capacity(n);
+        for i in 0..n {
+            sessions.push(GameSession::new(rand_seed.map(|seed| seed.wrapping_add(i as u64)))?);
+        }
+        let num_threads = std::thread::available_parallelism().map_or(1, |n| n.get()).min(n.max(1));
+        Ok(Self { sessions, num_threads, rand_seed, episodes: vec![0; n] })
+    }
+    pub fn len(&self) -> usize { self.sessions.len() }
+    pub fn is_empty(&self) -> bool { self.sessions.is_empty() }
+    pub fn sessions(&self) -> &[GameSession] { &self.sessions }
+    pub fn sessions_mut(&mut self) -> &mut [GameSession] { &mut self.sessions }
+    pub fn set_num_threads(&mut self, n: usize) { self.num_threads = n.max(1); }
+    /// If `rand_seed` is specified, the i-th session is seeded with `rand_seed + i`.
+    pub fn reset(&mut self, rand_seed: Option<u64>) -> Result<(), &'static str> {
+        for (i, session) in self.sessions.iter_mut().enumerate() {
+            session.reset(rand_seed.map(|seed| seed.wrapping_add(i as u64)))?;
+        }
+        self.rand_seed = rand_seed;
+        self.episodes.iter_mut().for_each(|e| *e = 0);
+        Ok(())
+    }
+    /// Same as [Self::reset], but with the options for all the sessions.
+    pub fn reset_with(&mut self, options: &ResetOptions) -> Result<(), &'static str> {
+        for (i, session) in self.sessions.iter_mut().enumerate() {
+            let rand_seed = options.rand_seed.map(|seed| seed.wrapping_add(i as u64));
+            session.reset_with(ResetOptions { rand_seed, ..options.clone() })?;
+        }
+        self.rand_seed = options.rand_seed;
+        self.episodes.iter_mut().for_each(|e| *e = 0);
+        Ok(())
+    }
+    /// Returns the rewards and whether each session was done by the step.
+    /// Done sessions are reset, so the observations are of the new episodes.
+    pub fn step(&mut self, actions: &[Action]) -> Result<(Vec<f32>, Vec<bool>), &'static str> {
+        if actions.len() != self.sessions.len() {
+            return Err("invalid number of actions");
+        }
+        let n = self.sessions.len();
+        let rand_seed = self.rand_seed;
+        let chunk_size = n.div_ceil(self.num_threads).max(1);
+        let results = std::thread::scope(|scope| {
+            let handles = self.sessions.chunks_mut(chunk_size)
+                .zip(self.episodes.chunks_mut(chunk_size))
+                .zip(actions.chunks(chunk_size))
+                .enumerate()
+                .map(|(c, ((sessions, episodes), actions))| scope.spawn(move || {
+                    let mut r = Vec::with_capacity(sessions.len());
+                    for (j, ((session, episode), action)) in sessions.iter_mut().zip(episodes.iter_mut()).zip(actions).enumerate() {
+                        session.step(*action)?;
+                        let reward = session.last_reward();
+                        let done = session.is_done();
+                        if done {
+                            *episode += 1;
+                            let i = (c * chunk_size + j) as u64;
+                            session.reset(rand_seed.map(|seed| seed.wrapping_add(episode.wrapping_mul(n as u64)).wrapping_add(i)))?;
+                        }
+                        r.push((reward, done));
+                    }
+                    Ok(r)
+                }))
+                .collect::<Vec<_>>();
+            handles.into_iter().map(|h| h.join().unwrap()).collect::<Result<Vec<Vec<_>>, &'static str>>()
+        })?;
+        Ok(results.into_iter().flatten().unzip())
+    }
+    /// Returns the observations of all the sessions concatenated.
+    pub fn observations(&self) -> Vec<u32> {
+        self.sessions.iter().flat_map(|s| s.observation()).collect()
+    }
+    pub fn legal_actions(&self) -> Vec<Vec<u32>> {
+        self.sessions.iter().map(|s| s.legal_actions()).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_game_session_batch() {
+        let mut batch = GameSessionBatch::new(3, Some(0)).unwrap();
+        batch.set_num_threads(2);
+        let obs_len = batch.sessions()[0].observation().len();
+        let mut sequential = (0..3).map(|i| GameSession::new(Some(i)).unwrap()).collect::<Vec<_>>();
+        for _ in 0..5 {
+            let actions = batch.legal_actions().iter()
+                .map(|actions| Action(*actions.iter().min().unwrap()))
+                .collect::<Vec<_>>();
+            let (rewards, dones) = batch.step(&actions).unwrap();
+            assert_eq!(3, rewards.len());
+            assert_eq!(3, dones.len());
+            for (s, a) in sequential.iter_mut().zip(actions.iter()) {
+                s.step(*a).unwrap();
+            }
+            assert_eq!(3 * obs_len, batch.observations().len());
+        }
+        for (b, s) in batch.sessions().iter().zip(sequential.iter()) {
+            assert_eq!(s.game_str(), b.game_str());
+        }
+        assert!(batch.step(&[Action(0)]).is_err());
+        // Seeds wrap around.
+        let batch = GameSessionBatch::new(2, Some(u64::MAX)).unwrap();
+        assert_eq!(GameSession::new(Some(0)).unwrap().game_str(), batch.sessions()[1].game_str());
+    }
+
+    #[test]
+    fn test_game_session_batch_auto_reset() {
+        let run = || {
+            let mut batch = GameSessionBatch::new(2, Some(0)).unwrap();
+            batch.reset_with(&ResetOptions { rand_seed: Some(10), max_pieces: Some(3), ..Default::default() }).unwrap();
+            let mut dones = Vec::new();
+            for _ in 0..3 {
+                // Without holds to lock a piece at every step.
+                let actions = batch.legal_actions().iter()
+                    .map(|actions| Action(*actions.iter().filter(|a| !Action(**a).is_hold()).min().unwrap()))
+                    .collect::<Vec<_>>();
+                dones = batch.step(&actions).unwrap().1;
+            }
+            assert_eq!(vec![true, true], dones);
+            batch
+        };
+        let (batch, other) = (run(), run());
+        for (i, (b, o)) in batch.sessions().iter().zip(other.sessions().iter()).enumerate() {
+            assert_eq!(o.game_str(), b.game_str());
+            // The next episodes are seeded with `10 + 1 * 2 + i` keeping the other options.
+            let mut expected = GameSession::new(None).unwrap();
+            expected.reset_with(ResetOptions { rand_seed: Some(12 + i as u64), max_pieces: Some(3), ..Default::default() }).unwrap();
+            assert_eq!(expected.game_str(), b.game_str());
+        }
+    }
+}
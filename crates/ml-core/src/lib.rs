@@ -1,10 +1,13 @@
 use std::collections::HashMap;
 use rand::prelude::StdRng;
-use rand::SeedableRng;
+use rand::{Rng, SeedableRng};
 use deep_trinity_grid::{Grid, Cell};
 
 #[cfg(feature = "async_session")]
 pub mod async_session;
+pub mod batch;
+pub mod n_step;
+pub mod ring_buffer;
 
 pub const HOLD_ACTION_ID: u32 = 0;
 pub const NUM_ACTIONS: u32 = 1 + 10 * 30 * 4 * 2;
@@ -34,76 +37,206 @@ impl Action {
     pub fn is_hold(&self) -> bool { self.0 == HOLD_ACTION_ID }
 }
 
+/// Rewards for each line clear shared by [calc_reward] and [calc_lock_reward].
+const LINE_CLEAR_REWARDS: [(u8, Option<deep_trinity_core::TSpin>, f32); 9] = {
+    use deep_trinity_core::TSpin;
+    [
+        (1, None, 0.1),
+        (2, None, 1.0),
+        (3, None, 2.0),
+        (4, None, 4.0),
+        (1, Some(TSpin::Standard), 2.0),
+        (2, Some(TSpin::Standard), 4.0),
+        (3, Some(TSpin::Standard), 6.0),
+        (1, Some(TSpin::Mini), 0.0),
+        (2, Some(TSpin::Mini), 1.0),
+    ]
+};
+const PERFECT_CLEAR_REWARD: f32 = 10.0;
+
 pub fn calc_reward(stats: &deep_trinity_core::Statistics) -> f32 {
-    use deep_trinity_core::{StatisticsEntryType, LineClear, TSpin};
+    use deep_trinity_core::{StatisticsEntryType, LineClear};
     let mut reward = 0.0;
-    for (ent_type, val) in &[
-        (StatisticsEntryType::LineClear(LineClear::new(1, None)), 0.1),
-        (StatisticsEntryType::LineClear(LineClear::new(2, None)), 1.0),
-        (StatisticsEntryType::LineClear(LineClear::new(3, None)), 2.0),
-        (StatisticsEntryType::LineClear(LineClear::new(4, None)), 4.0),
-        (StatisticsEntryType::LineClear(LineClear::new(1, Some(TSpin::Standard))), 2.0),
-        (StatisticsEntryType::LineClear(LineClear::new(2, Some(TSpin::Standard))), 4.0),
-        (StatisticsEntryType::LineClear(LineClear::new(3, Some(TSpin::Standard))), 6.0),
-        (StatisticsEntryType::LineClear(LineClear::new(1, Some(TSpin::Mini))), 0.0),
-        (StatisticsEntryType::LineClear(LineClear::new(2, Some(TSpin::Mini))), 1.0),
-        (StatisticsEntryType::PerfectClear, 10.0),
-    ] {
-        reward += stats.get(*ent_type) as f32 * val;
+    for (num_lines, tspin, val) in &LINE_CLEAR_REWARDS {
+        reward += stats.get(StatisticsEntryType::LineClear(LineClear::new(*num_lines, *tspin))) as f32 * val;
     }
+    reward += stats.get(StatisticsEntryType::PerfectClear) as f32 * PERFECT_CLEAR_REWARD;
     if stats.btb.max() > 0 {
         reward += 1.0;
     }
-    reward += match stats.combo.max() {
+    reward += combo_reward(stats.combo.max());
+    normalize_reward(reward)
+}
+
+/// Same as [calc_reward], but calculated from the outcome of a single lock.
+pub fn calc_lock_reward(outcome: &deep_trinity_core::LockOutcome) -> f32 {
+    use deep_trinity_core::LineClear;
+    let mut reward = 0.0;
+    for (num_lines, tspin, val) in &LINE_CLEAR_REWARDS {
+        if outcome.line_clear == LineClear::new(*num_lines, *tspin) {
+            reward += val;
+        }
+    }
+    if outcome.perfect_clear {
+        reward += PERFECT_CLEAR_REWARD;
+    }
+    if outcome.num_btbs.unwrap_or(0) > 0 {
+        reward += 1.0;
+    }
+    reward += combo_reward(outcome.num_combos.unwrap_or(0));
+    normalize_reward(reward)
+}
+
+fn combo_reward(num_combos: deep_trinity_core::Count) -> f32 {
+    match num_combos {
         0 | 1 => 0.0,
         2 | 3 => 1.0,
         4 | 5 => 2.0,
         6 | 7 => 3.0,
         8 | 9 | 10 => 4.0,
         _ => 5.0,
-    };
+    }
+}
+
+fn normalize_reward(reward: f32) -> f32 {
     const MAX: f32 = 22.0;
     if reward > MAX { 1.0 } else { reward / MAX }
 }
 
+/// Options of episodes for curricula (see [GameSession::reset_with]).
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ResetOptions {
+    pub rand_seed: Option<u64>,
+    /// The number of garbage rows pre-filled with random gaps.
+    pub garbage_rows: usize,
+    /// Supplied before the random pieces, e.g. fixed starting bags.
+    pub starting_pieces: Vec<deep_trinity_core::Piece>,
+    /// Limits the total number of pieces including `starting_pieces`.
+    /// The episode is done when the queue runs out.
+    pub queue_length: Option<usize>,
+    /// The episode is truncated after locking this number of pieces.
+    pub max_pieces: Option<usize>,
+    /// Garbage rows rising during the episode, e.g. by [survival_curriculum].
+    pub rising_garbage: Option<deep_trinity_core::mode::RisingGarbage>,
+}
+
+pub const MAX_SURVIVAL_STAGE: u32 = 6;
+
+/// The rising garbage of the survival curriculum. A row rises every 8 locks at the stage 0, and
+/// one lock less at each stage up to [MAX_SURVIVAL_STAGE]. The gaps are random.
+pub fn survival_curriculum(stage: u32, rand_seed: Option<u64>) -> deep_trinity_core::mode::RisingGarbage {
+    let mut rng = if let Some(seed) = rand_seed { StdRng::seed_from_u64(seed) } else { StdRng::from_entropy() };
+    let gaps = (0..64).map(|_| rng.gen_range(0..10)).collect::<Vec<_>>();
+    let interval = 8 - stage.min(MAX_SURVIVAL_STAGE);
+    deep_trinity_core::mode::RisingGarbage::new(interval, 1, gaps).unwrap()
+}
+
 #[derive(Clone, Debug)]
 pub struct GameSession {
-    piece_gen: deep_trinity_core::RandomPieceGenerator<StdRng>,
     game: deep_trinity_core::Game<'static>,
     legal_actions: HashMap<Action, deep_trinity_core::MoveTransition>,
     last_reward: f32,
+    options: ResetOptions,
+    num_locked_pieces: usize,
+    rising_garbage: Option<deep_trinity_core::mode::RisingGarbage>,
+    /// Kept here while the game has the limited queue.
+    reserved_piece_gen: Option<Box<dyn deep_trinity_core::PieceGenerator>>,
+    n_step: Option<(n_step::NStepOptions, n_step::NStepAccumulator)>,
 }
 
 impl GameSession {
     pub fn new(rand_seed: Option<u64>) -> Result<Self, &'static str> {
         let rng = if let Some(seed) = rand_seed { StdRng::seed_from_u64(seed) } else { StdRng::from_entropy() };
-        let mut pg = deep_trinity_core::RandomPieceGenerator::new(rng);
         let mut game: deep_trinity_core::Game = Default::default();
-        game.supply_next_pieces(&pg.generate());
+        game.set_piece_generator(Some(Box::new(deep_trinity_core::RandomPieceGenerator::new(rng))));
         game.setup_falling_piece(None).unwrap();
         let mut r = Self {
-            piece_gen: pg,
             game,
             legal_actions: HashMap::new(),
             last_reward: 0.0,
+            options: Default::default(),
+            num_locked_pieces: 0,
+            rising_garbage: None,
+            reserved_piece_gen: None,
+            n_step: None,
         };
         r.sync()?;
         Ok(r)
     }
+    /// Resets with the options of the last [Self::reset_with] except the seed.
     pub fn reset(&mut self, rand_seed: Option<u64>) -> Result<(), &'static str> {
-        if let Some(seed) = rand_seed {
-            self.piece_gen = deep_trinity_core::RandomPieceGenerator::new(StdRng::seed_from_u64(seed));
-        }
+        let options = ResetOptions { rand_seed, ..self.options.clone() };
+        self.reset_with(options)
+    }
+    /// The options are kept for later [Self::reset].
+    pub fn reset_with(&mut self, options: ResetOptions) -> Result<(), &'static str> {
+        let mut piece_gen = if let Some(seed) = options.rand_seed {
+            Some(Box::new(deep_trinity_core::RandomPieceGenerator::new(StdRng::seed_from_u64(seed))) as _)
+        } else {
+            self.game.take_piece_generator().or_else(|| self.reserved_piece_gen.take())
+        };
+        let (rules, num_visible_next_pieces) = (std::mem::take(&mut self.game.rules), self.game.state.next_pieces.visible_num);
         self.game = Default::default();
-        self.game.supply_next_pieces(&self.piece_gen.generate());
-        self.game.setup_falling_piece(None).unwrap();
+        self.game.rules = rules;
+        self.game.set_num_visible_next_pieces(num_visible_next_pieces);
+        if options.garbage_rows > 0 {
+            let mut rng = if let Some(seed) = options.rand_seed { StdRng::seed_from_u64(seed) } else { StdRng::from_entropy() };
+            let width = self.game.state.playfield.width();
+            let gaps = (0..options.garbage_rows).map(|_| rng.gen_range(0..width)).collect::<Vec<_>>();
+            self.game.receive_garbage(&gaps);
+        }
+        self.game.supply_next_pieces(&options.starting_pieces);
+        if let Some(n) = options.queue_length {
+            let mut pieces = Vec::new();
+            if let Some(g) = piece_gen.as_mut() {
+                while options.starting_pieces.len() + pieces.len() < n {
+                    pieces.extend(g.generate());
+                }
+            }
+            pieces.truncate(n.saturating_sub(options.starting_pieces.len()));
+            self.game.supply_next_pieces(&pieces);
+            self.game.state.next_pieces.pieces.truncate(n);
+            self.reserved_piece_gen = piece_gen;
+        } else {
+            self.game.set_piece_generator(piece_gen);
+        }
+        // An empty queue is treated as a done episode.
+        let _ = self.game.setup_falling_piece(None);
+        if let Some((_, acc)) = self.n_step.as_mut() {
+            acc.flush();
+        }
         self.last_reward = 0.0;
+        self.num_locked_pieces = 0;
+        self.rising_garbage = options.rising_garbage.clone();
+        if let Some(rg) = self.rising_garbage.as_mut() {
+            rg.reset();
+        }
+        self.options = options;
         self.sync()?;
         Ok(())
     }
+    /// Kept over `reset()`.
+    pub fn set_enable_hold(&mut self, enable: bool) { self.game.rules.enable_hold = enable; }
+    /// Kept over `reset()`. Note that the size of the observations depends on this.
+    pub fn set_num_visible_next_pieces(&mut self, n: usize) { self.game.set_num_visible_next_pieces(n); }
+    /// Switches the rewards to the grouped ones of [n_step::NStepOptions].
+    /// The n-step return of each placement can be taken by [Self::take_n_step_returns] after the
+    /// following `horizon` placements or the end of the episode. Hold steps have no rewards.
+    /// Kept over `reset()`.
+    pub fn set_n_step(&mut self, options: Option<n_step::NStepOptions>) {
+        self.n_step = options.map(|o| (o, n_step::NStepAccumulator::new(o.horizon, o.gamma)));
+    }
+    /// Returns the ready n-step returns in the order of the placements.
+    pub fn take_n_step_returns(&mut self) -> Vec<f32> {
+        self.n_step.as_mut().map_or_else(Vec::new, |(_, acc)| acc.take_returns())
+    }
     fn sync(&mut self) -> Result<(), &'static str> {
-        let piece = self.game.state.falling_piece.as_ref().unwrap().piece_spec.piece;
         let mut legal_actions = HashMap::new();
+        if self.game.state.falling_piece.is_none() || self.is_truncated() {
+            self.legal_actions = legal_actions;
+            return Ok(());
+        }
+        let piece = self.game.state.falling_piece.as_ref().unwrap().piece_spec.piece;
         let candidates = self.game.get_move_candidates()?;
         for mt in candidates.iter() {
             legal_actions.insert(Action::from_move_transition(mt, piece), *mt);
@@ -120,28 +253,37 @@ impl GameSession {
             let piece_spec = self.game.state.falling_piece.as_ref().unwrap().piece_spec;
             let fp = deep_trinity_core::FallingPiece::new_with_last_move_transition(piece_spec, &mt);
             self.game.state.falling_piece = Some(fp);
-            let stats = self.game.stats.clone();
-            self.game.lock()?;
-            let stats = self.game.stats.clone() - stats;
-            self.last_reward = calc_reward(&stats);
-        }
-        if self.game.should_supply_next_pieces() {
-            self.game.supply_next_pieces(&self.piece_gen.generate());
+            let outcome = self.game.lock_ex()?;
+            self.num_locked_pieces += 1;
+            if let Some(rg) = self.rising_garbage.as_mut() {
+                rg.on_lock(&mut self.game)?;
+            }
+            if let Some((options, acc)) = self.n_step.as_mut() {
+                self.last_reward = options.reward(&outcome);
+                acc.push(self.last_reward);
+            } else {
+                self.last_reward = calc_lock_reward(&outcome);
+            }
         }
         self.sync()?;
+        if self.is_done() {
+            if let Some((_, acc)) = self.n_step.as_mut() {
+                acc.flush();
+            }
+        }
         Ok(())
     }
     pub fn game_str(&self) -> String { format!("{}", self.game) }
     pub fn legal_actions(&self) -> Vec<u32> {
         let mut r = self.legal_actions.keys().map(|a| a.0).collect::<Vec<_>>();
-        if self.game.state.can_hold {
+        if self.game.can_hold() {
             r.push(HOLD_ACTION_ID);
         }
         r
     }
     pub fn observation(&self) -> Vec<u32> {
         let state = &self.game.state;
-        let fp = state.falling_piece.as_ref().unwrap();
+        let falling_piece = state.falling_piece.as_ref().map_or(0, |fp| fp.piece() as u32);
         let mut r = Vec::with_capacity(state.playfield.grid.height() as usize + 2);
         // [rows[n], rows[n+1]] * 20
         r.resize(state.playfield.grid.height() as usize / 2, 0 as u32);
@@ -150,9 +292,9 @@ impl GameSession {
         // }
         // [can_hold(2), hold_piece(8), falling_piece(7)]
         r.push(
-            if state.can_hold { 1 } else { 0 }
+            if self.game.can_hold() { 1 } else { 0 }
                 + if let Some(p) = state.hold_piece { p as u32 + 1 } else { 0 } * 2
-                + fp.piece() as u32 * 2 * 8
+                + falling_piece * 2 * 8
         );
         // [next]
         r.push(
@@ -167,16 +309,16 @@ impl GameSession {
     }
     pub fn observation_2d(&self) -> Vec<f32> {
         let state = &self.game.state;
-        let fp = state.falling_piece.as_ref().unwrap();
+        let falling_piece = state.falling_piece.as_ref().map_or(0.0, |fp| fp.piece() as i32 as f32);
         // [is_block(2), can_hold(2), hold_piece(8), falling_piece(7), next(7) * 5] * num_cells
         let mut r = Vec::with_capacity(
             state.playfield.grid.width() as usize * state.playfield.grid.height() as usize * (4 + state.next_pieces.visible_num));
         for y in 0..state.playfield.grid.height() {
             for x in 0..state.playfield.grid.width() {
                 r.push(if state.playfield.grid.cell((x, y).into()).is_empty() { 0.0 } else { 1.0 });
-                r.push(if state.can_hold { 1.0 } else { 0.0 });
+                r.push(if self.game.can_hold() { 1.0 } else { 0.0 });
                 r.push(if let Some(p) = state.hold_piece { (p as i32 as f32 + 1.0) / 8.0 } else { 0.0 });
-                r.push((fp.piece() as i32 as f32) / 7.0);
+                r.push(falling_piece / 7.0);
                 for p in state.next_pieces.pieces.iter().take(state.next_pieces.visible_num) {
                     r.push((*p as i32 as f32) / 7.0);
                 }
@@ -186,6 +328,11 @@ impl GameSession {
     }
     pub fn last_reward(&self) -> f32 { self.last_reward }
     pub fn is_done(&self) -> bool { self.game.state.is_game_over() || self.legal_actions.is_empty() }
+    /// Whether the episode is done by [ResetOptions::max_pieces] rather than the game.
+    pub fn is_truncated(&self) -> bool {
+        matches!(self.options.max_pieces, Some(n) if self.num_locked_pieces >= n)
+    }
+    pub fn num_locked_pieces(&self) -> usize { self.num_locked_pieces }
 }
 
 #[cfg(test)]
@@ -200,4 +347,115 @@ mod tests {
         let diff = stats2 - stats;
         assert!(calc_reward(&diff) > 0.0);
     }
+
+    #[test]
+    fn test_game_session_options() {
+        let mut session = GameSession::new(Some(0)).unwrap();
+        session.set_enable_hold(false);
+        session.set_num_visible_next_pieces(2);
+        session.reset(None).unwrap();
+        assert!(!session.legal_actions().contains(&HOLD_ACTION_ID));
+        let size = session.observation_2d().len();
+        session.set_num_visible_next_pieces(3);
+        assert!(session.observation_2d().len() > size);
+    }
+
+    #[test]
+    fn test_reset_with() {
+        use deep_trinity_core::Piece;
+        let mut session = GameSession::new(Some(0)).unwrap();
+        session.reset_with(ResetOptions {
+            rand_seed: Some(1),
+            garbage_rows: 4,
+            starting_pieces: vec![Piece::I, Piece::O],
+            queue_length: Some(3),
+            ..Default::default()
+        }).unwrap();
+        assert_eq!(4, session.game.state.playfield.stack_height());
+        assert_eq!(2, session.game.state.next_pieces.len());
+        assert_eq!(Some(Piece::O), session.game.state.next_pieces.pieces.front().copied());
+        session.set_enable_hold(false);
+        for _ in 0..3 {
+            assert!(!session.is_done());
+            let action = session.legal_actions()[0];
+            session.step(Action(action)).unwrap();
+        }
+        assert!(session.is_done());
+        assert!(!session.is_truncated());
+        session.observation();
+
+        // The options except the seed are kept.
+        session.reset(None).unwrap();
+        assert_eq!(Some(Piece::I), session.game.state.falling_piece.as_ref().map(|fp| fp.piece()));
+
+        session.reset_with(ResetOptions { max_pieces: Some(2), ..Default::default() }).unwrap();
+        assert_eq!(0, session.game.state.playfield.stack_height());
+        for _ in 0..2 {
+            let action = session.legal_actions().into_iter().find(|a| *a != HOLD_ACTION_ID).unwrap();
+            session.step(Action(action)).unwrap();
+        }
+        assert!(session.is_done());
+        assert!(session.is_truncated());
+    }
+
+    #[test]
+    fn test_survival_curriculum() {
+        assert_eq!(8, survival_curriculum(0, Some(0)).interval());
+        assert_eq!(2, survival_curriculum(MAX_SURVIVAL_STAGE + 1, Some(0)).interval());
+        assert_eq!(survival_curriculum(1, Some(0)), survival_curriculum(1, Some(0)));
+
+        let mut session = GameSession::new(Some(0)).unwrap();
+        session.set_enable_hold(false);
+        session.reset_with(ResetOptions {
+            rand_seed: Some(0),
+            rising_garbage: Some(survival_curriculum(MAX_SURVIVAL_STAGE, Some(0))),
+            max_pieces: Some(4),
+            ..Default::default()
+        }).unwrap();
+        while !session.is_done() {
+            let action = session.legal_actions()[0];
+            session.step(Action(action)).unwrap();
+        }
+        // 4 pieces and 2 garbage rows.
+        let pf = &session.game.state.playfield;
+        let num_filled = (0..pf.height()).flat_map(|y| (0..pf.width()).map(move |x| (x, y)))
+            .filter(|(x, y)| !pf.grid.cell((*x, *y).into()).is_empty())
+            .count();
+        let num_cleared = session.game.stats.line_clear.data.iter().map(|(lc, n)| lc.num_lines as usize * *n as usize).sum::<usize>();
+        assert_eq!(4 * 4 + 2 * 9, num_filled + num_cleared * 10);
+    }
+
+    #[test]
+    fn test_n_step() {
+        let mut session = GameSession::new(Some(0)).unwrap();
+        session.set_enable_hold(false);
+        session.set_n_step(Some(n_step::NStepOptions { horizon: 3, gamma: 1.0, attack_weight: 1.0, survival_bonus: 1.0 }));
+        session.reset_with(ResetOptions { max_pieces: Some(5), ..Default::default() }).unwrap();
+        let mut returns = Vec::new();
+        while !session.is_done() {
+            let action = session.legal_actions()[0];
+            session.step(Action(action)).unwrap();
+            assert_eq!(1.0, session.last_reward());
+            returns.extend(session.take_n_step_returns());
+        }
+        assert_eq!(vec![3.0, 3.0, 3.0, 2.0, 1.0], returns);
+    }
+
+    #[test]
+    fn test_calc_lock_reward() {
+        use deep_trinity_core::{LineClear, LockOutcome};
+        let outcome = LockOutcome {
+            line_clear: LineClear::new(1, None),
+            attack_sent: 0,
+            perfect_clear: false,
+            num_combos: Some(0),
+            num_btbs: None,
+            game_over: false,
+            next_piece_ready: true,
+        };
+        let mut stats: deep_trinity_core::Statistics = Default::default();
+        stats.line_clear.add(&outcome.line_clear, 1);
+        stats.combo.add(0, 1);
+        assert_eq!(calc_reward(&stats), calc_lock_reward(&outcome));
+    }
 }
@@ -0,0 +1,91 @@
+use std::collections::VecDeque;
+
+/// Reward of placement decisions accumulated over the next pieces (see [crate::GameSession::set_n_step]).
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct NStepOptions {
+    /// The number of placements whose rewards are accumulated into a return.
+    pub horizon: usize,
+    pub gamma: f32,
+    /// Multiplied by the number of garbage lines sent.
+    pub attack_weight: f32,
+    /// Added for every placement which doesn't top out.
+    pub survival_bonus: f32,
+}
+
+impl Default for NStepOptions {
+    fn default() -> Self {
+        Self { horizon: 4, gamma: 0.99, attack_weight: 1.0, survival_bonus: 0.1 }
+    }
+}
+
+impl NStepOptions {
+    pub fn reward(&self, outcome: &deep_trinity_core::LockOutcome) -> f32 {
+        let mut r = outcome.attack_sent as f32 * self.attack_weight;
+        if !outcome.game_over {
+            r += self.survival_bonus;
+        }
+        r
+    }
+}
+
+/// Computes discounted n-step returns from the stream of rewards.
+#[derive(Clone, Debug)]
+pub struct NStepAccumulator {
+    horizon: usize,
+    gamma: f32,
+    rewards: VecDeque<f32>,
+    returns: Vec<f32>,
+}
+
+impl NStepAccumulator {
+    pub fn new(horizon: usize, gamma: f32) -> Self {
+        Self { horizon: horizon.max(1), gamma, rewards: VecDeque::new(), returns: Vec::new() }
+    }
+    fn discounted_sum(&self) -> f32 {
+        self.rewards.iter().rev().fold(0.0, |acc, r| r + self.gamma * acc)
+    }
+    /// The return of the oldest pending reward gets ready when `horizon` rewards are pushed.
+    pub fn push(&mut self, reward: f32) {
+        self.rewards.push_back(reward);
+        if self.rewards.len() >= self.horizon {
+            let r = self.discounted_sum();
+            self.returns.push(r);
+            self.rewards.pop_front();
+        }
+    }
+    /// Makes the returns of all the pending rewards ready, e.g. at the end of the episode.
+    pub fn flush(&mut self) {
+        while !self.rewards.is_empty() {
+            let r = self.discounted_sum();
+            self.returns.push(r);
+            self.rewards.pop_front();
+        }
+    }
+    pub fn clear(&mut self) {
+        self.rewards.clear();
+        self.returns.clear();
+    }
+    pub fn num_pending(&self) -> usize { self.rewards.len() }
+    /// Returns the ready returns in the order of the rewards pushed.
+    pub fn take_returns(&mut self) -> Vec<f32> { std::mem::take(&mut self.returns) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_n_step_accumulator() {
+        let mut acc = NStepAccumulator::new(2, 0.5);
+        acc.push(1.0);
+        assert!(acc.take_returns().is_empty());
+        acc.push(2.0);
+        acc.push(4.0);
+        // 1 + 0.5 * 2, 2 + 0.5 * 4
+        assert_eq!(vec![2.0, 4.0], acc.take_returns());
+        assert_eq!(1, acc.num_pending());
+        acc.flush();
+        assert_eq!(vec![4.0], acc.take_returns());
+        assert_eq!(0, acc.num_pending());
+    }
+}
@@ -0,0 +1,323 @@
+//! File-backed ring buffer of transitions, which can be placed in shared memory (e.g. `/dev/shm`)
+//! and consumed by another process without pickling (e.g. by `numpy.memmap`).
+//!
+//! Layout (little endian):
+//!
+//! | offset | type | |
+//! |---|---|---|
+//! | 0 | `[u8; 4]` | magic `DTRB` |
+//! | 4 | `u32` | version |
+//! | 8 | `u32` | capacity (number of slots) |
+//! | 12 | `u32` | slot size in bytes |
+//! | 16 | `u32` | number of fields |
+//! | 20 | `u32` | reserved |
+//! | 24 | `u64` | number of written slots, updated after each slot is written |
+//! | 32 | `[u32; 6] * fields` | dtype, ndim and shape (up to 4 dims) of each field |
+//!
+//! The slots follow the header. The i-th transition is at `i % capacity`, and the fields are
+//! packed in the order without padding.
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+pub const MAGIC: [u8; 4] = *b"DTRB";
+pub const VERSION: u32 = 1;
+pub const MAX_NDIM: usize = 4;
+const FIXED_HEADER_SIZE: u64 = 32;
+const FIELD_HEADER_SIZE: u64 = 4 * (2 + MAX_NDIM as u64);
+const NUM_WRITTEN_OFFSET: u64 = 24;
+
+/// The values are written in the header.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum DType {
+    U8 = 0,
+    U32 = 1,
+    F32 = 2,
+}
+
+impl DType {
+    pub fn size(&self) -> usize {
+        match self {
+            DType::U8 => 1,
+            DType::U32 | DType::F32 => 4,
+        }
+    }
+    fn from_u32(v: u32) -> io::Result<Self> {
+        match v {
+            0 => Ok(DType::U8),
+            1 => Ok(DType::U32),
+            2 => Ok(DType::F32),
+            _ => Err(invalid_data("unknown dtype")),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FieldSpec {
+    pub dtype: DType,
+    pub shape: Vec<u32>,
+}
+
+impl FieldSpec {
+    pub fn new(dtype: DType, shape: &[u32]) -> Self { Self { dtype, shape: shape.to_vec() } }
+    pub fn num_bytes(&self) -> usize {
+        self.dtype.size() * self.shape.iter().product::<u32>() as usize
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum FieldData<'a> {
+    U8(&'a [u8]),
+    U32(&'a [u32]),
+    F32(&'a [f32]),
+}
+
+impl<'a> FieldData<'a> {
+    pub fn dtype(&self) -> DType {
+        match self {
+            FieldData::U8(_) => DType::U8,
+            FieldData::U32(_) => DType::U32,
+            FieldData::F32(_) => DType::F32,
+        }
+    }
+    fn write_to(&self, buf: &mut Vec<u8>) {
+        match self {
+            FieldData::U8(v) => buf.extend_from_slice(v),
+            FieldData::U32(v) => v.iter().for_each(|x| buf.extend_from_slice(&x.to_le_bytes())),
+            FieldData::F32(v) => v.iter().for_each(|x| buf.extend_from_slice(&x.to_le_bytes())),
+        }
+    }
+}
+
+/// Fields of transitions of [crate::GameSession]: observation, action, reward and done.
+pub fn transition_specs(observation_len: u32) -> Vec<FieldSpec> {
+    vec![
+        FieldSpec::new(DType::U32, &[observation_len]),
+        FieldSpec::new(DType::U32, &[1]),
+        FieldSpec::new(DType::F32, &[1]),
+        FieldSpec::new(DType::U8, &[1]),
+    ]
+}
+
+fn invalid_input(msg: &'static str) -> io::Error { io::Error::new(io::ErrorKind::InvalidInput, msg) }
+fn invalid_data(msg: &'static str) -> io::Error { io::Error::new(io::ErrorKind::InvalidData, msg) }
+
+fn header_size(num_fields: usize) -> u64 { FIXED_HEADER_SIZE + FIELD_HEADER_SIZE * num_fields as u64 }
+
+#[derive(Debug)]
+pub struct RingBufferWriter {
+    file: File,
+    specs: Vec<FieldSpec>,
+    capacity: u32,
+    slot_size: usize,
+    num_written: u64,
+    buf: Vec<u8>,
+}
+
+impl RingBufferWriter {
+    /// Creates or truncates the file at `path`.
+    pub fn create(path: impl AsRef<Path>, capacity: u32, specs: Vec<FieldSpec>) -> io::Result<Self> {
+        if capacity == 0 || specs.is_empty() {
+            return Err(invalid_input("empty ring buffer"));
+        }
+        if specs.iter().any(|s| s.shape.len() > MAX_NDIM) {
+            return Err(invalid_input("too many dimensions"));
+        }
+        let slot_size = specs.iter().map(|s| s.num_bytes()).sum::<usize>();
+        let mut header = Vec::with_capacity(header_size(specs.len()) as usize);
+        header.extend_from_slice(&MAGIC);
+        for v in &[VERSION, capacity, slot_size as u32, specs.len() as u32, 0] {
+            header.extend_from_slice(&v.to_le_bytes());
+        }
+        header.extend_from_slice(&0u64.to_le_bytes());
+        for spec in &specs {
+            header.extend_from_slice(&(spec.dtype as u32).to_le_bytes());
+            header.extend_from_slice(&(spec.shape.len() as u32).to_le_bytes());
+            for i in 0..MAX_NDIM {
+                header.extend_from_slice(&spec.shape.get(i).copied().unwrap_or(0).to_le_bytes());
+            }
+        }
+        let mut file = OpenOptions::new().read(true).write(true).create(true).truncate(true).open(path)?;
+        file.write_all(&header)?;
+        file.set_len(header.len() as u64 + slot_size as u64 * capacity as u64)?;
+        Ok(Self { file, specs, capacity, slot_size, num_written: 0, buf: Vec::with_capacity(slot_size) })
+    }
+    pub fn specs(&self) -> &[FieldSpec] { &self.specs }
+    pub fn num_written(&self) -> u64 { self.num_written }
+    /// Writes a slot, overwriting the oldest one if full.
+    pub fn write(&mut self, fields: &[FieldData]) -> io::Result<()> {
+        if fields.len() != self.specs.len() {
+            return Err(invalid_input("invalid number of fields"));
+        }
+        self.buf.clear();
+        for (data, spec) in fields.iter().zip(self.specs.iter()) {
+            if data.dtype() != spec.dtype {
+                return Err(invalid_input("dtype mismatch"));
+            }
+            let len = self.buf.len();
+            data.write_to(&mut self.buf);
+            if self.buf.len() - len != spec.num_bytes() {
+                return Err(invalid_input("shape mismatch"));
+            }
+        }
+        let slot = self.num_written % self.capacity as u64;
+        self.file.seek(SeekFrom::Start(header_size(self.specs.len()) + slot * self.slot_size as u64))?;
+        self.file.write_all(&self.buf)?;
+        self.num_written += 1;
+        self.file.seek(SeekFrom::Start(NUM_WRITTEN_OFFSET))?;
+        self.file.write_all(&self.num_written.to_le_bytes())?;
+        Ok(())
+    }
+    pub fn write_transition(&mut self, observation: &[u32], action: u32, reward: f32, done: bool) -> io::Result<()> {
+        self.write(&[
+            FieldData::U32(observation),
+            FieldData::U32(&[action]),
+            FieldData::F32(&[reward]),
+            FieldData::U8(&[done as u8]),
+        ])
+    }
+}
+
+/// Reads the slots written by [RingBufferWriter], mainly for tests and tools.
+#[derive(Debug)]
+pub struct RingBufferReader {
+    file: File,
+    specs: Vec<FieldSpec>,
+    capacity: u32,
+    slot_size: usize,
+}
+
+impl RingBufferReader {
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let mut file = File::open(path)?;
+        let mut header = [0u8; FIXED_HEADER_SIZE as usize];
+        file.read_exact(&mut header)?;
+        let u32_at = |b: &[u8], i: usize| u32::from_le_bytes([b[i], b[i + 1], b[i + 2], b[i + 3]]);
+        if header[0..4] != MAGIC {
+            return Err(invalid_data("invalid magic"));
+        }
+        if u32_at(&header, 4) != VERSION {
+            return Err(invalid_data("unsupported version"));
+        }
+        let capacity = u32_at(&header, 8);
+        let slot_size = u32_at(&header, 12) as usize;
+        let num_fields = u32_at(&header, 16) as usize;
+        if capacity == 0 || num_fields == 0 {
+            return Err(invalid_data("empty ring buffer"));
+        }
+        let file_size = file.metadata()?.len();
+        if header_size(num_fields) + slot_size as u64 * capacity as u64 > file_size {
+            return Err(invalid_data("truncated file"));
+        }
+        let mut fields = vec![0u8; FIELD_HEADER_SIZE as usize * num_fields];
+        file.read_exact(&mut fields)?;
+        let mut specs = Vec::with_capacity(num_fields);
+        let mut total_size = 0u64;
+        for b in fields.chunks(FIELD_HEADER_SIZE as usize) {
+            let ndim = u32_at(b, 4) as usize;
+            if ndim > MAX_NDIM {
+                return Err(invalid_data("too many dimensions"));
+            }
+            let shape = (0..ndim).map(|i| u32_at(b, 8 + 4 * i)).collect::<Vec<_>>();
+            let dtype = DType::from_u32(u32_at(b, 0))?;
+            total_size = shape.iter()
+                .try_fold(dtype.size() as u64, |n, &d| n.checked_mul(d as u64))
+                .and_then(|n| total_size.checked_add(n))
+                .ok_or_else(|| invalid_data("slot size mismatch"))?;
+            specs.push(FieldSpec { dtype, shape });
+        }
+        if total_size != slot_size as u64 {
+            return Err(invalid_data("slot size mismatch"));
+        }
+        Ok(Self { file, specs, capacity, slot_size })
+    }
+    pub fn specs(&self) -> &[FieldSpec] { &self.specs }
+    pub fn capacity(&self) -> u32 { self.capacity }
+    pub fn num_written(&mut self) -> io::Result<u64> {
+        let mut b = [0u8; 8];
+        self.file.seek(SeekFrom::Start(NUM_WRITTEN_OFFSET))?;
+        self.file.read_exact(&mut b)?;
+        Ok(u64::from_le_bytes(b))
+    }
+    /// Returns the raw bytes of each field of the `index`-th slot written,
+    /// or `None` if not written yet or already overwritten.
+    ///
+    /// Since the writer overwrites a slot before updating the number of written slots, the oldest
+    /// slot of a full buffer may be being overwritten and is treated as overwritten. The number is
+    /// read again after the copy to detect slots overwritten while being read.
+    pub fn read(&mut self, index: u64) -> io::Result<Option<Vec<Vec<u8>>>> {
+        let capacity = self.capacity as u64;
+        let num_written = self.num_written()?;
+        if index >= num_written || index + capacity <= num_written {
+            return Ok(None);
+        }
+        let slot = index % capacity;
+        let mut buf = vec![0u8; self.slot_size];
+        self.file.seek(SeekFrom::Start(header_size(self.specs.len()) + slot * self.slot_size as u64))?;
+        self.file.read_exact(&mut buf)?;
+        if index + capacity <= self.num_written()? {
+            return Ok(None);
+        }
+        let mut r = Vec::with_capacity(self.specs.len());
+        let mut offset = 0;
+        for spec in &self.specs {
+            r.push(buf[offset..offset + spec.num_bytes()].to_vec());
+            offset += spec.num_bytes();
+        }
+        Ok(Some(r))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ring_buffer() {
+        let path = std::env::temp_dir().join(format!("deep-trinity-ring-buffer-{}", std::process::id()));
+        let mut writer = RingBufferWriter::create(&path, 2, transition_specs(3)).unwrap();
+        let mut reader = RingBufferReader::open(&path).unwrap();
+        assert_eq!(transition_specs(3), reader.specs());
+        assert_eq!(None, reader.read(0).unwrap());
+        writer.write_transition(&[1, 2, 3], 4, 0.5, false).unwrap();
+        writer.write_transition(&[5, 6, 7], 8, 1.0, true).unwrap();
+        writer.write_transition(&[9, 10, 11], 12, 0.0, false).unwrap();
+        assert_eq!(3, reader.num_written().unwrap());
+        assert_eq!(None, reader.read(0).unwrap());
+        // The next write overwrites this slot.
+        assert_eq!(None, reader.read(1).unwrap());
+        let fields = reader.read(2).unwrap().unwrap();
+        assert_eq!(vec![9, 0, 0, 0, 10, 0, 0, 0, 11, 0, 0, 0], fields[0]);
+        assert_eq!(vec![12, 0, 0, 0], fields[1]);
+        assert_eq!(0.0f32.to_le_bytes().to_vec(), fields[2]);
+        assert_eq!(vec![0], fields[3]);
+        assert_eq!(None, reader.read(3).unwrap());
+        assert!(writer.write_transition(&[1, 2], 0, 0.0, false).is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_ring_buffer_invalid_header() {
+        let path = std::env::temp_dir().join(format!("deep-trinity-ring-buffer-header-{}", std::process::id()));
+        RingBufferWriter::create(&path, 2, transition_specs(3)).unwrap();
+        let original = std::fs::read(&path).unwrap();
+        let corrupt = |offset: usize, value: u32| {
+            let mut bytes = original.clone();
+            bytes[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+            std::fs::write(&path, bytes).unwrap();
+            RingBufferReader::open(&path).map(|_| ()).unwrap_err().kind()
+        };
+        // slot size
+        assert_eq!(io::ErrorKind::InvalidData, corrupt(12, 4));
+        assert_eq!(io::ErrorKind::InvalidData, corrupt(12, 1 << 20));
+        // capacity
+        assert_eq!(io::ErrorKind::InvalidData, corrupt(8, 0));
+        // ndim and shape of the first field
+        assert_eq!(io::ErrorKind::InvalidData, corrupt(36, MAX_NDIM as u32 + 1));
+        assert_eq!(io::ErrorKind::InvalidData, corrupt(40, u32::MAX));
+        std::fs::write(&path, &original).unwrap();
+        assert!(RingBufferReader::open(&path).is_ok());
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -1,122 +1,12 @@
-use std::error::Error;
 use std::ops::Deref;
 use std::process::exit;
-use std::rc::Rc;
+use std::sync::Arc;
 use std::str::FromStr;
 use clap::Parser;
 use rand::prelude::*;
 use deep_trinity_core::prelude::*;
-use deep_trinity_bot::Action;
-use deep_trinity_tree::arena::{NodeArena, NodeHandle};
-
-struct NodeData {
-    by_action: Option<Action>,
-    game: Game<'static>,
-    pps: Vec<Rc<PiecePlacement>>,
-    resource: MoveDecisionResource,
-}
-
-impl NodeData {
-    pub fn new(by_action: Option<Action>, game: Game<'static>, pps: Vec<Rc<PiecePlacement>>) -> Result<Self, &'static str> {
-        let resource = MoveDecisionResource::with_game(&game)?;
-        Ok(Self { by_action, game, pps, resource })
-    }
-}
-
-type VecNodeArena = deep_trinity_tree::arena::VecNodeArena<NodeData>;
-
-fn expand_node(arena: &mut VecNodeArena, node: NodeHandle) {
-    if let Some(fp) = arena[node].data.game.state.falling_piece.clone() {
-        let pps_len = arena[node].data.pps.len();
-        for i in 0..pps_len {
-            let pp = arena[node].data.pps.get(i).cloned().unwrap();
-            let (game, pps) = {
-                let data = &arena[node].data;
-                if pp.piece != fp.piece() || !data.resource.dst_candidates.contains(&pp.placement) {
-                    continue;
-                }
-                let mut game = data.game.clone();
-                game.state.falling_piece = Some(FallingPiece::new(fp.piece().default_spec(), pp.placement));
-                game.lock().unwrap();
-                let mut pps = data.pps.clone();
-                pps.remove(i);
-                (game, pps)
-            };
-            if game.state.falling_piece.is_some() {
-                arena.append_child(node, NodeData::new(
-                    Some(Action::Move(MoveTransition::new(pp.placement.clone(), None))),
-                    game,
-                    pps,
-                ).unwrap());
-            }
-        }
-    }
-    if arena[node].data.game.state.can_hold {
-        let mut game = arena[node].data.game.clone();
-        game.hold().unwrap();
-        if game.state.falling_piece.is_some() {
-            let pps = arena[node].data.pps.clone();
-            let child_data = NodeData::new(Some(Action::Hold), game, pps).unwrap();
-            arena.append_child(node, child_data);
-        }
-    }
-}
-
-// macro_rules! pp {
-//     ($piece_name:ident, $orientation:literal, $x:literal, $y:literal) => {
-//         PiecePlacement::new(
-//             deep_trinity_core::Piece::$piece_name,
-//             Placement::new(
-//                 deep_trinity_core::ORIENTATIONS[$orientation],
-//                 deep_trinity_grid::Vec2($x, $y),
-//             ),
-//         )
-//     }
-// }
-
-#[derive(Copy, Clone, Debug)]
-struct PiecePlacement {
-    pub piece: Piece,
-    pub placement: Placement,
-}
-
-impl PiecePlacement {
-    fn new(piece: Piece, placement: Placement) -> Self {
-        Self { piece, placement }
-    }
-}
-
-impl FromStr for PiecePlacement {
-    type Err = String;
-
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let mut parts = s.split(",");
-        let err_msg = "Invalid format.";
-        let mut part0 = parts.next().ok_or::<Self::Err>(err_msg.into())?.chars();
-        let part1 = parts.next().ok_or::<Self::Err>(err_msg.into())?;
-        let part2 = parts.next().ok_or::<Self::Err>(err_msg.into())?;
-
-        let piece = if let Some(c) = part0.next() {
-            if let Ok(p) = Piece::try_from_char(c) {
-                p
-            } else {
-                return Err(format!("'{}' is not piece character.", c).into());
-            }
-        } else {
-            return Err("A piece character is required..".into());
-        };
-        let orientation = if let Some(c) = part0.next() {
-            Orientation::from_str(c.to_string().as_str()).map_err(|e| e.to_string())?
-        } else {
-            return Err("An orientation value is required..".into());
-        };
-
-        let x = i8::from_str(part1).map_err(|_| Self::Err::from("Invalid x value."))?;
-        let y = i8::from_str(part2).map_err(|_| Self::Err::from("invalid y value."))?;
-
-        Ok(Self::new(piece, Placement::new(orientation, (x, y).into())))
-    }
-}
+use deep_trinity_tree::arena::NodeArena;
+use stack_resolver::{PiecePlacement, SolutionIter};
 
 #[derive(Debug)]
 struct PieceList(Vec<Piece>);
@@ -146,6 +36,18 @@ struct Args {
     pieces: PieceList,
     #[clap(long)]
     debug: bool,
+    /// Stop after finding the number of solutions.
+    #[clap(short = 'k', long)]
+    max_solutions: Option<usize>,
+    /// List only one of the solutions leading to the same board, with the number of them.
+    #[clap(short, long)]
+    unique: bool,
+    /// Print each solution in fumen.
+    #[clap(short, long)]
+    fumen: bool,
+    /// The initial playfield in fumen, compact or ASCII art (see `Playfield::parse`).
+    #[clap(short, long)]
+    board: Option<String>,
     positions: Vec<PiecePlacement>,
 }
 
@@ -158,7 +60,7 @@ fn main() {
 
     let debug_trace = args.debug;
 
-    let pps = args.positions.iter().map(|&pp| Rc::new(pp)).collect::<Vec<_>>();
+    let pps = args.positions.iter().map(|&pp| Arc::new(pp)).collect::<Vec<_>>();
     let pps_len = pps.len();
     println!("### Positions");
     {
@@ -171,6 +73,15 @@ fn main() {
     }
 
     let mut initial_game: Game = Default::default();
+    if let Some(board) = args.board.as_ref() {
+        initial_game.state.playfield = match Playfield::parse(board) {
+            Ok(pf) => pf,
+            Err(e) => {
+                println!("ERROR: Invalid board: {}", e);
+                exit(1);
+            }
+        };
+    }
     initial_game.state.playfield.grid.disable_basic_grid();
     initial_game.supply_next_pieces(args.pieces.as_slice());
     {
@@ -187,39 +98,29 @@ fn main() {
     initial_game.setup_falling_piece(None).unwrap();
     println!("\n### Initial Game\n{}", initial_game);
 
-    let mut arena = VecNodeArena::default();
-    let root = arena.create(NodeData::new(None, initial_game.clone(), pps).unwrap());
-
-    let mut open = vec![root];
-    while !open.is_empty() {
-        let target = open.pop().unwrap();
-        expand_node(&mut arena, target);
-        open.extend(arena[target].children());
-    }
+    let mut iter = SolutionIter::new(initial_game.clone(), pps, Default::default()).unwrap();
+    let found = iter.by_ref().take(args.max_solutions.unwrap_or(usize::MAX)).collect::<Vec<_>>();
+    let r = iter.into_result();
     if debug_trace {
-        arena.visit_depth_first(root, |arena, node, ctx| {
-            let indent = "  ".repeat(ctx.depth());
-            let n = &arena[node];
-            println!("{}- by_action: {:?}", indent, n.data.by_action);
-            println!("{}  game: |-\n{}", indent, n.data.game.to_string().split("\n")
-                .map(|line| format!("{}    {}", indent, line)).collect::<Vec<_>>().join("\n"));
-            println!("{}  children: {}", indent, if n.is_leaf() { "[]" } else { "" });
-        });
+        r.write_tree(&mut std::io::stdout()).unwrap();
     }
 
     println!("\n### Result");
 
-    let mut found = Vec::new();
-    arena.visit_depth_first(root, |arena, node, ctx| {
-        if arena[node].data.game.stats.lock == pps_len as u32 {
-            found.push(arena.route(node));
-            ctx.skip();
-        }
-    });
+    let found = if args.unique {
+        r.dedup_by_board(&found).into_iter().map(|u| (u.node, u.num_orderings)).collect::<Vec<_>>()
+    } else {
+        found.into_iter().map(|node| (node, 1)).collect()
+    };
 
-    for (i, route) in found.iter().enumerate() {
-        println!("--- {} ---", i);
-        for n in route.iter() {
+    let arena = &r.arena;
+    for (i, &(node, num_orderings)) in found.iter().enumerate() {
+        if args.unique {
+            println!("--- {} ({} orderings) ---", i, num_orderings);
+        } else {
+            println!("--- {} ---", i);
+        }
+        for n in arena.route(node).iter() {
             let prev_game = arena[*n].parent().map_or(&initial_game, |pn| &arena[pn].data.game);
             let data = &arena[*n].data;
             if let Some(action) = data.by_action {
@@ -231,6 +132,12 @@ fn main() {
                 );
             }
         }
+        if args.fumen {
+            match r.plan(node).and_then(|plan| plan.to_fumen(&initial_game)) {
+                Ok(fumen) => println!("{}", fumen),
+                Err(e) => println!("ERROR: {}", e),
+            }
+        }
     }
 }
 
@@ -249,4 +156,38 @@ mod tests {
             .success();
         io::stdout().write_all(&r.get_output().stdout).unwrap()
     }
+
+    #[test]
+    fn unique() {
+        let r = Command::cargo_bin("move-finder")
+            .unwrap()
+            .args("-u -p ISZTOJLISZTOJL I0,2,-2 O0,7,-1 L1,-1,0 S1,5,0 Z0,3,0 J2,3,2 T2,1,0".split(" ").collect::<Vec<_>>())
+            .assert()
+            .success();
+        let stdout = String::from_utf8(r.get_output().stdout.clone()).unwrap();
+        assert!(stdout.contains("orderings) ---"));
+    }
+
+    #[test]
+    fn max_solutions() {
+        let r = Command::cargo_bin("move-finder")
+            .unwrap()
+            .args("-k 2 -p ISZTOJLISZTOJL I0,2,-2 O0,7,-1 L1,-1,0 S1,5,0 Z0,3,0 J2,3,2 T2,1,0".split(" ").collect::<Vec<_>>())
+            .assert()
+            .success();
+        let stdout = String::from_utf8(r.get_output().stdout.clone()).unwrap();
+        assert!(stdout.contains("--- 1 ---"));
+        assert!(!stdout.contains("--- 2 ---"));
+    }
+
+    #[test]
+    fn fumen() {
+        let r = Command::cargo_bin("move-finder")
+            .unwrap()
+            .args("-f -k 1 -p ISZTOJLISZTOJL I0,2,-2 O0,7,-1 L1,-1,0 S1,5,0 Z0,3,0 J2,3,2 T2,1,0".split(" ").collect::<Vec<_>>())
+            .assert()
+            .success();
+        let stdout = String::from_utf8(r.get_output().stdout.clone()).unwrap();
+        assert!(stdout.contains("\nv115@vhAAgH"));
+    }
 }
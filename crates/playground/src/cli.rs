@@ -50,9 +50,21 @@ pub fn run() -> Result<(), Box<dyn Error>> {
         }
         write!(stdout, "{}{}", termion::clear::All, termion::cursor::Goto(1, 1))?;
         writeln!(stdout, "{} [ms] / {} [nodes] = {} [us/node] ", bot.expansion_duration.as_millis(), bot.num_expanded, bot.expansion_duration.as_micros() as usize / bot.num_expanded)?;
+        if let Some(info) = bot.last_think_info() {
+            writeln!(stdout, "depth: {}, score: {:?}, candidates: {}", info.max_depth, info.chosen_score, info.candidate_scores.len())?;
+        }
         write!(stdout, "{}", game)?;
         std::thread::sleep(std::time::Duration::from_millis(10));
     }
 
     Ok(())
-}
\ No newline at end of file
+}
+
+/// Runs the bot declared in the TOML file (see [deep_trinity_bot::config::BotConfig]).
+pub fn run_with_config(path: &str) -> Result<(), Box<dyn Error>> {
+    let conf = deep_trinity_bot::config::BotConfig::load(path)?;
+    let mut bot = conf.create_bot(&deep_trinity_bot::registry::BotFactory::default())?;
+    let game = conf.runner.to_runner()?.run_with_no_hooks(bot.as_mut())?;
+    println!("{}", game);
+    Ok(())
+}
@@ -1,6 +1,12 @@
 mod cli;
 mod opener_adviser;
+mod review;
 
 fn main() {
-    cli::run().unwrap();
+    let mut args = std::env::args().skip(1);
+    match args.next().as_deref() {
+        Some("review") => review::run(&args.next().unwrap_or_else(|| "simple_tree".into())).unwrap(),
+        Some(config_path) => cli::run_with_config(config_path).unwrap(),
+        None => cli::run().unwrap(),
+    }
 }
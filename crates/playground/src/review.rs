@@ -0,0 +1,107 @@
+//! Suggestion overlay for human play: after each decision, shows what the bot would have played
+//! and how much better it is.
+#![allow(deprecated)]
+
+use std::error::Error;
+use std::io::{stdin, stdout, Write};
+use rand::prelude::StdRng;
+use rand::SeedableRng;
+use termion::event::Key;
+use termion::input::TermRead;
+use termion::raw::IntoRawMode;
+use deep_trinity_core::{MoveTransition, RandomPieceGenerator};
+use deep_trinity_core::bot::{Action, Suggestion};
+use deep_trinity_bot::{Bot, Game};
+use deep_trinity_bot::registry::BotFactory;
+
+const HELP: &str = "←/→: shift, ↓: soft drop, z/x: rotate, c: hold, space: hard drop, q: quit";
+
+fn format_action(action: &Action) -> String {
+    match action {
+        Action::Hold => "hold".into(),
+        Action::Move(mt) => format!("{:?} {:?}", mt.placement.orientation, mt.placement.pos),
+    }
+}
+
+fn format_score(score: Option<f32>) -> String {
+    score.map_or("-".into(), |s| format!("{:.2}", s))
+}
+
+/// Same as [deep_trinity_core::bot::suggest] but for the bots of [BotFactory].
+fn suggest(bot: &mut dyn Bot, game: &Game, played: &Action) -> Result<Suggestion, Box<dyn Error>> {
+    let suggested = bot.think(game)?.into();
+    let info = bot.last_think_info().map(|info| info.map_actions(Into::into));
+    Ok(Suggestion::new(suggested, info.as_ref(), |a: &Action| a.is_same_decision(played)))
+}
+
+/// `bot_name` is the one registered in [BotFactory].
+pub fn run(bot_name: &str) -> Result<(), Box<dyn Error>> {
+    let mut bot = BotFactory::default().create(bot_name, &Default::default())?;
+    let mut stdout = stdout().into_raw_mode()?;
+    let mut keys = stdin().keys();
+
+    let mut game: Game = Default::default();
+    let mut pg = RandomPieceGenerator::new(StdRng::seed_from_u64(0));
+    game.supply_next_pieces(&pg.generate());
+    game.setup_falling_piece(None)?;
+    // The state where the current decision started.
+    let mut decision_game = game.clone();
+    let mut last: Option<(Action, Suggestion)> = None;
+    let mut total_delta = 0.0;
+    loop {
+        write!(stdout, "{}{}", termion::clear::All, termion::cursor::Goto(1, 1))?;
+        for line in format!("{}", game).lines() {
+            write!(stdout, "{}\r\n", line)?;
+        }
+        if let Some((played, s)) = last.as_ref() {
+            write!(stdout, "you: {} ({})\r\n", format_action(played), format_score(s.played_score))?;
+            write!(stdout, "bot: {} ({})\r\n", format_action(&s.suggested), format_score(s.suggested_score))?;
+            write!(stdout, "delta: {} / total: {:.2}\r\n", format_score(s.score_delta()), total_delta)?;
+        }
+        write!(stdout, "{}\r\n", HELP)?;
+        stdout.flush()?;
+        if game.state.is_game_over() {
+            break;
+        }
+
+        let key = match keys.next() {
+            Some(key) => key?,
+            None => break,
+        };
+        let played = match key {
+            Key::Char('q') | Key::Ctrl('c') => break,
+            Key::Left => { let _ = game.shift(-1, false); None }
+            Key::Right => { let _ = game.shift(1, false); None }
+            Key::Down => { let _ = game.drop(1); None }
+            Key::Char('z') => { let _ = game.rotate(-1); None }
+            Key::Char('x') | Key::Up => { let _ = game.rotate(1); None }
+            Key::Char('c') => {
+                if game.hold().is_err() {
+                    continue;
+                }
+                Some(Action::Hold)
+            }
+            Key::Char(' ') => {
+                game.firm_drop()?;
+                let fp = game.state.falling_piece.as_ref().unwrap();
+                let mt = fp.last_move_transition(true).unwrap_or_else(|| MoveTransition::new(fp.placement, None));
+                game.lock()?;
+                Some(Action::Move(mt))
+            }
+            _ => None,
+        };
+        if let Some(played) = played {
+            let s = suggest(bot.as_mut(), &decision_game, &played)?;
+            total_delta += s.score_delta().unwrap_or(0.0);
+            last = Some((played, s));
+            if game.should_supply_next_pieces() {
+                game.supply_next_pieces(&pg.generate());
+            }
+            if game.state.falling_piece.is_none() && !game.state.is_game_over() {
+                game.setup_falling_piece(None)?;
+            }
+            decision_game = game.clone();
+        }
+    }
+    Ok(())
+}
@@ -1,4 +1,4 @@
-from typing import List, Optional, Set
+from typing import Callable, Dict, List, Optional, Set, Tuple, Union
 
 
 class Cell:
@@ -47,6 +47,10 @@ class Game:
 
     def fast_mode(self): ...
 
+    def set_color_tracking(self, enabled: bool): ...
+
+    def is_color_tracking(self) -> bool: ...
+
     def should_supply_next_pieces(self) -> bool: ...
 
     def supply_next_pieces(self, piece_cell_ids: List[int]): ...
@@ -82,3 +86,77 @@ class Game:
     def __str__(self) -> str: ...
 
     def __copy__(self) -> Game: ...
+
+
+class Action:
+    @staticmethod
+    def hold() -> Action: ...
+
+    @staticmethod
+    def move_to(dst: Placement) -> Action: ...
+
+    @property
+    def is_hold(self) -> bool: ...
+
+    @property
+    def dst(self) -> Optional[Placement]: ...
+
+
+class Bot:
+    def __init__(self, name: str, params: Optional[Dict[str, str]] = None): ...
+
+    @staticmethod
+    def names() -> List[str]: ...
+
+    def think(self, game: Game) -> Action: ...
+
+
+def run_bot(bot: Union[Bot, Callable[[Game], Action]], max_iterations: int, random_seed: Optional[int] = None) -> Game: ...
+
+
+def piece_preview_grid(piece_cell_id: int) -> List[List[int]]: ...
+
+
+class Environment:
+    def __init__(self): ...
+    def clone(self) -> Environment: ...
+    def game_str(self) -> str: ...
+    @staticmethod
+    def num_actions() -> int: ...
+    def legal_actions(self) -> List[int]: ...
+    def observation(self) -> List[int]: ...
+    def last_reward(self) -> float: ...
+    def is_done(self) -> bool: ...
+    def is_truncated(self) -> bool: ...
+    def reset(self, rand_seed: Optional[int] = None): ...
+    def reset_with(self, rand_seed: Optional[int] = None, garbage_rows: int = 0,
+                   starting_piece_cell_ids: Optional[List[int]] = None, queue_length: Optional[int] = None,
+                   max_pieces: Optional[int] = None, survival_stage: Optional[int] = None): ...
+    def step(self, action_id: int): ...
+    def set_n_step(self, horizon: Optional[int], gamma: float = 0.99, attack_weight: float = 1.0,
+                   survival_bonus: float = 0.1): ...
+    def take_n_step_returns(self) -> List[float]: ...
+
+
+class VecEnvironment:
+    """Batched environments stepped in parallel without the GIL. Requires numpy."""
+    def __init__(self, num_envs: int, rand_seed: Optional[int] = None): ...
+    @property
+    def num_envs(self) -> int: ...
+    def set_num_threads(self, n: int): ...
+    def legal_actions(self) -> List[List[int]]: ...
+    def observation(self) -> "numpy.ndarray": ...
+    def reset(self, rand_seed: Optional[int] = None): ...
+    def reset_with(self, rand_seed: Optional[int] = None, garbage_rows: int = 0,
+                   starting_piece_cell_ids: Optional[List[int]] = None, queue_length: Optional[int] = None,
+                   max_pieces: Optional[int] = None, survival_stage: Optional[int] = None): ...
+    def step_batch(self, action_ids: List[int]) -> Tuple["numpy.ndarray", "numpy.ndarray", "numpy.ndarray"]: ...
+
+
+class TransitionWriter:
+    """Writes transitions to a file-backed ring buffer, e.g. in /dev/shm.
+    The header has the shapes and dtypes of the fields: observation, action, reward and done."""
+    def __init__(self, path: str, capacity: int, observation_len: int): ...
+    @property
+    def num_written(self) -> int: ...
+    def write(self, observation: List[int], action_id: int, reward: float, done: bool): ...
@@ -4,6 +4,7 @@ from deep_trinity import Game, Cell
 def test_game():
     game = Game()
     game.fast_mode()
+    assert not game.is_color_tracking()
     assert game.should_supply_next_pieces()
     pieces = [getattr(Cell, s).id for s in "SZLJITO"]
     game.supply_next_pieces(pieces)
@@ -11,5 +12,7 @@ def test_game():
     game.firm_drop()
     assert game.lock()
     assert len(str(game)) > 0
+    game.set_color_tracking(True)
+    assert game.is_color_tracking()
     resource = game.get_move_decision_resource()
     assert len(resource.get_dst_candidates()) > 0
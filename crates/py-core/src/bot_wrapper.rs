@@ -0,0 +1,108 @@
+// The bots are still implemented with the deprecated trait of deep_trinity_bot.
+#![allow(deprecated)]
+
+use std::collections::HashMap;
+use std::error::Error;
+use deep_trinity_core::{Game, MoveTransition};
+use deep_trinity_bot::{Action, Bot, BotRunner};
+use deep_trinity_bot::registry::BotFactory;
+use pyo3::prelude::*;
+use crate::core_wrapper::{GameWrapper, PlacementWrapper};
+
+#[derive(Copy, Clone)]
+#[pyclass(name = "Action")]
+pub struct ActionWrapper {
+    action: Action,
+}
+
+#[pymethods]
+impl ActionWrapper {
+    #[staticmethod]
+    pub fn hold() -> Self { Self { action: Action::Hold } }
+    #[staticmethod]
+    pub fn move_to(dst: PlacementWrapper) -> Self {
+        Self { action: Action::Move(MoveTransition::new(dst.placement, None)) }
+    }
+    #[getter]
+    pub fn is_hold(&self) -> PyResult<bool> { Ok(self.action == Action::Hold) }
+    /// `None` if hold.
+    #[getter]
+    pub fn dst(&self) -> PyResult<Option<PlacementWrapper>> {
+        Ok(match self.action {
+            Action::Move(mt) => Some(PlacementWrapper { placement: mt.placement }),
+            Action::Hold => None,
+        })
+    }
+}
+
+/// Bots of deep_trinity_bot instantiated by name (see [BotFactory]).
+#[pyclass(name = "Bot", unsendable)]
+pub struct BotWrapper {
+    bot: Box<dyn Bot>,
+}
+
+#[pymethods]
+impl BotWrapper {
+    #[new]
+    pub fn new(name: &str, params: Option<HashMap<String, String>>) -> PyResult<Self> {
+        let bot = BotFactory::default().create(name, &params.unwrap_or_default())
+            .map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))?;
+        Ok(Self { bot })
+    }
+    #[staticmethod]
+    pub fn names() -> Vec<String> {
+        BotFactory::default().names().map(|s| s.to_string()).collect()
+    }
+    pub fn think(&mut self, game: &GameWrapper) -> PyResult<ActionWrapper> {
+        let action = self.bot.think(&game.game)
+            .map_err(|e| pyo3::exceptions::PyRuntimeError::new_err(e.to_string()))?;
+        Ok(ActionWrapper { action })
+    }
+}
+
+/// A python callable `(Game) -> Action` as a bot.
+pub struct PyCallableBot {
+    callable: PyObject,
+}
+
+impl Bot for PyCallableBot {
+    fn think(&mut self, game: &Game<'static>) -> Result<Action, Box<dyn Error>> {
+        Python::with_gil(|py| {
+            let game = GameWrapper { game: game.clone() };
+            let r = self.callable.call1(py, (game,))?;
+            let action: ActionWrapper = r.extract(py)?;
+            Ok(action.action)
+        })
+    }
+}
+
+/// Runs `bot`, which is a `Bot` or a python callable `(Game) -> Action`, and returns the final game.
+#[pyfunction]
+pub fn run_bot(bot: &PyAny, max_iterations: usize, random_seed: Option<u64>) -> PyResult<GameWrapper> {
+    let runner = BotRunner::new(max_iterations, true, random_seed, false);
+    let r = if let Ok(mut wrapper) = bot.extract::<PyRefMut<BotWrapper>>() {
+        runner.run_with_no_hooks(wrapper.bot.as_mut())
+    } else {
+        let mut bot = PyCallableBot { callable: bot.into() };
+        runner.run_with_no_hooks(&mut bot)
+    };
+    let game = r.map_err(|e| pyo3::exceptions::PyRuntimeError::new_err(e.to_string()))?;
+    Ok(GameWrapper { game })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_bot_wrapper() {
+        assert!(BotWrapper::names().contains(&"simple".to_string()));
+        let mut bot = BotWrapper::new("simple", None).unwrap();
+        let mut game = GameWrapper { game: Default::default() };
+        game.game.supply_next_pieces(&[deep_trinity_core::Piece::T]);
+        game.game.setup_falling_piece(None).unwrap();
+        let action = bot.think(&game).unwrap();
+        assert!(!action.is_hold().unwrap());
+        assert!(action.dst().unwrap().is_some());
+    }
+}
@@ -55,7 +55,7 @@ impl CellWrapper {
 #[derive(Copy, Clone, Hash, PartialEq, Eq)]
 #[pyclass(name = "Placement")]
 pub struct PlacementWrapper {
-    placement: Placement,
+    pub(crate) placement: Placement,
 }
 
 #[pymethods]
@@ -94,7 +94,7 @@ impl MoveDecisionResourceWrapper {
 
 #[pyclass(name = "Game")]
 pub struct GameWrapper {
-    game: Game<'static>,
+    pub(crate) game: Game<'static>,
 }
 
 #[pymethods]
@@ -107,6 +107,13 @@ impl GameWrapper {
         self.game.performance_mode();
         Ok(())
     }
+    pub fn set_color_tracking(&mut self, enabled: bool) -> PyResult<()> {
+        self.game.set_color_tracking(enabled);
+        Ok(())
+    }
+    pub fn is_color_tracking(&self) -> PyResult<bool> {
+        Ok(self.game.is_color_tracking())
+    }
     pub fn should_supply_next_pieces(&self) -> PyResult<bool> {
         Ok(self.game.should_supply_next_pieces())
     }
@@ -201,6 +208,18 @@ impl GameWrapper {
     }
 }
 
+/// Returns the cell ids of the piece cropped to its blocks for the next and hold previews.
+/// The rows are from the bottom.
+#[pyfunction]
+pub fn piece_preview_grid(piece_cell_id: u8) -> PyResult<Vec<Vec<u8>>> {
+    let cell = Cell::try_from_u8(piece_cell_id).map_err(pyo3::exceptions::PyValueError::new_err)?;
+    let p = cell.try_to_piece().map_err(pyo3::exceptions::PyValueError::new_err)?;
+    let grid = p.default_spec().preview_grid();
+    Ok((0..grid.height())
+        .map(|y| (0..grid.width()).map(|x| grid.cell((x, y).into()).to_u8()).collect())
+        .collect())
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -211,4 +230,11 @@ mod test {
         g.set_playfield_with_u64_rows(vec![0b1100110011, 0, 0, 0, 0, 0, 0]).unwrap();
         println!("{}", g.__str__().unwrap());
     }
+
+    #[test]
+    fn test_piece_preview_grid() {
+        let t = Cell::T.to_u8();
+        assert_eq!(vec![vec![t, t, t], vec![0, t, 0]], piece_preview_grid(t).unwrap());
+        assert!(piece_preview_grid(Cell::Garbage.to_u8()).is_err());
+    }
 }
@@ -1,7 +1,11 @@
 mod core_wrapper;
+mod bot_wrapper;
 
 use pyo3::prelude::*;
-use ml_core::GameSession;
+use pyo3::types::PyBytes;
+use deep_trinity_core::Cell;
+use ml_core::{GameSession, ResetOptions};
+use ml_core::batch::GameSessionBatch;
 
 fn to_py_err(e: &'static str) -> PyErr {
     pyo3::exceptions::PyRuntimeError::new_err(e)
@@ -31,30 +35,173 @@ impl Environment {
     pub fn observation(&self) -> Vec<u32> { self.session.observation() }
     pub fn last_reward(&self) -> f32 { self.session.last_reward() }
     pub fn is_done(&self) -> bool { self.session.is_done() }
+    pub fn is_truncated(&self) -> bool { self.session.is_truncated() }
     pub fn reset(&mut self, rand_seed: Option<u64>) -> PyResult<()> {
         self.session.reset(rand_seed).map_err(to_py_err)
     }
+    /// Switches to the n-step rewards of placements. `horizon=None` disables it.
+    #[pyo3(signature = (horizon, gamma=0.99, attack_weight=1.0, survival_bonus=0.1))]
+    pub fn set_n_step(&mut self, horizon: Option<usize>, gamma: f32, attack_weight: f32, survival_bonus: f32) {
+        let options = horizon.map(|horizon| ml_core::n_step::NStepOptions { horizon, gamma, attack_weight, survival_bonus });
+        self.session.set_n_step(options);
+    }
+    pub fn take_n_step_returns(&mut self) -> Vec<f32> { self.session.take_n_step_returns() }
+    /// The options are kept for later `reset()`.
+    #[pyo3(signature = (rand_seed=None, garbage_rows=0, starting_piece_cell_ids=None, queue_length=None, max_pieces=None, survival_stage=None))]
+    pub fn reset_with(
+        &mut self,
+        rand_seed: Option<u64>,
+        garbage_rows: usize,
+        starting_piece_cell_ids: Option<Vec<u8>>,
+        queue_length: Option<usize>,
+        max_pieces: Option<usize>,
+        survival_stage: Option<u32>,
+    ) -> PyResult<()> {
+        let options = to_reset_options(rand_seed, garbage_rows, starting_piece_cell_ids, queue_length, max_pieces, survival_stage)?;
+        self.session.reset_with(options).map_err(to_py_err)
+    }
     pub fn step(&mut self, action_id: u32) -> PyResult<()> {
         self.session.step(ml_core::Action(action_id)).map_err(to_py_err)
     }
 }
 
+fn to_reset_options(
+    rand_seed: Option<u64>,
+    garbage_rows: usize,
+    starting_piece_cell_ids: Option<Vec<u8>>,
+    queue_length: Option<usize>,
+    max_pieces: Option<usize>,
+    survival_stage: Option<u32>,
+) -> PyResult<ResetOptions> {
+    let mut starting_pieces = Vec::new();
+    for cell_id in starting_piece_cell_ids.unwrap_or_default() {
+        let cell = Cell::try_from_u8(cell_id).map_err(pyo3::exceptions::PyValueError::new_err)?;
+        starting_pieces.push(cell.try_to_piece().map_err(pyo3::exceptions::PyValueError::new_err)?);
+    }
+    let rising_garbage = survival_stage.map(|stage| ml_core::survival_curriculum(stage, rand_seed));
+    Ok(ResetOptions { rand_seed, garbage_rows, starting_pieces, queue_length, max_pieces, rising_garbage })
+}
+
+/// Returns a numpy array of `dtype` with the shape `(n, -1)` viewing the bytes.
+fn to_ndarray<'py>(py: Python<'py>, bytes: &[u8], dtype: &str, n: usize) -> PyResult<&'py PyAny> {
+    let numpy = py.import("numpy")?;
+    let a = numpy.call_method1("frombuffer", (PyBytes::new(py, bytes), dtype))?;
+    a.call_method1("reshape", (n, -1))
+}
+
+fn u32s_to_bytes(v: &[u32]) -> Vec<u8> { v.iter().flat_map(|x| x.to_ne_bytes()).collect() }
+
+/// Batched environments which are stepped in parallel without the GIL.
+/// Observations and results are returned as numpy arrays.
+#[pyclass]
+struct VecEnvironment {
+    batch: GameSessionBatch,
+}
+
+#[pymethods]
+impl VecEnvironment {
+    #[new]
+    fn new(num_envs: usize, rand_seed: Option<u64>) -> PyResult<Self> {
+        if num_envs == 0 {
+            return Err(pyo3::exceptions::PyValueError::new_err("num_envs should be positive."));
+        }
+        let batch = GameSessionBatch::new(num_envs, rand_seed).map_err(to_py_err)?;
+        Ok(Self { batch })
+    }
+    #[getter]
+    pub fn num_envs(&self) -> usize { self.batch.len() }
+    pub fn set_num_threads(&mut self, n: usize) { self.batch.set_num_threads(n); }
+    pub fn legal_actions(&self) -> Vec<Vec<u32>> { self.batch.legal_actions() }
+    /// Returns a `uint32` array of the shape `(num_envs, observation_size)`.
+    pub fn observation<'py>(&self, py: Python<'py>) -> PyResult<&'py PyAny> {
+        let obs = self.batch.observations();
+        to_ndarray(py, &u32s_to_bytes(&obs), "uint32", self.batch.len())
+    }
+    pub fn reset(&mut self, py: Python, rand_seed: Option<u64>) -> PyResult<()> {
+        py.allow_threads(|| self.batch.reset(rand_seed)).map_err(to_py_err)
+    }
+    /// The options are kept for later `reset()` including the automatic ones.
+    #[pyo3(signature = (rand_seed=None, garbage_rows=0, starting_piece_cell_ids=None, queue_length=None, max_pieces=None, survival_stage=None))]
+    pub fn reset_with(
+        &mut self,
+        py: Python,
+        rand_seed: Option<u64>,
+        garbage_rows: usize,
+        starting_piece_cell_ids: Option<Vec<u8>>,
+        queue_length: Option<usize>,
+        max_pieces: Option<usize>,
+        survival_stage: Option<u32>,
+    ) -> PyResult<()> {
+        let options = to_reset_options(rand_seed, garbage_rows, starting_piece_cell_ids, queue_length, max_pieces, survival_stage)?;
+        py.allow_threads(|| self.batch.reset_with(&options)).map_err(to_py_err)
+    }
+    /// Returns `(observations, rewards, dones)`. Done environments are reset automatically,
+    /// so the observations of them are the first ones of the new episodes.
+    pub fn step_batch<'py>(&mut self, py: Python<'py>, action_ids: Vec<u32>) -> PyResult<(&'py PyAny, &'py PyAny, &'py PyAny)> {
+        let actions = action_ids.into_iter().map(ml_core::Action).collect::<Vec<_>>();
+        let (obs, rewards, dones) = py.allow_threads(|| -> Result<_, &'static str> {
+            let (rewards, dones) = self.batch.step(&actions)?;
+            Ok((self.batch.observations(), rewards, dones))
+        }).map_err(to_py_err)?;
+        let n = self.batch.len();
+        let rewards = rewards.iter().flat_map(|x| x.to_ne_bytes()).collect::<Vec<_>>();
+        let dones = dones.iter().map(|&x| x as u8).collect::<Vec<_>>();
+        Ok((
+            to_ndarray(py, &u32s_to_bytes(&obs), "uint32", n)?,
+            to_ndarray(py, &rewards, "float32", n)?.call_method1("reshape", (n,))?,
+            to_ndarray(py, &dones, "bool", n)?.call_method1("reshape", (n,))?,
+        ))
+    }
+}
+
+/// Writes transitions to a file-backed ring buffer (see [ml_core::ring_buffer]).
+#[pyclass]
+struct TransitionWriter {
+    writer: ml_core::ring_buffer::RingBufferWriter,
+}
+
+#[pymethods]
+impl TransitionWriter {
+    #[new]
+    fn new(path: &str, capacity: u32, observation_len: u32) -> PyResult<Self> {
+        let specs = ml_core::ring_buffer::transition_specs(observation_len);
+        let writer = ml_core::ring_buffer::RingBufferWriter::create(path, capacity, specs)?;
+        Ok(Self { writer })
+    }
+    #[getter]
+    pub fn num_written(&self) -> u64 { self.writer.num_written() }
+    pub fn write(&mut self, observation: Vec<u32>, action_id: u32, reward: f32, done: bool) -> PyResult<()> {
+        Ok(self.writer.write_transition(&observation, action_id, reward, done)?)
+    }
+}
+
 #[pymodule]
 fn deep_trinity(_py: Python, m: &PyModule) -> PyResult<()> {
     m.add_class::<Environment>()?;
+    m.add_class::<VecEnvironment>()?;
+    m.add_class::<TransitionWriter>()?;
     m.add_class::<core_wrapper::CellWrapper>()?;
     m.add_class::<core_wrapper::PlacementWrapper>()?;
     m.add_class::<core_wrapper::MoveDecisionResourceWrapper>()?;
     m.add_class::<core_wrapper::GameWrapper>()?;
+    m.add_class::<bot_wrapper::ActionWrapper>()?;
+    m.add_class::<bot_wrapper::BotWrapper>()?;
+    m.add_function(wrap_pyfunction!(bot_wrapper::run_bot, m)?)?;
+    m.add_function(wrap_pyfunction!(core_wrapper::piece_preview_grid, m)?)?;
     Ok(())
 }
 
 #[cfg(test)]
 mod test {
-    use super::Environment;
+    use super::{Environment, VecEnvironment};
 
     #[test]
     fn test() {
-        Environment::new().unwrap();
+        let mut env = Environment::new().unwrap();
+        env.reset_with(Some(0), 2, Some(vec![6]), Some(1), None, None).unwrap();
+        assert!(env.reset_with(None, 0, Some(vec![0]), None, None, None).is_err());
+        env.reset_with(Some(0), 0, None, None, None, Some(3)).unwrap();
+        assert_eq!(4, VecEnvironment::new(4, Some(0)).unwrap().num_envs());
+        assert!(VecEnvironment::new(0, None).is_err());
     }
 }
@@ -1,11 +1,17 @@
+use std::collections::HashMap;
 use std::fmt::{Display, Formatter};
-use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::str::FromStr;
 use deep_trinity_core::prelude::*;
-use deep_trinity_tree::arena::{NodeArena, NodeHandle};
+use deep_trinity_core::bot::Plan;
+use deep_trinity_core::goal::Goal;
+use deep_trinity_core::helper::derive_spin_hint;
+use deep_trinity_tree::arena::{ArenaBudget, NodeArena, NodeHandle};
 
-// TODO: Remove or refactor move-finder.
+pub mod puzzle;
 
+#[derive(Copy, Clone, Debug)]
 pub struct PiecePlacement {
     pub piece: Piece,
     pub placement: Placement,
@@ -62,14 +68,14 @@ impl Display for PiecePlacement {
 //---
 
 pub struct NodeData<'a> {
-    by_action: Option<Action>,
-    game: Game<'a>,
-    remains_pps: Vec<Rc<PiecePlacement>>,
+    pub by_action: Option<Action>,
+    pub game: Game<'a>,
+    pub remains_pps: Vec<Arc<PiecePlacement>>,
     mdr: MoveDecisionResource,
 }
 
 impl<'a> NodeData<'a> {
-    pub fn new(by_action: Option<Action>, game: Game<'a>, pps: Vec<Rc<PiecePlacement>>) -> Result<Self, &'static str> {
+    pub fn new(by_action: Option<Action>, game: Game<'a>, pps: Vec<Arc<PiecePlacement>>) -> Result<Self, &'static str> {
         let mdr = MoveDecisionResource::with_game(&game)?;
         Ok(Self { by_action, game, remains_pps: pps, mdr })
     }
@@ -77,55 +83,114 @@ impl<'a> NodeData<'a> {
 
 pub type VecNodeArena<'a> = deep_trinity_tree::arena::VecNodeArena<NodeData<'a>>;
 
-pub fn expand_node(arena: &mut VecNodeArena, node: NodeHandle) {
-    if let Some(fp) = arena[node].data.game.state.falling_piece.clone() {
-        let pps_len = arena[node].data.remains_pps.len();
-        for i in 0..pps_len {
-            let pp = arena[node].data.remains_pps.get(i).cloned().unwrap();
-            let (game, pps) = {
-                let data = &arena[node].data;
-                if pp.piece != fp.piece() || !data.mdr.dst_candidates.contains(&pp.placement) {
-                    continue;
-                }
-                let mut game = data.game.clone();
-                game.state.falling_piece = Some(FallingPiece::new(fp.piece().default_spec(), pp.placement));
-                game.lock().unwrap();
-                let mut pps = data.remains_pps.clone();
-                pps.remove(i);
-                (game, pps)
-            };
+/// Returns the data of the children of the node.
+pub fn expand_node_data<'a>(data: &NodeData<'a>) -> Vec<NodeData<'a>> {
+    let mut r = Vec::new();
+    if let Some(fp) = data.game.state.falling_piece.as_ref() {
+        for (i, pp) in data.remains_pps.iter().enumerate() {
+            if pp.piece != fp.piece() || !data.mdr.dst_candidates.contains(&pp.placement) {
+                continue;
+            }
+            // Placements reachable only by rotations are locked with the rotation so that spins count.
+            let hint = derive_spin_hint(&data.game.state.playfield, fp, pp.placement, &data.game.rules, &data.mdr.brute_force_search_result);
+            let mt = MoveTransition::new(pp.placement, hint);
+            let mut game = data.game.clone();
+            game.state.falling_piece = Some(FallingPiece::new_with_last_move_transition(fp.piece().default_spec(), &mt));
+            game.lock().unwrap();
+            let mut pps = data.remains_pps.clone();
+            pps.remove(i);
             if game.state.falling_piece.is_some() {
-                arena.append_child(node, NodeData::new(
-                    Some(Action::Move(MoveTransition::new(pp.placement.clone(), None))),
+                r.push(NodeData::new(
+                    Some(Action::Move(mt)),
                     game,
                     pps,
                 ).unwrap());
             }
         }
     }
-    if arena[node].data.game.state.can_hold {
-        let mut game = arena[node].data.game.clone();
+    if data.game.state.can_hold {
+        let mut game = data.game.clone();
         game.hold().unwrap();
         if game.state.falling_piece.is_some() {
-            let pps = arena[node].data.remains_pps.clone();
-            let child_data = NodeData::new(Some(Action::Hold), game, pps).unwrap();
-            arena.append_child(node, child_data);
+            let pps = data.remains_pps.clone();
+            r.push(NodeData::new(Some(Action::Hold), game, pps).unwrap());
         }
     }
+    r
+}
+
+pub fn expand_node(arena: &mut VecNodeArena, node: NodeHandle) {
+    for child_data in expand_node_data(&arena[node].data) {
+        arena.append_child(node, child_data);
+    }
 }
 
-pub fn expand_all(arena: &mut VecNodeArena, node: NodeHandle) {
+/// Returns `false` if stopped by the budget of the arena.
+pub fn expand_all(arena: &mut VecNodeArena, node: NodeHandle) -> bool {
     let mut open = vec![node];
     while !open.is_empty() {
+        if arena.is_budget_exceeded() {
+            return false;
+        }
         let target = open.pop().unwrap();
         expand_node(arena, target);
         open.extend(arena[target].children());
     }
+    true
+}
+
+/// Expands the tree lazily in the depth-first order and yields the nodes where all the piece
+/// placements are done, so that callers can stop at the first solutions.
+/// The iteration also ends when the budget of the arena is exceeded.
+pub struct SolutionIter<'a> {
+    arena: VecNodeArena<'a>,
+    root: NodeHandle,
+    open: Vec<NodeHandle>,
+    budget_exceeded: bool,
+}
+
+impl<'a> SolutionIter<'a> {
+    pub fn new(game: Game<'a>, pps: Vec<Arc<PiecePlacement>>, budget: ArenaBudget) -> Result<Self, &'static str> {
+        Ok(Self::with_root_data(NodeData::new(None, game, pps)?, budget))
+    }
+    fn with_root_data(root_data: NodeData<'a>, budget: ArenaBudget) -> Self {
+        let mut arena = VecNodeArena::with_budget(budget);
+        let root = arena.create(root_data);
+        Self { arena, root, open: vec![root], budget_exceeded: false }
+    }
+    pub fn arena(&self) -> &VecNodeArena<'a> { &self.arena }
+    pub fn root(&self) -> NodeHandle { self.root }
+    pub fn budget_exceeded(&self) -> bool { self.budget_exceeded }
+    /// Returns the tree expanded so far.
+    pub fn into_result(self) -> ResolveStackingResult<'a> {
+        ResolveStackingResult { arena: self.arena, root: self.root, budget_exceeded: self.budget_exceeded }
+    }
+}
+
+impl<'a> Iterator for SolutionIter<'a> {
+    type Item = NodeHandle;
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(target) = self.open.pop() {
+            if self.arena[target].data.remains_pps.is_empty() {
+                return Some(target);
+            }
+            if self.arena.is_budget_exceeded() {
+                self.budget_exceeded = true;
+                self.open.clear();
+                return None;
+            }
+            expand_node(&mut self.arena, target);
+            self.open.extend(self.arena[target].children().iter().rev());
+        }
+        None
+    }
 }
 
 pub struct ResolveStackingResult<'a> {
     pub arena: VecNodeArena<'a>,
     pub root: NodeHandle,
+    /// If `true`, the tree is partially expanded and the solutions are the ones found so far.
+    pub budget_exceeded: bool,
 }
 
 impl<'a> ResolveStackingResult<'a> {
@@ -157,13 +222,242 @@ impl<'a> ResolveStackingResult<'a> {
         });
         found
     }
+    /// Returns the nodes where all the piece placements are done.
+    pub fn solutions(&self) -> Vec<NodeHandle> {
+        let mut found = Vec::new();
+        self.arena.visit_depth_first(self.root, |arena, node, ctx| {
+            if arena[node].data.remains_pps.is_empty() {
+                found.push(node);
+                ctx.skip();
+            }
+        });
+        found
+    }
+    /// Returns the first nodes on each route where `goal` is achieved, whether or not all the piece
+    /// placements are done.
+    pub fn solutions_by_goal(&self, goal: &Goal) -> Vec<NodeHandle> {
+        let start = &self.arena[self.root].data.game.stats;
+        let mut found = Vec::new();
+        self.arena.visit_depth_first(self.root, |arena, node, ctx| {
+            if goal.is_achieved(start, &arena[node].data.game) {
+                found.push(node);
+                ctx.skip();
+            }
+        });
+        found
+    }
+    /// Groups `nodes` (e.g. [Self::solutions]) by the resulting board in the order of appearance.
+    pub fn dedup_by_board(&self, nodes: &[NodeHandle]) -> Vec<UniqueOutcome> {
+        let mut r: Vec<UniqueOutcome> = Vec::new();
+        let mut indices: HashMap<u64, usize> = HashMap::new();
+        for &node in nodes.iter() {
+            let hash = self.arena[node].data.game.state.playfield.grid_hash();
+            match indices.get(&hash) {
+                Some(&i) => r[i].num_orderings += 1,
+                None => {
+                    indices.insert(hash, r.len());
+                    r.push(UniqueOutcome { node, num_orderings: 1 });
+                }
+            }
+        }
+        r
+    }
+    /// Returns the actions from the root to `node`.
+    pub fn actions(&self, node: NodeHandle) -> Vec<Action> {
+        self.arena.route(node).into_iter().filter_map(|n| self.arena[n].data.by_action).collect()
+    }
+    /// Returns the route to `node` as a plan, e.g. to be replayed or encoded in fumen.
+    pub fn plan(&self, node: NodeHandle) -> Result<Plan, &'static str> {
+        Plan::from_actions(&self.arena[self.root].data.game, &self.actions(node))
+    }
+    pub fn score_solution(&self, node: NodeHandle) -> Result<SolutionScore, &'static str> {
+        let mut score = SolutionScore::default();
+        let mut t_lock_index = None;
+        let mut num_locks = 0;
+        for &n in self.arena.route(node).iter() {
+            let prev_game = match self.arena[n].parent() {
+                Some(pn) => &self.arena[*pn].data.game,
+                None => continue,
+            };
+            match self.arena[n].data.by_action {
+                Some(Action::Move(mt)) => {
+                    score.movement_cost += prev_game.get_almost_good_move_path(&mt)?.len() as u32;
+                    let is_t = prev_game.state.falling_piece.as_ref().map_or(false, |fp| fp.piece() == Piece::T);
+                    if is_t && t_lock_index.is_none() {
+                        t_lock_index = Some(num_locks);
+                    }
+                    num_locks += 1;
+                }
+                Some(Action::Hold) => score.num_holds += 1,
+                None => {}
+            }
+        }
+        if let Some(i) = t_lock_index {
+            score.t_earliness = num_locks - 1 - i;
+        }
+        Ok(score)
+    }
+    /// Returns the solutions sorted from the best.
+    pub fn rank_solutions(&self, weights: &SolutionScoreWeights) -> Result<Vec<(NodeHandle, SolutionScore)>, &'static str> {
+        let mut r = self.solutions().into_iter()
+            .map(|node| self.score_solution(node).map(|score| (node, score)))
+            .collect::<Result<Vec<_>, _>>()?;
+        r.sort_by_key(|(node, score)| (score.total(weights), *node));
+        Ok(r)
+    }
+    pub fn best_solutions(&self, weights: &SolutionScoreWeights, k: usize) -> Result<Vec<(NodeHandle, SolutionScore)>, &'static str> {
+        let mut r = self.rank_solutions(weights)?;
+        r.truncate(k);
+        Ok(r)
+    }
+    /// Returns the nodes where at least `min_placed` piece placements are done and no more
+    /// placements can be done by the following actions. Complete solutions are also included.
+    pub fn partial_solutions(&self, min_placed: usize) -> Vec<PartialSolution> {
+        fn min_remains(arena: &VecNodeArena, node: NodeHandle, memo: &mut HashMap<NodeHandle, usize>) -> usize {
+            let mut r = arena[node].data.remains_pps.len();
+            for &child in arena[node].children().iter() {
+                r = r.min(min_remains(arena, child, memo));
+            }
+            memo.insert(node, r);
+            r
+        }
+        let mut memo = HashMap::new();
+        min_remains(&self.arena, self.root, &mut memo);
+        let num_pps = self.arena[self.root].data.remains_pps.len();
+        let mut found = Vec::new();
+        self.arena.visit_depth_first(self.root, |arena, node, ctx| {
+            let remains = &arena[node].data.remains_pps;
+            if num_pps - remains.len() >= min_placed && remains.len() == memo[&node] {
+                found.push(PartialSolution { node, skipped: remains.clone() });
+                ctx.skip();
+            }
+        });
+        found
+    }
+    /// Returns the partial solutions sorted from the best, i.e. by the number of skipped placements,
+    /// and then by the score.
+    pub fn rank_partial_solutions(&self, min_placed: usize, weights: &SolutionScoreWeights) -> Result<Vec<(PartialSolution, SolutionScore)>, &'static str> {
+        let mut r = self.partial_solutions(min_placed).into_iter()
+            .map(|ps| self.score_solution(ps.node).map(|score| (ps, score)))
+            .collect::<Result<Vec<_>, _>>()?;
+        r.sort_by_key(|(ps, score)| (ps.skipped.len(), score.total(weights), ps.node));
+        Ok(r)
+    }
+}
+
+/// Solutions leading to the same board.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct UniqueOutcome {
+    /// The first one of the solutions.
+    pub node: NodeHandle,
+    /// The number of the solutions, i.e. the orderings of actions.
+    pub num_orderings: usize,
+}
+
+pub struct PartialSolution {
+    pub node: NodeHandle,
+    /// The piece placements which are not done.
+    pub skipped: Vec<Arc<PiecePlacement>>,
+}
+
+/// The cost of a solution. Smaller is better.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct SolutionScore {
+    pub num_holds: u32,
+    /// The sum of the lengths of the move paths.
+    pub movement_cost: u32,
+    /// The number of pieces locked after the (first) T piece.
+    /// 0 if no T piece is used, so that T pieces tend to be kept for later T-spins.
+    pub t_earliness: u32,
+}
+
+impl SolutionScore {
+    pub fn total(&self, weights: &SolutionScoreWeights) -> u32 {
+        self.num_holds * weights.hold + self.movement_cost * weights.movement + self.t_earliness * weights.t_earliness
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct SolutionScoreWeights {
+    pub hold: u32,
+    pub movement: u32,
+    pub t_earliness: u32,
+}
+
+impl Default for SolutionScoreWeights {
+    fn default() -> Self {
+        Self { hold: 3, movement: 1, t_earliness: 5 }
+    }
 }
 
-pub fn resolve_stacking(game: Game, pps: Vec<Rc<PiecePlacement>>) -> Result<ResolveStackingResult, &'static str> {
-    let mut arena = VecNodeArena::default();
-    let root = arena.create(NodeData::new(None, game, pps).unwrap());
-    expand_all(&mut arena, root);
-    Ok(ResolveStackingResult { arena, root })
+pub fn resolve_stacking(game: Game, pps: Vec<Arc<PiecePlacement>>) -> Result<ResolveStackingResult, &'static str> {
+    resolve_stacking_with_budget(game, pps, Default::default())
+}
+
+pub fn resolve_stacking_with_budget(game: Game, pps: Vec<Arc<PiecePlacement>>, budget: ArenaBudget) -> Result<ResolveStackingResult, &'static str> {
+    let mut iter = SolutionIter::new(game, pps, budget)?;
+    iter.by_ref().for_each(drop);
+    Ok(iter.into_result())
+}
+
+/// Same as [resolve_stacking_with_budget] but the subtrees of the root children are expanded in
+/// `num_threads` threads, where each thread takes the next child from the shared queue when it
+/// finishes one. The subtrees are merged in the order of the children, and `budget` is applied to
+/// the merged tree through the number of the nodes counted among the threads.
+pub fn resolve_stacking_parallel(game: Game, pps: Vec<Arc<PiecePlacement>>, budget: ArenaBudget, num_threads: usize) -> Result<ResolveStackingResult, &'static str> {
+    let root_data = NodeData::new(None, game, pps)?;
+    let children = if root_data.remains_pps.is_empty() { Vec::new() } else { expand_node_data(&root_data) };
+    let node_bytes = std::mem::size_of::<deep_trinity_tree::arena::Node<NodeData>>();
+    let is_exceeded = |n: usize| {
+        budget.max_nodes.map_or(false, |max| n > max) || budget.max_bytes.map_or(false, |max| n * node_bytes > max)
+    };
+    // The root and its children.
+    let num_nodes = AtomicUsize::new(1 + children.len());
+    let queue = Mutex::new(children.into_iter().enumerate().rev().collect::<Vec<_>>());
+    let mut subtrees = std::thread::scope(|scope| {
+        let handles = (0..num_threads.max(1))
+            .map(|_| scope.spawn(|| {
+                let mut r = Vec::new();
+                loop {
+                    let next = queue.lock().unwrap().pop();
+                    let (i, data) = match next {
+                        Some(next) => next,
+                        None => break,
+                    };
+                    let mut arena = VecNodeArena::default();
+                    let root = arena.create(data);
+                    let mut open = vec![root];
+                    let mut budget_exceeded = false;
+                    while let Some(target) = open.pop() {
+                        if arena[target].data.remains_pps.is_empty() {
+                            continue;
+                        }
+                        if is_exceeded(num_nodes.load(Ordering::Relaxed)) {
+                            budget_exceeded = true;
+                            break;
+                        }
+                        expand_node(&mut arena, target);
+                        num_nodes.fetch_add(arena[target].children().len(), Ordering::Relaxed);
+                        open.extend(arena[target].children().iter().rev());
+                    }
+                    r.push((i, ResolveStackingResult { arena, root, budget_exceeded }));
+                }
+                r
+            }))
+            .collect::<Vec<_>>();
+        handles.into_iter().flat_map(|h| h.join().unwrap()).collect::<Vec<_>>()
+    });
+    subtrees.sort_by_key(|(i, _)| *i);
+
+    let mut arena = VecNodeArena::with_budget(budget);
+    let root = arena.create(root_data);
+    let mut budget_exceeded = false;
+    for (_, r) in subtrees.into_iter() {
+        budget_exceeded |= r.budget_exceeded;
+        arena.append_tree(root, r.arena, r.root);
+    }
+    budget_exceeded |= arena.is_budget_exceeded();
+    Ok(ResolveStackingResult { arena, root, budget_exceeded })
 }
 
 //---
@@ -185,7 +479,7 @@ mod tests {
         let next_pieces = NEXT_PIECES.chars().map(|c| Piece::try_from_char(c).unwrap()).collect::<Vec<_>>();
         // TODO: let mirror = false;
         let pps = PPS.split(" ")
-            .map(|s| Rc::new(PiecePlacement::from_str(s).unwrap()))
+            .map(|s| Arc::new(PiecePlacement::from_str(s).unwrap()))
             .collect::<Vec<_>>();
         let pps_len = pps.len();
 
@@ -219,5 +513,84 @@ mod tests {
                 }
             }
         }
+
+        let weights = SolutionScoreWeights::default();
+        let ranked = r.rank_solutions(&weights).unwrap();
+        assert_eq!(found.len(), ranked.len());
+        assert!(ranked.windows(2).all(|w| w[0].1.total(&weights) <= w[1].1.total(&weights)));
+        let best = r.best_solutions(&weights, 3).unwrap();
+        assert_eq!(&ranked[..best.len().min(3)], &best[..]);
+        let (_, score) = best[0];
+        assert_eq!(0, score.t_earliness);
+
+        let unique = r.dedup_by_board(&found);
+        assert!(unique.len() < found.len());
+        assert_eq!(found.len(), unique.iter().map(|u| u.num_orderings).sum());
+        assert_eq!(found[0], unique[0].node);
+
+        // The last T is locked by the rotation.
+        assert_eq!(1, r.arena[found[0]].data.game.stats.line_clear.get(&deep_trinity_core::LineClear::tsd()));
+
+        let plan = r.plan(found[0]).unwrap();
+        assert_eq!(pps_len, plan.len());
+        let mut replayed = initial_game.clone();
+        plan.execute(&mut replayed).unwrap();
+        assert_eq!(r.arena[found[0]].data.game.state.playfield, replayed.state.playfield);
+        assert!(plan.to_fumen(&initial_game).is_ok());
+
+        let pattern = r.arena[found[0]].data.game.state.playfield.clone();
+        assert_eq!(found, r.solutions_by_goal(&Goal::BoardMatches(pattern)));
+        assert!(!r.budget_exceeded);
+
+        let pps = PPS.split(" ")
+            .map(|s| Arc::new(PiecePlacement::from_str(s).unwrap()))
+            .collect::<Vec<_>>();
+        let parallel = resolve_stacking_parallel(initial_game.clone(), pps.clone(), Default::default(), 4).unwrap();
+        let parallel_found = parallel.collect_nodes_by_lock_count(pps_len as u32);
+        assert_eq!(found.len(), parallel_found.len());
+        for (&a, &b) in found.iter().zip(parallel_found.iter()) {
+            assert_eq!(r.arena[a].data.game, parallel.arena[b].data.game);
+        }
+
+        let first = SolutionIter::new(initial_game.clone(), pps.clone(), Default::default()).unwrap().take(3).collect::<Vec<_>>();
+        assert_eq!(&found[..3], &first[..]);
+        let half = ArenaBudget::nodes(r.arena.used_len() / 2);
+        let parallel = resolve_stacking_parallel(initial_game.clone(), pps.clone(), half, 4).unwrap();
+        assert!(parallel.budget_exceeded);
+        assert!(parallel.arena.used_len() < r.arena.used_len());
+        assert!(parallel.solutions().len() < found.len());
+        let r = resolve_stacking_with_budget(initial_game, pps, half).unwrap();
+        assert!(r.budget_exceeded);
+        assert!(r.solutions().len() < found.len());
+    }
+
+    #[test]
+    fn test_partial_solutions() {
+        const NEXT_PIECES: &'static str = "ISZTOJLISZTOJL";
+        // The last T cannot be placed because it is floating.
+        const PPS: &'static str = "I0,2,-2 O0,7,-1 L1,-1,0 S1,5,0 Z0,3,0 J2,3,2 T2,1,0 T0,4,10";
+
+        let next_pieces = NEXT_PIECES.chars().map(|c| Piece::try_from_char(c).unwrap()).collect::<Vec<_>>();
+        let pps = PPS.split(" ")
+            .map(|s| Arc::new(PiecePlacement::from_str(s).unwrap()))
+            .collect::<Vec<_>>();
+
+        let mut initial_game = StdGame::default();
+        initial_game.performance_mode();
+        initial_game.supply_next_pieces(&next_pieces);
+        initial_game.setup_falling_piece(None).unwrap();
+
+        let r = resolve_stacking(initial_game, pps).unwrap();
+        assert!(r.solutions().is_empty());
+        assert!(r.partial_solutions(8).is_empty());
+
+        let weights = SolutionScoreWeights::default();
+        let ranked = r.rank_partial_solutions(7, &weights).unwrap();
+        assert!(!ranked.is_empty());
+        for (ps, _) in ranked.iter() {
+            assert_eq!(1, ps.skipped.len());
+            assert_eq!("T0,4,10", ps.skipped[0].to_string());
+        }
+        assert!(r.partial_solutions(6).len() >= ranked.len());
     }
 }
@@ -0,0 +1,217 @@
+//! Puzzles: reach the goal from the starting board with the fixed queue,
+//! e.g. "perfect clear with these pieces" or "TSD with these pieces".
+//!
+//! A puzzle is documented in the compact text protocol of [deep_trinity_core::io] with the
+//! additional `goal=<goal>` field, where `<goal>` is parsed by [Goal::parse].
+//!
+//! ```txt
+//! board=@@@@6/@@@3@@@@/@@@@1@@@@@ queue=IT hold=- goal=tsd
+//! ```
+
+use deep_trinity_core::prelude::*;
+use deep_trinity_core::{LockOutcome, Move, Statistics};
+use deep_trinity_core::goal::Goal;
+use deep_trinity_core::helper::derive_spin_hint;
+use deep_trinity_core::io::BoardUpdate;
+use deep_trinity_tree::arena::{NodeArena, NodeHandle};
+
+#[derive(Clone, Debug)]
+pub struct Puzzle {
+    pub playfield: Playfield<'static>,
+    /// The current piece followed by the next pieces.
+    pub queue: Vec<Piece>,
+    pub hold: Option<Piece>,
+    pub goal: Goal,
+}
+
+impl Puzzle {
+    pub fn new(playfield: Playfield<'static>, queue: Vec<Piece>, goal: Goal) -> Self {
+        Self { playfield, queue, hold: None, goal }
+    }
+    /// Parses a puzzle document (see the module document). `board`, `queue` and `goal` are required.
+    pub fn parse(doc: &str) -> Result<Self, &'static str> {
+        let mut goal = None;
+        let mut fields = Vec::new();
+        for field in doc.split_whitespace() {
+            match field.strip_prefix("goal=") {
+                Some(value) => goal = Some(Goal::parse(value)?),
+                None => fields.push(field),
+            }
+        }
+        let update = BoardUpdate::parse(&fields.join(" "))?;
+        let mut puzzle = Self::new(
+            update.playfield.ok_or("board is required")?,
+            update.queue.ok_or("queue is required")?,
+            goal.ok_or("goal is required")?,
+        );
+        puzzle.hold = update.hold.flatten();
+        Ok(puzzle)
+    }
+    /// Returns the game at the start of the puzzle.
+    pub fn game(&self) -> Result<Game<'static>, &'static str> {
+        let mut game: Game<'static> = Default::default();
+        game.state.playfield = self.playfield.clone();
+        game.state.hold_piece = self.hold;
+        let (current, next) = self.queue.split_first().ok_or("empty queue")?;
+        game.supply_next_pieces(next);
+        game.setup_falling_piece(Some(*current))?;
+        Ok(game)
+    }
+    /// Plays `actions` and returns whether the goal is achieved by the last action.
+    /// An error is returned if any action is not playable, or actions remain after the goal.
+    pub fn validate(&self, actions: &[Action]) -> Result<bool, &'static str> {
+        let mut game = self.game()?;
+        let start = game.stats.clone();
+        let mut solved = false;
+        for action in actions.iter() {
+            if solved {
+                return Err("actions remain after the goal");
+            }
+            play(&mut game, action)?;
+            solved = self.goal.is_achieved(&start, &game);
+        }
+        Ok(solved)
+    }
+    /// Returns up to `max_solutions` action sequences achieving the goal.
+    pub fn solve(&self, max_solutions: usize) -> Result<Vec<Vec<Action>>, &'static str> {
+        let game = self.game()?;
+        let start = game.stats.clone();
+        let mut arena = VecNodeArena::default();
+        let root = arena.create(NodeData { by_action: None, game, solved: false });
+        let mut found = Vec::new();
+        let mut open = vec![root];
+        while let Some(target) = open.pop() {
+            if found.len() >= max_solutions {
+                break;
+            }
+            if arena[target].data.solved {
+                found.push(target);
+                continue;
+            }
+            expand_node(&mut arena, target, &self.goal, &start)?;
+            open.extend(arena[target].children().iter().rev());
+        }
+        Ok(found.into_iter()
+            .map(|node| arena.route(node).into_iter().filter_map(|n| arena[n].data.by_action).collect())
+            .collect())
+    }
+}
+
+/// Plays the action after checking that it's reachable.
+fn play(game: &mut Game<'static>, action: &Action) -> Result<(), &'static str> {
+    let mt = match action {
+        Action::Hold => {
+            game.hold()?;
+            return Ok(());
+        }
+        Action::Move(mt) => mt,
+    };
+    let fp = game.state.falling_piece.as_ref().ok_or("no falling piece")?;
+    let resource = MoveDecisionResource::with_game(game)?;
+    if !resource.dst_candidates.contains(&mt.placement) {
+        return Err("unreachable placement");
+    }
+    if let Some(hint) = mt.hint {
+        let cw = match hint.by {
+            Move::Rotate(1) => true,
+            Move::Rotate(-1) => false,
+            _ => return Err("unsupported move hint"),
+        };
+        let dst = FallingPiece::new(fp.piece_spec, mt.placement);
+        if !resource.brute_force_search_result.contains(&hint.placement)
+            || !game.state.playfield.check_reverse_rotation(game.rules.rotation_mode, &dst, cw).contains(&hint.placement) {
+            return Err("invalid move hint");
+        }
+    }
+    lock_move(game, mt).map(|_| ())
+}
+
+fn lock_move(game: &mut Game<'static>, mt: &MoveTransition) -> Result<LockOutcome, &'static str> {
+    let spec = game.state.falling_piece.as_ref().ok_or("no falling piece")?.piece_spec;
+    game.state.falling_piece = Some(FallingPiece::new_with_last_move_transition(spec, mt));
+    game.lock_ex()
+}
+
+/// The placements of the falling piece with the T-spin variants, where the placements reachable
+/// only by rotations have the hints.
+fn move_transitions(game: &Game) -> Result<Vec<MoveTransition>, &'static str> {
+    let resource = MoveDecisionResource::with_game(game)?;
+    let fp = game.state.falling_piece.as_ref().unwrap();
+    let mut r = resource.sorted_candidates().into_iter()
+        .map(|pl| MoveTransition::new(pl, derive_spin_hint(&game.state.playfield, fp, pl, &game.rules, &resource.brute_force_search_result)))
+        .collect::<Vec<_>>();
+    if fp.piece() == Piece::T {
+        let mut tspin_moves = MoveDecisionHelper::with_game(game, &resource)?.tspin_moves()?;
+        tspin_moves.sort_by_key(|(mt, _)| (mt.placement, mt.hint.map(|h| h.placement)));
+        for (mt, _) in tspin_moves {
+            if !r.contains(&mt) {
+                r.push(mt);
+            }
+        }
+    }
+    Ok(r)
+}
+
+struct NodeData {
+    by_action: Option<Action>,
+    game: Game<'static>,
+    solved: bool,
+}
+
+type VecNodeArena = deep_trinity_tree::arena::VecNodeArena<NodeData>;
+
+fn expand_node(arena: &mut VecNodeArena, node: NodeHandle, goal: &Goal, start: &Statistics) -> Result<(), &'static str> {
+    if arena[node].data.game.state.falling_piece.is_none() {
+        return Ok(());
+    }
+    for mt in move_transitions(&arena[node].data.game)? {
+        let mut game = arena[node].data.game.clone();
+        let outcome = lock_move(&mut game, &mt)?;
+        let solved = goal.is_achieved(start, &game);
+        if solved || !outcome.game_over {
+            arena.append_child(node, NodeData { by_action: Some(Action::Move(mt)), game, solved });
+        }
+    }
+    if arena[node].data.game.can_hold() {
+        let mut game = arena[node].data.game.clone();
+        game.hold()?;
+        if game.state.falling_piece.is_some() {
+            arena.append_child(node, NodeData { by_action: Some(Action::Hold), game, solved: false });
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_puzzle() {
+        let puzzle = Puzzle::parse("board=@@@@@@@@2/@@@@@@@@2 queue=IO goal=pc").unwrap();
+        assert_eq!(Goal::PerfectClear, puzzle.goal);
+        let solutions = puzzle.solve(1).unwrap();
+        assert_eq!(1, solutions.len());
+        assert_eq!(Action::Hold, solutions[0][0]);
+        assert!(puzzle.validate(&solutions[0]).unwrap());
+        assert!(!puzzle.validate(&solutions[0][..1]).unwrap());
+        let mut actions = solutions[0].clone();
+        actions.push(Action::Hold);
+        assert!(puzzle.validate(&actions).is_err());
+
+        let puzzle = Puzzle::parse("board=@@@@6/@@@3@@@@/@@@@1@@@@@ queue=T goal=tsd").unwrap();
+        assert_eq!(Goal::LineClear(deep_trinity_core::LineClear::tsd()), puzzle.goal);
+        let solutions = puzzle.solve(1).unwrap();
+        assert_eq!(1, solutions.len());
+        assert!(puzzle.validate(&solutions[0]).unwrap());
+        // The same placement without the spin is not a TSD.
+        let mut actions = solutions[0].clone();
+        if let Action::Move(mt) = actions.last_mut().unwrap() {
+            mt.hint = None;
+        }
+        assert!(!puzzle.validate(&actions).unwrap_or(false));
+
+        assert!(Puzzle::parse("board=- queue=T").is_err());
+        assert!(Puzzle::parse("board=- queue=T goal=foo").is_err());
+    }
+}
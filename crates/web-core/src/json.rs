@@ -0,0 +1,517 @@
+//! JSON representation of the whole game state.
+//!
+//! The playfield is stored as rows of cell chars (bottom row first), and pieces as piece chars,
+//! so that the output is readable and stable against internal representation changes.
+
+use std::collections::VecDeque;
+use std::error::Error;
+use serde::{Serialize, Deserialize};
+use deep_trinity_core::{Count, NUM_PIECES, Game, GameRules, SpawnNudge, LineClear, TSpin, Piece, Cell, Orientation, LossConditions, FallingPiece, MovePath, MovePathItem, InputTimingProfile};
+use deep_trinity_core::attack::{AttackTable, BtbRule, ComboTable};
+use deep_trinity_grid::{Grid, Cell as _, X, Y};
+
+const VERSION: u32 = 1;
+
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+struct PlacementJson {
+    orientation: u8,
+    x: X,
+    y: Y,
+}
+
+impl From<deep_trinity_core::Placement> for PlacementJson {
+    fn from(p: deep_trinity_core::Placement) -> Self {
+        Self { orientation: p.orientation.to_u8(), x: p.pos.0, y: p.pos.1 }
+    }
+}
+
+impl PlacementJson {
+    fn to_placement(self) -> Result<deep_trinity_core::Placement, Box<dyn Error>> {
+        Ok(deep_trinity_core::Placement::new(Orientation::try_from_u8(self.orientation)?, (self.x, self.y).into()))
+    }
+}
+
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum MoveJson {
+    Shift(i8),
+    Drop(i8),
+    Rotate(i8),
+}
+
+impl From<deep_trinity_core::Move> for MoveJson {
+    fn from(mv: deep_trinity_core::Move) -> Self {
+        match mv {
+            deep_trinity_core::Move::Shift(n) => MoveJson::Shift(n),
+            deep_trinity_core::Move::Drop(n) => MoveJson::Drop(n),
+            deep_trinity_core::Move::Rotate(n) => MoveJson::Rotate(n),
+        }
+    }
+}
+
+impl From<MoveJson> for deep_trinity_core::Move {
+    fn from(mv: MoveJson) -> Self {
+        match mv {
+            MoveJson::Shift(n) => deep_trinity_core::Move::Shift(n),
+            MoveJson::Drop(n) => deep_trinity_core::Move::Drop(n),
+            MoveJson::Rotate(n) => deep_trinity_core::Move::Rotate(n),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct FallingPieceJson {
+    piece: char,
+    initial_placement: PlacementJson,
+    path: Vec<(MoveJson, PlacementJson)>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct LineClearCountJson {
+    num_lines: u8,
+    tspin: Option<char>,
+    count: Count,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct StatisticsJson {
+    line_clear: Vec<LineClearCountJson>,
+    combo: Vec<(Count, Count)>,
+    btb: Vec<(Count, Count)>,
+    spike: Vec<(Count, Count)>,
+    perfect_clear: Count,
+    hold: Count,
+    lock: Count,
+    /// Per-piece counts in the order of [deep_trinity_core::PIECES].
+    #[serde(default)]
+    hold_by_piece: [Count; NUM_PIECES],
+    #[serde(default)]
+    lock_by_piece: [Count; NUM_PIECES],
+    #[serde(default)]
+    landing_height_by_piece: [Count; NUM_PIECES],
+    #[serde(default)]
+    wasted_t: Count,
+    #[serde(default)]
+    misused_i: Count,
+}
+
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum SpawnNudgeJson {
+    Disabled,
+    UpTo(Y),
+    Guideline,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum ComboTableJson {
+    Additive(Vec<Count>),
+    Multiplier,
+}
+
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum BtbRuleJson {
+    Flat(Count),
+    Charge { bonus: Count, threshold: Count },
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct AttackTableJson {
+    normal: [Count; 4],
+    tspin: [Count; 3],
+    tspin_mini: [Count; 2],
+    combo: ComboTableJson,
+    btb: BtbRuleJson,
+    perfect_clear: Count,
+}
+
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+struct InputTimingJson {
+    das: u32,
+    arr: u32,
+    soft_drop_interval: u32,
+    key_interval: u32,
+}
+
+/// The rules except for the modes having only one variant. Missing fields are the defaults of
+/// [GameRules], so that older JSON having only `loss_conds` and `enable_hold` can be read.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+struct RulesJson {
+    loss_conds: u8,
+    spawn_nudge: SpawnNudgeJson,
+    instant_gravity: bool,
+    enable_hold: bool,
+    attack_table: AttackTableJson,
+    input_timing: InputTimingJson,
+}
+
+impl Default for RulesJson {
+    fn default() -> Self { (&GameRules::default()).into() }
+}
+
+impl From<&GameRules> for RulesJson {
+    fn from(rules: &GameRules) -> Self {
+        let t = &rules.attack_table;
+        Self {
+            loss_conds: rules.loss_conds.bits(),
+            spawn_nudge: match rules.spawn_nudge {
+                SpawnNudge::Disabled => SpawnNudgeJson::Disabled,
+                SpawnNudge::UpTo(n) => SpawnNudgeJson::UpTo(n),
+                SpawnNudge::Guideline => SpawnNudgeJson::Guideline,
+            },
+            instant_gravity: rules.instant_gravity,
+            enable_hold: rules.enable_hold,
+            attack_table: AttackTableJson {
+                normal: t.normal,
+                tspin: t.tspin,
+                tspin_mini: t.tspin_mini,
+                combo: match &t.combo {
+                    ComboTable::Additive(table) => ComboTableJson::Additive(table.to_vec()),
+                    ComboTable::Multiplier => ComboTableJson::Multiplier,
+                },
+                btb: match t.btb {
+                    BtbRule::Flat(bonus) => BtbRuleJson::Flat(bonus),
+                    BtbRule::Charge { bonus, threshold } => BtbRuleJson::Charge { bonus, threshold },
+                },
+                perfect_clear: t.perfect_clear,
+            },
+            input_timing: InputTimingJson {
+                das: rules.input_timing.das,
+                arr: rules.input_timing.arr,
+                soft_drop_interval: rules.input_timing.soft_drop_interval,
+                key_interval: rules.input_timing.key_interval,
+            },
+        }
+    }
+}
+
+impl RulesJson {
+    fn to_rules(&self) -> Result<GameRules, Box<dyn Error>> {
+        let t = &self.attack_table;
+        let combo = match &t.combo {
+            ComboTableJson::Additive(table) => {
+                if table.is_empty() {
+                    return Err("empty combo table".into());
+                }
+                ComboTable::Additive(table.clone().into())
+            }
+            ComboTableJson::Multiplier => ComboTable::Multiplier,
+        };
+        Ok(GameRules {
+            loss_conds: LossConditions::from_bits(self.loss_conds).ok_or("invalid loss_conds")?,
+            spawn_nudge: match self.spawn_nudge {
+                SpawnNudgeJson::Disabled => SpawnNudge::Disabled,
+                SpawnNudgeJson::UpTo(n) => SpawnNudge::UpTo(n),
+                SpawnNudgeJson::Guideline => SpawnNudge::Guideline,
+            },
+            instant_gravity: self.instant_gravity,
+            enable_hold: self.enable_hold,
+            attack_table: AttackTable {
+                normal: t.normal,
+                tspin: t.tspin,
+                tspin_mini: t.tspin_mini,
+                combo,
+                btb: match t.btb {
+                    BtbRuleJson::Flat(bonus) => BtbRule::Flat(bonus),
+                    BtbRuleJson::Charge { bonus, threshold } => BtbRule::Charge { bonus, threshold },
+                },
+                perfect_clear: t.perfect_clear,
+            },
+            input_timing: InputTimingProfile {
+                das: self.input_timing.das,
+                arr: self.input_timing.arr,
+                soft_drop_interval: self.input_timing.soft_drop_interval,
+                key_interval: self.input_timing.key_interval,
+            },
+            ..Default::default()
+        })
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct GameJson {
+    version: u32,
+    #[serde(flatten)]
+    rules: RulesJson,
+    playfield: Vec<String>,
+    visible_height: Y,
+    next_pieces: String,
+    num_visible_next_pieces: usize,
+    falling_piece: Option<FallingPieceJson>,
+    hold_piece: Option<char>,
+    can_hold: bool,
+    num_combos: Option<Count>,
+    num_btbs: Option<Count>,
+    recent_attacks: Vec<Count>,
+    game_over_reason: u8,
+    stats: StatisticsJson,
+}
+
+fn piece_from_char(c: char) -> Result<Piece, Box<dyn Error>> {
+    Ok(c.to_string().parse::<Piece>()?)
+}
+
+fn tspin_to_char(tspin: Option<TSpin>) -> Option<char> {
+    tspin.map(|t| match t {
+        TSpin::Standard => 'S',
+        TSpin::Mini => 'M',
+    })
+}
+
+fn tspin_from_char(c: Option<char>) -> Result<Option<TSpin>, Box<dyn Error>> {
+    Ok(match c {
+        None => None,
+        Some('S') => Some(TSpin::Standard),
+        Some('M') => Some(TSpin::Mini),
+        Some(_) => return Err("invalid tspin".into()),
+    })
+}
+
+pub fn game_to_json(game: &Game) -> Result<String, Box<dyn Error>> {
+    let s = &game.state;
+    let pf = &s.playfield;
+    let mut line_clear = game.stats.line_clear.data.iter()
+        .map(|(lc, n)| LineClearCountJson { num_lines: lc.num_lines, tspin: tspin_to_char(lc.tspin), count: *n })
+        .collect::<Vec<_>>();
+    line_clear.sort_by_key(|v| (v.num_lines, v.tspin));
+    let counter = |c: &deep_trinity_core::ConsecutiveCountCounter| c.data.iter().map(|(k, v)| (*k, *v)).collect();
+    let v = GameJson {
+        version: VERSION,
+        rules: (&game.rules).into(),
+        playfield: (0..pf.height())
+            .map(|y| (0..pf.width()).map(|x| pf.grid.cell((x, y).into()).to_char()).collect())
+            .collect(),
+        visible_height: pf.visible_height,
+        next_pieces: s.next_pieces.iter().map(|p| p.to_char()).collect(),
+        num_visible_next_pieces: s.next_pieces.visible_num,
+        falling_piece: s.falling_piece.as_ref().map(|fp| FallingPieceJson {
+            piece: fp.piece().to_char(),
+            initial_placement: fp.move_path.initial_placement.into(),
+            path: fp.move_path.iter().map(|item| (item.by.into(), item.placement.into())).collect(),
+        }),
+        hold_piece: s.hold_piece.map(|p| p.to_char()),
+        can_hold: s.can_hold,
+        num_combos: s.num_combos,
+        num_btbs: s.num_btbs,
+        recent_attacks: s.recent_attacks.iter().copied().collect(),
+        game_over_reason: s.game_over_reason.bits(),
+        stats: StatisticsJson {
+            line_clear,
+            combo: counter(&game.stats.combo),
+            btb: counter(&game.stats.btb),
+            spike: counter(&game.stats.spike),
+            perfect_clear: game.stats.perfect_clear,
+            hold: game.stats.hold,
+            lock: game.stats.lock,
+            hold_by_piece: game.stats.hold_by_piece.data,
+            lock_by_piece: game.stats.lock_by_piece.data,
+            landing_height_by_piece: game.stats.landing_height_by_piece.data,
+            wasted_t: game.stats.wasted_t,
+            misused_i: game.stats.misused_i,
+        },
+    };
+    Ok(serde_json::to_string(&v)?)
+}
+
+pub fn game_from_json(json: &str) -> Result<Game<'static>, Box<dyn Error>> {
+    let v: GameJson = serde_json::from_str(json)?;
+    if v.version != VERSION {
+        return Err("unsupported version".into());
+    }
+    let mut game: Game<'static> = Default::default();
+    game.rules = v.rules.to_rules()?;
+
+    let s = &mut game.state;
+    if v.playfield.len() != s.playfield.height() as usize {
+        return Err("invalid playfield height".into());
+    }
+    for (y, row) in v.playfield.iter().enumerate() {
+        if row.chars().count() != s.playfield.width() as usize {
+            return Err("invalid playfield width".into());
+        }
+        for (x, c) in row.chars().enumerate() {
+            s.playfield.set_cell((x as X, y as Y).into(), Cell::try_from_char(c)?);
+        }
+    }
+    s.playfield.visible_height = v.visible_height;
+    s.next_pieces.visible_num = v.num_visible_next_pieces;
+    for c in v.next_pieces.chars() {
+        s.next_pieces.pieces.push_back(piece_from_char(c)?);
+    }
+    if let Some(fp) = v.falling_piece {
+        let spec = game.piece_specs.get(piece_from_char(fp.piece)?);
+        let mut move_path = MovePath::new(fp.initial_placement.to_placement()?);
+        for (by, placement) in fp.path {
+            move_path.items.push(MovePathItem::new(by.into(), placement.to_placement()?));
+        }
+        let placement = move_path.last().map(|item| item.placement).unwrap_or(move_path.initial_placement);
+        let fp = FallingPiece { piece_spec: spec, placement, move_path };
+        if !s.playfield.can_put(&fp) {
+            return Err("falling piece can't be put".into());
+        }
+        s.falling_piece = Some(fp);
+    }
+    s.hold_piece = v.hold_piece.map(piece_from_char).transpose()?;
+    s.can_hold = v.can_hold;
+    s.num_combos = v.num_combos;
+    s.num_btbs = v.num_btbs;
+    s.recent_attacks = v.recent_attacks.into_iter().collect::<VecDeque<_>>();
+    s.game_over_reason = LossConditions::from_bits(v.game_over_reason).ok_or("invalid game_over_reason")?;
+
+    let stats = &mut game.stats;
+    for lc in v.stats.line_clear {
+        stats.line_clear.add(&LineClear::new(lc.num_lines, tspin_from_char(lc.tspin)?), lc.count);
+    }
+    for (k, n) in v.stats.combo {
+        stats.combo.add(k, n);
+    }
+    for (k, n) in v.stats.btb {
+        stats.btb.add(k, n);
+    }
+    for (k, n) in v.stats.spike {
+        stats.spike.add(k, n);
+    }
+    stats.perfect_clear = v.stats.perfect_clear;
+    stats.hold = v.stats.hold;
+    stats.lock = v.stats.lock;
+    stats.hold_by_piece.data = v.stats.hold_by_piece;
+    stats.lock_by_piece.data = v.stats.lock_by_piece;
+    stats.landing_height_by_piece.data = v.stats.landing_height_by_piece;
+    stats.wasted_t = v.stats.wasted_t;
+    stats.misused_i = v.stats.misused_i;
+    Ok(game)
+}
+
+#[cfg(feature = "deep-trinity-bot")]
+#[derive(Clone, Debug, Serialize)]
+struct ThinkCandidateJson {
+    /// `None` means hold.
+    placement: Option<PlacementJson>,
+    score: f32,
+}
+
+#[cfg(feature = "deep-trinity-bot")]
+#[derive(Clone, Debug, Serialize)]
+struct ThinkInfoJson {
+    num_expanded_nodes: usize,
+    max_depth: usize,
+    candidates: Vec<ThinkCandidateJson>,
+    chosen_score: Option<f32>,
+}
+
+#[cfg(feature = "deep-trinity-bot")]
+pub fn think_info_to_json(info: &deep_trinity_core::bot::ThinkInfo) -> Result<String, Box<dyn Error>> {
+    let v = ThinkInfoJson {
+        num_expanded_nodes: info.num_expanded_nodes,
+        max_depth: info.max_depth,
+        candidates: info.candidate_scores.iter()
+            .map(|(action, score)| {
+                let placement = match action {
+                    deep_trinity_core::bot::Action::Move(mt) => Some(mt.placement.into()),
+                    deep_trinity_core::bot::Action::Hold => None,
+                };
+                ThinkCandidateJson { placement, score: *score }
+            })
+            .collect(),
+        chosen_score: info.chosen_score,
+    };
+    Ok(serde_json::to_string(&v)?)
+}
+
+/// Parses a JSON object into bot parameters. Non-string values are stored as their JSON text.
+#[cfg(feature = "deep-trinity-bot")]
+pub fn parse_bot_params(json: &str) -> Result<deep_trinity_bot::registry::BotParams, Box<dyn Error>> {
+    let v: serde_json::Map<String, serde_json::Value> = serde_json::from_str(json)?;
+    Ok(v.into_iter()
+        .map(|(k, v)| {
+            let v = match v {
+                serde_json::Value::String(s) => s,
+                v => v.to_string(),
+            };
+            (k, v)
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use deep_trinity_core::RandomPieceGenerator;
+    use rand::SeedableRng;
+
+    #[test]
+    fn test_json() {
+        let mut game: Game = Default::default();
+        let mut rpg = RandomPieceGenerator::new(rand::rngs::StdRng::seed_from_u64(0));
+        game.supply_next_pieces(&rpg.generate());
+        game.setup_falling_piece(None).unwrap();
+        game.hold().unwrap();
+        for i in 0..5 {
+            if game.should_supply_next_pieces() {
+                game.supply_next_pieces(&rpg.generate());
+            }
+            game.rotate(i % 2).unwrap();
+            game.shift(if i % 2 == 0 { -1 } else { 1 }, true).unwrap();
+            game.firm_drop().unwrap();
+            game.lock().unwrap();
+        }
+        game.shift(1, false).unwrap();
+        let json = game_to_json(&game).unwrap();
+        let restored = game_from_json(&json).unwrap();
+        assert_eq!(game, restored);
+        assert_eq!(json, game_to_json(&restored).unwrap());
+
+        // An overlapping falling piece.
+        let mut v: serde_json::Value = serde_json::from_str(&json).unwrap();
+        v["playfield"][19] = serde_json::Value::String("GGGGGGGGGG".into());
+        v["playfield"][20] = serde_json::Value::String("GGGGGGGGGG".into());
+        assert!(game_from_json(&v.to_string()).is_err());
+
+        // Older JSON without the other rules.
+        let mut v: serde_json::Value = serde_json::from_str(&json).unwrap();
+        v.as_object_mut().unwrap().retain(|k, _| !matches!(k.as_str(), "spawn_nudge" | "instant_gravity" | "attack_table"));
+        assert_eq!(game, game_from_json(&v.to_string()).unwrap());
+    }
+
+    #[test]
+    fn test_json_rules() {
+        let mut game: Game = Default::default();
+        game.rules = GameRules::tetrio();
+        game.rules.instant_gravity = true;
+        game.rules.attack_table.combo = ComboTable::Additive(vec![0, 1, 2].into());
+        game.supply_next_pieces(&[Piece::T, Piece::O]);
+        game.setup_falling_piece(None).unwrap();
+        let restored = game_from_json(&game_to_json(&game).unwrap()).unwrap();
+        assert_eq!(game.rules, restored.rules);
+
+        game.rules = GameRules::guideline();
+        game.rules.spawn_nudge = SpawnNudge::Disabled;
+        let restored = game_from_json(&game_to_json(&game).unwrap()).unwrap();
+        assert_eq!(game.rules, restored.rules);
+    }
+
+    #[cfg(feature = "deep-trinity-bot")]
+    #[test]
+    fn test_parse_bot_params() {
+        let params = parse_bot_params(r#"{"num_iterations": 10, "name": "x"}"#).unwrap();
+        assert_eq!(Some(&"10".to_string()), params.get("num_iterations"));
+        assert_eq!(Some(&"x".to_string()), params.get("name"));
+        assert!(parse_bot_params("[]").is_err());
+    }
+
+    #[cfg(feature = "deep-trinity-bot")]
+    #[test]
+    fn test_think_info_to_json() {
+        let info = deep_trinity_core::bot::ThinkInfo {
+            num_expanded_nodes: 2,
+            max_depth: 1,
+            candidate_scores: vec![(deep_trinity_core::bot::Action::Hold, 0.5)],
+            chosen_score: Some(0.5),
+        };
+        let json = think_info_to_json(&info).unwrap();
+        assert!(json.contains(r#""candidates":[{"placement":null,"score":0.5}]"#));
+    }
+}
@@ -3,11 +3,12 @@ use rand::SeedableRng;
 use deep_trinity_core::MovePathItem;
 use deep_trinity_grid::Grid;
 
+mod json;
+
 #[wasm_bindgen(js_name = setPanicHook)]
 pub fn set_panic_hook() {
-    if cfg!(feature = "console_error_panic_hook") {
-        console_error_panic_hook::set_once();
-    }
+    #[cfg(feature = "console_error_panic_hook")]
+    console_error_panic_hook::set_once();
 }
 
 #[wasm_bindgen]
@@ -150,18 +151,209 @@ impl From<deep_trinity_core::MoveTransition> for MoveTransition {
     }
 }
 
+#[wasm_bindgen]
+#[derive(Copy, Clone, Debug)]
+pub struct LockOutcome {
+    pub num_lines: u8,
+    pub is_tspin: bool,
+    pub is_tspin_mini: bool,
+    pub attack_sent: u32,
+    pub perfect_clear: bool,
+    pub game_over: bool,
+    pub next_piece_ready: bool,
+}
+
+impl From<deep_trinity_core::LockOutcome> for LockOutcome {
+    fn from(o: deep_trinity_core::LockOutcome) -> Self {
+        Self {
+            num_lines: o.line_clear.num_lines,
+            is_tspin: o.line_clear.is_tspin(),
+            is_tspin_mini: o.line_clear.is_tspin_mini(),
+            attack_sent: o.attack_sent,
+            perfect_clear: o.perfect_clear,
+            game_over: o.game_over,
+            next_piece_ready: o.next_piece_ready,
+        }
+    }
+}
+
+#[wasm_bindgen]
+#[derive(Copy, Clone, Debug)]
+pub struct HoldPreview {
+    pub piece: u8,
+    pub from_next_pieces: bool,
+    pub blocks_out: bool,
+}
+
+impl From<deep_trinity_core::HoldPreview> for HoldPreview {
+    fn from(p: deep_trinity_core::HoldPreview) -> Self {
+        Self { piece: p.piece as u8, from_next_pieces: p.from_next_pieces, blocks_out: p.blocks_out }
+    }
+}
+
+/// The cells of a piece cropped to its blocks for the next and hold previews.
+#[wasm_bindgen]
+#[derive(Clone)]
+pub struct PiecePreview {
+    pub width: deep_trinity_grid::X,
+    pub height: deep_trinity_grid::Y,
+    cells: Vec<Cell>,
+}
+
+#[wasm_bindgen]
+impl PiecePreview {
+    #[wasm_bindgen(js_name = ofPiece)]
+    pub fn of_piece(piece: Piece) -> Self {
+        let grid = deep_trinity_core::Piece::try_from_u8(piece as u8).unwrap().default_spec().preview_grid();
+        let cells = (0..grid.height())
+            .flat_map(|y| (0..grid.width()).map(move |x| (x, y)))
+            .map(|(x, y)| grid.cell((x, y).into()).into())
+            .collect();
+        Self { width: grid.width(), height: grid.height(), cells }
+    }
+    /// `y` is from the bottom as the playfield.
+    #[wasm_bindgen(js_name = getCell)]
+    pub fn get_cell(&self, x: i8, y: i8) -> Cell { self.cells[(y as usize) * (self.width as usize) + x as usize] }
+}
+
+#[wasm_bindgen]
+#[derive(Copy, Clone, Debug)]
+pub enum GameEventKind {
+    PieceLocked,
+    LinesCleared,
+    GarbageReceived,
+    BtbBroken,
+    ComboExtended,
+}
+
+/// Fields not related to `kind` are unset.
+#[wasm_bindgen]
+#[derive(Clone, Debug)]
+pub struct GameEvent {
+    pub kind: GameEventKind,
+    pub piece: Option<u8>,
+    pub placement: Option<Placement>,
+    pub num_lines: Option<u8>,
+    pub is_tspin: Option<bool>,
+    pub is_tspin_mini: Option<bool>,
+    /// Garbage rows, back-to-backs or combos.
+    pub count: Option<u32>,
+    rows: Vec<i8>,
+}
+
+#[wasm_bindgen]
+impl GameEvent {
+    /// Cleared rows of `LinesCleared`.
+    #[wasm_bindgen(getter)]
+    pub fn rows(&self) -> Box<[i8]> { self.rows.clone().into_boxed_slice() }
+}
+
+impl From<deep_trinity_core::GameEvent> for GameEvent {
+    fn from(e: deep_trinity_core::GameEvent) -> Self {
+        use deep_trinity_core::GameEvent as E;
+        let mut r = Self {
+            kind: GameEventKind::PieceLocked,
+            piece: None,
+            placement: None,
+            num_lines: None,
+            is_tspin: None,
+            is_tspin_mini: None,
+            count: None,
+            rows: Vec::new(),
+        };
+        match e {
+            E::PieceLocked { piece, placement } => {
+                r.piece = Some(piece as u8);
+                r.placement = Some(placement.into());
+            }
+            E::LinesCleared { rows, line_clear } => {
+                r.kind = GameEventKind::LinesCleared;
+                r.num_lines = Some(line_clear.num_lines);
+                r.is_tspin = Some(line_clear.is_tspin());
+                r.is_tspin_mini = Some(line_clear.is_tspin_mini());
+                r.rows = rows;
+            }
+            E::GarbageReceived { rows } => {
+                r.kind = GameEventKind::GarbageReceived;
+                r.count = Some(rows as u32);
+            }
+            E::BtbBroken { num_btbs } => {
+                r.kind = GameEventKind::BtbBroken;
+                r.count = Some(num_btbs);
+            }
+            E::ComboExtended { num_combos } => {
+                r.kind = GameEventKind::ComboExtended;
+                r.count = Some(num_combos);
+            }
+        }
+        r
+    }
+}
+
+thread_local! {
+    static THEMES: std::cell::RefCell<deep_trinity_core::theme::ThemeRegistry> = Default::default();
+}
+
+/// Cell colors shared by renderers. Custom themes can be registered by name.
+#[wasm_bindgen]
+#[derive(Copy, Clone)]
+pub struct Theme {
+    theme: deep_trinity_core::theme::Theme,
+}
+
+#[wasm_bindgen]
+impl Theme {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Self { Self { theme: Default::default() } }
+    #[wasm_bindgen(js_name = byName)]
+    pub fn by_name(name: &str) -> Option<Theme> {
+        THEMES.with(|themes| themes.borrow().get(name).map(|&theme| Self { theme }))
+    }
+    #[wasm_bindgen(js_name = themeNames)]
+    pub fn theme_names() -> Vec<String> {
+        THEMES.with(|themes| themes.borrow().names().map(|s| s.to_string()).collect())
+    }
+    /// The existing one is replaced.
+    pub fn register(&self, name: &str) {
+        THEMES.with(|themes| themes.borrow_mut().register(name, self.theme));
+    }
+    /// CSS color.
+    #[wasm_bindgen(js_name = cellColor)]
+    pub fn cell_color(&self, cell: Cell) -> String {
+        self.theme.color(deep_trinity_core::Cell::from_u8_unchecked(cell as u8)).to_css()
+    }
+    /// `0xRRGGBBAA`
+    #[wasm_bindgen(js_name = cellColorRgba)]
+    pub fn cell_color_rgba(&self, cell: Cell) -> u32 {
+        self.theme.color(deep_trinity_core::Cell::from_u8_unchecked(cell as u8)).to_u32()
+    }
+    #[wasm_bindgen(js_name = setCellColorRgba)]
+    pub fn set_cell_color_rgba(&mut self, cell: Cell, rgba: u32) {
+        let color = deep_trinity_core::theme::Rgba::from_u32(rgba);
+        self.theme.set_color(deep_trinity_core::Cell::from_u8_unchecked(cell as u8), color);
+    }
+}
+
+impl Default for Theme {
+    fn default() -> Self { Self::new() }
+}
+
 #[wasm_bindgen]
 pub struct Game {
     game: deep_trinity_core::Game<'static>,
+    /// The playfield at the last `takeDirtyCells()`.
+    rendered_playfield: Option<deep_trinity_core::Playfield<'static>>,
+}
+
+impl From<deep_trinity_core::Game<'static>> for Game {
+    fn from(game: deep_trinity_core::Game<'static>) -> Self { Self { game, rendered_playfield: None } }
 }
 
 #[wasm_bindgen]
 impl Game {
     #[wasm_bindgen(constructor)]
     pub fn new() -> Self {
-        Self {
-            game: Default::default(),
-        }
+        deep_trinity_core::Game::default().into()
     }
     pub fn width(&self) -> deep_trinity_grid::X { self.game.state.playfield.width() }
     pub fn height(&self) -> deep_trinity_grid::Y { self.game.state.playfield.height() }
@@ -169,10 +361,33 @@ impl Game {
     pub fn visible_height(&self) -> deep_trinity_grid::Y { self.game.state.playfield.visible_height }
     #[wasm_bindgen(js_name = getCell)]
     pub fn get_cell(&self, x: i8, y: i8) -> Cell { self.game.state.playfield.grid.cell((x, y).into()).into() }
+    /// Returns the cells of the playfield changed since the last call as `[x0, y0, x1, y1, ...]`.
+    /// All the cells are returned at the first call.
+    #[wasm_bindgen(js_name = takeDirtyCells)]
+    pub fn take_dirty_cells(&mut self) -> Box<[i8]> {
+        let pf = &self.game.state.playfield;
+        let cells = match self.rendered_playfield.as_ref() {
+            Some(prev) if (prev.width(), prev.height()) == (pf.width(), pf.height()) => pf.diff_cells(prev),
+            _ => (0..pf.height()).flat_map(|y| (0..pf.width()).map(move |x| (x, y).into())).collect(),
+        };
+        self.rendered_playfield = Some(pf.clone());
+        cells.into_iter().flat_map(|pos: deep_trinity_grid::Vec2| [pos.0, pos.1]).collect()
+    }
     #[wasm_bindgen(js_name = getHoldPiece)]
     pub fn get_hold_piece(&self) -> Option<u8> {
         self.game.state.hold_piece.map(|p| { p as u8 })
     }
+    #[wasm_bindgen(js_name = setEnableHold)]
+    pub fn set_enable_hold(&mut self, enable: bool) { self.game.rules.enable_hold = enable; }
+    #[wasm_bindgen(js_name = canHold)]
+    pub fn can_hold(&self) -> bool { self.game.can_hold() }
+    #[wasm_bindgen(js_name = setNumVisibleNextPieces)]
+    pub fn set_num_visible_next_pieces(&mut self, n: usize) { self.game.set_num_visible_next_pieces(n); }
+    /// Returns `undefined` if hold is not available.
+    #[wasm_bindgen(js_name = peekHoldResult)]
+    pub fn peek_hold_result(&self) -> Option<HoldPreview> {
+        self.game.peek_hold_result().ok().map(|p| p.into())
+    }
     #[wasm_bindgen(js_name = getNextPieces)]
     pub fn get_next_pieces(&self, visible: bool) -> Box<[u8]> {
         let np = &self.game.state.next_pieces;
@@ -213,6 +428,11 @@ impl Game {
         }
         self.game.supply_next_pieces(&ps);
     }
+    /// Takes the ownership of `gen`, then the next pieces are supplied automatically.
+    #[wasm_bindgen(js_name = setPieceGenerator)]
+    pub fn set_piece_generator(&mut self, gen: RandomPieceGenerator) {
+        self.game.set_piece_generator(Some(Box::new(gen.gen)));
+    }
     #[wasm_bindgen(js_name = shouldSupplyNextPieces)]
     pub fn should_supply_next_pieces(&self) -> bool { self.game.should_supply_next_pieces() }
     #[wasm_bindgen(js_name = isGameOver)]
@@ -255,21 +475,51 @@ impl Game {
             Err(e) => Err(e.into()),
         }
     }
+    #[wasm_bindgen(js_name = lockEx)]
+    pub fn lock_ex(&mut self) -> Result<LockOutcome, JsValue> {
+        match self.game.lock_ex() {
+            Ok(o) => Ok(o.into()),
+            Err(e) => Err(e.into()),
+        }
+    }
     pub fn hold(&mut self) -> Result<bool, JsValue> {
         match self.game.hold() {
             Ok(b) => Ok(b),
             Err(e) => Err(e.into()),
         }
     }
+    #[wasm_bindgen(js_name = setEventRecording)]
+    pub fn set_event_recording(&mut self, enabled: bool) { self.game.set_event_recording(enabled); }
+    /// Returns the events recorded since the last call.
+    #[wasm_bindgen(js_name = drainEvents)]
+    pub fn drain_events(&mut self) -> Vec<GameEvent> {
+        self.game.drain_events().into_iter().map(|e| e.into()).collect()
+    }
+    #[wasm_bindgen(js_name = receiveGarbage)]
+    pub fn receive_garbage(&mut self, gap_x_list: &[i8]) -> bool { self.game.receive_garbage(gap_x_list) }
     #[wasm_bindgen(js_name = toString)]
     pub fn to_string(&self) -> String {
         self.game.to_string()
     }
+    /// Returns the whole game state as a JSON string.
+    #[wasm_bindgen(js_name = toJSON)]
+    pub fn to_json(&self) -> Result<String, JsValue> {
+        json::game_to_json(&self.game).map_err(|e| e.to_string().into())
+    }
+    /// Restores the game from a JSON string returned by `toJSON()`.
+    #[wasm_bindgen(js_name = fromJSON)]
+    pub fn from_json(json: &str) -> Result<Game, JsValue> {
+        json::game_from_json(json)
+            .map(|game| game.into())
+            .map_err(|e| e.to_string().into())
+    }
 }
 
 #[wasm_bindgen]
 pub struct RandomPieceGenerator {
-    gen: deep_trinity_core::RandomPieceGenerator<rand::rngs::StdRng>,
+    /// Unlike `StdRng`, the algorithm is fixed so that the same seeds give the same pieces in all
+    /// versions of the clients.
+    gen: deep_trinity_core::RandomPieceGenerator<rand_chacha::ChaCha8Rng>,
 }
 
 #[wasm_bindgen]
@@ -277,9 +527,16 @@ impl RandomPieceGenerator {
     #[wasm_bindgen(constructor)]
     pub fn new(seed: u64) -> Self {
         Self {
-            gen: deep_trinity_core::RandomPieceGenerator::new(rand::rngs::StdRng::seed_from_u64(seed))
+            gen: deep_trinity_core::RandomPieceGenerator::new(rand_chacha::ChaCha8Rng::seed_from_u64(seed))
         }
     }
+    /// Same as `new()` with the seed derived from `seed`, which is exact in JS unlike numbers.
+    #[wasm_bindgen(js_name = fromStringSeed)]
+    pub fn from_string_seed(seed: &str) -> Self { Self::new(deep_trinity_core::seed_from_str(seed)) }
+    /// The generator of the daily games of the date in `YYYY-MM-DD`, the same in all clients.
+    pub fn daily(date: &str) -> Result<RandomPieceGenerator, JsValue> {
+        Ok(Self::new(deep_trinity_core::daily_seed(date)?))
+    }
     pub fn generate(&mut self) -> Box<[u8]> {
         self.gen.generate()
             .iter()
@@ -289,11 +546,13 @@ impl RandomPieceGenerator {
     }
 }
 
+#[cfg(feature = "deep-trinity-bot")]
 #[wasm_bindgen]
 pub struct Action {
     bot_action: deep_trinity_bot::Action,
 }
 
+#[cfg(feature = "deep-trinity-bot")]
 #[wasm_bindgen]
 impl Action {
     fn new(bot_action: deep_trinity_bot::Action) -> Self {
@@ -314,21 +573,34 @@ impl Action {
     }
 }
 
+#[cfg(feature = "deep-trinity-bot")]
 #[wasm_bindgen]
 pub struct Bot {
     bot: Box<dyn deep_trinity_bot::Bot>,
 }
 
+#[cfg(feature = "deep-trinity-bot")]
 #[wasm_bindgen]
 impl Bot {
     #[wasm_bindgen(constructor)]
     pub fn new(bot_type: Option<u8>) -> Result<Bot, JsValue> {
-        let bot: Box<dyn deep_trinity_bot::Bot> = match bot_type.unwrap_or(1) {
-            1 => Box::new(deep_trinity_bot::simple::SimpleBot::default()),
-            2 => Box::new(deep_trinity_bot::simple_tree::SimpleTreeBot::default()),
-            3 => Box::new(deep_trinity_bot::mcts_puct::MctsPuctBot::default()),
+        let name = match bot_type.unwrap_or(1) {
+            1 => "simple",
+            2 => "simple_tree",
+            3 => "mcts_puct",
             _ => return Err("invalid bot type".into()),
         };
+        Self::new_by_name(name, None)
+    }
+    /// `params` is a JSON object of the bot parameters (see [deep_trinity_bot::registry]).
+    #[wasm_bindgen(js_name = newByName)]
+    pub fn new_by_name(name: &str, params: Option<String>) -> Result<Bot, JsValue> {
+        let params: deep_trinity_bot::registry::BotParams = match params {
+            Some(params) => json::parse_bot_params(&params).map_err(|e| JsValue::from(e.to_string()))?,
+            None => Default::default(),
+        };
+        let factory = deep_trinity_bot::registry::BotFactory::default();
+        let bot = factory.create(name, &params).map_err(|e| JsValue::from(e.to_string()))?;
         Ok(Self { bot })
     }
     pub fn think(&mut self, game: &Game) -> Result<Action, JsValue> {
@@ -337,6 +609,141 @@ impl Bot {
             Ok(action) => Ok(Action::new(action)),
         }
     }
+    /// Returns the diagnostics of the last `think()` as JSON if the bot supports it.
+    #[wasm_bindgen(js_name = lastThinkInfo)]
+    pub fn last_think_info(&self) -> Result<Option<String>, JsValue> {
+        self.bot.last_think_info()
+            .map(|info| json::think_info_to_json(&info.map_actions(Into::into)).map_err(|e| JsValue::from(e.to_string())))
+            .transpose()
+    }
+}
+
+#[cfg(feature = "stack-resolver")]
+#[wasm_bindgen]
+pub struct Puzzle {
+    puzzle: stack_resolver::puzzle::Puzzle,
+}
+
+#[cfg(feature = "stack-resolver")]
+#[wasm_bindgen]
+impl Puzzle {
+    /// `doc` is a puzzle document (see [stack_resolver::puzzle]).
+    pub fn parse(doc: &str) -> Result<Puzzle, JsValue> {
+        stack_resolver::puzzle::Puzzle::parse(doc)
+            .map(|puzzle| Self { puzzle })
+            .map_err(|e| e.into())
+    }
+    /// Returns the game at the start of the puzzle.
+    pub fn game(&self) -> Result<Game, JsValue> {
+        self.puzzle.game()
+            .map(|game| game.into())
+            .map_err(|e| e.into())
+    }
+    /// Returns whether the goal is achieved by `game` played from `start`, i.e. the result of `game()`.
+    #[wasm_bindgen(js_name = isAchieved)]
+    pub fn is_achieved(&self, start: &Game, game: &Game) -> bool {
+        self.puzzle.goal.is_achieved(&start.game.stats, &game.game)
+    }
+    pub fn solve(&self, max_solutions: usize) -> Result<Vec<PuzzleSolution>, JsValue> {
+        self.puzzle.solve(max_solutions)
+            .map(|solutions| solutions.into_iter().map(|actions| PuzzleSolution { actions }).collect())
+            .map_err(|e| e.into())
+    }
+}
+
+#[cfg(feature = "stack-resolver")]
+#[wasm_bindgen]
+pub struct PuzzleSolution {
+    actions: Vec<deep_trinity_core::bot::Action>,
+}
+
+#[cfg(feature = "stack-resolver")]
+#[wasm_bindgen]
+impl PuzzleSolution {
+    pub fn len(&self) -> usize { self.actions.len() }
+    #[wasm_bindgen(js_name = isHold)]
+    pub fn is_hold(&self, i: usize) -> bool {
+        matches!(self.actions.get(i), Some(deep_trinity_core::bot::Action::Hold))
+    }
+    /// Returns `undefined` if the `i`-th action is the hold.
+    pub fn dst(&self, i: usize) -> Option<MoveTransition> {
+        match self.actions.get(i) {
+            Some(deep_trinity_core::bot::Action::Move(mt)) => Some((*mt).into()),
+            _ => None,
+        }
+    }
+}
+
+/// A move path where each step is a repeated [Move], e.g. 3 times of `Left`.
+#[wasm_bindgen]
+pub struct MovePath {
+    path: deep_trinity_core::MovePath,
+}
+
+#[wasm_bindgen]
+impl MovePath {
+    pub fn len(&self) -> usize { self.path.len() }
+    #[wasm_bindgen(js_name = initialPlacement)]
+    pub fn initial_placement(&self) -> Placement { self.path.initial_placement.into() }
+    /// The placement after the `i`-th step.
+    pub fn dst(&self, i: usize) -> Option<Placement> { self.path.items.get(i).map(|item| item.placement.into()) }
+    pub fn by(&self, i: usize) -> Option<Move> { self.path.items.get(i).map(|item| split_move(item.by).0) }
+    /// The number of times [Self::by] is repeated in the `i`-th step.
+    pub fn count(&self, i: usize) -> Option<u8> { self.path.items.get(i).map(|item| split_move(item.by).1) }
+}
+
+fn split_move(mv: deep_trinity_core::Move) -> (Move, u8) {
+    let (unit, n) = match mv {
+        deep_trinity_core::Move::Shift(n) => (deep_trinity_core::Move::Shift(n.signum()), n),
+        deep_trinity_core::Move::Drop(n) => (deep_trinity_core::Move::Drop(n.signum()), n),
+        deep_trinity_core::Move::Rotate(n) => (deep_trinity_core::Move::Rotate(n.signum()), n),
+    };
+    (unit.into(), n.unsigned_abs())
+}
+
+/// Queries of the placements of the falling piece without bots, e.g. for setup explorers.
+#[cfg(feature = "decision-helper")]
+#[wasm_bindgen]
+pub struct JsMoveDecisionHelper {
+    game: deep_trinity_core::Game<'static>,
+    resource: deep_trinity_core::helper::MoveDecisionResource,
+}
+
+#[cfg(feature = "decision-helper")]
+#[wasm_bindgen]
+impl JsMoveDecisionHelper {
+    /// The helper is for the current falling piece of `game`, and isn't updated with it.
+    #[wasm_bindgen(constructor)]
+    pub fn new(game: &Game) -> Result<JsMoveDecisionHelper, JsValue> {
+        let resource = deep_trinity_core::helper::MoveDecisionResource::with_game(&game.game)?;
+        Ok(Self { game: game.game.clone(), resource })
+    }
+    /// The reachable placements where the piece can lock.
+    #[wasm_bindgen(js_name = dstCandidates)]
+    pub fn dst_candidates(&self) -> Vec<Placement> {
+        self.resource.sorted_candidates().into_iter().map(|p| p.into()).collect()
+    }
+    /// The moves of T-spins with line clears. Empty for the other pieces.
+    #[wasm_bindgen(js_name = tspinCandidates)]
+    pub fn tspin_candidates(&self) -> Result<Vec<MoveTransition>, JsValue> {
+        if self.game.state.falling_piece.as_ref().map(|fp| fp.piece()) != Some(deep_trinity_core::Piece::T) {
+            return Ok(Vec::new());
+        }
+        let helper = deep_trinity_core::helper::MoveDecisionHelper::with_game(&self.game, &self.resource)?;
+        Ok(helper.tspin_moves()?.into_iter().map(|(mt, _)| mt.into()).collect())
+    }
+    /// Returns the path to `placement` or to its alternative placement with the same cells, ending
+    /// with the rotation if it's reachable only by rotations.
+    #[wasm_bindgen(js_name = pathTo)]
+    pub fn path_to(&self, placement: &Placement) -> Result<MovePath, JsValue> {
+        let placement = self.game.alternative_placements(&(*placement).into())?
+            .into_iter()
+            .find(|p| self.resource.dst_candidates.contains(p))
+            .ok_or("unreachable placement")?;
+        let mt = self.game.establish_move_hint(&deep_trinity_core::MoveTransition::new(placement, None))?;
+        let path = self.game.get_almost_good_move_path(&mt)?;
+        Ok(MovePath { path })
+    }
 }
 
 #[wasm_bindgen]
@@ -359,9 +766,88 @@ impl MovePlayer {
             move_player: deep_trinity_core::MovePlayer::new(path),
         })
     }
+    /// Plays the action of bots including the hold.
+    #[cfg(feature = "deep-trinity-bot")]
+    #[wasm_bindgen(js_name = fromAction)]
+    pub fn from_action(game: &Game, action: &Action) -> Result<MovePlayer, JsValue> {
+        let mt = action.dst().map(|mt| mt.into());
+        let path = game.game.get_action_path(action.is_hold(), mt.as_ref())?;
+        Ok(Self {
+            move_player: deep_trinity_core::MovePlayer::new(path),
+        })
+    }
+    #[wasm_bindgen(js_name = fromPath)]
+    pub fn from_path(path: &MovePath) -> MovePlayer {
+        Self { move_player: deep_trinity_core::MovePlayer::new(path.path.clone()) }
+    }
     pub fn step(&mut self, game: &mut Game) -> Result<bool, JsValue> {
         self.move_player.step(&mut game.game).map_err(|e| { e.into() })
     }
     #[wasm_bindgen(js_name = isEnd)]
     pub fn is_end(&self) -> bool { self.move_player.is_end() }
+    #[wasm_bindgen(js_name = fastForward)]
+    pub fn fast_forward(&mut self, game: &mut Game) -> Result<usize, JsValue> {
+        self.move_player.fast_forward(&mut game.game).map_err(|e| { e.into() })
+    }
+    #[wasm_bindgen(js_name = currentIndex)]
+    pub fn current_index(&self) -> usize { self.move_player.current_index() }
+    pub fn len(&self) -> usize { self.move_player.len() }
+    pub fn seek(&mut self, game: &mut Game, i: usize) -> Result<(), JsValue> {
+        self.move_player.seek(&mut game.game, i).map_err(|e| { e.into() })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_random_piece_generator() {
+        // Pinned since the pieces of the same seeds should not change across versions.
+        let mut daily = RandomPieceGenerator::daily("2026-10-16").unwrap();
+        let pieces = [daily.generate(), daily.generate()].concat();
+        assert_eq!(vec![6, 1, 5, 2, 3, 0, 4, 0, 3, 1, 6, 5, 4, 2], pieces);
+        let mut gen = RandomPieceGenerator::from_string_seed("daily:2026-10-16");
+        assert_eq!(pieces, [gen.generate(), gen.generate()].concat());
+    }
+
+    #[cfg(feature = "decision-helper")]
+    #[test]
+    fn test_move_decision_helper() {
+        use deep_trinity_core::Piece;
+        let mut game: deep_trinity_core::Game<'static> = Default::default();
+        game.state.playfield.set_rows_with_strs((0, 0).into(), &[
+            "@@@@      ",
+            "@@@   @@@@",
+            "@@@@ @@@@@",
+        ]);
+        game.supply_next_pieces(&[Piece::T, Piece::I]);
+        game.setup_falling_piece(None).unwrap();
+        let played = |helper: &JsMoveDecisionHelper, path: MovePath| {
+            let mut game = helper.game.clone();
+            deep_trinity_core::MovePlayer::new(path.path).fast_forward(&mut game).unwrap();
+            game.state.falling_piece.unwrap().placement
+        };
+        let helper = JsMoveDecisionHelper::new(&game.clone().into()).unwrap();
+        let candidates = helper.dst_candidates();
+        assert_eq!(helper.resource.dst_candidates.len(), candidates.len());
+        let tspins = helper.tspin_candidates().unwrap();
+        assert!(!tspins.is_empty());
+        for mt in tspins.iter() {
+            assert!(candidates.iter().any(|p| (p.orientation, p.x, p.y) == (mt.dst.orientation, mt.dst.x, mt.dst.y)));
+            let dst: deep_trinity_core::Placement = mt.dst.into();
+            assert_eq!(dst, played(&helper, helper.path_to(&mt.dst).unwrap()));
+        }
+
+        // The other pieces have no T-spins, and the placements not in the candidates are resolved
+        // to their alternative placements.
+        game.hold().unwrap();
+        let mut helper = JsMoveDecisionHelper::new(&game.into()).unwrap();
+        assert!(helper.tspin_candidates().unwrap().is_empty());
+        let dst = helper.resource.sorted_candidates()[0];
+        let alternative = deep_trinity_core::helper::get_alternative_placements(Piece::I, &dst)[0];
+        assert!(helper.resource.dst_candidates.remove(&alternative));
+        let path = helper.path_to(&alternative.into()).unwrap();
+        assert_eq!(dst, played(&helper, path));
+    }
 }
@@ -21,6 +21,9 @@ export enum Cell {
   Garbage,
 }
 
+export const CELLS =
+  Object.values(Cell).filter(v => typeof v === "number") as Cell[];
+
 export const pieceToCell = (p: Piece): Cell => p + 2;
 
 export const cellToPiece = (c: Cell): Piece | undefined => (c < 2 || 8 < c) ? undefined : c - 2;
@@ -18,7 +18,7 @@ enum BotRunnerState {
 class BotRunner {
   private state = BotRunnerState.Think;
   private game = new core.Game();
-  private pg?: core.RandomPieceGenerator;
+  private isSetup = false;
   private bot = new core.Bot();
   private movePlayer?: core.MovePlayer;
   public isRunning = false;
@@ -28,16 +28,16 @@ class BotRunner {
   }
 
   shouldSetup(): boolean {
-    return !this.pg;
+    return !this.isSetup;
   }
 
   setup(bot: number, seed: number): void {
     this.state = BotRunnerState.Think;
     this.game = new core.Game();
-    this.pg = new core.RandomPieceGenerator(BigInt(seed));
     this.bot = new core.Bot(bot);
+    this.isSetup = true;
 
-    this.game.supplyNextPieces(this.pg.generate());
+    this.game.setPieceGenerator(new core.RandomPieceGenerator(BigInt(seed)));
     this.game.setupFallingPiece();
   }
 
@@ -50,15 +50,12 @@ class BotRunner {
   }
 
   update(): void {
-    const { game, pg, bot } = this;
-    if (!pg) {
+    const { game, bot } = this;
+    if (!this.isSetup) {
       throw new Error("should setup");
     }
     switch (this.state) {
       case BotRunnerState.Think: {
-        if (game.shouldSupplyNextPieces()) {
-          game.supplyNextPieces(pg.generate());
-        }
         const action = bot.think(game);
         if (action.isHold()) {
           game.hold();
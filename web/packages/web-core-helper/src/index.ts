@@ -24,3 +24,12 @@ export const getGameModel = (game: core.Game, onlyVisibleNext: boolean): model.G
   };
   return g;
 };
+
+/** Cell colors of the theme defined in core, e.g. for `Theme.cellColors` of the view. */
+export const getCellColors = (theme: core.Theme): { [cell: number]: string } => {
+  const colors: { [cell: number]: string } = {};
+  for (const cell of model.CELLS) {
+    colors[cell] = theme.cellColor(cell);
+  }
+  return colors;
+};